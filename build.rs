@@ -1,13 +1,44 @@
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
+
 const EMBED_NAME: &str = "embedded";
 const RESOURCES_DIR: &str = "resources";
 const PANDOC_UPX: &str = "pandoc_upx.exe";
+/// Embedded with the `embed-pandoc-linux` feature, put there before building.
+const PANDOC_LINUX: &str = "pandoc-linux";
+/// Embedded with the `embed-pandoc-macos` feature, put there before building.
+const PANDOC_MACOS: &str = "pandoc-macos";
 
 fn main() {
     println!("cargo:rerun-if-env-changed={EMBED_NAME}");
     println!("cargo:rerun-if-env-changed={RESOURCES_DIR}");
     println!("cargo:rerun-if-env-changed={PANDOC_UPX}");
-    println!("cargo:rerun-if-changed={RESOURCES_DIR}/{PANDOC_UPX}");
+    println!("cargo::rustc-check-cfg=cfg(embedded_pandoc)");
+
+    // Windows always embeds pandoc, Linux and macOS only with their feature
+    let target_os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let feature =
+        |name: &str| std::env::var_os(format!("CARGO_FEATURE_{name}")).is_some();
+    let embedded = match target_os.as_str() {
+        "windows" => PANDOC_UPX,
+        "linux" if feature("EMBED_PANDOC_LINUX") => PANDOC_LINUX,
+        "macos" if feature("EMBED_PANDOC_MACOS") => PANDOC_MACOS,
+        _ => return,
+    };
+
+    let path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default())
+        .join(RESOURCES_DIR)
+        .join(embedded);
+    println!("cargo:rerun-if-changed={}", path.display());
 
-    #[cfg(target_os = "windows")]
-    include_bytes!("resources/pandoc_upx.exe");
+    // Checked against the unpacked binary at runtime, see `embedded::unpack`
+    let bytes = std::fs::read(&path).unwrap_or_else(|e| {
+        panic!("Could not read the pandoc binary to embed at {}: {e}", path.display())
+    });
+    let digest = Sha256::digest(&bytes);
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    println!("cargo:rustc-env=PANDOC_EXPECTED_SHA256={hex}");
+    println!("cargo:rustc-env=PANDOC_EMBEDDED_PATH={}", path.display());
+    println!("cargo:rustc-cfg=embedded_pandoc");
 }
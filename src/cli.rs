@@ -1,8 +1,30 @@
-use std::path::PathBuf;
+pub mod config;
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use clap::{Parser, ValueEnum};
+use clap::error::ErrorKind;
+use clap::{
+    ArgMatches,
+    Args,
+    CommandFactory,
+    FromArgMatches,
+    Parser,
+    Subcommand,
+    ValueEnum,
+};
 
+use crate::conversion::filter::{parse_size, parse_time};
+use crate::conversion::naming::FlattenSuffix;
+use crate::conversion::order::Order;
+use crate::conversion::originals::SourceDisposal;
+use crate::conversion::pandoc::PandocVersion;
+use crate::conversion::pdf::PdfEngine;
+use crate::conversion::report::ReportFormat;
+use crate::conversion::shutdown::DEFAULT_GRACE_SECS;
+use crate::conversion::{Engine, OnExists};
+use crate::log_file::LogRotation;
 use crate::prelude::*;
 
 #[rustfmt::skip]
@@ -14,21 +36,160 @@ use crate::prelude::*;
     about = crate::crate_description!(),
     long_about = "\n\
     This is a simple CLI application that crawls a directory and runs a process on each file found.\n\
+    Without a command it converts, as `convert` does.\n\
+    Defaults for most flags can be kept in a `config.toml`, see `--config`.\n\
     ",
-    arg_required_else_help = true
+    args_conflicts_with_subcommands = true
 )]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// A bare invocation (`<INPUT_DIRECTORY> <INPUT_EXTENSION> <OUTPUT_EXTENSION> [OPTIONS]`) is a `convert`.
+    #[command(flatten)]
+    pub convert: ConvertArgs,
+}
+
+/// What to run, see `Cli::parse_command`.
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Convert every matching file below a directory, what a bare invocation does.
+    Convert(Box<ConvertArgs>),
+    /// Convert like `convert`, then keep converting new or modified files until Ctrl-C.
+    Watch(Box<ConvertArgs>),
+    /// Print the files `convert` would pick up with the same arguments, and where their outputs would go.
+    List(Box<ListArgs>),
+    /// Check that the converters are installed, and print their versions.
+    Doctor(DoctorArgs),
+    /// Remove what crashed runs left behind: partial and empty outputs, orphaned media folders and temp files.
+    Clean(CleanArgs),
+    /// Print a completion script for a shell, e.g. `completions bash > /etc/bash_completion.d/dcc`.
+    Completions(CompletionsArgs),
+}
+
+impl Command {
+    #[inline]
+    pub fn verbosity_level(&self) -> VerbosityLevel {
+        match self {
+            Command::Convert(args) | Command::Watch(args) => args.verbosity_level(),
+            Command::List(args) => args.convert.verbosity_level(),
+            Command::Doctor(args) => args.level_verbosity,
+            Command::Clean(args) => args.level_verbosity,
+            // Only the script goes to stdout
+            Command::Completions(_) => VerbosityLevel::Error,
+        }
+    }
+
+    #[inline]
+    pub fn log_format(&self) -> LogFormat {
+        match self {
+            Command::Convert(args) | Command::Watch(args) => args.log_format,
+            Command::List(args) => args.convert.log_format,
+            Command::Doctor(args) => args.log_format,
+            Command::Clean(args) => args.log_format,
+            Command::Completions(_) => LogFormat::Pretty,
+        }
+    }
+}
+
+#[rustfmt::skip]
+#[derive(Args, Debug, Clone)]
+pub struct DoctorArgs {
+    /// The engine whose converters are checked, `auto` checks both pandoc and LibreOffice.
+    #[arg(value_enum, short = 'c', long = "engine", help = "The engine whose converters are checked, auto checks pandoc, LibreOffice and the converters routed in.", required = false, default_value = "auto", value_hint = clap::ValueHint::Other)]
+    pub engine: Engine,
+
+    #[arg(value_enum, short = 'l', long = "level_verbosity", help = "The verbosity level of the logger.", required = false, default_value = "INFO", value_hint = clap::ValueHint::Other)]
+    pub level_verbosity: VerbosityLevel,
+
+    #[arg(value_enum, long = "log-format", help = "How log lines are written, json for log aggregation.", required = false, default_value = "pretty", value_hint = clap::ValueHint::Other)]
+    pub log_format: LogFormat,
+}
+
+#[rustfmt::skip]
+#[derive(Args, Debug, Clone)]
+pub struct ListArgs {
+    #[command(flatten)]
+    pub convert: ConvertArgs,
+
+    /// How the files are printed, `json` for scripts.
+    #[arg(value_enum, long = "format", help = "How the files are printed, json for scripts.", required = false, default_value = "text", value_hint = clap::ValueHint::Other)]
+    pub format: ListFormat,
+}
+
+#[rustfmt::skip]
+#[derive(Args, Debug, Clone)]
+pub struct CleanArgs {
+    /// The directory (and everything below it) to clean up.
+    #[arg(index = 1, help = "The directory to clean up, recursively.", default_value = ".", value_hint = clap::ValueHint::DirPath)]
+    pub directory: PathBuf,
+
+    /// The output extension(s) runs converted to, comma-separated, for the empty outputs and orphaned media folders
+    /// of the outputs runs recorded. Without any only partial outputs are removed.
+    #[arg(index = 2, help = "The output extension(s) runs converted to, comma-separated. Empty outputs with them, and media folders whose output is gone, are removed too, if a run recorded the output.", value_hint = clap::ValueHint::Other)]
+    pub output_extension: Option<String>,
+
+    /// Print what would be removed without removing it.
+    #[arg(short = 'n', long = "dry-run", help = "Print what would be removed without removing anything.", required = false)]
+    pub dry_run: bool,
+
+    #[arg(value_enum, short = 'l', long = "level_verbosity", help = "The verbosity level of the logger.", required = false, default_value = "INFO", value_hint = clap::ValueHint::Other)]
+    pub level_verbosity: VerbosityLevel,
+
+    #[arg(value_enum, long = "log-format", help = "How log lines are written, json for log aggregation.", required = false, default_value = "pretty", value_hint = clap::ValueHint::Other)]
+    pub log_format: LogFormat,
+}
+
+#[rustfmt::skip]
+#[derive(Args, Debug, Clone)]
+pub struct CompletionsArgs {
+    /// The shell to print the completion script for.
+    #[arg(value_enum, index = 1, help = "The shell to print the completion script for.", required = true, value_hint = clap::ValueHint::Other)]
+    pub shell: clap_complete::Shell,
+}
+
+/// Everything `convert`, `watch` and `list` (and a bare invocation) take.
+#[rustfmt::skip]
+#[derive(Args, Debug, Clone)]
+pub struct ConvertArgs {
     /// The directory to act as the root of the crawler.
+    /// Not used with `--input-list`, see `ConvertArgs::remap_for_input_list`.
+    ///
+    /// The positionals aren't `required` as far as clap is concerned, since they may come
+    /// from the config file, `ConvertArgs::check_required` checks for them once that's been merged.
     #[arg(index = 1, help = "The directory to act as the root of the crawler.", value_hint = clap::ValueHint::FilePath)]
-    pub input_directory: PathBuf,
+    pub input_directory: Option<PathBuf>,
 
-    /// The input extension to crawl for.
-    #[arg(index = 2, help = "The input extension to crawl for.", value_hint = clap::ValueHint::Other)]
-    pub input_extension: String,
+    /// The input extension(s) to crawl for, comma-separated (`docx,doc,rtf`) for several in one walk.
+    /// Optional with `--input-list`, where it filters the listed files.
+    #[arg(index = 2, help = "The input extension(s) to crawl for, comma-separated for several, e.g. docx,doc,rtf.", value_hint = clap::ValueHint::Other)]
+    pub input_extension: Option<String>,
 
-    /// The output extension to convert to. This is subject to Pandoc's supported formats.
-    #[arg(index = 3, help = "The output extension to convert to. This is subject to Pandoc's supported formats.", value_hint = clap::ValueHint::Other)]
-    pub output_extension: String,
+    /// The output extension(s) to convert to. This is subject to Pandoc's supported formats.
+    /// Comma-separated (`md,html,pdf`) converts every file to each, into a subfolder per format below `--output`.
+    #[arg(index = 3, help = "The output extension(s) to convert to, comma-separated for several, e.g. md,html,pdf. This is subject to Pandoc's supported formats.", value_hint = clap::ValueHint::Other)]
+    pub output_extension: Option<String>,
+
+    /// Convert the files listed (one per line) in this file, or stdin for `-`, instead of crawling a directory.
+    /// Blank lines and lines starting with `#` are ignored, relative paths are resolved against the current directory.
+    /// The positionals then become `[INPUT_EXTENSION] <OUTPUT_EXTENSION>`, with the input extension acting as a filter.
+    #[arg(long = "input-list", visible_alias = "files-from", help = "Convert the files listed (one per line) in this file, or stdin for -, instead of crawling a directory.", required = false, value_parser = existing_file_or_stdin, conflicts_with = "watch", value_hint = clap::ValueHint::FilePath)]
+    pub input_list: Option<PathBuf>,
+
+    /// Convert a single document read from stdin, or `--input`, and write the result to stdout, for shell pipelines
+    /// and editor integrations. The positionals then become `[INPUT_EXTENSION] <OUTPUT_EXTENSION>`,
+    /// the input extension saying what stdin holds. Logs go to stderr.
+    #[arg(long = "pipe", help = "Convert a single document from stdin (or --input) to stdout, the positionals become [INPUT_EXTENSION] <OUTPUT_EXTENSION>.", required = false, conflicts_with_all = ["input_list", "watch", "dry_run"])]
+    pub pipe: bool,
+
+    /// The document `--pipe` converts, instead of stdin. Its extension says what it is.
+    #[arg(long = "input", value_name = "FILE", help = "The document --pipe converts instead of stdin, its extension says what it is.", required = false, value_parser = existing_file, requires = "pipe", value_hint = clap::ValueHint::FilePath)]
+    pub pipe_input: Option<PathBuf>,
+
+    /// The `--input-list` separates its paths with NULs, as `find -print0` and `fd -0` write them,
+    /// so names with newlines in them come through. Every entry is taken as is, `#` included.
+    #[arg(short = '0', long = "null", help = "The --input-list separates its paths with NULs (find -print0, fd -0) instead of newlines.", required = false, requires = "input_list")]
+    pub null_separated: bool,
 
     /// A custom output directory that the converted file and its media container will be written to.
     #[arg(short = 'o', long = "output", help = "A custom output directory that the converted file and its media container will be written to", required = false, value_hint = clap::ValueHint::FilePath)]
@@ -43,26 +204,593 @@ pub struct Cli {
     #[arg(value_enum, short = 'l', long = "level_verbosity", help = "The verbosity level of the logger.", required = false, default_value = "INFO", value_hint = clap::ValueHint::Other)]
     pub level_verbosity: Option<VerbosityLevel>,
 
+    /// How log lines are written, `json` writes a JSON object per line
+    /// for log aggregation (Loki, ELK), without colors or thread ids.
+    #[arg(value_enum, long = "log-format", help = "How log lines are written, json for log aggregation.", required = false, default_value = "pretty", value_hint = clap::ValueHint::Other)]
+    pub log_format: LogFormat,
+
+    /// The conversion engine to use, `auto` picks one based on the input extension.
+    /// `--converter` is kept as an alias.
+    #[arg(value_enum, short = 'c', long = "engine", visible_alias = "converter", help = "The conversion engine to use, auto picks one based on the input extension.", required = false, default_value = "pandoc", value_hint = clap::ValueHint::Other)]
+    pub engine: Engine,
+
+    /// Extra argument passed through to pandoc, may be given multiple times.
+    /// Use the `--pandoc-arg=<VALUE>` form for values that start with a dash.
+    #[arg(long = "pandoc-arg", value_name = "VALUE", help = "Extra argument passed through to pandoc, may be given multiple times.", required = false, allow_hyphen_values = true, action = clap::ArgAction::Append, value_hint = clap::ValueHint::Other)]
+    pub pandoc_args: Vec<String>,
+
+    /// A pandoc defaults file (YAML) applied to every conversion, forwarded as `--defaults`.
+    /// Other pandoc options given here are placed after it, so they take precedence.
+    #[arg(long = "defaults", value_name = "FILE", help = "A pandoc defaults file (YAML) applied to every conversion.", required = false, value_parser = existing_file, value_hint = clap::ValueHint::FilePath)]
+    pub defaults: Option<PathBuf>,
+
+    /// A pandoc template to use for every conversion, forwarded as `--template`.
+    #[arg(long = "template", help = "A pandoc template to use for every conversion.", required = false, value_parser = existing_file, value_hint = clap::ValueHint::FilePath)]
+    pub template: Option<PathBuf>,
+
+    /// A reference document for styling docx/odt/pptx output, forwarded as `--reference-doc`.
+    #[arg(long = "reference-doc", help = "A reference document for styling docx/odt/pptx output.", required = false, value_parser = existing_file, value_hint = clap::ValueHint::FilePath)]
+    pub reference_doc: Option<PathBuf>,
+
+    /// A bibliography file for resolving citations, forwarded as `--bibliography`.
+    /// May be given multiple times, citations are only resolved with `--citeproc`.
+    #[arg(long = "bibliography", value_name = "FILE", help = "A bibliography file (.bib, .json, .yaml...) for resolving citations, may be given multiple times.", required = false, value_parser = existing_file, action = clap::ArgAction::Append, value_hint = clap::ValueHint::FilePath)]
+    pub bibliography: Vec<PathBuf>,
+
+    /// A CSL style for formatting citations and the bibliography, forwarded as `--csl`.
+    #[arg(long = "csl", value_name = "FILE", help = "A CSL style for formatting citations and the bibliography.", required = false, value_parser = existing_file, value_hint = clap::ValueHint::FilePath)]
+    pub csl: Option<PathBuf>,
+
+    /// Resolve citations and append a bibliography, forwarded as `--citeproc`.
+    /// Without it, `@key` citations are left in the output as they are.
+    #[arg(long = "citeproc", help = "Resolve citations and append a bibliography.", required = false)]
+    pub citeproc: bool,
+
+    /// A pandoc Lua filter applied to every conversion, forwarded as `--lua-filter`.
+    /// May be given multiple times, the filters run in the order given.
+    #[arg(long = "lua-filter", value_name = "FILE", help = "A pandoc Lua filter applied to every conversion, may be given multiple times.", required = false, value_parser = existing_file, action = clap::ArgAction::Append, value_hint = clap::ValueHint::FilePath)]
+    pub lua_filters: Vec<PathBuf>,
+
+    /// The program pandoc writes pdf outputs with, forwarded as `--pdf-engine`.
+    /// Without it a run converting to pdf picks the first installed of pdflatex, xelatex, lualatex, tectonic,
+    /// typst, weasyprint and wkhtmltopdf, and fails up front if there's none.
+    #[arg(value_enum, long = "pdf-engine", value_name = "ENGINE", help = "The program pandoc writes pdf with, by default the first one installed.", required = false, value_hint = clap::ValueHint::Other)]
+    pub pdf_engine: Option<PdfEngine>,
+
+    /// Start a single `pandoc server` and convert every file with a request to it, instead of a pandoc process per file.
+    /// Formats it can't convert (pdf) and the pandoc options naming files get a pandoc per file as before,
+    /// as does everything if this pandoc has no server mode. It doesn't extract images (`--extract-media`).
+    #[arg(long = "pandoc-server", help = "Convert through a single pandoc server instead of starting pandoc for every file.", required = false)]
+    pub pandoc_server: bool,
+
+    /// If there's no pandoc to be found, download the latest official release from GitHub,
+    /// check it against the checksum GitHub lists, and install it to the per-user cache for this and later runs.
+    /// Linux and macOS only, on Windows pandoc comes with the binary.
+    #[arg(long = "auto-install", help = "If pandoc isn't installed, download the official release into the per-user cache and use it (Linux, macOS).", required = false)]
+    pub auto_install: bool,
+
+    /// The pandoc binary to convert with, instead of the one in `PATH` or the embedded one (which isn't unpacked then).
+    /// Also read from `DCC_PANDOC_PATH`, the flag wins.
+    #[arg(long = "pandoc-path", value_name = "FILE", help = "The pandoc binary to use, over the embedded one and any in PATH.", required = false, value_parser = existing_file, value_hint = clap::ValueHint::FilePath)]
+    pub pandoc_path: Option<PathBuf>,
+
+    /// Refuse to start with a pandoc older than this, instead of failing file after file half way through the batch.
+    /// Options that need a newer pandoc (`--defaults` 2.8, `--citeproc` 2.11, `--embed-resources` 2.19) raise it on their own.
+    #[arg(long = "min-pandoc-version", value_name = "VERSION", help = "The oldest pandoc to convert with, e.g. 2.19.", required = false, value_hint = clap::ValueHint::Other)]
+    pub min_pandoc_version: Option<PandocVersion>,
+
+    /// Delete the unpacked built-in pandoc once the run ends (a panicking one too), instead of keeping it
+    /// in the per-user cache for the next run. Left there while another run is still using it.
+    /// Builds without a built-in pandoc have nothing to delete.
+    #[arg(long = "ephemeral-binary", help = "Delete the unpacked built-in pandoc once the run ends, instead of keeping it for the next one.", required = false, overrides_with = "keep_binary")]
+    pub ephemeral_binary: bool,
+
+    /// Keep the unpacked built-in pandoc for the next run, the default. Overrides `ephemeral_binary = true` in the config.
+    #[arg(long = "keep-binary", help = "Keep the unpacked built-in pandoc for the next run (the default).", required = false, overrides_with = "ephemeral_binary")]
+    pub keep_binary: bool,
+
+    /// Write every output straight into `--output` instead of mirroring the input folders.
+    /// Same-named outputs get the input's parent folder (`parent`, the default) or a number (`counter`) appended.
+    #[arg(value_enum, long = "flatten", value_name = "SUFFIX", help = "Write every output straight into --output, telling same-named files apart by parent folder (default) or counter.", required = false, num_args = 0..=1, require_equals = true, default_missing_value = "parent", value_hint = clap::ValueHint::Other)]
+    pub flatten: Option<FlattenSuffix>,
+
+    /// What to do with a file whose output already exists.
+    #[arg(value_enum, long = "on-exists", value_name = "POLICY", help = "What to do with a file whose output already exists.", required = false, default_value = "skip", value_hint = clap::ValueHint::Other)]
+    pub on_exists: OnExists,
+
+    /// Copy each input's modification time (and on unix, its permission bits) onto its output.
+    #[arg(long = "preserve-metadata", help = "Copy each input's modification time (and on unix, its permissions) onto its output.", required = false)]
+    pub preserve_metadata: bool,
+
+    /// Check each file's content against its extension before converting it: empty placeholders are skipped
+    /// and files that are really something else (an HTML page saved as `.docx`) are converted as what they are.
+    #[arg(long = "detect-content", help = "Check each file's content against its extension, skipping empty placeholders and converting misnamed files as what they really are.", required = false)]
+    pub detect_content: bool,
+
+    /// Read scanned PDFs and images with Tesseract and convert the text it found, instead of converting them as they are.
+    /// PDFs need poppler's `pdftoppm` too. Going to an image format they're still converted as images.
+    #[arg(long = "ocr", help = "Read scanned PDFs and images with Tesseract OCR and convert the text.", required = false)]
+    pub ocr: bool,
+
+    /// The language(s) Tesseract reads with `--ocr`, `+`-separated (`eng+deu`), as its traineddata files are named.
+    #[arg(long = "ocr-lang", value_name = "LANG", help = "The language(s) OCR reads, +-separated (eng+deu).", required = false, default_value = crate::conversion::ocr::DEFAULT_LANG, value_hint = clap::ValueHint::Other)]
+    pub ocr_lang: String,
+
+    /// Write a file per sheet of a spreadsheet going to csv or markdown: the first sheet to the output,
+    /// the others next to it as `<stem>-<sheet>.<ext>`. Without it a markdown output holds every sheet and a csv only the first.
+    #[arg(long = "split-sheets", help = "Write a file per sheet of a spreadsheet going to csv or markdown, as <stem>-<sheet>.<ext> after the first.", required = false)]
+    pub split_sheets: bool,
+
+    /// Give up on a single file's conversion after this many seconds.
+    /// The file is counted as failed and the rest of the batch carries on.
+    #[arg(short = 't', long = "timeout", value_name = "SECONDS", help = "Give up on a single file's conversion after this many seconds.", required = false, value_hint = clap::ValueHint::Other)]
+    pub timeout: Option<u64>,
+
+    /// The output file name, with `{stem}`, `{ext}`, `{parent}`, `{n}`, `{rel_dir}`, `{top}` and `{date}` placeholders.
+    /// `{n}` is the file's 1-based position in the batch.
+    /// With a `/` in it, it's a path template that picks the folders below the output root too.
+    #[arg(long = "output-pattern", visible_alias = "output-template", value_name = "PATTERN", help = "The output file name, with {stem}, {ext}, {parent}, {n}, {rel_dir}, {top} and {date} placeholders. With a / in it, the whole path below the output root.", required = false, default_value = "{stem}.{ext}", value_hint = clap::ValueHint::Other)]
+    pub output_pattern: String,
+
+    /// Retry a failed conversion up to this many times, backing off from 100ms up to 5s.
+    /// Meant for flaky network drives or files briefly locked by another program.
+    #[arg(long = "retries", value_name = "N", help = "Retry a failed conversion up to this many times, with exponential backoff.", required = false, default_value_t = 0, value_hint = clap::ValueHint::Other)]
+    pub retries: usize,
+
+    /// Only look this many folders deep below the input directory, 0 means root files only.
+    /// Unbounded if not given.
+    #[arg(long = "max-depth", value_name = "N", help = "Only look this many folders deep below the input directory, 0 means root files only.", required = false, value_hint = clap::ValueHint::Other)]
+    pub max_depth: Option<usize>,
+
+    /// Only convert files matching one of these globs (relative to the input directory, or just the file name).
+    #[arg(long = "include", value_name = "GLOB", help = "Only convert files matching this glob, may be given multiple times.", required = false, value_parser = glob_pattern, action = clap::ArgAction::Append, value_hint = clap::ValueHint::Other)]
+    pub include: Vec<String>,
+
+    /// Leave out files and folders matching any of these globs (relative to the input directory, or just the name),
+    /// e.g. `--exclude '**/Archive/**' --exclude '*draft*'`. Excluded folders aren't walked at all.
+    #[arg(long = "exclude", value_name = "GLOB", help = "Leave out files and folders matching this glob, may be given multiple times.", required = false, value_parser = glob_pattern, action = clap::ArgAction::Append, value_hint = clap::ValueHint::Other)]
+    pub exclude: Vec<String>,
+
+    /// Don't skip what `.gitignore`, `.ignore` and `.convertignore` files in (and above) the input directory ignore.
+    #[arg(long = "no-ignore", help = "Don't skip files that .gitignore, .ignore or .convertignore files ignore.", required = false)]
+    pub no_ignore: bool,
+
+    /// Walk into symlinked folders too, which are skipped by default as they may lead into huge unrelated trees
+    /// (mounted shares, home folders). A link back to one of its own parents is only walked once.
+    #[arg(long = "follow-symlinks", help = "Walk into symlinked folders too, a link back to one of its own parents is only walked once.", required = false)]
+    pub follow_symlinks: bool,
+
+    /// Skip files smaller than this, like stubs left behind by a sync client. Takes `K`, `M`, `G` and `T` (1024-based) suffixes.
+    #[arg(long = "min-size", value_name = "SIZE", help = "Skip files smaller than this (e.g. 1K), sizes take K, M, G and T suffixes.", required = false, value_parser = parse_size, value_hint = clap::ValueHint::Other)]
+    pub min_size: Option<u64>,
+
+    /// Skip files larger than this, like huge scans that stall pandoc. Takes `K`, `M`, `G` and `T` (1024-based) suffixes.
+    #[arg(long = "max-size", value_name = "SIZE", help = "Skip files larger than this (e.g. 100M), sizes take K, M, G and T suffixes.", required = false, value_parser = parse_size, value_hint = clap::ValueHint::Other)]
+    pub max_size: Option<u64>,
+
+    /// Skip files last modified before this: a UTC date (`2024-05-01`, `2024-05-01T08:30`) or how long ago (`7d`, `12h`).
+    /// Relative times count back from when the run starts.
+    #[arg(long = "modified-since", value_name = "WHEN", help = "Skip files last modified before this, a UTC date (2024-05-01) or how long ago (7d, 12h).", required = false, value_parser = point_in_time, value_hint = clap::ValueHint::Other)]
+    pub modified_since: Option<String>,
+
+    /// Skip files last modified at or after this, in the same formats as `--modified-since`.
+    #[arg(long = "modified-before", value_name = "WHEN", help = "Skip files last modified at or after this, a UTC date (2024-05-01) or how long ago (7d, 12h).", required = false, value_parser = point_in_time, value_hint = clap::ValueHint::Other)]
+    pub modified_before: Option<String>,
+
+    /// Convert files in this order rather than as they're found, say the small ones first for quick wins.
+    /// The whole tree is walked before the first conversion starts.
+    #[arg(value_enum, long = "order", value_name = "ORDER", help = "Convert files in this order instead of as they're found, after walking the whole tree.", required = false, value_hint = clap::ValueHint::Other)]
+    pub order: Option<Order>,
+
+    /// Only convert the first N files found (by name), to try a recipe out before running it on the whole tree.
+    #[arg(long = "limit", value_name = "N", help = "Only convert the first N files found (by name), to try a recipe out on a huge tree.", required = false, value_parser = at_least_one, conflicts_with = "sample", value_hint = clap::ValueHint::Other)]
+    pub limit: Option<usize>,
+
+    /// Only convert N files picked at random from the ones found, which are logged along with the seed that picked them.
+    #[arg(long = "sample", value_name = "N", help = "Only convert N files picked at random, the files and the --seed are logged so the run can be repeated.", required = false, value_parser = at_least_one, value_hint = clap::ValueHint::Other)]
+    pub sample: Option<usize>,
+
+    /// Picks the same `--sample` again, from an unchanged tree. Taken from the clock if not given.
+    #[arg(long = "seed", value_name = "SEED", help = "Seed for --sample, the same seed picks the same files from an unchanged tree.", required = false, requires = "sample", value_hint = clap::ValueHint::Other)]
+    pub seed: Option<u64>,
+
+    /// Characters replaced with `--replacement` in output paths below `--output`, and with `--sanitize-names` in input file
+    /// (and, with `--no-stream`, folder) names.
+    /// Every character in the string counts on its own, an empty string disables replacing.
+    #[arg(long = "danger-chars", visible_alias = "replace-chars", value_name = "CHARS", help = "Characters replaced with --replacement in file and folder names, each character counts on its own.", required = false, default_value = "~$", value_hint = clap::ValueHint::Other)]
+    pub danger_chars: String,
+
+    /// What each of the `--danger-chars` is replaced with, an empty string drops them.
+    #[arg(long = "replacement", value_name = "STRING", help = "What each of the --danger-chars is replaced with, an empty string drops them.", required = false, default_value = "_", value_parser = no_separators, value_hint = clap::ValueHint::Other)]
+    pub replacement: String,
+
+    /// Shorten file and folder names to at most this many characters, keeping the extension.
+    #[arg(long = "max-name-len", value_name = "N", help = "Shorten file and folder names to at most this many characters, keeping the extension.", required = false, value_parser = at_least_one, value_hint = clap::ValueHint::Other)]
+    pub max_name_len: Option<usize>,
+
+    /// Strip trailing dots and spaces from file and folder names, which Windows can't handle.
+    #[arg(long = "trim-trailing", help = "Strip trailing dots and spaces from file and folder names.", required = false)]
+    pub trim_trailing: bool,
+
+    /// Rename input files (and, with `--no-stream`, folders) whose names break the naming rules
+    /// (`--danger-chars`, `--max-name-len`, `--trim-trailing`),
+    /// appending every rename to `.document_converter_renames.jsonl` so it can be reversed.
+    /// Without it inputs are never renamed.
+    #[arg(long = "sanitize-names", help = "Rename inputs whose names break the naming rules, journaling every rename.", required = false)]
+    pub sanitize_names: bool,
+
+    /// How many conversions may run at the same time, defaults to the number of CPUs.
+    /// Every conversion is its own pandoc/soffice process, so this caps those too.
+    #[arg(short = 'j', long = "jobs", value_name = "N", help = "How many conversions may run at the same time, defaults to the number of CPUs.", required = false, value_parser = at_least_one, value_hint = clap::ValueHint::Other)]
+    pub jobs: Option<usize>,
+
+    /// Discover (and sanitize) the whole input tree before starting any conversion,
+    /// instead of converting files as they're found.
+    #[arg(long = "no-stream", help = "Discover (and sanitize) the whole input tree before starting any conversion.", required = false)]
+    pub no_stream: bool,
+
+    /// Stop the whole run on the first failed conversion, instead of carrying on.
+    #[arg(long = "fail-fast", help = "Stop the whole run on the first failed conversion.", required = false)]
+    pub fail_fast: bool,
+
+    /// On Ctrl-C or SIGTERM (Ctrl-Break on Windows), no new conversion is started and the ones running get this many seconds
+    /// to finish before they're killed, a second Ctrl-C kills them straight away. Either way their partial outputs are removed,
+    /// the summary and reports are written as usual, and the run exits with 130.
+    #[arg(long = "grace-period", value_name = "SECONDS", help = "On Ctrl-C, how many seconds the conversions running get to finish before they're killed.", required = false, default_value_t = DEFAULT_GRACE_SECS, value_hint = clap::ValueHint::Other)]
+    pub grace_period: u64,
+
+    /// Also exit non-zero if any warning was logged, e.g. a skipped existing output
+    /// or an input extension the converter doesn't list as supported.
+    #[arg(long = "strict", help = "Also exit non-zero if any warning was logged.", required = false)]
+    pub strict: bool,
+
+    /// Print the input and output formats the selected converter supports, then exit.
+    /// No files are converted.
+    #[arg(long = "list-formats", help = "Print the formats the selected converter supports, then exit.", required = false)]
+    pub list_formats: bool,
+
+    /// Skip the files an interrupted run already converted, as recorded in its checkpoint
+    /// (`.document_converter_checkpoint.jsonl` in the output directory).
+    /// Without it the checkpoint is started over.
+    #[arg(long = "resume", help = "Skip the files an interrupted run already converted, going by its checkpoint file.", required = false)]
+    pub resume: bool,
+
+    /// Print the planned `input -> output` mapping (renames included) and exit,
+    /// without renaming, creating or converting anything.
+    #[arg(short = 'n', long = "dry-run", help = "Print what would be renamed and converted, and to where, without changing anything.", required = false, conflicts_with = "watch")]
+    pub dry_run: bool,
+
+    /// Keep running after the initial batch, converting new or modified files as they appear.
+    #[arg(short = 'w', long = "watch", help = "Keep running after the initial batch, converting new or modified files until Ctrl-C.", required = false)]
+    pub watch: bool,
+
+    /// Also write the log to this file, as `--log-format` says but never colored,
+    /// at `--log-file-level` whatever the console's verbosity.
+    #[arg(long = "log-file", value_name = "PATH", help = "Also write the log to this file, at --log-file-level whatever the console's verbosity.", required = false, value_hint = clap::ValueHint::FilePath)]
+    pub log_file: Option<PathBuf>,
+
+    /// The verbosity of the `--log-file`, independent of `--level_verbosity`.
+    #[arg(value_enum, long = "log-file-level", value_name = "LEVEL", help = "The verbosity of the --log-file, independent of the console's.", required = false, default_value = "INFO", requires = "log_file", value_hint = clap::ValueHint::Other)]
+    pub log_file_level: VerbosityLevel,
+
+    /// When the `--log-file` moves on to a new file: `hourly` and `daily` add the date to its name,
+    /// `size` moves it to `<file>.1` once it reaches `--log-max-size`.
+    #[arg(value_enum, long = "log-rotation", value_name = "WHEN", help = "When the --log-file moves on to a new file, by time or once it reaches --log-max-size.", required = false, default_value = "never", requires = "log_file", value_hint = clap::ValueHint::Other)]
+    pub log_rotation: LogRotation,
+
+    /// How big the `--log-file` may get with `--log-rotation size`. Takes `K`, `M`, `G` and `T` (1024-based) suffixes.
+    #[arg(long = "log-max-size", value_name = "SIZE", help = "How big the --log-file may get with --log-rotation size (e.g. 10M).", required = false, default_value = "10M", value_parser = parse_size, requires = "log_file", value_hint = clap::ValueHint::Other)]
+    pub log_max_size: u64,
+
+    /// How many rotated `--log-file`s are kept, the oldest are removed.
+    #[arg(long = "log-keep", value_name = "N", help = "How many rotated --log-file files are kept.", required = false, default_value_t = 7, requires = "log_file", value_hint = clap::ValueHint::Other)]
+    pub log_keep: usize,
+
+    /// Append a plain-text line per conversion (input, output, result) to this file once the run finishes.
+    #[arg(long = "conversion-log", value_name = "PATH", help = "Append a plain-text line per conversion to this file once the run finishes.", required = false, value_hint = clap::ValueHint::FilePath)]
+    pub conversion_log: Option<PathBuf>,
+
+    /// Also print the `--conversion-log` to stdout.
+    #[arg(long = "log-tee", help = "Also print the --conversion-log to stdout.", required = false, requires = "conversion_log")]
+    pub log_tee: bool,
+
+    /// Write every file's lifecycle (discovered, started, succeeded, failed, skipped) to this path
+    /// as one JSON object per line, with timestamps, paths, the engine and durations.
+    /// `-` writes them to stdout, the log then goes to stderr.
+    #[arg(long = "events", value_name = "PATH|-", help = "Write every file's lifecycle events to this path as JSON lines, - for stdout.", required = false, value_hint = clap::ValueHint::FilePath)]
+    pub events: Option<PathBuf>,
+
+    /// Serve Prometheus metrics at `http://<ADDR>/metrics` while the run lasts, for watch mode in particular:
+    /// files converted, failed and skipped, conversion durations and file sizes, queue depth and conversions in flight.
+    #[arg(long = "metrics-addr", value_name = "ADDR", help = "Serve Prometheus metrics at http://<ADDR>/metrics while the run lasts, e.g. 127.0.0.1:9898.", required = false, value_hint = clap::ValueHint::Other)]
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// Show a live dashboard instead of the log while converting: what each job slot is converting,
+    /// overall progress and throughput, and the warnings and errors logged, in a pane of their own.
+    /// `p` pauses and resumes, `s` skips the file of the lane picked with the arrow keys,
+    /// `q` stops once the conversions running are finished, and aborts them when pressed again.
+    /// The log is printed once the dashboard closes.
+    #[arg(long = "tui", help = "Show a live dashboard of the conversions instead of the log, with keys to pause, skip a file or stop.", required = false, conflicts_with_all = ["watch", "pipe", "dry_run"])]
+    pub tui: bool,
+
+    /// Write a per-file conversion report (input, output, status, duration, error) to this path once the run finishes.
+    /// A `.html` report also has the run's totals, to be opened in a browser.
+    #[arg(long = "report", help = "Write a per-file conversion report to this path once the run finishes, .json, .csv or .html.", required = false, value_hint = clap::ValueHint::FilePath)]
+    pub report: Option<PathBuf>,
+
+    /// The format of the `--report` file, by default going by its extension (JSON if it's neither `.csv` nor `.html`).
+    #[arg(value_enum, long = "report-format", help = "The format of the report file, by default going by its extension.", required = false, requires = "report", value_hint = clap::ValueHint::Other)]
+    pub report_format: Option<ReportFormat>,
+
+    /// Write every failed file, with the engine used, its exit code and stderr, to this path as JSON.
+    #[arg(long = "error-report", value_name = "PATH", help = "Write every failed file, with the engine used, its exit code and stderr, to this path as JSON.", required = false, value_hint = clap::ValueHint::FilePath)]
+    pub error_report: Option<PathBuf>,
+
+    /// How the run's totals are given once it finishes: `text` only logs them, `json` also prints a single JSON document
+    /// to stdout (the log then goes to stderr) with the file counts, success rate, durations and failed paths.
+    #[arg(value_enum, long = "summary", value_name = "FORMAT", help = "How the run's totals are given, json prints them to stdout as a single JSON document.", required = false, default_value = "text", value_hint = clap::ValueHint::Other)]
+    pub summary: SummaryFormat,
+
+    /// Write the `--summary json` document to this path instead of stdout, whatever `--summary` says.
+    #[arg(long = "summary-file", value_name = "PATH", help = "Write the JSON summary to this path instead of stdout.", required = false, value_hint = clap::ValueHint::FilePath)]
+    pub summary_file: Option<PathBuf>,
+
+    /// Copy every input that failed to convert (after any retries) into this folder,
+    /// next to a `<name>.error.txt` note with the error and the converter's stderr.
+    #[arg(long = "quarantine-dir", value_name = "DIR", help = "Copy every input that failed to convert into this folder, with a note holding the error and stderr.", required = false, value_hint = clap::ValueHint::DirPath)]
+    pub quarantine_dir: Option<PathBuf>,
+
+    /// Move failed inputs into `--quarantine-dir` instead of copying them.
+    #[arg(long = "quarantine-move", help = "Move failed inputs into --quarantine-dir instead of copying them.", required = false, requires = "quarantine_dir")]
+    pub quarantine_move: bool,
+
+    /// Once every output of a source was written (and isn't empty), delete the source,
+    /// or with `move`, move it into `converted-originals/` below the input directory.
+    /// Only the initial batch in `--watch` mode.
+    #[arg(value_enum, long = "delete-source", value_name = "MODE", help = "Delete each source once its outputs were written and aren't empty, or move it into converted-originals/ with =move.", required = false, num_args = 0..=1, require_equals = true, default_missing_value = "delete", value_hint = clap::ValueHint::Other)]
+    pub delete_source: Option<SourceDisposal>,
+
+    /// Load defaults from this file instead of the per-user `config.toml`.
+    #[arg(long = "config", value_name = "PATH", help = "Load defaults from this file instead of the per-user config.toml.", required = false, value_parser = existing_file, value_hint = clap::ValueHint::FilePath)]
+    pub config: Option<PathBuf>,
+
+    /// Print the effective configuration (config file merged with the command line) as TOML, then exit.
+    #[arg(long = "print-config", help = "Print the effective configuration as TOML, then exit.", required = false)]
+    pub print_config: bool,
+
+    /// Warnings from loading the config file, logged once the logger is up.
+    #[arg(skip)]
+    pub config_warnings: Vec<String>,
+
+    /// The soffice binary from the config's `[libreoffice]` table, there's no flag for it.
+    #[arg(skip)]
+    pub libreoffice_program: Option<PathBuf>,
+
     // /// Other version flag
     // #[arg(short = 'v', long = "version", help = "Prints version information", required = false)]
     // pub version: bool,
 }
 
 impl Cli {
-    pub fn new() -> Self {
-        let s = Self::parse();
+    /// Parses the command line into the `Command` to run, a bare invocation being a `convert`.
+    /// For `convert`, `watch` and `list` the config file is merged in first, see `ConvertArgs::prepare`.
+    pub fn parse_command() -> Command {
+        let matches = Self::command().get_matches();
+        let cli = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+        let (mut command, matches) = match cli.command {
+            Some(command) => {
+                let (_, matches) =
+                    matches.subcommand().expect("clap parsed a subcommand");
+                (command, matches.clone())
+            }
+            None => (Command::Convert(Box::new(cli.convert)), matches),
+        };
+
+        match command {
+            Command::Convert(ref mut args) => args.prepare(&matches, false),
+            Command::Watch(ref mut args) => {
+                if args.dry_run || args.pipe || args.input_list.is_some() || args.tui {
+                    Cli::command()
+                        .error(
+                            ErrorKind::ArgumentConflict,
+                            "watch can't be combined with --dry-run, --pipe, --input-list or --tui",
+                        )
+                        .exit();
+                }
+                args.watch = true;
+                args.prepare(&matches, false);
+            }
+            Command::List(ref mut args) => args.convert.prepare(&matches, true),
+            Command::Doctor(_) | Command::Clean(_) | Command::Completions(_) => {}
+        }
+        command
+    }
+
+    /// Writes the completion script for `shell` to stdout,
+    /// covering every command, flag and the values of the flags that take one of a set.
+    pub fn print_completions(shell: clap_complete::Shell) {
+        let mut command = Self::command();
+        let name = command.get_name().to_string();
+        clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+    }
+}
 
-        if let Some(output_dir) = &s.output_directory
-            && !output_dir.exists()
+impl ConvertArgs {
+    /// Moves the positionals into place, merges in the config file and checks nothing required is missing,
+    /// exiting with a usage error if something is.
+    /// `listing` (the `list` command) only needs to know what to look for, not what to convert it to.
+    fn prepare(&mut self, matches: &ArgMatches, listing: bool) {
+        if self.input_list.is_some() || self.pipe {
+            self.remap_for_input_list();
+        }
+
+        self.load_config(matches);
+
+        if self.print_config {
+            match config::Config::from(&*self).to_toml() {
+                Ok(toml) => print!("{toml}"),
+                Err(e) => Cli::command().error(ErrorKind::Io, e).exit(),
+            }
+            std::process::exit(0);
+        }
+
+        self.check_required(listing);
+    }
+
+    /// Merges in the `--config` file, or the first of `./document_converter.toml` and
+    /// the per-user `config.toml` that exists (see `Config::find`).
+    /// Anything given on the command line takes precedence.
+    fn load_config(&mut self, matches: &ArgMatches) {
+        let Some(path) = self.config.clone().or_else(config::Config::find) else {
+            return;
+        };
+
+        let config = config::Config::load(&path).unwrap_or_else(|e| {
+            Cli::command()
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("failed to load config {}: {e}", path.display()),
+                )
+                .exit()
+        });
+
+        self.config_warnings = config.unknown_key_warnings(&path);
+        config.merge_into(self, matches);
+
+        // These bypass the `existing_file` value parser when they come from the config
+        let files = [
+            &self.defaults,
+            &self.template,
+            &self.reference_doc,
+            &self.csl,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(&self.bibliography)
+        .chain(&self.lua_filters);
+        for file in files {
+            if !file.is_file() {
+                Cli::command()
+                    .error(
+                        ErrorKind::InvalidValue,
+                        format!(
+                            "file not found (from {}): {}",
+                            path.display(),
+                            file.display()
+                        ),
+                    )
+                    .exit();
+            }
+        }
+    }
+
+    /// Exits with a usage error if the positionals are still missing after merging the config.
+    /// For `listing` the output extension is optional.
+    fn check_required(&self, listing: bool) {
+        if self.list_formats {
+            return;
+        }
+
+        let (missing, required) = if listing {
+            let missing = self.input_list.is_none()
+                && (self.input_directory.is_none() || self.input_extensions().is_empty());
+            (missing, "<INPUT_DIRECTORY> <INPUT_EXTENSION> are")
+        } else if self.pipe {
+            let missing = self.output_extensions().is_empty()
+                || (self.pipe_input.is_none() && self.input_extensions().is_empty());
+            (
+                missing,
+                "<INPUT_EXTENSION> <OUTPUT_EXTENSION> (or --input and <OUTPUT_EXTENSION>) are",
+            )
+        } else if self.input_list.is_some() {
+            (self.output_extensions().is_empty(), "<OUTPUT_EXTENSION> is")
+        } else {
+            let missing = self.input_directory.is_none()
+                || self.input_extensions().is_empty()
+                || self.output_extensions().is_empty();
+            (missing, "<INPUT_DIRECTORY> <INPUT_EXTENSION> <OUTPUT_EXTENSION> are")
+        };
+
+        if missing {
+            Cli::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    format!("{required} required, either on the command line or in the config file"),
+                )
+                .exit();
+        }
+        if self.pipe
+            && (self.output_extensions().len() > 1
+                || (self.pipe_input.is_none() && self.input_extensions().len() > 1))
         {
-            std::fs::create_dir_all(output_dir).unwrap();
+            Cli::command()
+                .error(
+                    ErrorKind::WrongNumberOfValues,
+                    "--pipe converts a single document, from one format to one other",
+                )
+                .exit();
         }
+        if self.flatten.is_some() && self.output_directory.is_none() {
+            Cli::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "--flatten needs an output directory (--output), \
+                     either on the command line or in the config file",
+                )
+                .exit();
+        }
+    }
+
+    /// With `--input-list` or `--pipe` there's no input directory, so clap hands the
+    /// `[INPUT_EXTENSION] <OUTPUT_EXTENSION>` positionals to the first two slots.
+    /// This moves them to where they belong, or exits with a usage error.
+    fn remap_for_input_list(&mut self) {
+        let mut values: Vec<String> = [
+            self.input_directory.take().map(|p| p.display().to_string()),
+            self.input_extension.take(),
+            self.output_extension.take(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
 
-        // if s.version {
-        //     println!("{} {}", crate::crate_name!(), crate::crate_version!());
-        //     std::process::exit(0);
-        // }
-        s
+        if values.is_empty() || values.len() > 2 {
+            Cli::command()
+                .error(
+                    ErrorKind::WrongNumberOfValues,
+                    "with --input-list or --pipe, pass only [INPUT_EXTENSION] <OUTPUT_EXTENSION>",
+                )
+                .exit();
+        }
+
+        self.output_extension = values.pop();
+        self.input_extension = values.pop();
+    }
+
+    /// The root directory to crawl, `.` when converting from `--input-list`.
+    #[inline]
+    pub fn input_directory(&self) -> &Path {
+        self.input_directory
+            .as_deref()
+            .unwrap_or_else(|| Path::new("."))
+    }
+
+    /// The input extensions to crawl for, without dots.
+    /// Only ever empty with `--input-list`, where they're a filter.
+    pub fn input_extensions(&self) -> Vec<String> {
+        self.input_extension
+            .as_deref()
+            .map(crate::conversion::split_extensions)
+            .unwrap_or_default()
+    }
+
+    /// The output extensions to convert to, without dots.
+    pub fn output_extensions(&self) -> Vec<String> {
+        self.output_extension
+            .as_deref()
+            .map(crate::conversion::split_extensions)
+            .unwrap_or_default()
     }
 
     #[inline]
@@ -71,6 +799,55 @@ impl Cli {
     }
 }
 
+/// Clap value parser for `--input-list`, which also takes `-` for stdin.
+fn existing_file_or_stdin(s: &str) -> std::result::Result<PathBuf, String> {
+    if s == crate::conversion::STDIN_LIST {
+        return Ok(PathBuf::from(s));
+    }
+    existing_file(s)
+}
+
+/// Clap value parser for flags that must point at an existing file,
+/// so a typo is reported up front instead of failing every conversion mid-batch.
+fn existing_file(s: &str) -> std::result::Result<PathBuf, String> {
+    let path = PathBuf::from(s);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(format!("file not found: {}", path.display()))
+    }
+}
+
+/// Clap value parser for text that ends up in a file name, like `--replacement`.
+fn no_separators(s: &str) -> std::result::Result<String, String> {
+    if s.contains(std::path::is_separator) {
+        return Err("must not contain a path separator".to_string());
+    }
+    Ok(s.to_string())
+}
+
+/// Clap value parser for `--include`/`--exclude`, so a broken glob is reported up front.
+fn glob_pattern(s: &str) -> std::result::Result<String, String> {
+    globset::Glob::new(s)
+        .map(|_| s.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Clap value parser for `--modified-since`/`--modified-before`.
+/// Kept as given, so a relative time is only resolved once the run starts.
+fn point_in_time(s: &str) -> std::result::Result<String, String> {
+    parse_time(s).map(|_| s.to_string())
+}
+
+/// Clap value parser for counts where 0 makes no sense, like `--jobs`.
+fn at_least_one(s: &str) -> std::result::Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 /// The verbosity level of the logger.
 ///
 /// The least verbose as 0 (Error -> Error Only)
@@ -90,6 +867,33 @@ pub enum VerbosityLevel {
     Trace,
 }
 
+/// How `list` prints the files it finds.
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq, Eq, Default)]
+#[clap(name = "ListFormat", rename_all = "lower")]
+pub enum ListFormat {
+    /// One file per line, `input -> output [converter]` once there's an output format.
+    #[default]
+    #[value(name = "text", alias = "TEXT")]
+    Text,
+    /// A JSON array of `{"input", "renamed", "output", "converter", "skipped"}` objects,
+    /// just `{"input"}` without an output format.
+    #[value(name = "json", alias = "JSON")]
+    Json,
+}
+
+/// How the totals are given once a run finishes (`--summary`).
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq, Eq, Default)]
+#[clap(name = "SummaryFormat", rename_all = "lower")]
+pub enum SummaryFormat {
+    /// Logged as prose, as always.
+    #[default]
+    #[value(name = "text", alias = "TEXT")]
+    Text,
+    /// Also printed as a JSON object, see `report::Summary`.
+    #[value(name = "json", alias = "JSON")]
+    Json,
+}
+
 impl From<VerbosityLevel> for tracing_subscriber::filter::EnvFilter {
     #[inline]
     fn from(level: VerbosityLevel) -> Self {
@@ -0,0 +1,578 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::{ConvertArgs, SummaryFormat, VerbosityLevel};
+use crate::conversion::naming::FlattenSuffix;
+use crate::conversion::order::Order;
+use crate::conversion::originals::SourceDisposal;
+use crate::conversion::pandoc::PandocVersion;
+use crate::conversion::pdf::PdfEngine;
+use crate::conversion::report::ReportFormat;
+use crate::conversion::{Engine, OnExists};
+use crate::log_file::LogRotation;
+use crate::prelude::*;
+
+/// Folder (below the platform's config dir) holding `config.toml`.
+const CONFIG_DIR_NAME: &str = "document_conversion_crawler";
+const CONFIG_FILE_NAME: &str = "config.toml";
+/// Picked up from the current directory, ahead of the per-user `config.toml`,
+/// so a job that's run repeatedly can keep its settings next to it.
+const PROJECT_FILE_NAME: &str = "document_converter.toml";
+
+/// Defaults loaded from `config.toml`, mirroring the CLI flags of the same name.
+///
+/// Every field is optional, anything given on the command line wins.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Config {
+    pub input_directory:    Option<PathBuf>,
+    pub input_extension:    Option<String>,
+    pub output_extension:   Option<String>,
+    pub output_directory:   Option<PathBuf>,
+    #[serde(default, with = "value_enum")]
+    pub level_verbosity:    Option<VerbosityLevel>,
+    #[serde(default, with = "value_enum")]
+    pub log_format:         Option<LogFormat>,
+    #[serde(default, with = "value_enum", alias = "converter")]
+    pub engine:             Option<Engine>,
+    pub pandoc_args:        Option<Vec<String>>,
+    pub defaults:           Option<PathBuf>,
+    pub template:           Option<PathBuf>,
+    pub reference_doc:      Option<PathBuf>,
+    pub bibliography:       Option<Vec<PathBuf>>,
+    pub csl:                Option<PathBuf>,
+    pub citeproc:           Option<bool>,
+    pub lua_filters:        Option<Vec<PathBuf>>,
+    #[serde(default, with = "value_enum")]
+    pub pdf_engine:         Option<PdfEngine>,
+    pub pandoc_server:      Option<bool>,
+    pub pandoc_path:        Option<PathBuf>,
+    pub auto_install:       Option<bool>,
+    pub ephemeral_binary:   Option<bool>,
+    pub min_pandoc_version: Option<PandocVersion>,
+    pub output_pattern:     Option<String>,
+    #[serde(default, with = "value_enum")]
+    pub flatten:            Option<FlattenSuffix>,
+    #[serde(default, with = "value_enum")]
+    pub on_exists:          Option<OnExists>,
+    pub preserve_metadata:  Option<bool>,
+    pub detect_content:     Option<bool>,
+    pub ocr:                Option<bool>,
+    pub ocr_lang:           Option<String>,
+    pub split_sheets:       Option<bool>,
+    pub timeout:            Option<u64>,
+    pub retries:            Option<usize>,
+    pub max_depth:          Option<usize>,
+    pub include:            Option<Vec<String>>,
+    pub exclude:            Option<Vec<String>>,
+    pub no_ignore:          Option<bool>,
+    pub follow_symlinks:    Option<bool>,
+    #[serde(default, with = "byte_size")]
+    pub min_size:           Option<u64>,
+    #[serde(default, with = "byte_size")]
+    pub max_size:           Option<u64>,
+    pub modified_since:     Option<String>,
+    pub modified_before:    Option<String>,
+    #[serde(default, with = "value_enum")]
+    pub order:              Option<Order>,
+    pub limit:              Option<usize>,
+    pub sample:             Option<usize>,
+    pub seed:               Option<u64>,
+    #[serde(alias = "replace_chars")]
+    pub danger_chars:       Option<String>,
+    pub replacement:        Option<String>,
+    pub max_name_len:       Option<usize>,
+    pub trim_trailing:      Option<bool>,
+    pub sanitize_names:     Option<bool>,
+    pub jobs:               Option<usize>,
+    pub no_stream:          Option<bool>,
+    pub fail_fast:          Option<bool>,
+    pub grace_period:       Option<u64>,
+    pub strict:             Option<bool>,
+    pub log_file:           Option<PathBuf>,
+    #[serde(default, with = "value_enum")]
+    pub log_file_level:     Option<VerbosityLevel>,
+    #[serde(default, with = "value_enum")]
+    pub log_rotation:       Option<LogRotation>,
+    #[serde(default, with = "byte_size")]
+    pub log_max_size:       Option<u64>,
+    pub log_keep:           Option<usize>,
+    pub conversion_log:     Option<PathBuf>,
+    pub log_tee:            Option<bool>,
+    pub events:             Option<PathBuf>,
+    pub metrics_addr:       Option<SocketAddr>,
+    pub tui:                Option<bool>,
+    pub report:             Option<PathBuf>,
+    #[serde(default, with = "value_enum")]
+    pub report_format:      Option<ReportFormat>,
+    pub error_report:       Option<PathBuf>,
+    #[serde(default, with = "value_enum")]
+    pub summary:            Option<SummaryFormat>,
+    pub summary_file:       Option<PathBuf>,
+    pub quarantine_dir:     Option<PathBuf>,
+    pub quarantine_move:    Option<bool>,
+    #[serde(default, with = "value_enum")]
+    pub delete_source:      Option<SourceDisposal>,
+    /// `[pandoc]`, an alternative spelling of the top-level pandoc settings.
+    pub pandoc:             Option<PandocSection>,
+    /// `[libreoffice]`, settings only the config file has.
+    pub libreoffice:        Option<LibreOfficeSection>,
+
+    /// Anything we don't recognise, reported with a warning rather than failing the load.
+    #[serde(flatten, skip_serializing)]
+    pub unknown: BTreeMap<String, toml::Value>,
+}
+
+/// The `[pandoc]` table.
+///
+/// Used for any of the top-level pandoc settings (`args` standing in for `pandoc_args`)
+/// not set at the top level.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PandocSection {
+    pub args:          Option<Vec<String>>,
+    pub defaults:      Option<PathBuf>,
+    pub template:      Option<PathBuf>,
+    pub reference_doc: Option<PathBuf>,
+    pub bibliography:  Option<Vec<PathBuf>>,
+    pub csl:           Option<PathBuf>,
+    pub citeproc:      Option<bool>,
+    pub lua_filters:   Option<Vec<PathBuf>>,
+    #[serde(default, with = "value_enum")]
+    pub pdf_engine:    Option<PdfEngine>,
+    /// Stands in for `pandoc_server`.
+    pub server:        Option<bool>,
+    /// Stands in for `pandoc_path`.
+    pub path:          Option<PathBuf>,
+    /// Stands in for `min_pandoc_version`.
+    pub min_version:   Option<PandocVersion>,
+
+    #[serde(flatten, skip_serializing)]
+    pub unknown: BTreeMap<String, toml::Value>,
+}
+
+/// The `[libreoffice]` table.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LibreOfficeSection {
+    /// The soffice binary to run, instead of the first one found in PATH.
+    pub program: Option<PathBuf>,
+
+    #[serde(flatten, skip_serializing)]
+    pub unknown: BTreeMap<String, toml::Value>,
+}
+
+impl Config {
+    /// The config file to load when `--config` isn't given:
+    /// `./document_converter.toml` if it exists, otherwise the per-user `config.toml` if that does.
+    pub fn find() -> Option<PathBuf> {
+        let project = PathBuf::from(PROJECT_FILE_NAME);
+        if project.is_file() {
+            return Some(project);
+        }
+        Self::default_path().filter(|path| path.is_file())
+    }
+
+    /// `~/.config/document_conversion_crawler/config.toml`,
+    /// or `%APPDATA%\document_conversion_crawler\config.toml` on Windows.
+    pub fn default_path() -> Option<PathBuf> {
+        #[cfg(windows)]
+        let base = std::env::var_os("APPDATA").map(PathBuf::from);
+        #[cfg(not(windows))]
+        let base =
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"));
+
+        base.map(|base| base.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+    }
+
+    /// Reads and parses the config file at `path`.
+    ///
+    /// # Errors
+    /// * If the file can't be read, or isn't valid TOML for a `Config`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// The config as TOML, leaving out unset fields.
+    ///
+    /// # Errors
+    /// * If serialization fails.
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// One warning message per unrecognised key, tables included.
+    pub fn unknown_key_warnings(&self, path: &Path) -> Vec<String> {
+        let tables = [
+            ("pandoc.", self.pandoc.as_ref().map(|p| &p.unknown)),
+            ("libreoffice.", self.libreoffice.as_ref().map(|l| &l.unknown)),
+        ];
+
+        std::iter::once(("", Some(&self.unknown)))
+            .chain(tables)
+            .filter_map(|(prefix, unknown)| Some((prefix, unknown?)))
+            .flat_map(|(prefix, unknown)| unknown.keys().map(move |key| (prefix, key)))
+            .map(|(prefix, key)| {
+                format!(
+                    "Ignoring unknown key '{prefix}{key}' in config file {}",
+                    path.display()
+                )
+            })
+            .collect()
+    }
+
+    /// Fills in every field of `cli` that wasn't given on the command line.
+    pub fn merge_into(mut self, cli: &mut ConvertArgs, matches: &ArgMatches) {
+        let from_cli =
+            |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        macro_rules! merge {
+            ($($field:ident),+ $(,)?) => {
+                $(
+                    if !from_cli(stringify!($field))
+                        && let Some(value) = self.$field
+                    {
+                        cli.$field = value.into();
+                    }
+                )+
+            };
+        }
+
+        if let Some(pandoc) = self.pandoc.take() {
+            self.pandoc_args = self.pandoc_args.or(pandoc.args);
+            self.defaults = self.defaults.or(pandoc.defaults);
+            self.template = self.template.or(pandoc.template);
+            self.reference_doc = self.reference_doc.or(pandoc.reference_doc);
+            self.bibliography = self.bibliography.or(pandoc.bibliography);
+            self.csl = self.csl.or(pandoc.csl);
+            self.citeproc = self.citeproc.or(pandoc.citeproc);
+            self.lua_filters = self.lua_filters.or(pandoc.lua_filters);
+            self.pdf_engine = self.pdf_engine.or(pandoc.pdf_engine);
+            self.pandoc_server = self.pandoc_server.or(pandoc.server);
+            self.pandoc_path = self.pandoc_path.or(pandoc.path);
+            self.min_pandoc_version = self.min_pandoc_version.or(pandoc.min_version);
+        }
+        cli.libreoffice_program = self.libreoffice.take().and_then(|l| l.program);
+
+        // Positionals may have been shuffled by `ConvertArgs::remap_for_input_list`,
+        // so they're only filled in if still empty.
+        cli.input_directory = cli.input_directory.take().or(self.input_directory);
+        cli.input_extension = cli.input_extension.take().or(self.input_extension);
+        cli.output_extension = cli.output_extension.take().or(self.output_extension);
+
+        // `--keep-binary` turns it off however it was set
+        if !from_cli("keep_binary")
+            && !from_cli("ephemeral_binary")
+            && let Some(ephemeral) = self.ephemeral_binary
+        {
+            cli.ephemeral_binary = ephemeral;
+        }
+
+        merge!(
+            output_directory,
+            level_verbosity,
+            log_format,
+            engine,
+            pandoc_args,
+            defaults,
+            template,
+            reference_doc,
+            bibliography,
+            csl,
+            citeproc,
+            lua_filters,
+            pdf_engine,
+            pandoc_server,
+            pandoc_path,
+            auto_install,
+            min_pandoc_version,
+            output_pattern,
+            flatten,
+            on_exists,
+            preserve_metadata,
+            detect_content,
+            ocr,
+            ocr_lang,
+            split_sheets,
+            timeout,
+            retries,
+            max_depth,
+            include,
+            exclude,
+            no_ignore,
+            follow_symlinks,
+            min_size,
+            max_size,
+            modified_since,
+            modified_before,
+            order,
+            limit,
+            sample,
+            seed,
+            danger_chars,
+            replacement,
+            max_name_len,
+            trim_trailing,
+            sanitize_names,
+            jobs,
+            no_stream,
+            fail_fast,
+            grace_period,
+            strict,
+            log_file,
+            log_file_level,
+            log_rotation,
+            log_max_size,
+            log_keep,
+            conversion_log,
+            log_tee,
+            events,
+            metrics_addr,
+            tui,
+            report,
+            report_format,
+            error_report,
+            summary,
+            summary_file,
+            quarantine_dir,
+            quarantine_move,
+            delete_source,
+        );
+    }
+}
+
+impl From<&ConvertArgs> for Config {
+    fn from(cli: &ConvertArgs) -> Self {
+        Self {
+            input_directory:    cli.input_directory.clone(),
+            input_extension:    cli.input_extension.clone(),
+            output_extension:   cli.output_extension.clone(),
+            output_directory:   cli.output_directory.clone(),
+            level_verbosity:    Some(cli.verbosity_level()),
+            log_format:         Some(cli.log_format),
+            engine:             Some(cli.engine),
+            pandoc_args:        Some(cli.pandoc_args.clone()),
+            defaults:           cli.defaults.clone(),
+            template:           cli.template.clone(),
+            reference_doc:      cli.reference_doc.clone(),
+            bibliography:       Some(cli.bibliography.clone()),
+            csl:                cli.csl.clone(),
+            citeproc:           Some(cli.citeproc),
+            lua_filters:        Some(cli.lua_filters.clone()),
+            pdf_engine:         cli.pdf_engine,
+            pandoc_server:      Some(cli.pandoc_server),
+            pandoc_path:        cli.pandoc_path.clone(),
+            auto_install:       Some(cli.auto_install),
+            ephemeral_binary:   Some(cli.ephemeral_binary),
+            min_pandoc_version: cli.min_pandoc_version.clone(),
+            output_pattern:     Some(cli.output_pattern.clone()),
+            flatten:            cli.flatten,
+            on_exists:          Some(cli.on_exists),
+            preserve_metadata:  Some(cli.preserve_metadata),
+            detect_content:     Some(cli.detect_content),
+            ocr:                Some(cli.ocr),
+            ocr_lang:           Some(cli.ocr_lang.clone()),
+            split_sheets:       Some(cli.split_sheets),
+            timeout:            cli.timeout,
+            retries:            Some(cli.retries),
+            max_depth:          cli.max_depth,
+            include:            Some(cli.include.clone()),
+            exclude:            Some(cli.exclude.clone()),
+            no_ignore:          Some(cli.no_ignore),
+            follow_symlinks:    Some(cli.follow_symlinks),
+            min_size:           cli.min_size,
+            max_size:           cli.max_size,
+            modified_since:     cli.modified_since.clone(),
+            modified_before:    cli.modified_before.clone(),
+            order:              cli.order,
+            limit:              cli.limit,
+            sample:             cli.sample,
+            seed:               cli.seed,
+            danger_chars:       Some(cli.danger_chars.clone()),
+            replacement:        Some(cli.replacement.clone()),
+            max_name_len:       cli.max_name_len,
+            trim_trailing:      Some(cli.trim_trailing),
+            sanitize_names:     Some(cli.sanitize_names),
+            jobs:               cli.jobs,
+            no_stream:          Some(cli.no_stream),
+            fail_fast:          Some(cli.fail_fast),
+            grace_period:       Some(cli.grace_period),
+            strict:             Some(cli.strict),
+            log_file:           cli.log_file.clone(),
+            log_file_level:     Some(cli.log_file_level),
+            log_rotation:       Some(cli.log_rotation),
+            log_max_size:       Some(cli.log_max_size),
+            log_keep:           Some(cli.log_keep),
+            conversion_log:     cli.conversion_log.clone(),
+            log_tee:            Some(cli.log_tee),
+            events:             cli.events.clone(),
+            metrics_addr:       cli.metrics_addr,
+            tui:                Some(cli.tui),
+            report:             cli.report.clone(),
+            report_format:      cli.report_format,
+            error_report:       cli.error_report.clone(),
+            summary:            Some(cli.summary),
+            summary_file:       cli.summary_file.clone(),
+            quarantine_dir:     cli.quarantine_dir.clone(),
+            quarantine_move:    Some(cli.quarantine_move),
+            delete_source:      cli.delete_source,
+            pandoc:             None,
+            libreoffice:        cli.libreoffice_program.clone().map(|program| {
+                LibreOfficeSection {
+                    program: Some(program),
+                    ..Default::default()
+                }
+            }),
+            unknown:            BTreeMap::new(),
+        }
+    }
+}
+
+/// (De)serializes `ValueEnum`s by their clap names, so the config file
+/// accepts exactly what the matching CLI flag does (aliases included).
+mod value_enum {
+    use clap::ValueEnum;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: ValueEnum,
+        S: Serializer,
+    {
+        match value.as_ref().and_then(ValueEnum::to_possible_value) {
+            Some(v) => serializer.serialize_str(v.get_name()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: ValueEnum,
+        D: Deserializer<'de>,
+    {
+        let Some(s) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        T::from_str(&s, true)
+            .map(Some)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Sizes as a number of bytes, or a string with a unit (`"10M"`, see `filter::parse_size`).
+mod byte_size {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::conversion::filter::parse_size;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<u64>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        match Option::<Size>::deserialize(deserializer)? {
+            None => Ok(None),
+            Some(Size::Bytes(bytes)) => Ok(Some(bytes)),
+            Some(Size::Text(text)) => {
+                parse_size(&text)
+                    .map(Some)
+                    .map_err(serde::de::Error::custom)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_with_unknown_keys() {
+        let config: Config = toml::from_str(
+            r#"
+            input_extension = "docx"
+            output_directory = "out"
+            converter = "soffice"
+            level_verbosity = "debug"
+            retries = 2
+            min_size = 512
+            max_size = "100M"
+            modified_since = "30d"
+            order = "smallest-first"
+            colour = "blue"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.input_extension.as_deref(), Some("docx"));
+        assert_eq!(config.engine, Some(Engine::LibreOffice));
+        assert_eq!(config.level_verbosity, Some(VerbosityLevel::Debug));
+        assert_eq!(config.retries, Some(2));
+        assert_eq!(config.min_size, Some(512));
+        assert_eq!(config.max_size, Some(100 * 1024 * 1024));
+        assert_eq!(config.modified_since.as_deref(), Some("30d"));
+        assert_eq!(config.order, Some(Order::SmallestFirst));
+        assert_eq!(config.unknown_key_warnings(Path::new("c.toml")).len(), 1);
+
+        assert!(toml::from_str::<Config>("engine = \"word\"").is_err());
+        assert!(toml::from_str::<Config>("max_size = \"lots\"").is_err());
+    }
+
+    #[test]
+    fn test_engine_tables() {
+        let config: Config = toml::from_str(
+            r#"
+            template = "top.tex"
+
+            [pandoc]
+            args = ["--toc"]
+            template = "table.tex"
+
+            [libreoffice]
+            program = "/opt/lo/soffice"
+            headless = false
+            "#,
+        )
+        .unwrap();
+
+        let pandoc = config.pandoc.as_ref().unwrap();
+        assert_eq!(pandoc.args.as_deref(), Some(&["--toc".to_string()][..]));
+        assert_eq!(config.template, Some(PathBuf::from("top.tex")));
+        assert_eq!(
+            config.libreoffice.as_ref().unwrap().program,
+            Some(PathBuf::from("/opt/lo/soffice"))
+        );
+        assert_eq!(
+            config.unknown_key_warnings(Path::new("c.toml")),
+            ["Ignoring unknown key 'libreoffice.headless' in config file c.toml"]
+        );
+    }
+
+    #[test]
+    fn test_to_toml_skips_unset() {
+        let config = Config {
+            output_extension: Some("md".into()),
+            report_format: Some(ReportFormat::Csv),
+            ..Default::default()
+        };
+        let toml = config.to_toml().unwrap();
+        assert!(toml.contains("output_extension = \"md\""));
+        assert!(toml.contains("report_format = \"csv\""));
+        assert!(!toml.contains("input_directory"));
+    }
+}
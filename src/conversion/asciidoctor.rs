@@ -0,0 +1,138 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::shutdown::shield_from_ctrl_c;
+use crate::conversion::{Converter, canonical_ext, find_program, version_of};
+use crate::prelude::*;
+
+/// What `Engine::Auto` routes to Asciidoctor: AsciiDoc sources going to any of `OUTPUT_FORMATS`.
+pub(crate) const INPUT_FORMATS: [&str; 2] = ["adoc", "asciidoc"];
+
+/// What Asciidoctor writes, by extension.
+pub(crate) const OUTPUT_FORMATS: [&str; 4] = ["html", "pdf", "xml", "dbk"];
+
+/// Converts AsciiDoc with Asciidoctor, which knows the whole language where pandoc's reader drops
+/// what it doesn't (includes, conditionals, admonitions and the like).
+///
+/// Pdf goes through the `asciidoctor-pdf` extension (`-r asciidoctor-pdf -b pdf`), which has to be installed
+/// alongside it, DocBook (`.xml`/`.dbk`) is DocBook 5.
+pub struct AsciidoctorConverter<P = PathBuf> {
+    program_name: P,
+}
+
+impl AsciidoctorConverter {
+    /// Create a new `AsciidoctorConverter` for the `asciidoctor` in PATH,
+    /// falling back to the bare name for `check_installed` to report as missing.
+    #[inline]
+    pub fn new() -> Self {
+        let program_name = find_program("asciidoctor").unwrap_or_else(|| {
+            debug!("Couldn't find asciidoctor in PATH, falling back to 'asciidoctor'");
+            PathBuf::from("asciidoctor")
+        });
+        Self { program_name }
+    }
+}
+
+impl Default for AsciidoctorConverter {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The arguments picking the backend for `ext`, `None` if Asciidoctor doesn't write it.
+fn backend_args(ext: &str) -> Option<&'static [&'static str]> {
+    match ext {
+        "html" => Some(&["-b", "html5"]),
+        "pdf" => Some(&["-r", "asciidoctor-pdf", "-b", "pdf"]),
+        "xml" | "dbk" => Some(&["-b", "docbook5"]),
+        _ => None,
+    }
+}
+
+#[async_trait::async_trait]
+impl Converter for AsciidoctorConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        trace!("Converting '{}' to '{}'", input.display(), output.display());
+
+        let ext = output
+            .extension()
+            .map(|ext| canonical_ext(&ext.to_string_lossy()))
+            .unwrap_or_default();
+        let backend = backend_args(&ext)
+            .ok_or_else(|| Error::InvalidExtension(output.display().to_string()))?;
+
+        // Dropped (and removed) on any early return, so a failed run leaves no half-written output
+        let partial = PartialOutput::new(output);
+
+        let mut command = tokio::process::Command::new(&self.program_name);
+        let cmd_output = shield_from_ctrl_c(&mut command)
+            .args(backend)
+            .arg("-o")
+            .arg(partial.path())
+            .arg(input)
+            // A timed-out conversion drops this future, take asciidoctor down with it
+            .kill_on_drop(true)
+            .output()
+            .await?;
+
+        if !cmd_output.status.success() {
+            let mut stderr = String::new();
+            cmd_output.stderr.as_slice().read_to_string(&mut stderr)?;
+            return Err(Error::ConverterFailed {
+                program: self.name(),
+                input: input.to_path_buf(),
+                code: cmd_output.status.code(),
+                stderr,
+            });
+        }
+
+        partial.commit().await
+    }
+
+    async fn check_installed(&self) -> bool {
+        let checked = tokio::process::Command::new(&self.program_name)
+            .arg("--version")
+            .output()
+            .await
+            .map(|output| output.status.success());
+
+        debug!("Checked if {:?} is installed: {checked:?}", self.program_name);
+        checked.unwrap_or_else(|_| {
+            warn!("{:?} is not installed", self.program_name);
+            false
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        self.program_name.display().to_string()
+    }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Ok(INPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        Ok(OUTPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn version(&self) -> Option<String> {
+        version_of(&self.program_name).await
+    }
+}
+
+#[cfg(test)]
+mod asciidoctor_tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_args() {
+        assert_eq!(backend_args("html"), Some(&["-b", "html5"][..]));
+        assert_eq!(backend_args("dbk"), backend_args("xml"));
+        assert_eq!(backend_args("md"), None);
+        // Every output we route here has a backend
+        assert!(OUTPUT_FORMATS.iter().all(|ext| backend_args(ext).is_some()));
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::shutdown::shield_from_ctrl_c;
+use crate::conversion::{Converter, canonical_ext, find_program, version_of};
+use crate::prelude::*;
+
+/// Ebooks pandoc can't read, which `Engine::Auto` routes to Calibre going to any of `OUTPUT_FORMATS`.
+pub(crate) const INPUT_FORMATS: [&str; 3] = ["mobi", "azw3", "azw"];
+
+/// Ebooks pandoc can't write, which `Engine::Auto` routes to Calibre from any of `READ_FORMATS`.
+pub(crate) const EBOOK_OUTPUTS: [&str; 2] = ["mobi", "azw3"];
+
+/// What `ebook-convert` reads, by canonical extension (see `canonical_ext`).
+pub(crate) const READ_FORMATS: [&str; 12] = [
+    "epub", "mobi", "azw3", "azw", "fb2", "docx", "odt", "rtf", "html", "markdown",
+    "txt", "pdf",
+];
+
+/// What `ebook-convert` writes, picked by the output's extension.
+/// Markdown is its txt output with markdown formatting, see `CalibreConverter::convert`.
+pub(crate) const OUTPUT_FORMATS: [&str; 11] = [
+    "epub", "mobi", "azw3", "fb2", "docx", "rtf", "html", "markdown", "txt", "pdf", "pdb",
+];
+
+/// Converts ebooks with Calibre's `ebook-convert`, for the Kindle formats (MOBI/AZW3)
+/// pandoc can neither read nor write.
+///
+/// EPUB stays with pandoc unless it's going to one of those, which it reads and writes well enough.
+pub struct CalibreConverter<P = PathBuf> {
+    program_name: P,
+}
+
+impl CalibreConverter {
+    /// Create a new `CalibreConverter` for the `ebook-convert` in PATH,
+    /// falling back to the bare name for `check_installed` to report as missing.
+    #[inline]
+    pub fn new() -> Self {
+        let program_name = find_program("ebook-convert").unwrap_or_else(|| {
+            debug!(
+                "Couldn't find ebook-convert in PATH, falling back to 'ebook-convert'"
+            );
+            PathBuf::from("ebook-convert")
+        });
+        Self { program_name }
+    }
+}
+
+impl Default for CalibreConverter {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl Converter for CalibreConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        trace!("Converting '{}' to '{}'", input.display(), output.display());
+
+        let is_markdown = output
+            .extension()
+            .is_some_and(|ext| canonical_ext(&ext.to_string_lossy()) == "markdown");
+        // Keeps the extension, which is all ebook-convert picks the output format from,
+        // and it only writes markdown as formatted txt
+        let partial = if is_markdown {
+            let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+            PartialOutput::in_staging_dir(output, Path::new(&format!("{stem}.txt")))
+                .await?
+        } else {
+            PartialOutput::new(output)
+        };
+
+        let mut command = tokio::process::Command::new(&self.program_name);
+        shield_from_ctrl_c(&mut command)
+            .arg(input)
+            .arg(partial.path());
+        if is_markdown {
+            command.arg("--txt-output-formatting=markdown");
+        }
+        let cmd_output = command
+            // A timed-out conversion drops this future, take ebook-convert down with it
+            .kill_on_drop(true)
+            .output()
+            .await?;
+
+        if !cmd_output.status.success() {
+            let mut stderr = String::new();
+            cmd_output.stderr.as_slice().read_to_string(&mut stderr)?;
+            return Err(Error::ConverterFailed {
+                program: self.name(),
+                input: input.to_path_buf(),
+                code: cmd_output.status.code(),
+                stderr,
+            });
+        }
+
+        partial.commit().await
+    }
+
+    async fn check_installed(&self) -> bool {
+        let checked = tokio::process::Command::new(&self.program_name)
+            .arg("--version")
+            .output()
+            .await
+            .map(|output| output.status.success());
+
+        debug!("Checked if {:?} is installed: {checked:?}", self.program_name);
+        checked.unwrap_or_else(|_| {
+            warn!("{:?} is not installed", self.program_name);
+            false
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        self.program_name.display().to_string()
+    }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Ok(READ_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        Ok(OUTPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn version(&self) -> Option<String> {
+        version_of(&self.program_name).await
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::{Converter, calibre, canonical_ext, image, libreoffice};
+use crate::prelude::*;
+
+/// What `Engine::Auto` routes through LibreOffice to docx and on to pandoc: the word processor documents
+/// pandoc can't read, going to what LibreOffice doesn't write (see `document_pairs`).
+pub(crate) const DOCUMENT_INPUTS: [&str; 4] = ["doc", "dot", "wpd", "pages"];
+
+/// What `Engine::Auto` routes through LibreOffice to pptx and on to the pptx reader: the presentations
+/// neither pandoc nor the pptx reader can read, going to markdown.
+pub(crate) const PRESENTATION_INPUTS: [&str; 3] = ["ppt", "pps", "odp"];
+
+/// Converts in two steps, with `first` to an intermediate `via` format in the output's staging folder
+/// and from there with `then` to the output, for the formats no single converter takes all the way
+/// (a `.doc` going to markdown).
+pub struct ChainedConverter {
+    first: Box<dyn Converter>,
+    /// The extension of the intermediate file, one `first` writes and `then` reads.
+    via:   &'static str,
+    then:  Box<dyn Converter>,
+}
+
+impl ChainedConverter {
+    /// Create a new `ChainedConverter`, converting with `first` to `via` and from that with `then`.
+    #[inline]
+    pub fn new(
+        first: Box<dyn Converter>,
+        via: &'static str,
+        then: Box<dyn Converter>,
+    ) -> Self {
+        Self { first, via, then }
+    }
+}
+
+/// Every document going to one of `target_exts` LibreOffice doesn't write,
+/// except to images or the Kindle formats, which pandoc doesn't write either.
+pub(crate) fn document_pairs(target_exts: &[String]) -> Vec<(String, String)> {
+    target_exts
+        .iter()
+        .filter(|output| {
+            let output = canonical_ext(output);
+            !libreoffice::OUTPUT_FORMATS.contains(&output.as_str())
+                && !image::OUTPUT_FORMATS.contains(&output.as_str())
+                && !calibre::EBOOK_OUTPUTS.contains(&output.as_str())
+        })
+        .flat_map(|output| {
+            DOCUMENT_INPUTS
+                .iter()
+                .map(move |input| (input.to_string(), output.clone()))
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl Converter for ChainedConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        trace!(
+            "Converting '{}' to '{}' by way of {}",
+            input.display(),
+            output.display(),
+            self.via
+        );
+
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        // Never committed, the staging folder goes with the intermediate file once the output is written
+        let intermediate = PartialOutput::in_staging_dir(
+            output,
+            Path::new(&format!("{stem}.{}", self.via)),
+        )
+        .await?;
+        self.first.convert(input, intermediate.path()).await?;
+        self.then.convert(intermediate.path(), output).await
+    }
+
+    async fn check_installed(&self) -> bool {
+        self.first.check_installed().await && self.then.check_installed().await
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        format!("{}+{}", self.first.name(), self.then.name())
+    }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        self.first.supported_input_formats().await
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        self.then.supported_output_formats().await
+    }
+
+    async fn version(&self) -> Option<String> {
+        match (self.first.version().await, self.then.version().await) {
+            (Some(first), Some(then)) => Some(format!("{first}, {then}")),
+            (first, then) => first.or(then),
+        }
+    }
+
+    async fn check_version(&self) -> Result<()> {
+        self.first.check_version().await?;
+        self.then.check_version().await
+    }
+
+    fn degraded(&self, output: &Path) -> bool {
+        self.then.degraded(output)
+    }
+}
+
+#[cfg(test)]
+mod chained_tests {
+    use super::*;
+
+    #[test]
+    fn test_document_pairs() {
+        let targets = [
+            "md".to_string(),
+            "pdf".to_string(),
+            "png".to_string(),
+            "epub".to_string(),
+        ];
+        let pairs = document_pairs(&targets);
+        assert!(pairs.contains(&("doc".to_string(), "md".to_string())));
+        assert!(pairs.contains(&("wpd".to_string(), "epub".to_string())));
+        assert!(
+            !pairs
+                .iter()
+                .any(|(_, output)| output == "pdf" || output == "png")
+        );
+    }
+}
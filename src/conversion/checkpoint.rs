@@ -0,0 +1,171 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// Kept in the output directory (or the input directory without `--output`).
+pub const FILE_NAME: &str = ".document_converter_checkpoint.jsonl";
+
+/// One line of the checkpoint file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    input:  PathBuf,
+    output: PathBuf,
+}
+
+/// Records every successful conversion as it finishes, one JSON object per line,
+/// so `--resume` can skip them after an interrupted run.
+///
+/// Failed conversions aren't recorded, so they're tried again on resume.
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: PathBuf,
+    /// `(input, output)` pairs, an input converted to several formats has one per format.
+    done: HashSet<(PathBuf, PathBuf)>,
+    file: Mutex<std::fs::File>,
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint at `path`.
+    ///
+    /// With `resume` the files it already lists count as done and new ones are appended,
+    /// otherwise it's started over.
+    ///
+    /// # Errors
+    /// * If the file can't be read, created or truncated
+    pub fn open<P: AsRef<Path>>(path: P, resume: bool) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let contents = if resume && path.is_file() {
+            std::fs::read_to_string(&path)?
+        } else {
+            String::new()
+        };
+        let done = parse_entries(&contents, &path);
+        if resume {
+            info!(
+                "Resuming, {} files already converted ({})",
+                done.len(),
+                path.display()
+            );
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(resume)
+            .write(true)
+            .truncate(!resume)
+            .open(&path)?;
+
+        // Don't glue the next entry onto a line the interruption cut short
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            file.write_all(b"\n")?;
+        }
+
+        Ok(Self {
+            path,
+            done,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Whether `input` was converted to `output` by the run being resumed.
+    #[inline]
+    pub fn is_done(&self, input: &Path, output: &Path) -> bool {
+        self.done
+            .contains(&(input.to_path_buf(), output.to_path_buf()))
+    }
+
+    /// Appends `input` as converted to `output`.
+    ///
+    /// # Errors
+    /// * If the line can't be written
+    pub fn record(&self, input: &Path, output: &Path) -> Result<()> {
+        let entry = Entry {
+            input:  input.to_path_buf(),
+            output: output.to_path_buf(),
+        };
+        let mut line = serde_json::to_string(&entry).map_err(|e| {
+            Error::Generic(format!(
+                "Failed to write checkpoint {}: {e}",
+                self.path.display()
+            ))
+        })?;
+        line.push('\n');
+
+        // A single write per line, so concurrent tasks can't interleave within one
+        let mut file = self.file.lock().expect("checkpoint file lock poisoned");
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// The inputs listed in the checkpoint `contents`, read from `path`.
+///
+/// A line that doesn't parse (most likely cut short by the interruption) is skipped with a warning.
+fn parse_entries(contents: &str, path: &Path) -> HashSet<(PathBuf, PathBuf)> {
+    let mut done = HashSet::new();
+    for (idx, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Entry>(line) {
+            Ok(entry) => {
+                done.insert((entry.input, entry.output));
+            }
+            Err(e) => warn!("Skipping bad line {} in {}: {e}", idx + 1, path.display()),
+        }
+    }
+
+    done
+}
+
+#[cfg(test)]
+mod checkpoint_tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_keeps_entries() {
+        let dir =
+            std::env::temp_dir().join(format!("dcc_checkpoint_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(FILE_NAME);
+
+        let checkpoint = Checkpoint::open(&path, false).unwrap();
+        checkpoint
+            .record(Path::new("a.docx"), Path::new("a.md"))
+            .unwrap();
+        drop(checkpoint);
+        // An interrupted write
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"input\":\"b.do")
+            .unwrap();
+
+        let checkpoint = Checkpoint::open(&path, true).unwrap();
+        assert!(checkpoint.is_done(Path::new("a.docx"), Path::new("a.md")));
+        assert!(!checkpoint.is_done(Path::new("a.docx"), Path::new("a.html")));
+        assert!(!checkpoint.is_done(Path::new("b.docx"), Path::new("b.md")));
+        checkpoint
+            .record(Path::new("c.docx"), Path::new("c.md"))
+            .unwrap();
+        drop(checkpoint);
+        assert!(
+            Checkpoint::open(&path, true)
+                .unwrap()
+                .is_done(Path::new("c.docx"), Path::new("c.md"))
+        );
+
+        let checkpoint = Checkpoint::open(&path, false).unwrap();
+        assert!(!checkpoint.is_done(Path::new("a.docx"), Path::new("a.md")));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+use serde::Deserialize;
+
+use crate::conversion::partial::is_partial_name;
+use crate::conversion::{checkpoint, fingerprint, has_ext, libreoffice, pipe};
+use crate::prelude::*;
+
+/// Removes what runs that crashed or were killed left behind (`clean`), or with `dry_run` only logs it:
+/// * below `dir`, the partial outputs and staging folders (see `partial::PartialOutput`)
+/// * below `dir` and given `output_exts`, empty outputs with one of them, which would otherwise be kept
+///   as already converted, and media folders pandoc extracted for an output that's no longer there,
+///   both only for outputs a run recorded (in a checkpoint or fingerprints file below `dir`)
+/// * in the temp folder, LibreOffice profiles and `--pipe` work folders of runs that are no longer running,
+///   and on Windows the unpacked pandoc binary
+///
+/// Hidden folders (`.git` and the like) other than staging folders aren't walked into.
+/// Returns what was (or would be) removed. One that can't be removed is logged and skipped.
+///
+/// # Errors
+/// * If the walk can't be run
+pub async fn clean(
+    dir: &Path,
+    output_exts: &[String],
+    dry_run: bool,
+) -> Result<Vec<PathBuf>> {
+    let root = dir.to_path_buf();
+    let exts = output_exts.to_vec();
+    let leftovers = tokio::task::spawn_blocking(move || {
+        let mut leftovers = find_leftovers(&root, &exts);
+        leftovers.extend(find_temp_leftovers(&std::env::temp_dir()));
+        leftovers
+    })
+    .await?;
+
+    for path in &leftovers {
+        if dry_run {
+            info!("Would remove {path:?}");
+            continue;
+        }
+
+        let removed = if path.is_dir() {
+            tokio::fs::remove_dir_all(path).await
+        } else {
+            tokio::fs::remove_file(path).await
+        };
+        match removed {
+            Ok(()) => info!("Removed {path:?}"),
+            Err(e) => warn!("Failed to remove {path:?}: {e}"),
+        }
+    }
+    Ok(leftovers)
+}
+
+/// Every partial output below `root`, without walking into the staging folders it finds (or other hidden folders),
+/// and with `output_exts` the empty outputs and orphaned media folders of the outputs recorded below `root`.
+fn find_leftovers(root: &Path, output_exts: &[String]) -> Vec<PathBuf> {
+    let mut leftovers = Vec::new();
+    let mut state_files = Vec::new();
+    let mut candidates = Vec::new();
+    let walk = WalkBuilder::new(root)
+        .standard_filters(false)
+        .filter_entry(|entry| {
+            if entry.depth() == 0 {
+                return true;
+            }
+            let in_staging_dir = entry
+                .path()
+                .parent()
+                .and_then(Path::file_name)
+                .is_some_and(is_partial_name);
+            let hidden_dir = entry.file_type().is_some_and(|t| t.is_dir())
+                && entry.file_name().to_string_lossy().starts_with('.')
+                && !is_partial_name(entry.file_name());
+            !(entry.depth() > 1 && in_staging_dir) && !hidden_dir
+        })
+        .build();
+
+    for entry in walk {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                warn!("Skipping what can't be walked: {e}");
+                continue;
+            }
+        };
+        if entry.depth() == 0 {
+            continue;
+        }
+
+        if is_partial_name(entry.file_name()) {
+            leftovers.push(entry.into_path());
+        } else if [checkpoint::FILE_NAME, fingerprint::FILE_NAME]
+            .iter()
+            .any(|name| entry.file_name() == OsStr::new(name))
+        {
+            state_files.push(entry.into_path());
+        } else if !output_exts.is_empty() {
+            candidates.push(entry.into_path());
+        }
+    }
+    if candidates.is_empty() {
+        return leftovers;
+    }
+
+    let recorded = recorded_outputs(&state_files);
+    leftovers.extend(candidates.into_iter().filter(|path| {
+        if path.is_dir() {
+            is_orphaned_media_folder(path, output_exts, &recorded)
+        } else {
+            recorded.contains(&absolute(path)) && is_empty_output(path, output_exts)
+        }
+    }));
+    leftovers
+}
+
+/// One line of a checkpoint or fingerprints file, as far as `clean` cares.
+#[derive(Debug, Deserialize)]
+struct Recorded {
+    output: PathBuf,
+}
+
+/// Every output the checkpoint and fingerprints files `state_files` list, made absolute.
+/// A file or line that can't be read is skipped.
+fn recorded_outputs(state_files: &[PathBuf]) -> HashSet<PathBuf> {
+    let mut recorded = HashSet::new();
+    for path in state_files {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("Skipping {path:?}, it can't be read: {e}");
+                continue;
+            }
+        };
+        recorded.extend(
+            contents
+                .lines()
+                .filter_map(|line| serde_json::from_str::<Recorded>(line).ok())
+                .map(|entry| absolute(&entry.output)),
+        );
+    }
+    recorded
+}
+
+/// `path` made absolute against the current folder, as it is when it can't be.
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// An output with one of `output_exts` that has nothing in it.
+fn is_empty_output(path: &Path, output_exts: &[String]) -> bool {
+    has_ext(path, output_exts) && path.metadata().is_ok_and(|meta| meta.len() == 0)
+}
+
+/// A folder holding nothing but the `media` folder pandoc extracts into (see `PandocConverter::media_folder`)
+/// for one of the `recorded` outputs with one of `output_exts`, which is gone or empty.
+fn is_orphaned_media_folder(
+    dir: &Path,
+    output_exts: &[String],
+    recorded: &HashSet<PathBuf>,
+) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    let names: Vec<_> = entries.flatten().map(|entry| entry.file_name()).collect();
+    if names != [OsStr::new("media")] || !dir.join("media").is_dir() {
+        return false;
+    }
+
+    let outputs: Vec<_> = output_exts
+        .iter()
+        .map(|ext| dir.with_extension(ext))
+        .collect();
+    outputs
+        .iter()
+        .any(|output| recorded.contains(&absolute(output)))
+        && !outputs
+            .iter()
+            .any(|output| output.is_file() && !is_empty_output(output, output_exts))
+}
+
+/// What our runs leave in `temp` (the system's temp folder): LibreOffice profiles and `--pipe` work folders
+/// of runs that are gone without removing them, and on Windows (or with an `embed-pandoc-*` feature)
+/// the pandoc binary versions before the per-user cache unpacked there.
+///
+/// The folders are named after the process that made them, those of one still running are its own and kept.
+fn find_temp_leftovers(temp: &Path) -> Vec<PathBuf> {
+    let mut leftovers = Vec::new();
+
+    #[cfg(embedded_pandoc)]
+    {
+        let unpacked = temp.join(crate::embedded::PANDOC_TEMP_NAME);
+        if unpacked.is_file() {
+            leftovers.push(unpacked);
+        }
+    }
+
+    let Ok(entries) = std::fs::read_dir(temp) else {
+        return leftovers;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let owner = [libreoffice::PROFILE_DIR_PREFIX, pipe::WORK_DIR_PREFIX]
+            .iter()
+            .find_map(|prefix| name.strip_prefix(prefix))
+            .and_then(|rest| rest.split('_').next()?.parse::<u32>().ok());
+        if let Some(pid) = owner
+            && !is_running(pid)
+            && entry.path().is_dir()
+        {
+            leftovers.push(entry.path());
+        }
+    }
+    leftovers
+}
+
+/// Whether the process `pid` is still running. When that can't be told it's taken to be.
+fn is_running(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        Path::new("/proc").join(pid.to_string()).exists()
+    }
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        std::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map_or(true, |status| status.success())
+    }
+    #[cfg(windows)]
+    {
+        std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+            .output()
+            .map_or(true, |out| {
+                String::from_utf8_lossy(&out.stdout).contains(&pid.to_string())
+            })
+    }
+}
+
+#[cfg(test)]
+mod clean_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_clean() {
+        let tmp = std::env::temp_dir().join(format!("dcc_clean_{}", std::process::id()));
+        std::fs::create_dir_all(tmp.join("a/.report.md.partial")).unwrap();
+        std::fs::write(tmp.join("a/.report.md.partial/report.md"), "half").unwrap();
+        std::fs::write(tmp.join("a/.notes.partial.md"), "half").unwrap();
+        std::fs::write(tmp.join("a/notes.md"), "done").unwrap();
+
+        let found = clean(&tmp, &[], true).await.unwrap();
+        assert!(found.contains(&tmp.join("a/.report.md.partial")));
+        assert!(found.contains(&tmp.join("a/.notes.partial.md")));
+        assert!(tmp.join("a/.notes.partial.md").exists());
+
+        clean(&tmp, &[], false).await.unwrap();
+        assert!(!tmp.join("a/.report.md.partial").exists());
+        assert!(!tmp.join("a/.notes.partial.md").exists());
+        assert!(tmp.join("a/notes.md").exists());
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn test_find_leftovers() {
+        let tmp =
+            std::env::temp_dir().join(format!("dcc_clean_find_{}", std::process::id()));
+        std::fs::create_dir_all(tmp.join("a/.report.md.partial")).unwrap();
+        std::fs::write(tmp.join("a/.report.md.partial/report.md"), "half").unwrap();
+        std::fs::write(tmp.join("a/.notes.partial.md"), "half").unwrap();
+        std::fs::write(tmp.join("a/notes.md"), "done").unwrap();
+        std::fs::write(tmp.join("a/empty.md"), "").unwrap();
+        std::fs::write(tmp.join("a/empty.docx"), "").unwrap();
+        // Not recorded by a run, so the user's own
+        std::fs::write(tmp.join("a/blank.md"), "").unwrap();
+        std::fs::create_dir_all(tmp.join(".git")).unwrap();
+        std::fs::write(tmp.join(".git/.HEAD.partial.md"), "").unwrap();
+        for media in [
+            "a/notes/media",
+            "a/gone/media",
+            "a/empty/media",
+            "a/photos/media",
+            "a/assets/media",
+        ] {
+            std::fs::create_dir_all(tmp.join(media)).unwrap();
+        }
+        std::fs::write(tmp.join("a/photos/cat.png"), "png").unwrap();
+        let recorded: String = ["notes", "empty", "gone", "photos"]
+            .iter()
+            .map(|name| {
+                format!(
+                    "{}\n",
+                    serde_json::json!({"input": "in.docx", "output": tmp.join(format!("a/{name}.md"))})
+                )
+            })
+            .collect();
+        std::fs::write(tmp.join(checkpoint::FILE_NAME), recorded).unwrap();
+
+        let found = |exts: &[&str]| {
+            let exts: Vec<String> = exts.iter().map(ToString::to_string).collect();
+            let mut found: Vec<String> = find_leftovers(&tmp, &exts)
+                .iter()
+                .map(|path| {
+                    path.strip_prefix(&tmp)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect();
+            found.sort();
+            found
+        };
+
+        assert_eq!(found(&[]), ["a/.notes.partial.md", "a/.report.md.partial"]);
+        assert_eq!(
+            found(&["md"]),
+            [
+                "a/.notes.partial.md",
+                "a/.report.md.partial",
+                "a/empty",
+                "a/empty.md",
+                "a/gone"
+            ]
+        );
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn test_find_temp_leftovers() {
+        let pid = std::process::id();
+        let tmp = std::env::temp_dir().join(format!("dcc_clean_temp_{pid}"));
+        // No process has the largest pid there is
+        let gone = u32::MAX - 1;
+        for dir in [
+            format!("dcc_lo_profile_{gone}_0/user"),
+            format!("dcc_lo_profile_{pid}_0/user"),
+            format!("dcc_pipe_{gone}"),
+            format!("dcc_pipe_{pid}"),
+            "dcc_pipe_x".to_string(),
+            "dcc_other".to_string(),
+        ] {
+            std::fs::create_dir_all(tmp.join(dir)).unwrap();
+        }
+        std::fs::write(tmp.join("dcc_pipe_2"), "not a folder").unwrap();
+
+        let mut found = find_temp_leftovers(&tmp);
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                tmp.join(format!("dcc_lo_profile_{gone}_0")),
+                tmp.join(format!("dcc_pipe_{gone}"))
+            ]
+        );
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use tokio::sync::OnceCell;
+
+use crate::conversion::ooxml::{Package, prefixed_attribute, push_ref};
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::{Converter, canonical_ext};
+use crate::prelude::*;
+
+/// What `Engine::Auto` routes through the `DocxFallbackConverter`: docx going to any of `OUTPUT_FORMATS`.
+pub(crate) const INPUT_FORMATS: [&str; 1] = ["docx"];
+
+/// What the text is extracted as, by canonical extension.
+pub(crate) const OUTPUT_FORMATS: [&str; 2] = ["markdown", "txt"];
+
+/// What the `DocxFallbackConverter` reports as its name and version, there's no program to ask.
+const NAME: &str = "docx-text";
+const VERSION: &str = "built-in";
+
+/// Where a docx keeps its body.
+const DOCUMENT_XML: &str = "word/document.xml";
+
+/// Converts docx to markdown or plain text with pandoc, extracting the text itself when pandoc
+/// fails or isn't installed, for bulk ingestion that would rather have lossy text than nothing.
+///
+/// The extraction keeps paragraphs, headings, list items and tables, but not images, footnotes, comments or formatting.
+/// Its outputs are marked as degraded in the report, see `Converter::degraded`.
+pub struct DocxFallbackConverter {
+    pandoc:           Box<dyn Converter>,
+    /// Checked once, by the first conversion, rather than failing pandoc for every file.
+    pandoc_installed: OnceCell<bool>,
+    /// The outputs written by extraction rather than pandoc, until `degraded` takes them.
+    degraded:         Mutex<HashSet<PathBuf>>,
+}
+
+impl DocxFallbackConverter {
+    /// Create a new `DocxFallbackConverter`, converting with `pandoc` while it can.
+    #[inline]
+    pub fn new(pandoc: Box<dyn Converter>) -> Self {
+        Self {
+            pandoc,
+            pandoc_installed: OnceCell::new(),
+            degraded: Mutex::default(),
+        }
+    }
+
+    async fn pandoc_installed(&self) -> bool {
+        *self
+            .pandoc_installed
+            .get_or_init(|| {
+                async {
+                    let installed = self.pandoc.check_installed().await;
+                    if !installed {
+                        warn!(
+                            "{} isn't installed, extracting the text of docx files",
+                            self.pandoc.name()
+                        );
+                    }
+                    installed
+                }
+            })
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl Converter for DocxFallbackConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        if self.pandoc_installed().await {
+            match self.pandoc.convert(input, output).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    warn!("{e}, extracting the text of {input:?} instead");
+                }
+            }
+        }
+        trace!("Extracting the text of '{}' to '{}'", input.display(), output.display());
+
+        let markdown = output
+            .extension()
+            .is_some_and(|ext| canonical_ext(&ext.to_string_lossy()) == "markdown");
+        let path = input.to_path_buf();
+        let text =
+            tokio::task::spawn_blocking(move || extract_text(&path, markdown)).await??;
+
+        // Dropped (and removed) on any early return, so a failed write leaves no half-written output
+        let partial = PartialOutput::new(output);
+        tokio::fs::write(partial.path(), text).await?;
+        partial.commit().await?;
+
+        self.degraded
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(output.to_path_buf());
+        Ok(())
+    }
+
+    /// Always, the text can be extracted without pandoc.
+    async fn check_installed(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        NAME.to_string()
+    }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Ok(INPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        Ok(OUTPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn version(&self) -> Option<String> {
+        Some(VERSION.to_string())
+    }
+
+    fn degraded(&self, output: &Path) -> bool {
+        self.degraded
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(output)
+    }
+}
+
+/// The text of the docx at `path`, as markdown or plain text.
+fn extract_text(path: &Path, markdown: bool) -> Result<String> {
+    let xml = Package::open(path)?.read_string(DOCUMENT_XML)?;
+    document_text(&xml, markdown).map_err(|reason| {
+        Error::UnreadableDocument {
+            path: path.to_path_buf(),
+            reason,
+        }
+    })
+}
+
+/// The text of a docx's `word/document.xml`: a paragraph per `w:p`, its heading level (as `#`s)
+/// and list items (as `- `) kept for markdown, and a table row per line with the cells
+/// separated by ` | ` (as a markdown table) or tabs.
+fn document_text(xml: &str, markdown: bool) -> std::result::Result<String, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut text = TextWriter {
+        markdown,
+        ..Default::default()
+    };
+
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) => text.start(&e),
+            Event::Empty(e) => {
+                text.start(&e);
+                text.end(e.local_name().as_ref());
+            }
+            Event::End(e) => text.end(e.local_name().as_ref()),
+            Event::Text(e) if text.in_text => {
+                text.paragraph.push_str(&e.xml10_content());
+            }
+            Event::GeneralRef(e) if text.in_text => push_ref(&mut text.paragraph, &e)?,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(text.out.trim_end_matches('\n').to_string() + "\n")
+}
+
+/// Builds up the text of a `word/document.xml` as `document_text` reads it.
+#[derive(Default)]
+struct TextWriter {
+    markdown:  bool,
+    out:       String,
+    /// The text of the paragraph being read.
+    paragraph: String,
+    /// What the paragraph starts with, its heading level or list marker.
+    prefix:    String,
+    /// In a `w:r`, whose tabs and breaks are text (unlike the tab stops of the paragraph's properties).
+    in_run:    bool,
+    /// In a `w:t`, whose text is the document's.
+    in_text:   bool,
+    /// How deep in tables, whose cells of cells are read into the outermost one.
+    tables:    usize,
+    /// The rows of the outermost table written so far.
+    rows:      usize,
+    /// The cells of the row being read, the last of them the one being read.
+    cells:     Vec<String>,
+}
+
+impl TextWriter {
+    fn start(&mut self, e: &BytesStart<'_>) {
+        match e.local_name().as_ref() {
+            "pStyle" if self.markdown => {
+                if let Some(level) = prefixed_attribute(e, "val")
+                    .as_deref()
+                    .and_then(heading_level)
+                {
+                    self.prefix = format!("{} ", "#".repeat(level));
+                }
+            }
+            "numPr" if self.markdown && self.prefix.is_empty() => {
+                self.prefix = "- ".to_string();
+            }
+            "r" => self.in_run = true,
+            "t" => self.in_text = true,
+            "tab" if self.in_run => self.paragraph.push('\t'),
+            "br" | "cr" if self.in_run => self.paragraph.push('\n'),
+            "tbl" => {
+                self.tables += 1;
+                if self.tables == 1 {
+                    self.rows = 0;
+                }
+            }
+            "tc" if self.tables == 1 => self.cells.push(String::new()),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        match name {
+            "r" => self.in_run = false,
+            "t" => self.in_text = false,
+            "p" => self.end_paragraph(),
+            "tr" if self.tables == 1 => self.end_row(),
+            "tbl" => {
+                self.tables = self.tables.saturating_sub(1);
+                if self.tables == 0 {
+                    self.out.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end_paragraph(&mut self) {
+        let paragraph = std::mem::take(&mut self.paragraph);
+        let prefix = std::mem::take(&mut self.prefix);
+        let paragraph = paragraph.trim();
+
+        if self.tables > 0 {
+            if let Some(cell) = self.cells.last_mut()
+                && !paragraph.is_empty()
+            {
+                if !cell.is_empty() {
+                    cell.push(' ');
+                }
+                cell.push_str(&paragraph.replace(['\n', '\t'], " "));
+            }
+        } else if !paragraph.is_empty() {
+            self.out.push_str(&prefix);
+            self.out.push_str(paragraph);
+            self.out.push_str("\n\n");
+        }
+    }
+
+    fn end_row(&mut self) {
+        let cells = std::mem::take(&mut self.cells);
+        if self.markdown {
+            let cells: Vec<_> =
+                cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            self.out.push_str(&format!("| {} |\n", cells.join(" | ")));
+            if self.rows == 0 {
+                self.out
+                    .push_str(&format!("|{}\n", "---|".repeat(cells.len().max(1))));
+            }
+        } else {
+            self.out.push_str(&cells.join("\t"));
+            self.out.push('\n');
+        }
+        self.rows += 1;
+    }
+}
+
+/// The heading level of the paragraph style `style`, `Title` as a level 1 heading.
+fn heading_level(style: &str) -> Option<usize> {
+    if style.eq_ignore_ascii_case("title") {
+        return Some(1);
+    }
+    let level = style
+        .strip_prefix("Heading")
+        .or_else(|| style.strip_prefix("heading"))?
+        .trim()
+        .parse()
+        .ok()?;
+    (1..=6).contains(&level).then_some(level)
+}
+
+#[cfg(test)]
+mod docx_tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main">
+<w:body>
+<w:p><w:pPr><w:pStyle w:val="Heading2"/><w:tabs><w:tab w:val="left" w:pos="720"/></w:tabs></w:pPr><w:r><w:t>Results</w:t></w:r></w:p>
+<w:p><w:r><w:t xml:space="preserve">Fish &amp; </w:t></w:r><w:r><w:t>chips</w:t><w:tab/><w:t>&#8364;5</w:t></w:r></w:p>
+<w:p><w:pPr><w:numPr><w:ilvl w:val="0"/><w:numId w:val="1"/></w:numPr></w:pPr><w:r><w:t>An item</w:t></w:r></w:p>
+<w:tbl>
+<w:tr><w:tc><w:p><w:r><w:t>a|b</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>c</w:t></w:r></w:p></w:tc></w:tr>
+<w:tr><w:tc><w:p><w:r><w:t>1</w:t></w:r></w:p><w:p><w:r><w:t>more</w:t></w:r></w:p></w:tc><w:tc><w:p/></w:tc></w:tr>
+</w:tbl>
+<w:p><w:r><w:delText>deleted</w:delText></w:r></w:p>
+</w:body>
+</w:document>"#;
+
+    #[test]
+    fn test_document_text_markdown() {
+        let text = document_text(DOCUMENT, true).unwrap();
+        assert_eq!(
+            text,
+            "## Results\n\nFish & chips\t€5\n\n- An item\n\n| a\\|b | c |\n|---|---|\n| 1 more |  |\n"
+        );
+    }
+
+    #[test]
+    fn test_document_text_plain() {
+        let text = document_text(DOCUMENT, false).unwrap();
+        assert_eq!(text, "Results\n\nFish & chips\t€5\n\nAn item\n\na|b\tc\n1 more\t\n");
+    }
+
+    #[test]
+    fn test_heading_level() {
+        assert_eq!(heading_level("Heading1"), Some(1));
+        assert_eq!(heading_level("heading 3"), Some(3));
+        assert_eq!(heading_level("Title"), Some(1));
+        assert_eq!(heading_level("Heading9"), None);
+        assert_eq!(heading_level("Normal"), None);
+    }
+
+    #[test]
+    fn test_extract_text() {
+        use std::io::Write;
+
+        let tmp = std::env::temp_dir().join(format!("dcc_docx_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp).unwrap();
+
+        let path = tmp.join("report.docx");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        zip.start_file(DOCUMENT_XML, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(DOCUMENT.as_bytes()).unwrap();
+        zip.finish().unwrap();
+        assert!(
+            extract_text(&path, true)
+                .unwrap()
+                .starts_with("## Results\n")
+        );
+
+        let broken = tmp.join("broken.docx");
+        std::fs::write(&broken, "not a zip").unwrap();
+        assert!(matches!(
+            extract_text(&broken, true),
+            Err(Error::UnreadableDocument { .. })
+        ));
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}
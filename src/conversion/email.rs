@@ -0,0 +1,605 @@
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::Path;
+
+use mail_parser::{Address, MessageParser, MimeHeaders};
+use serde::Serialize;
+
+use crate::conversion::pandoc::PandocConverter;
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::report::html_escape;
+use crate::conversion::{Converter, calibre, canonical_ext, image};
+use crate::prelude::*;
+
+/// What `Engine::Auto` routes to the `EmailConverter`: emails, going to anything pandoc writes (see `routed_pairs`).
+pub(crate) const INPUT_FORMATS: [&str; 2] = ["eml", "msg"];
+
+/// What the `EmailConverter` reports as its name and version, there's no program to ask.
+const NAME: &str = "mail-parser";
+const VERSION: &str = "mail-parser 0.11";
+
+/// The attachments' manifest, in the media folder next to them.
+const MANIFEST: &str = "attachments.json";
+
+/// Seconds between the Windows epoch (1601) Outlook's FILETIMEs count from and the unix epoch.
+const FILETIME_UNIX_OFFSET: i64 = 11_644_473_600;
+
+/// Converts emails, MIME `.eml` files and Outlook `.msg` files, which pandoc can't read.
+///
+/// The headers (subject, from, to, cc, date) and the body, its HTML part if it has one, are handed to pandoc as HTML
+/// to write the output. Attachments are saved into the output's media folder (`<stem>/media/`, see
+/// `PandocConverter::media_folder`), listed at the end of the output and in an `attachments.json` manifest next to them.
+pub struct EmailConverter {
+    /// Writes the output from the email as HTML.
+    pandoc: Box<dyn Converter>,
+}
+
+impl EmailConverter {
+    /// Create a new `EmailConverter`, writing its outputs with `pandoc`.
+    #[inline]
+    pub fn new(pandoc: Box<dyn Converter>) -> Self {
+        Self { pandoc }
+    }
+}
+
+/// What the `EmailConverter` reads of an email, whichever format it's in.
+#[derive(Debug, Default)]
+struct Email {
+    subject:     Option<String>,
+    from:        Option<String>,
+    to:          Option<String>,
+    cc:          Option<String>,
+    /// RFC 3339.
+    date:        Option<String>,
+    html:        Option<String>,
+    text:        Option<String>,
+    attachments: Vec<Attachment>,
+}
+
+#[derive(Debug)]
+struct Attachment {
+    name:         String,
+    content_type: Option<String>,
+    data:         Vec<u8>,
+}
+
+/// An entry of the attachments' manifest.
+#[derive(Debug, Serialize)]
+struct ManifestEntry<'a> {
+    name:         &'a str,
+    /// Where it was saved, relative to the output.
+    file:         String,
+    content_type: Option<&'a str>,
+    size:         usize,
+}
+
+/// Every email going to one of `target_exts`, except to images or the Kindle formats, which pandoc doesn't write.
+pub(crate) fn routed_pairs(target_exts: &[String]) -> Vec<(String, String)> {
+    target_exts
+        .iter()
+        .filter(|output| {
+            let output = canonical_ext(output);
+            !image::OUTPUT_FORMATS.contains(&output.as_str())
+                && !calibre::EBOOK_OUTPUTS.contains(&output.as_str())
+        })
+        .flat_map(|output| {
+            INPUT_FORMATS
+                .iter()
+                .map(move |input| (input.to_string(), output.clone()))
+        })
+        .collect()
+}
+
+/// The `.eml` file `path` holds `bytes`.
+fn parse_eml(path: &Path, bytes: &[u8]) -> Result<Email> {
+    let message = MessageParser::default().parse(bytes).ok_or_else(|| {
+        Error::UnreadableDocument {
+            path:   path.to_path_buf(),
+            reason: "not a MIME message".to_string(),
+        }
+    })?;
+
+    let attachments = message
+        .attachments()
+        .enumerate()
+        .map(|(n, part)| {
+            Attachment {
+                name:         part
+                    .attachment_name()
+                    .map_or_else(|| format!("attachment-{}", n + 1), ToString::to_string),
+                content_type: part.content_type().map(|ct| {
+                    match ct.subtype() {
+                        Some(subtype) => format!("{}/{subtype}", ct.ctype()),
+                        None => ct.ctype().to_string(),
+                    }
+                }),
+                data:         part.contents().to_vec(),
+            }
+        })
+        .collect();
+
+    Ok(Email {
+        subject: message.subject().map(ToString::to_string),
+        from: message.from().map(addresses),
+        to: message.to().map(addresses),
+        cc: message.cc().map(addresses),
+        date: message.date().map(|date| date.to_rfc3339()),
+        // Only a real HTML part, `body_html` makes one up from the text otherwise
+        html: message
+            .html_part(0)
+            .filter(|part| part.is_text_html())
+            .and_then(|_| message.body_html(0))
+            .map(|html| html.into_owned()),
+        text: message.body_text(0).map(|text| text.into_owned()),
+        attachments,
+    })
+}
+
+/// `address` as `Name <address>`, comma-separated.
+fn addresses(address: &Address<'_>) -> String {
+    address
+        .iter()
+        .map(|addr| {
+            match (addr.name.as_deref(), addr.address.as_deref()) {
+                (Some(name), Some(address)) => format!("{name} <{address}>"),
+                (Some(name), None) => name.to_string(),
+                (None, Some(address)) => address.to_string(),
+                (None, None) => String::new(),
+            }
+        })
+        .filter(|addr| !addr.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The Outlook `.msg` file at `path`, a compound file of MAPI properties.
+///
+/// Only attachments that are files are saved, not embedded messages or OLE objects.
+fn parse_msg(path: &Path) -> Result<Email> {
+    let unreadable = |e: std::io::Error| {
+        Error::UnreadableDocument {
+            path:   path.to_path_buf(),
+            reason: e.to_string(),
+        }
+    };
+    let mut msg = cfb::open(path).map_err(unreadable)?;
+
+    let attachment_storages: Vec<_> = msg
+        .read_root_storage()
+        .filter(|entry| {
+            entry.is_storage() && entry.name().starts_with("__attach_version1.0_")
+        })
+        .map(|entry| entry.path().to_path_buf())
+        .collect();
+    let mut attachments = Vec::new();
+    for storage in attachment_storages {
+        let storage = storage.to_string_lossy();
+        let Some(data) = msg_binary(&mut msg, &storage, 0x3701) else {
+            continue;
+        };
+        let name = msg_string(&mut msg, &storage, 0x3707)
+            .or_else(|| msg_string(&mut msg, &storage, 0x3704))
+            .unwrap_or_else(|| format!("attachment-{}", attachments.len() + 1));
+        attachments.push(Attachment {
+            name,
+            content_type: msg_string(&mut msg, &storage, 0x370E),
+            data,
+        });
+    }
+
+    let from = match (
+        msg_string(&mut msg, "", 0x0C1A),
+        msg_string(&mut msg, "", 0x5D01).or_else(|| msg_string(&mut msg, "", 0x0C1F)),
+    ) {
+        (Some(name), Some(address)) if name != address => {
+            Some(format!("{name} <{address}>"))
+        }
+        (name, address) => address.or(name),
+    };
+    let html = msg_binary(&mut msg, "", 0x1013)
+        .map(|html| String::from_utf8_lossy(&html).into_owned())
+        .or_else(|| msg_string(&mut msg, "", 0x1013));
+    // When it was sent, or without that when it was delivered
+    let date = msg_time(&mut msg, 0x0039).or_else(|| msg_time(&mut msg, 0x0E06));
+
+    Ok(Email {
+        subject: msg_string(&mut msg, "", 0x0037),
+        from,
+        to: msg_string(&mut msg, "", 0x0E04),
+        cc: msg_string(&mut msg, "", 0x0E03),
+        date: date.map(|seconds| {
+            mail_parser::DateTime::from_timestamp(seconds - FILETIME_UNIX_OFFSET)
+                .to_rfc3339()
+        }),
+        html,
+        text: msg_string(&mut msg, "", 0x1000),
+        attachments,
+    })
+}
+
+/// The stream holding property `id` of `prop_type` (`001F`) in `storage` (the root if empty).
+fn msg_stream<F: std::io::Read + std::io::Seek>(
+    msg: &mut cfb::CompoundFile<F>,
+    storage: &str,
+    id: u16,
+    prop_type: &str,
+) -> Option<Vec<u8>> {
+    let path = format!("{storage}/__substg1.0_{id:04X}{prop_type}");
+    let mut bytes = Vec::new();
+    msg.open_stream(&path).ok()?.read_to_end(&mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// The string property `id` of `storage`, UTF-16 or (in older files) 8 bit.
+fn msg_string<F: std::io::Read + std::io::Seek>(
+    msg: &mut cfb::CompoundFile<F>,
+    storage: &str,
+    id: u16,
+) -> Option<String> {
+    let text = match msg_stream(msg, storage, id, "001F") {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16
+                .as_chunks::<2>()
+                .0
+                .iter()
+                .map(|unit| u16::from_le_bytes(*unit))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => {
+            String::from_utf8_lossy(&msg_stream(msg, storage, id, "001E")?).into_owned()
+        }
+    };
+    let text = text.trim_end_matches('\0').to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// The binary property `id` of `storage`.
+fn msg_binary<F: std::io::Read + std::io::Seek>(
+    msg: &mut cfb::CompoundFile<F>,
+    storage: &str,
+    id: u16,
+) -> Option<Vec<u8>> {
+    msg_stream(msg, storage, id, "0102")
+}
+
+/// The time property `id` of the message, in seconds since 1601 (a FILETIME),
+/// from the fixed-size properties in its `__properties_version1.0` stream.
+fn msg_time<F: std::io::Read + std::io::Seek>(
+    msg: &mut cfb::CompoundFile<F>,
+    id: u16,
+) -> Option<i64> {
+    let mut properties = Vec::new();
+    msg.open_stream("/__properties_version1.0")
+        .ok()?
+        .read_to_end(&mut properties)
+        .ok()?;
+    filetime_property(&properties, id)
+}
+
+/// The `PT_SYSTIME` property `id` of a message's properties stream, in seconds since 1601.
+///
+/// The stream is a 32 byte header and then 16 bytes per property: its tag (type in the low 16 bits, id in the high),
+/// flags, and the value itself.
+fn filetime_property(properties: &[u8], id: u16) -> Option<i64> {
+    const PT_SYSTIME: u32 = 0x0040;
+    let tag = (u32::from(id) << 16) | PT_SYSTIME;
+    properties
+        .get(32..)?
+        .as_chunks::<16>()
+        .0
+        .iter()
+        .find_map(|property| {
+            let (found, rest) = property.split_first_chunk::<4>()?;
+            if u32::from_le_bytes(*found) != tag {
+                return None;
+            }
+            let (_flags, filetime) = rest.split_first_chunk::<4>()?;
+            // In 100 ns intervals
+            Some(i64::from_le_bytes(filetime.first_chunk::<8>()?.to_owned()) / 10_000_000)
+        })
+}
+
+/// `email` as an HTML document for pandoc: its headers, its body and a list of its `attachments`,
+/// linked as saved (see `ManifestEntry::file`).
+fn to_html(email: &Email, attachments: &[ManifestEntry<'_>]) -> String {
+    let subject = email.subject.as_deref().unwrap_or_default();
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n",
+        html_escape(subject)
+    );
+    if !subject.is_empty() {
+        html.push_str(&format!("<h1>{}</h1>\n", html_escape(subject)));
+    }
+
+    let headers: Vec<_> = [
+        ("From", &email.from),
+        ("To", &email.to),
+        ("Cc", &email.cc),
+        ("Date", &email.date),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| {
+        value
+            .as_deref()
+            .map(|value| format!("<strong>{name}:</strong> {}", html_escape(value)))
+    })
+    .collect();
+    if !headers.is_empty() {
+        html.push_str(&format!("<p>{}</p>\n<hr>\n", headers.join("<br>\n")));
+    }
+
+    match (&email.html, &email.text) {
+        (Some(body), _) => {
+            html.push_str(body_inner(body));
+            html.push('\n');
+        }
+        (None, Some(text)) => {
+            let text = text.replace("\r\n", "\n");
+            for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+                let lines: Vec<_> = paragraph.lines().map(html_escape).collect();
+                html.push_str(&format!("<p>{}</p>\n", lines.join("<br>\n")));
+            }
+        }
+        (None, None) => {}
+    }
+
+    if !attachments.is_empty() {
+        html.push_str("<h2>Attachments</h2>\n<ul>\n");
+        for attachment in attachments {
+            html.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                html_escape(&attachment.file),
+                html_escape(attachment.name)
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// What's in the `<body>` of `html`, all of it if it's a fragment without one.
+fn body_inner(html: &str) -> &str {
+    let lower = html.to_ascii_lowercase();
+    let Some(start) = lower
+        .find("<body")
+        .and_then(|body| lower[body..].find('>').map(|end| body + end + 1))
+    else {
+        return html;
+    };
+    let end = lower
+        .rfind("</body>")
+        .filter(|&end| end >= start)
+        .unwrap_or(html.len());
+    &html[start..end]
+}
+
+/// A file name for an attachment called `name`, without folders, unique among `taken`.
+fn attachment_file_name(name: &str, taken: &mut HashSet<String>) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name).trim();
+    let name = if name.is_empty() || name == "." || name == ".." {
+        "attachment"
+    } else {
+        name
+    };
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{ext}")),
+        _ => (name, String::new()),
+    };
+
+    let mut file_name = name.to_string();
+    let mut n = 1;
+    while !taken.insert(file_name.clone()) {
+        n += 1;
+        file_name = format!("{stem}-{n}{ext}");
+    }
+    file_name
+}
+
+#[async_trait::async_trait]
+impl Converter for EmailConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        trace!("Converting '{}' to '{}'", input.display(), output.display());
+
+        let is_msg = input
+            .extension()
+            .is_some_and(|ext| canonical_ext(&ext.to_string_lossy()) == "msg");
+        let path = input.to_path_buf();
+        let email = tokio::task::spawn_blocking(move || {
+            if is_msg {
+                parse_msg(&path)
+            } else {
+                parse_eml(&path, &std::fs::read(&path)?)
+            }
+        })
+        .await??;
+
+        // Where pandoc would extract media to, `<stem>/media` next to the output, linked relative to it
+        let media_dir = PandocConverter::media_folder(output)?
+            .as_ref()
+            .join("media");
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        // The manifest's name is taken, an attachment with it would be overwritten
+        let mut taken = HashSet::from([MANIFEST.to_string()]);
+        let mut manifest = Vec::with_capacity(email.attachments.len());
+        for attachment in &email.attachments {
+            let file_name = attachment_file_name(&attachment.name, &mut taken);
+            tokio::fs::create_dir_all(&media_dir).await.map_err(|e| {
+                Error::MediaFolderCreationFailed(format!("{}: {e}", media_dir.display()))
+            })?;
+            tokio::fs::write(media_dir.join(&file_name), &attachment.data).await?;
+            manifest.push(ManifestEntry {
+                name:         &attachment.name,
+                file:         format!("{stem}/media/{file_name}"),
+                content_type: attachment.content_type.as_deref(),
+                size:         attachment.data.len(),
+            });
+        }
+        if !manifest.is_empty() {
+            tokio::fs::write(
+                media_dir.join(MANIFEST),
+                serde_json::to_string_pretty(&manifest)?,
+            )
+            .await?;
+        }
+
+        let ext = output
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // The HTML pandoc gets goes in the staging folder, which goes once the output is written
+        let partial =
+            PartialOutput::in_staging_dir(output, Path::new(&format!("{stem}.{ext}")))
+                .await?;
+        let staging = partial
+            .path()
+            .parent()
+            .unwrap_or(partial.path())
+            .to_path_buf();
+        let html = staging.join(format!("{stem}.email.html"));
+        tokio::fs::write(&html, to_html(&email, &manifest)).await?;
+        self.pandoc.convert(&html, partial.path()).await?;
+        partial.commit().await
+    }
+
+    async fn check_installed(&self) -> bool {
+        self.pandoc.check_installed().await
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        NAME.to_string()
+    }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Ok(INPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        self.pandoc.supported_output_formats().await
+    }
+
+    async fn version(&self) -> Option<String> {
+        Some(VERSION.to_string())
+    }
+
+    async fn check_version(&self) -> Result<()> {
+        self.pandoc.check_version().await
+    }
+}
+
+#[cfg(test)]
+mod email_tests {
+    use super::*;
+
+    const EML: &str = "From: Ann Example <ann@example.com>\r\n\
+To: bob@example.com, Carol <carol@example.com>\r\n\
+Subject: Q3 numbers & plans\r\n\
+Date: Tue, 1 Oct 2024 09:30:00 +0000\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"b1\"\r\n\
+\r\n\
+--b1\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+\r\n\
+Hi Bob,\r\n\
+\r\n\
+See attached.\r\nAnn\r\n\
+--b1\r\n\
+Content-Type: text/csv; name=\"q3.csv\"\r\n\
+Content-Disposition: attachment; filename=\"q3.csv\"\r\n\
+\r\n\
+a,b\r\n\
+--b1--\r\n";
+
+    #[test]
+    fn test_parse_eml() {
+        let email = parse_eml(Path::new("q3.eml"), EML.as_bytes()).unwrap();
+        assert_eq!(email.subject.as_deref(), Some("Q3 numbers & plans"));
+        assert_eq!(email.from.as_deref(), Some("Ann Example <ann@example.com>"));
+        assert_eq!(
+            email.to.as_deref(),
+            Some("bob@example.com, Carol <carol@example.com>")
+        );
+        assert_eq!(email.date.as_deref(), Some("2024-10-01T09:30:00Z"));
+        assert_eq!(email.attachments.len(), 1);
+        assert_eq!(email.attachments[0].name, "q3.csv");
+        assert_eq!(email.attachments[0].content_type.as_deref(), Some("text/csv"));
+    }
+
+    #[test]
+    fn test_to_html() {
+        let email = parse_eml(Path::new("q3.eml"), EML.as_bytes()).unwrap();
+        let manifest = [ManifestEntry {
+            name:         "q3.csv",
+            file:         "q3/media/q3.csv".to_string(),
+            content_type: Some("text/csv"),
+            size:         3,
+        }];
+        let html = to_html(&email, &manifest);
+        assert!(html.contains("<h1>Q3 numbers &amp; plans</h1>"));
+        assert!(
+            html.contains(
+                "<strong>From:</strong> Ann Example &lt;ann@example.com&gt;<br>"
+            )
+        );
+        assert!(html.contains("<p>Hi Bob,</p>\n<p>See attached.<br>\nAnn</p>"));
+        assert!(html.contains("<li><a href=\"q3/media/q3.csv\">q3.csv</a></li>"));
+    }
+
+    #[test]
+    fn test_body_inner() {
+        assert_eq!(
+            body_inner("<html><BODY class=\"x\"><p>hi</p></body></html>"),
+            "<p>hi</p>"
+        );
+        assert_eq!(body_inner("<p>hi</p>"), "<p>hi</p>");
+    }
+
+    #[test]
+    fn test_attachment_file_name() {
+        let mut taken = HashSet::new();
+        assert_eq!(attachment_file_name("report.pdf", &mut taken), "report.pdf");
+        assert_eq!(attachment_file_name("report.pdf", &mut taken), "report-2.pdf");
+        assert_eq!(attachment_file_name("../../etc/passwd", &mut taken), "passwd");
+        assert_eq!(attachment_file_name("..", &mut taken), "attachment");
+        assert_eq!(attachment_file_name(".profile", &mut taken), ".profile");
+
+        let mut taken = HashSet::from([MANIFEST.to_string()]);
+        assert_eq!(attachment_file_name(MANIFEST, &mut taken), "attachments-2.json");
+    }
+
+    #[test]
+    fn test_routed_pairs() {
+        let targets = [
+            "md".to_string(),
+            "png".to_string(),
+            "mobi".to_string(),
+            "docx".to_string(),
+        ];
+        let pairs = routed_pairs(&targets);
+        assert!(pairs.contains(&("eml".to_string(), "md".to_string())));
+        assert!(pairs.contains(&("msg".to_string(), "docx".to_string())));
+        assert!(
+            !pairs
+                .iter()
+                .any(|(_, output)| output == "png" || output == "mobi")
+        );
+    }
+
+    #[test]
+    fn test_filetime_property() {
+        let mut properties = vec![0; 32];
+        // PR_CLIENT_SUBMIT_TIME, 2024-10-01T09:30:00Z
+        properties.extend(0x0039_0040_u32.to_le_bytes());
+        properties.extend(6_u32.to_le_bytes());
+        let seconds: i64 = 1_727_775_000 + FILETIME_UNIX_OFFSET;
+        properties.extend((seconds * 10_000_000).to_le_bytes());
+        assert_eq!(
+            filetime_property(&properties, 0x0039),
+            Some(1_727_775_000 + FILETIME_UNIX_OFFSET)
+        );
+        assert_eq!(filetime_property(&properties, 0x0E06), None);
+    }
+}
@@ -0,0 +1,251 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::conversion::naming::civil_from_days;
+use crate::prelude::*;
+
+/// The `--events` target that writes the events to stdout.
+pub const STDOUT_EVENTS: &str = "-";
+
+/// Something that happened to a file during a run, written as one line of the `--events` stream.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event<'a> {
+    /// The file was picked up, before anything is worked out for it.
+    Discovered { input: &'a Path },
+    /// Its conversion to `output` got a job slot and the converter is running.
+    Started {
+        input:  &'a Path,
+        output: &'a Path,
+        engine: &'a str,
+    },
+    /// The conversion finished and `output` is in place.
+    Succeeded {
+        input:       &'a Path,
+        output:      &'a Path,
+        engine:      &'a str,
+        duration_ms: u128,
+        /// How many retries it took, `0` if the first attempt succeeded.
+        retries:     usize,
+    },
+    /// The conversion failed, after any retries.
+    Failed {
+        input:       &'a Path,
+        output:      &'a Path,
+        engine:      &'a str,
+        duration_ms: u128,
+        error:       String,
+    },
+    /// The file isn't converted (to `output`, if it got as far as working that out), see `reason`.
+    Skipped {
+        input:  &'a Path,
+        output: Option<&'a Path>,
+        reason: SkipReason,
+    },
+}
+
+/// Why a file was `Event::Skipped`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipReason {
+    /// `--detect-content` found it empty.
+    Empty,
+    /// The checkpoint being resumed already has it (`--resume`).
+    Checkpointed,
+    /// Its output is newer than it (`--on-exists if-newer`, and in watch mode).
+    UpToDate,
+    /// It hasn't changed since it was last converted, going by its fingerprint.
+    Unchanged,
+    /// Its output already exists and is kept (`--on-exists skip`).
+    OutputExists,
+}
+
+/// A line of the stream: the event, and when it happened.
+#[derive(Serialize)]
+struct Record<'a> {
+    /// UTC, RFC 3339 with milliseconds.
+    time:  String,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// Writes every `Event` of a run as a JSON object per line (`--events`), for dashboards and audits
+/// that can't parse the log. Each line is flushed as it's written, so the stream can be followed live.
+///
+/// Lines are appended to a file, which is created if it's missing, or written to stdout for `-`.
+pub struct EventLog {
+    sink: Mutex<Box<dyn Write + Send>>,
+}
+
+impl std::fmt::Debug for EventLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventLog").finish_non_exhaustive()
+    }
+}
+
+impl EventLog {
+    /// Opens the stream at `target`, a file or `-` for stdout.
+    ///
+    /// # Errors
+    /// * If the file or the folders it's in can't be created, or opened for appending
+    pub fn open<P: AsRef<Path>>(target: P) -> Result<Self> {
+        let target = target.as_ref();
+        if target == Path::new(STDOUT_EVENTS) {
+            return Ok(Self::to_writer(std::io::stdout()));
+        }
+
+        if let Some(parent) = target.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(target)?;
+        Ok(Self::to_writer(file))
+    }
+
+    /// A stream written to `writer`.
+    pub fn to_writer<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            sink: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Writes `event` as a line, stamped with the current time.
+    /// A line that can't be written is logged, it doesn't fail the conversion it's about.
+    pub fn emit(&self, event: &Event<'_>) {
+        let record = Record {
+            time: utc_timestamp(SystemTime::now()),
+            event,
+        };
+        let mut line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize the event {event:?}: {e}");
+                return;
+            }
+        };
+        line.push('\n');
+
+        // A single write per line, so concurrent tasks can't interleave within one
+        let mut sink = self.sink.lock().expect("event stream lock poisoned");
+        if let Err(e) = sink.write_all(line.as_bytes()).and_then(|()| sink.flush()) {
+            warn!("Failed to write to the event stream: {e}");
+        }
+    }
+
+    /// Writes how the conversion of `input` to `output` ended, `res` being what `convert_single` returned
+    /// after it `took` this long.
+    pub fn finished(
+        &self,
+        input: &Path,
+        output: &Path,
+        engine: &str,
+        res: &Result<usize>,
+        took: Duration,
+    ) {
+        let duration_ms = took.as_millis();
+        self.emit(&match res {
+            Ok(retries) => {
+                Event::Succeeded {
+                    input,
+                    output,
+                    engine,
+                    duration_ms,
+                    retries: *retries,
+                }
+            }
+            Err(e) => {
+                Event::Failed {
+                    input,
+                    output,
+                    engine,
+                    duration_ms,
+                    error: e.to_string(),
+                }
+            }
+        });
+    }
+}
+
+/// `at` as `2024-05-01T08:30:15.123Z`.
+fn utc_timestamp(at: SystemTime) -> String {
+    let since = at.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+    let secs = since.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400).cast_signed());
+    let time_of_day = secs % 86_400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        time_of_day / 3600,
+        time_of_day % 3600 / 60,
+        time_of_day % 60,
+        since.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod events_tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// A writer the test can read back after handing it to the `EventLog`.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_emit() {
+        let shared = Shared::default();
+        let events = EventLog::to_writer(shared.clone());
+        let (input, output) = (Path::new("in/a.docx"), Path::new("out/a.md"));
+
+        events.emit(&Event::Discovered { input });
+        events.emit(&Event::Succeeded {
+            input,
+            output,
+            engine: "pandoc",
+            duration_ms: 12,
+            retries: 1,
+        });
+        events.emit(&Event::Skipped {
+            input,
+            output: None,
+            reason: SkipReason::UpToDate,
+        });
+
+        let written = String::from_utf8(shared.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = written
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["event"], "discovered");
+        assert_eq!(lines[0]["input"], "in/a.docx");
+        assert_eq!(lines[1]["event"], "succeeded");
+        assert_eq!(lines[1]["engine"], "pandoc");
+        assert_eq!(lines[1]["duration_ms"], 12);
+        assert_eq!(lines[2]["reason"], "up-to-date");
+        assert!(lines[2]["output"].is_null());
+        assert!(lines[0]["time"].as_str().unwrap().ends_with('Z'));
+
+        let at = UNIX_EPOCH + Duration::from_millis(1_709_195_415_123);
+        assert_eq!(utc_timestamp(at), "2024-02-29T08:30:15.123Z");
+    }
+}
@@ -0,0 +1,340 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+use crate::conversion::naming::{civil_from_days, days_from_civil};
+use crate::prelude::*;
+
+/// The `--include` and `--exclude` globs, checked against every path the walk comes across,
+/// and the `--min-size`/`--max-size` and `--modified-since`/`--modified-before` bounds
+/// for the files it picks up.
+///
+/// A pattern matches a path relative to the input root (`**/Archive/**`, where `*` also spans folders)
+/// or just its name (`*draft*`, `Archive`). Excluded folders aren't walked at all.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include:         Option<GlobSet>,
+    exclude:         Option<GlobSet>,
+    /// In bytes, inclusive.
+    min_size:        Option<u64>,
+    /// In bytes, inclusive.
+    max_size:        Option<u64>,
+    /// Inclusive.
+    modified_since:  Option<SystemTime>,
+    /// Exclusive.
+    modified_before: Option<SystemTime>,
+}
+
+impl PathFilter {
+    /// # Errors
+    /// * `Error::InvalidGlob` - If one of the patterns isn't a valid glob
+    pub fn new<S: AsRef<str>>(include: &[S], exclude: &[S]) -> Result<Self> {
+        Ok(Self {
+            include: glob_set(include)?,
+            exclude: glob_set(exclude)?,
+            ..Self::default()
+        })
+    }
+
+    /// Only picks up files of at least `min` and at most `max` bytes.
+    #[must_use]
+    pub fn with_sizes(mut self, min: Option<u64>, max: Option<u64>) -> Self {
+        self.min_size = min;
+        self.max_size = max;
+        self
+    }
+
+    /// Only picks up files last modified at or after `since`, and before `before`.
+    #[must_use]
+    pub fn with_modified(
+        mut self,
+        since: Option<SystemTime>,
+        before: Option<SystemTime>,
+    ) -> Self {
+        self.modified_since = since;
+        self.modified_before = before;
+        self
+    }
+
+    /// Whether `relative` (to the input root) should be walked into, for a folder, or picked up, for a file.
+    /// Only files have to match an include pattern, if there are any.
+    pub fn allows(&self, relative: &Path, is_dir: bool) -> bool {
+        let matches = |set: &GlobSet| {
+            set.is_match(relative)
+                || relative.file_name().is_some_and(|name| set.is_match(name))
+        };
+
+        if self.exclude.as_ref().is_some_and(matches) {
+            return false;
+        }
+        is_dir || self.include.as_ref().is_none_or(matches)
+    }
+
+    /// Whether the file at `path` is within the size and modification time bounds, if there are any.
+    /// One whose metadata can't be read is let through, its conversion fails on its own.
+    pub fn allows_file(&self, path: &Path) -> bool {
+        let bounds = [
+            self.min_size.is_some(),
+            self.max_size.is_some(),
+            self.modified_since.is_some(),
+            self.modified_before.is_some(),
+        ];
+        if !bounds.contains(&true) {
+            return true;
+        }
+        let Ok(meta) = path.metadata() else {
+            return true;
+        };
+
+        let len = meta.len();
+        if !(self.min_size.is_none_or(|min| len >= min)
+            && self.max_size.is_none_or(|max| len <= max))
+        {
+            debug!(
+                "Skipping {path:?}, its {len} bytes are outside --min-size/--max-size"
+            );
+            return false;
+        }
+
+        let Ok(modified) = meta.modified() else {
+            return true;
+        };
+        if !(self.modified_since.is_none_or(|since| modified >= since)
+            && self.modified_before.is_none_or(|before| modified < before))
+        {
+            debug!(
+                "Skipping {path:?}, it was modified outside --modified-since/--modified-before"
+            );
+            return false;
+        }
+        true
+    }
+}
+
+/// Parses a size like `500`, `10K`, `1.5M` or `2GiB` into bytes.
+/// Units are 1024-based whether they're spelled `M`, `MB` or `MiB`, and ignore case.
+///
+/// # Errors
+/// * If `s` isn't a number, optionally followed by one of the units `B`, `K`, `M`, `G` or `T`
+pub fn parse_size(s: &str) -> std::result::Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("'{s}' doesn't start with a number"))?;
+
+    let unit = unit.trim().to_ascii_uppercase();
+    let unit = unit
+        .strip_suffix("IB")
+        .or_else(|| unit.strip_suffix('B'))
+        .unwrap_or(&unit);
+    let exponent = match unit {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => return Err(format!("unknown unit in '{s}', expected B, K, M, G or T")),
+    };
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    Ok((number * 1024_f64.powi(exponent)).round() as u64)
+}
+
+/// `bytes` the way `parse_size` reads it back, in the largest unit it's at least one of,
+/// like `512B`, `10.0K` or `1.5M`.
+#[allow(clippy::cast_precision_loss)]
+pub fn format_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "K", "M", "G"] {
+        if size < 1024.0 {
+            return if unit == "B" {
+                format!("{bytes}B")
+            } else {
+                format!("{size:.1}{unit}")
+            };
+        }
+        size /= 1024.0;
+    }
+    format!("{size:.1}T")
+}
+
+/// Parses a point in time: a UTC date (`2024-05-01`) or date and time (`2024-05-01T08:30`,
+/// seconds optional, a space works too), or how long ago (`90s`, `30m`, `12h`, `7d`, `2w`).
+///
+/// # Errors
+/// * If `s` is neither, or names a day that doesn't exist
+pub fn parse_time(s: &str) -> std::result::Result<SystemTime, String> {
+    let s = s.trim();
+    if let Some(ago) = parse_ago(s) {
+        return SystemTime::now()
+            .checked_sub(ago)
+            .ok_or_else(|| format!("'{s}' reaches too far back"));
+    }
+    parse_utc(s).ok_or_else(|| {
+        format!("'{s}' is neither a date (2024-05-01, 2024-05-01T08:30) nor a duration (7d, 12h)")
+    })
+}
+
+/// `7d` and friends, a whole number of seconds, minutes, hours, days or weeks.
+fn parse_ago(s: &str) -> Option<Duration> {
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit())?);
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(number.parse::<u64>().ok()?.checked_mul(unit_secs)?))
+}
+
+/// `YYYY-MM-DD`, optionally followed by `THH:MM` or `THH:MM:SS`, in UTC.
+fn parse_utc(s: &str) -> Option<SystemTime> {
+    let (date, time) = match s.split_once(['T', ' ']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+
+    let mut date_parts = date.split('-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: u32 = date_parts.next()?.parse().ok()?;
+    let day: u32 = date_parts.next()?.parse().ok()?;
+    let days = days_from_civil(year, month, day);
+    // Catches the 31st of February and friends, which would otherwise roll over
+    if date_parts.next().is_some() || civil_from_days(days) != (year, month, day) {
+        return None;
+    }
+
+    let mut secs = days * 24 * 60 * 60;
+    if let Some(time) = time {
+        let parts: Vec<i64> = time
+            .split(':')
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()?;
+        let (hour, minute, second) = match parts[..] {
+            [hour, minute] => (hour, minute, 0),
+            [hour, minute, second] => (hour, minute, second),
+            _ => return None,
+        };
+        if !((0..24).contains(&hour)
+            && (0..60).contains(&minute)
+            && (0..60).contains(&second))
+        {
+            return None;
+        }
+        secs += hour * 60 * 60 + minute * 60 + second;
+    }
+
+    let offset = Duration::from_secs(secs.unsigned_abs());
+    if secs >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(offset)
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(offset)
+    }
+}
+
+/// `None` for no `patterns`, which matches nothing (rather than everything).
+fn glob_set<S: AsRef<str>>(patterns: &[S]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern.as_ref())?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+#[cfg(test)]
+mod filter_tests {
+    use super::*;
+
+    #[test]
+    fn test_include_and_exclude() {
+        let filter =
+            PathFilter::new(&["*.docx"], &["**/Archive/**", "*draft*", "Old"]).unwrap();
+        let file = |path: &str| filter.allows(Path::new(path), false);
+        let dir = |path: &str| filter.allows(Path::new(path), true);
+
+        assert!(file("a/report.docx"));
+        assert!(!file("a/report.doc"));
+        assert!(!file("a/Archive/report.docx"));
+        assert!(!file("Archive/report.docx"));
+        assert!(!file("a/report_draft_2.docx"));
+        assert!(dir("a"));
+        assert!(!dir("a/Old"));
+
+        assert!(PathFilter::default().allows(Path::new("a/b.doc"), false));
+        assert!(matches!(PathFilter::new(&["a[b"], &[]), Err(Error::InvalidGlob(_))));
+    }
+
+    #[test]
+    fn test_sizes() {
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size("10K"), Ok(10 * 1024));
+        assert_eq!(parse_size("10kb"), Ok(10 * 1024));
+        assert_eq!(parse_size("1.5M"), Ok(1024 * 1024 * 3 / 2));
+        assert_eq!(parse_size("2 GiB"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("12B"), Ok(12));
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("10X").is_err());
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(10 * 1024), "10.0K");
+        assert_eq!(format_size(1024 * 1024 * 3 / 2), "1.5M");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024 * 1024), "3.0T");
+
+        let tmp = std::env::temp_dir().join(format!("dcc_filter_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp).unwrap();
+        let file = |name: &str, len: usize| {
+            let path = tmp.join(name);
+            std::fs::write(&path, "x".repeat(len)).unwrap();
+            path
+        };
+        let filter = PathFilter::default().with_sizes(Some(2), Some(4));
+        assert!(!filter.allows_file(&file("stub.docx", 1)));
+        assert!(filter.allows_file(&file("fine.docx", 4)));
+        assert!(!filter.allows_file(&file("huge.docx", 5)));
+        assert!(PathFilter::default().allows_file(&tmp.join("huge.docx")));
+
+        let hour_ago = parse_time("1h").unwrap();
+        let filter = PathFilter::default().with_modified(Some(hour_ago), None);
+        assert!(filter.allows_file(&tmp.join("fine.docx")));
+        let filter = PathFilter::default().with_modified(None, Some(hour_ago));
+        assert!(!filter.allows_file(&tmp.join("fine.docx")));
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let at = |s: &str| {
+            parse_time(s)
+                .unwrap()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        };
+        assert_eq!(at("1970-01-02"), 86_400);
+        assert_eq!(at("2024-02-29"), 1_709_164_800);
+        assert_eq!(at("2024-02-29T08:30"), 1_709_164_800 + 8 * 3600 + 30 * 60);
+        assert_eq!(at("2024-02-29 08:30:15"), 1_709_164_800 + 8 * 3600 + 30 * 60 + 15);
+        assert!(parse_time("2023-02-29").is_err());
+        assert!(parse_time("2024-01-01T24:00").is_err());
+        assert!(parse_time("yesterday").is_err());
+
+        let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 3600);
+        let parsed = parse_time("7d").unwrap();
+        assert!(
+            parsed <= week_ago + Duration::from_secs(5)
+                && parsed >= week_ago - Duration::from_secs(5)
+        );
+        assert!(parse_time("7y").is_err());
+    }
+}
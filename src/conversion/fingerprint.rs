@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// Kept next to the checkpoint, in the output directory (or the input directory without `--output`).
+pub const FILE_NAME: &str = ".document_converter_fingerprints.jsonl";
+
+/// A cheap stand-in for a source file's contents: its size and modification time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub size:     u64,
+    /// Nanoseconds since the Unix epoch, 0 if the platform can't tell.
+    pub modified: u64,
+}
+
+impl Fingerprint {
+    /// The fingerprint of the file at `path` as it is right now.
+    ///
+    /// # Errors
+    /// * If the file's metadata can't be read
+    pub fn of<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let meta = std::fs::metadata(path)?;
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| u64::try_from(since.as_nanos()).unwrap_or(u64::MAX));
+
+        Ok(Self {
+            size: meta.len(),
+            modified,
+        })
+    }
+}
+
+/// One line of the fingerprints file.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    input:  PathBuf,
+    output: PathBuf,
+    #[serde(flatten)]
+    print:  Fingerprint,
+}
+
+/// For every output we wrote, the fingerprint its source had at the time, kept across runs
+/// so a source that changed since gets converted again, instead of keeping its stale output.
+///
+/// New fingerprints are appended as conversions finish, the file is compacted
+/// (one line per output) whenever it's opened.
+#[derive(Debug)]
+pub struct Fingerprints {
+    /// output -> (input, fingerprint of input)
+    known: HashMap<PathBuf, (PathBuf, Fingerprint)>,
+    file:  Mutex<std::fs::File>,
+}
+
+impl Fingerprints {
+    /// Loads the fingerprints at `path`, creating the file if it doesn't exist yet.
+    ///
+    /// # Errors
+    /// * If the file can't be read or written
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        // Later lines are newer, so they win
+        let mut known = HashMap::new();
+        for (idx, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<Entry>(line) {
+                Ok(entry) => {
+                    known.insert(entry.output, (entry.input, entry.print));
+                }
+                Err(e) => {
+                    warn!("Skipping bad line {} in {}: {e}", idx + 1, path.display())
+                }
+            }
+        }
+
+        let mut compacted = String::new();
+        for (output, (input, print)) in &known {
+            compacted.push_str(&to_line(input, output, *print)?);
+        }
+        std::fs::write(path, compacted)?;
+
+        let file = std::fs::OpenOptions::new().append(true).open(path)?;
+        Ok(Self {
+            known,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// The fingerprint the source of `output` had when `output` was written, if we wrote it.
+    #[inline]
+    pub fn get(&self, output: &Path) -> Option<Fingerprint> {
+        self.known.get(output).map(|&(_, print)| print)
+    }
+
+    /// Appends `print` as the fingerprint `input` had when it was converted to `output`.
+    ///
+    /// # Errors
+    /// * If the line can't be written
+    pub fn record(&self, input: &Path, output: &Path, print: Fingerprint) -> Result<()> {
+        let line = to_line(input, output, print)?;
+        let mut file = self.file.lock().expect("fingerprints file lock poisoned");
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn to_line(input: &Path, output: &Path, print: Fingerprint) -> Result<String> {
+    let entry = Entry {
+        input: input.to_path_buf(),
+        output: output.to_path_buf(),
+        print,
+    };
+    let mut line = serde_json::to_string(&entry)
+        .map_err(|e| Error::Generic(format!("Failed to write fingerprint: {e}")))?;
+    line.push('\n');
+    Ok(line)
+}
+
+#[cfg(test)]
+mod fingerprint_tests {
+    use super::*;
+
+    #[test]
+    fn test_latest_wins_and_compacts() {
+        let dir =
+            std::env::temp_dir().join(format!("dcc_fingerprints_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(FILE_NAME);
+        let (old, new) = (
+            Fingerprint {
+                size:     1,
+                modified: 10,
+            },
+            Fingerprint {
+                size:     2,
+                modified: 20,
+            },
+        );
+
+        let (input, md, html) =
+            (Path::new("a.docx"), Path::new("a.md"), Path::new("a.html"));
+        let prints = Fingerprints::open(&path).unwrap();
+        assert_eq!(prints.get(md), None);
+        prints.record(input, md, old).unwrap();
+        prints.record(input, md, new).unwrap();
+        prints.record(input, html, old).unwrap();
+        drop(prints);
+
+        let prints = Fingerprints::open(&path).unwrap();
+        assert_eq!(prints.get(md), Some(new));
+        assert_eq!(prints.get(html), Some(old));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
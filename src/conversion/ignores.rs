@@ -0,0 +1,77 @@
+use std::path::Path;
+
+use ignore::Match;
+use ignore::gitignore::Gitignore;
+
+/// Our own ignore file, for folders that aren't (or shouldn't be) under git.
+pub const CONVERTIGNORE: &str = ".convertignore";
+
+/// The ignore files a folder can have, the ones that take precedence first,
+/// as the walk (`ignore::WalkBuilder`) reads them.
+const IGNORE_FILES: [&str; 3] = [CONVERTIGNORE, ".ignore", ".gitignore"];
+
+/// Whether `path` (below `root`) is ignored by an ignore file, or sits in a folder that is,
+/// the way the walk would have left it out. For paths the watcher reports one at a time.
+pub fn is_ignored(root: &Path, path: &Path, is_dir: bool) -> bool {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return false;
+    };
+
+    // From the top down, as the walk would never have gone into an ignored folder
+    let mut entry = root.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        entry.push(component);
+        let entry_is_dir = is_dir || components.peek().is_some();
+        if matched(&entry, entry_is_dir) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Whether the ignore files in the folders above `entry` ignore it.
+/// A deeper folder's ignore file wins over its parents', so a `!pattern` there can bring it back.
+fn matched(entry: &Path, is_dir: bool) -> bool {
+    for dir in entry.ancestors().skip(1) {
+        for name in IGNORE_FILES {
+            let ignore_file = dir.join(name);
+            if !ignore_file.is_file() {
+                continue;
+            }
+
+            // A broken line only costs its own pattern
+            let (gitignore, _) = Gitignore::new(&ignore_file);
+            match gitignore.matched(entry, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod ignores_tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ignored() {
+        let tmp =
+            std::env::temp_dir().join(format!("dcc_ignores_{}", std::process::id()));
+        std::fs::create_dir_all(tmp.join("a/b")).unwrap();
+        std::fs::write(tmp.join(CONVERTIGNORE), "Archive/\n*.tmp.docx\n").unwrap();
+        std::fs::write(tmp.join("a/.gitignore"), "b/\n").unwrap();
+        std::fs::write(tmp.join("a/.convertignore"), "!keep.tmp.docx\n").unwrap();
+        let ignored = |relative: &str| is_ignored(&tmp, &tmp.join(relative), false);
+
+        assert!(ignored("Archive/x.docx"));
+        assert!(ignored("draft.tmp.docx"));
+        assert!(ignored("a/b/x.docx"));
+        assert!(!ignored("a/x.docx"));
+        assert!(!ignored("a/keep.tmp.docx"));
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::shutdown::shield_from_ctrl_c;
+use crate::conversion::{Converter, canonical_ext, find_program, version_of};
+use crate::prelude::*;
+
+/// What `Engine::Auto` routes to the image converter: images going to any of `OUTPUT_FORMATS`.
+pub(crate) const INPUT_FORMATS: [&str; 8] =
+    ["png", "jpg", "tiff", "tif", "webp", "gif", "bmp", "heic"];
+
+/// What the image converter writes, picked by the output's extension.
+/// Pdf keeps every page of a multi-page scan, the others only the first.
+pub(crate) const OUTPUT_FORMATS: [&str; 8] =
+    ["png", "jpg", "tiff", "tif", "webp", "gif", "bmp", "pdf"];
+
+/// Which program converts the images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    /// ImageMagick 7, `magick input output`.
+    Magick,
+    /// libvips, `vips copy input output`.
+    Vips,
+}
+
+/// Converts images (scans, photos) between formats with ImageMagick or libvips, neither of which pandoc touches,
+/// so a run over a scanned archive can go `tiff` -> `png` or `tiff` -> `pdf`.
+pub struct ImageConverter<P = PathBuf> {
+    program_name: P,
+    tool:         Tool,
+}
+
+impl ImageConverter {
+    /// Create a new `ImageConverter` for the `magick` in PATH, or without one `vips`,
+    /// falling back to the bare `magick` for `check_installed` to report as missing.
+    #[inline]
+    pub fn new() -> Self {
+        if let Some(program_name) = find_program("magick") {
+            return Self {
+                program_name,
+                tool: Tool::Magick,
+            };
+        }
+        if let Some(program_name) = find_program("vips") {
+            return Self {
+                program_name,
+                tool: Tool::Vips,
+            };
+        }
+        debug!("Couldn't find magick or vips in PATH, falling back to 'magick'");
+        Self {
+            program_name: PathBuf::from("magick"),
+            tool:         Tool::Magick,
+        }
+    }
+}
+
+impl Default for ImageConverter {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What `tool` reads of `input` going to `output_ext`: every frame for a pdf,
+/// only the first otherwise, instead of ImageMagick writing `<stem>-0.png`, `<stem>-1.png`, ... itself.
+fn source_arg(tool: Tool, input: &Path, output_ext: &str) -> std::ffi::OsString {
+    let mut source = input.as_os_str().to_os_string();
+    if tool == Tool::Magick && output_ext != "pdf" {
+        source.push("[0]");
+    }
+    source
+}
+
+#[async_trait::async_trait]
+impl Converter for ImageConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        trace!("Converting '{}' to '{}'", input.display(), output.display());
+
+        let ext = output
+            .extension()
+            .map(|ext| canonical_ext(&ext.to_string_lossy()))
+            .ok_or_else(|| Error::InvalidExtension(output.display().to_string()))?;
+
+        // Keeps the extension, which both tools pick the output format from
+        let partial = PartialOutput::new(output);
+
+        let mut command = tokio::process::Command::new(&self.program_name);
+        shield_from_ctrl_c(&mut command);
+        if self.tool == Tool::Vips {
+            command.arg("copy");
+        }
+        let cmd_output = command
+            .arg(source_arg(self.tool, input, &ext))
+            .arg(partial.path())
+            // A timed-out conversion drops this future, take the converter down with it
+            .kill_on_drop(true)
+            .output()
+            .await?;
+
+        if !cmd_output.status.success() {
+            let mut stderr = String::new();
+            cmd_output.stderr.as_slice().read_to_string(&mut stderr)?;
+            return Err(Error::ConverterFailed {
+                program: self.name(),
+                input: input.to_path_buf(),
+                code: cmd_output.status.code(),
+                stderr,
+            });
+        }
+
+        partial.commit().await
+    }
+
+    async fn check_installed(&self) -> bool {
+        let checked = tokio::process::Command::new(&self.program_name)
+            .arg("--version")
+            .output()
+            .await
+            .map(|output| output.status.success());
+
+        debug!("Checked if {:?} is installed: {checked:?}", self.program_name);
+        checked.unwrap_or_else(|_| {
+            warn!("{:?} is not installed", self.program_name);
+            false
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        self.program_name.display().to_string()
+    }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Ok(INPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        Ok(OUTPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn version(&self) -> Option<String> {
+        version_of(&self.program_name).await
+    }
+}
+
+#[cfg(test)]
+mod image_tests {
+    use super::*;
+
+    #[test]
+    fn test_source_arg() {
+        let input = Path::new("scans/page.tiff");
+        assert_eq!(source_arg(Tool::Magick, input, "png"), "scans/page.tiff[0]");
+        assert_eq!(source_arg(Tool::Magick, input, "pdf"), "scans/page.tiff");
+        assert_eq!(source_arg(Tool::Vips, input, "png"), "scans/page.tiff");
+    }
+}
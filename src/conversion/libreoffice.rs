@@ -0,0 +1,216 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::shutdown::shield_from_ctrl_c;
+use crate::conversion::{Converter, version_of};
+use crate::prelude::*;
+
+/// Candidate binary names for LibreOffice, checked in order against each PATH entry.
+#[cfg(not(target_os = "windows"))]
+const CANDIDATES: [&str; 3] = ["soffice", "libreoffice", "libreoffice7.6"];
+
+#[cfg(target_os = "windows")]
+const CANDIDATES: [&str; 3] = ["soffice.exe", "libreoffice.exe", "libreoffice7.6.exe"];
+
+/// Extensions LibreOffice can import, including the legacy binary Office formats pandoc can't read.
+const INPUT_FORMATS: &[&str] = &[
+    "doc", "docx", "dot", "dotx", "rtf", "odt", "ott", "wpd", "pages", "txt", "html",
+    "xls", "xlsx", "ods", "csv", "ppt", "pptx", "pps", "ppsx", "odp",
+];
+
+/// Extensions LibreOffice can export to via `--convert-to`.
+pub(crate) const OUTPUT_FORMATS: &[&str] = &[
+    "pdf", "docx", "doc", "odt", "rtf", "txt", "html", "xlsx", "xls", "ods", "csv",
+    "pptx", "ppt", "odp",
+];
+
+/// Used to give every conversion its own LibreOffice user profile,
+/// see `LibreOfficeConverter::convert` for why.
+static PROFILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// What the throwaway profile folders in the temp folder are named, see `clean`.
+pub(crate) const PROFILE_DIR_PREFIX: &str = "dcc_lo_profile_";
+
+/// Per-run LibreOffice settings, from the config's `[libreoffice]` table.
+#[derive(Debug, Clone, Default)]
+pub struct LibreOfficeOptions {
+    /// The soffice binary to run, instead of scanning PATH for one.
+    pub program: Option<PathBuf>,
+}
+
+/// Converts with LibreOffice (`soffice --headless --convert-to`), for the formats pandoc can't read.
+pub struct LibreOfficeConverter<P = PathBuf> {
+    program_name: P,
+}
+
+impl LibreOfficeConverter {
+    /// Create a new `LibreOfficeConverter`
+    ///
+    /// ### Note:
+    /// The binary is discovered via a best-effort PATH scan (see `scan_path_env_for_soffice`).
+    /// If nothing is found we fall back to the bare `soffice` name and let
+    /// `check_installed` report the problem before any conversion starts.
+    #[inline]
+    pub fn new() -> Self {
+        let program_name = scan_path_env_for_soffice().unwrap_or_else(|| {
+            debug!("Couldn't find LibreOffice in PATH, falling back to 'soffice'");
+            PathBuf::from(CANDIDATES[0])
+        });
+        Self { program_name }
+    }
+
+    /// Applies `options`, an explicit `program` replaces whatever was found in PATH.
+    #[must_use]
+    pub fn with_options(mut self, options: LibreOfficeOptions) -> Self {
+        if let Some(program) = options.program {
+            self.program_name = program;
+        }
+        self
+    }
+}
+
+impl Default for LibreOfficeConverter {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl Converter for LibreOfficeConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        trace!("Converting '{}' to '{}'", input.display(), output.display());
+
+        let target_ext = output
+            .extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| Error::InvalidExtension(output.display().to_string()))?;
+        let input_stem = input.file_stem().ok_or_else(|| {
+            Error::Generic(format!("No file stem for {}", input.display()))
+        })?;
+        // LibreOffice only accepts an output *directory* and always names the result
+        // after the input's stem, so we point it at a staging folder next to the requested output
+        // and move the result onto it once it's complete.
+        let partial = PartialOutput::in_staging_dir(
+            output,
+            &Path::new(input_stem).with_extension(target_ext),
+        )
+        .await?;
+        let produced = partial.path();
+        let out_dir = produced.parent().unwrap_or(produced);
+
+        // Concurrent soffice processes sharing the default user profile will fail
+        // (or silently do nothing), so every conversion gets a throwaway profile.
+        let profile_dir = std::env::temp_dir().join(format!(
+            "{PROFILE_DIR_PREFIX}{}_{}",
+            std::process::id(),
+            PROFILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let mut command = tokio::process::Command::new(&self.program_name);
+        let cmd = shield_from_ctrl_c(&mut command)
+            .arg(format!("-env:UserInstallation={}", file_url(&profile_dir)))
+            .arg("--headless")
+            .arg("--convert-to")
+            .arg(target_ext)
+            .arg("--outdir")
+            .arg(out_dir)
+            .arg(input)
+            // A timed-out conversion drops this future, take soffice down with it
+            .kill_on_drop(true)
+            .output()
+            .await;
+
+        if let Err(e) = tokio::fs::remove_dir_all(&profile_dir).await {
+            trace!("Failed to remove LibreOffice profile dir {profile_dir:?}: {e}");
+        }
+
+        let cmd_output = cmd.map_err(Error::from)?;
+
+        if !cmd_output.status.success() || !produced.exists() {
+            let mut stderr = String::new();
+            cmd_output.stderr.as_slice().read_to_string(&mut stderr)?;
+            // soffice can exit 0 without writing anything
+            if cmd_output.status.success() && stderr.is_empty() {
+                stderr = format!("no output written to {}", produced.display());
+            }
+            return Err(Error::ConverterFailed {
+                program: self.name(),
+                input: input.to_path_buf(),
+                code: cmd_output.status.code(),
+                stderr,
+            });
+        }
+
+        partial.commit().await
+    }
+
+    async fn check_installed(&self) -> bool {
+        let program_name = self.program_name.clone();
+
+        let checked = tokio::process::Command::new(&program_name)
+            .arg("--version")
+            .output()
+            .await
+            .map(|output| output.status.success());
+
+        debug!("Checked if {program_name:?} is installed: {checked:?}");
+
+        if checked.is_err() {
+            warn!("{program_name:?} is not installed");
+            return false;
+        }
+
+        checked.unwrap_or(false)
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        self.program_name.display().to_string()
+    }
+
+    /// soffice has no way to list its filters, so this is a fixed list of the common ones.
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Ok(INPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    /// See `supported_input_formats`.
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        Ok(OUTPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn version(&self) -> Option<String> {
+        version_of(&self.program_name).await
+    }
+}
+
+/// Takes a best-effort scan of the PATH environment variable
+/// checking each of the `CANDIDATES` names against every path entry.
+///
+#[must_use]
+pub fn scan_path_env_for_soffice() -> Option<PathBuf> {
+    let path_env = std::env::var("PATH").unwrap_or_default();
+
+    for p in std::env::split_paths(&path_env) {
+        for cand in &CANDIDATES {
+            let full_path = p.join(cand);
+            if full_path.exists() && full_path.is_file() {
+                return Some(full_path);
+            }
+        }
+    }
+
+    None
+}
+
+/// LibreOffice wants the `UserInstallation` as a `file://` URL.
+fn file_url(path: &Path) -> String {
+    let path = path.display().to_string().replace('\\', "/");
+    if path.starts_with('/') {
+        format!("file://{path}")
+    } else {
+        format!("file:///{path}")
+    }
+}
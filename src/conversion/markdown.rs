@@ -0,0 +1,103 @@
+use std::path::Path;
+
+use pulldown_cmark::{Options, Parser};
+
+use crate::conversion::Converter;
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::report::html_escape;
+use crate::prelude::*;
+
+/// The pair `Engine::Auto` converts with the `MarkdownConverter`, by canonical extension.
+pub(crate) const ROUTE: (&str, &str) = ("markdown", "html");
+
+/// What the `MarkdownConverter` reports as its name and version, there's no program to ask.
+const NAME: &str = "pulldown-cmark";
+const VERSION: &str = "pulldown-cmark 0.13";
+
+/// Converts markdown to HTML in-process with `pulldown-cmark`, the most common conversion
+/// without starting pandoc for every file (or needing it installed at all).
+///
+/// Writes a standalone page like `pandoc -s` does, titled after the file, with CommonMark
+/// plus the GitHub extensions (tables, footnotes, strikethrough, task lists).
+/// Pandoc's own markdown extensions (definition lists, citations, ...) aren't read, see `ConverterRegistry::for_engine`.
+#[derive(Debug, Default)]
+pub struct MarkdownConverter;
+
+impl MarkdownConverter {
+    /// Create a new `MarkdownConverter`.
+    #[inline]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// `markdown` as a standalone HTML page titled `title`.
+fn to_html(title: &str, markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_HEADING_ATTRIBUTES;
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n",
+        html_escape(title)
+    );
+    pulldown_cmark::html::push_html(&mut html, Parser::new_ext(markdown, options));
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[async_trait::async_trait]
+impl Converter for MarkdownConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        trace!("Converting '{}' to '{}'", input.display(), output.display());
+
+        let bytes = tokio::fs::read(input).await?;
+        let title = input.file_stem().unwrap_or_default().to_string_lossy();
+        let html = to_html(&title, &String::from_utf8_lossy(&bytes));
+
+        // Dropped (and removed) on any early return, so a failed write leaves no half-written output
+        let partial = PartialOutput::new(output);
+        tokio::fs::write(partial.path(), html).await?;
+        partial.commit().await
+    }
+
+    async fn check_installed(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        NAME.to_string()
+    }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Ok(vec![ROUTE.0.to_string()])
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        Ok(vec![ROUTE.1.to_string()])
+    }
+
+    async fn version(&self) -> Option<String> {
+        Some(VERSION.to_string())
+    }
+}
+
+#[cfg(test)]
+mod markdown_tests {
+    use super::*;
+
+    #[test]
+    fn test_to_html() {
+        let html =
+            to_html("a <b>", "# Title\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n~~gone~~\n");
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>a &lt;b&gt;</title>"));
+        assert!(html.contains("<h1>Title</h1>"));
+        assert!(html.contains("<table>"));
+        assert!(html.contains("<del>gone</del>"));
+        assert!(html.ends_with("</body>\n</html>\n"));
+    }
+}
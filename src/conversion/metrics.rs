@@ -0,0 +1,248 @@
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use prometheus_client::encoding::text::encode;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{Histogram, exponential_buckets};
+use prometheus_client::registry::{Registry, Unit};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::prelude::*;
+
+/// How much of a request is read before it's answered, only its first line matters.
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+/// Counters, histograms and gauges of a run, served in the Prometheus text format at `/metrics` (`--metrics-addr`).
+///
+/// Cheap to update from many tasks at once, every metric is atomic.
+#[derive(Debug)]
+pub struct Metrics {
+    registry:  Registry,
+    converted: Counter,
+    failed:    Counter,
+    skipped:   Counter,
+    duration:  Histogram,
+    size:      Histogram,
+    queued:    Gauge,
+    in_flight: Gauge,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let metrics = Self {
+            registry:  Registry::with_prefix("document_converter"),
+            converted: Counter::default(),
+            failed:    Counter::default(),
+            skipped:   Counter::default(),
+            // 0.1s to ~7 minutes
+            duration:  Histogram::new(exponential_buckets(0.1, 2.0, 13)),
+            // 1 KiB to 1 GiB
+            size:      Histogram::new(exponential_buckets(1024.0, 4.0, 11)),
+            queued:    Gauge::default(),
+            in_flight: Gauge::default(),
+        };
+        metrics.registered()
+    }
+}
+
+impl Metrics {
+    /// Adds every metric to the registry, which only holds handles to them.
+    fn registered(mut self) -> Self {
+        let registry = &mut self.registry;
+        registry.register("files_converted", "Files converted", self.converted.clone());
+        registry.register(
+            "files_failed",
+            "Files whose conversion failed, after any retries",
+            self.failed.clone(),
+        );
+        registry.register(
+            "files_skipped",
+            "Files skipped, e.g. because their output is up to date",
+            self.skipped.clone(),
+        );
+        registry.register_with_unit(
+            "conversion_duration",
+            "How long each conversion took, retries included",
+            Unit::Seconds,
+            self.duration.clone(),
+        );
+        registry.register_with_unit(
+            "input_size",
+            "The size of each converted file",
+            Unit::Bytes,
+            self.size.clone(),
+        );
+        registry.register(
+            "queue_depth",
+            "Files waiting for a job slot",
+            self.queued.clone(),
+        );
+        registry.register(
+            "conversions_in_flight",
+            "Conversions running right now",
+            self.in_flight.clone(),
+        );
+        self
+    }
+
+    /// A file was queued, and waits for a job slot.
+    pub fn queued(&self) {
+        self.queued.inc();
+    }
+
+    /// A queued file got its job slot, or won't need one after all.
+    pub fn dequeued(&self) {
+        self.queued.dec();
+    }
+
+    /// A conversion started.
+    pub fn started(&self) {
+        self.in_flight.inc();
+    }
+
+    /// A started conversion of `input` finished after it `took` this long, `res` being what `convert_single` returned.
+    pub fn finished(&self, input: &Path, res: &Result<usize>, took: Duration) {
+        self.in_flight.dec();
+        self.duration.observe(took.as_secs_f64());
+        if let Ok(meta) = input.metadata() {
+            #[allow(clippy::cast_precision_loss)]
+            self.size.observe(meta.len() as f64);
+        }
+        match res {
+            Ok(_) => self.converted.inc(),
+            Err(_) => self.failed.inc(),
+        };
+    }
+
+    /// A file failed without its conversion being started, like one that doesn't hold what its extension says.
+    pub fn failed(&self) {
+        self.failed.inc();
+    }
+
+    /// A file was skipped.
+    pub fn skipped(&self) {
+        self.skipped.inc();
+    }
+
+    /// Every metric, in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut text = String::new();
+        encode(&mut text, &self.registry).expect("writing to a String can't fail");
+        text
+    }
+
+    /// Binds `addr` and answers `GET /metrics` there for as long as the runtime runs.
+    ///
+    /// # Errors
+    /// * If `addr` can't be bound
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        info!("Serving metrics at http://{}/metrics", listener.local_addr()?);
+
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Failed to accept a metrics connection: {e}");
+                        continue;
+                    }
+                };
+                let metrics = Arc::clone(&self);
+                tokio::spawn(async move {
+                    if let Err(e) = metrics.respond(stream).await {
+                        debug!("Failed to answer a metrics request: {e}");
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Answers a single request, closing the connection after it.
+    async fn respond(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut request = Vec::new();
+        let mut buf = [0; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n")
+            && request.len() < MAX_REQUEST_LEN
+        {
+            let read = stream.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..read]);
+        }
+
+        let line = request.split(|&b| b == b'\r').next().unwrap_or_default();
+        let (status, content_type, body) = if line.starts_with(b"GET /metrics ") {
+            (
+                "200 OK",
+                "application/openmetrics-text; version=1.0.0; charset=utf-8",
+                self.encode(),
+            )
+        } else {
+            (
+                "404 Not Found",
+                "text/plain; charset=utf-8",
+                "Not found, try /metrics\n".to_string(),
+            )
+        };
+        let response = format!(
+            "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serve() {
+        let metrics = Arc::new(Metrics::default());
+        metrics.queued();
+        metrics.dequeued();
+        metrics.started();
+        metrics.finished(Path::new("Cargo.toml"), &Ok(0), Duration::from_millis(300));
+        metrics.queued();
+        metrics.skipped();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        Arc::clone(&metrics).serve(addr).await.unwrap();
+
+        let get = |path: &'static str| {
+            async move {
+                let mut stream = TcpStream::connect(addr).await.unwrap();
+                stream
+                    .write_all(
+                        format!("GET {path} HTTP/1.1\r\nHost: x\r\n\r\n").as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).await.unwrap();
+                response
+            }
+        };
+
+        let response = get("/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("document_converter_files_converted_total 1"));
+        assert!(response.contains("document_converter_files_skipped_total 1"));
+        assert!(response.contains("document_converter_queue_depth 1"));
+        assert!(response.contains("document_converter_conversions_in_flight 0"));
+        assert!(
+            response.contains("document_converter_conversion_duration_seconds_count 1")
+        );
+        assert!(get("/").await.starts_with("HTTP/1.1 404"));
+    }
+}
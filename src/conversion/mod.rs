@@ -1,31 +1,391 @@
-pub(crate) mod pandoc;
+pub mod asciidoctor;
+pub mod calibre;
+pub mod chained;
+pub mod checkpoint;
+pub mod clean;
+pub mod docx;
+pub mod email;
+pub mod events;
+pub mod filter;
+pub mod fingerprint;
+pub mod ignores;
+pub mod image;
+pub mod libreoffice;
+pub mod markdown;
+pub mod metrics;
+pub mod naming;
+pub mod ocr;
+mod ooxml;
+pub mod order;
+pub mod originals;
+pub mod pandoc;
+pub mod pandoc_server;
+pub mod partial;
+pub mod pdf;
+pub mod pipe;
+pub mod plan;
+mod pool;
+pub mod pptx;
+pub mod quarantine;
+pub mod registry;
+pub mod renames;
+pub mod report;
+pub mod retry;
+pub mod shutdown;
+pub mod sniff;
+pub mod spreadsheet;
+pub mod subset;
+pub mod typst;
+pub mod watch;
 
 // use std::collections::HashMap;
 
-use std::ops::{Div, Mul};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-
-use walkdir::WalkDir;
-
+use std::time::Duration;
+
+use futures::StreamExt;
+use futures::stream::FuturesUnordered;
+use ignore::WalkBuilder;
+use indicatif::ProgressBar;
+use tokio::io::AsyncReadExt;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::conversion::checkpoint::Checkpoint;
+use crate::conversion::events::{Event, EventLog, SkipReason};
+use crate::conversion::filter::PathFilter;
+use crate::conversion::fingerprint::{Fingerprint, Fingerprints};
+use crate::conversion::metrics::Metrics;
+use crate::conversion::order::Order;
+use crate::conversion::pool::{Job, Outcome, Shared, WorkerPool};
+use crate::conversion::registry::ConverterRegistry;
+use crate::conversion::renames::RenameJournal;
+use crate::conversion::report::{ConversionReport, FileOutcome};
+use crate::conversion::shutdown::Shutdown;
+use crate::conversion::sniff::Detected;
+use crate::conversion::subset::Subset;
 #[allow(unused_imports)]
 use crate::lazy_logger::LazyLogger;
 use crate::prelude::*;
+use crate::progress;
+use crate::tui::Dashboard;
+
+/// Characters replaced in file/folder names unless `--danger-chars` says otherwise.
+pub const DEFAULT_DANGER_CHARS: [char; 2] = ['$', '~'];
+
+/// The input list (`--files-from`) name that reads the list from stdin.
+pub const STDIN_LIST: &str = "-";
+
+/// How dangerous file and folder names get fixed (`--danger-chars`, `--replacement`,
+/// `--max-name-len` and `--trim-trailing`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameRules {
+    /// Characters replaced with `replacement`.
+    pub danger_chars:  Vec<char>,
+    /// What each dangerous character becomes, empty drops them.
+    pub replacement:   String,
+    /// The longest a name may be, in characters. Longer names lose the end of their stem,
+    /// their extension is kept.
+    pub max_len:       Option<usize>,
+    /// Strip trailing dots and spaces, which Windows (and so most file shares) can't handle.
+    pub trim_trailing: bool,
+}
 
-const DANGER_CHARS: [&str; 2] = ["$", "~"];
+impl Default for NameRules {
+    fn default() -> Self {
+        Self {
+            danger_chars:  DEFAULT_DANGER_CHARS.to_vec(),
+            replacement:   "_".to_string(),
+            max_len:       None,
+            trim_trailing: false,
+        }
+    }
+}
+
+impl NameRules {
+    /// A single file or folder `name` with the rules applied.
+    /// `.` and `..` are left alone, and trimming never leaves a name empty.
+    pub fn apply(&self, name: &str) -> String {
+        if name.is_empty() || name == "." || name == ".." {
+            return name.to_string();
+        }
+
+        let mut fixed = name.replace(self.danger_chars.as_slice(), &self.replacement);
+        if let Some(max_len) = self.max_len
+            && fixed.chars().count() > max_len
+        {
+            fixed = truncate_name(&fixed, max_len);
+        }
+        if self.trim_trailing {
+            let trimmed = fixed.trim_end_matches(['.', ' ']);
+            if !trimmed.is_empty() {
+                fixed.truncate(trimmed.len());
+            }
+        }
+        fixed
+    }
+}
+
+/// `name` cut down to `max_len` characters, taking them off the end of the stem
+/// unless the extension alone is too long.
+fn truncate_name(name: &str, max_len: usize) -> String {
+    let (stem, ext) = match name.rfind('.') {
+        Some(dot) if dot > 0 => name.split_at(dot),
+        _ => (name, ""),
+    };
+    let ext_len = ext.chars().count();
+    if ext_len >= max_len {
+        return name.chars().take(max_len).collect();
+    }
+
+    let mut truncated: String = stem.chars().take(max_len - ext_len).collect();
+    truncated.push_str(ext);
+    truncated
+}
 
 static INITIAL_CAPACITY: usize = 1024;
 
+/// Extensions that are another spelling of a format, `(alias, format)`, see `canonical_ext`.
+const EXT_ALIASES: [(&str, &str); 3] =
+    [("md", "markdown"), ("htm", "html"), ("jpeg", "jpg")];
+
+/// Input extensions pandoc can't read, which `Engine::Auto` hands to LibreOffice.
+pub(crate) const LIBREOFFICE_ONLY_INPUTS: [&str; 12] = [
+    "doc", "dot", "wpd", "pages", "xls", "xlsx", "ods", "ppt", "pps", "pptx", "ppsx",
+    "odp",
+];
+
+/// Which `Converter` implementation a run uses.
+#[derive(Debug, clap::ValueEnum, Clone, Copy, PartialEq, Eq, Default)]
+#[clap(name = "Engine", rename_all = "lower")]
+pub enum Engine {
+    #[default]
+    #[value(name = "pandoc", alias = "Pandoc")]
+    Pandoc,
+    #[value(name = "libreoffice", alias = "LibreOffice", alias = "soffice")]
+    LibreOffice,
+    /// LibreOffice for formats pandoc can't read (legacy Office, spreadsheets, slides),
+    /// pandoc otherwise, see `ConverterRegistry::for_engine`.
+    #[value(name = "auto", alias = "Auto")]
+    Auto,
+}
+
+/// What to do with a file whose output already exists (`--on-exists`).
+#[derive(Debug, clap::ValueEnum, Clone, Copy, PartialEq, Eq, Default)]
+#[clap(name = "OnExists", rename_all = "kebab-case")]
+pub enum OnExists {
+    /// Keep the output, unless the input changed since we wrote it (see `fingerprint::Fingerprints`).
+    #[default]
+    Skip,
+    /// Always convert again, replacing the output.
+    Overwrite,
+    /// Convert to the first free `name (1).md`, `name (2).md`, ... next to it instead.
+    Rename,
+    /// Convert again if the input was modified after the output was written.
+    IfNewer,
+}
+
+/// A conversion backend.
+///
+/// Kept dyn-compatible so a `ConverterRegistry` can hold several and pick one per file.
 #[async_trait::async_trait]
-pub trait Converter {
-    // TODO: Have convert take an 'engine' enum - pandoc, libreoffice, etc
-    async fn convert<P: AsRef<Path> + Send + Sync>(
-        &self,
-        input: P,
-        output: P,
-    ) -> Result<()>;
-    async fn check_installed(&self) -> impl Into<bool>;
-    fn name(&self) -> impl AsRef<str>;
+pub trait Converter: Send + Sync {
+    /// Converts `input` to `output`, whose extension says what to convert it to.
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()>;
+    /// Whether the backend's program can be run, see `ConverterRegistry::ensure_installed`.
+    async fn check_installed(&self) -> bool;
+    /// What the backend is called in logs and reports.
+    fn name(&self) -> String;
+
+    /// Like `convert`, but reads `input` as `format` whatever its extension says (see `sniff::detect`).
+    /// Backends that work the format out from the content themselves can keep the default.
+    async fn convert_as(&self, input: &Path, output: &Path, format: &str) -> Result<()> {
+        trace!("Converting {input:?} as '{format}'");
+        self.convert(input, output).await
+    }
+
+    /// The input formats this backend can read, as reported by the backend itself.
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Err(Error::Generic("not supported".into()))
+    }
+
+    /// The output formats this backend can write, as reported by the backend itself.
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        Err(Error::Generic("not supported".into()))
+    }
+
+    /// The backend's version as it reports it, `None` if it can't be run or doesn't say.
+    async fn version(&self) -> Option<String> {
+        None
+    }
+
+    /// Fails if the installed program is too old for the run, checked up front by `ConverterRegistry::ensure_installed`
+    /// rather than failing every file half way through the batch.
+    async fn check_version(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// The mean OCR confidence (0 - 100) of the words read converting to `output`, for the report.
+    /// `None` for backends that don't OCR, or if no words were read.
+    fn ocr_confidence(&self, _output: &Path) -> Option<f64> {
+        None
+    }
+
+    /// Whether `output` was written by a lossy fallback (text extraction) rather than a real conversion,
+    /// for the report to mark it.
+    fn degraded(&self, _output: &Path) -> bool {
+        false
+    }
+
+    /// The files converting to `output` wrote next to it (a spreadsheet's other sheets),
+    /// for the checkpoint, the fingerprints and the report to have them as outputs too.
+    fn extra_outputs(&self, _output: &Path) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// Where `program` (without `.exe`, that's added on Windows) is in `PATH`, `None` if it's in none of its folders.
+pub(crate) fn find_program(program: &str) -> Option<PathBuf> {
+    let program = format!("{program}{}", std::env::consts::EXE_SUFFIX);
+    let path_env = std::env::var_os("PATH").unwrap_or_default();
+    std::env::split_paths(&path_env)
+        .map(|dir| dir.join(&program))
+        .find(|path| path.is_file())
+}
+
+/// The first line `program --version` prints, for `Converter::version`.
+pub(crate) async fn version_of(program: &Path) -> Option<String> {
+    let output = tokio::process::Command::new(program)
+        .arg("--version")
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+/// Knobs for a conversion run that aren't specific to a single backend.
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    /// Per-file timeout in seconds, `None` waits for as long as the converter takes.
+    pub timeout:            Option<u64>,
+    /// Abort the whole run on the first failed conversion.
+    pub fail_fast:          bool,
+    /// Output file name pattern, or path template, see `naming::expand_pattern`.
+    pub output_pattern:     String,
+    /// How many times a failed conversion is retried, see `retry::with_retry`.
+    pub retries:            usize,
+    /// How many folders deep below the input root to look, `Some(0)` is root files only.
+    pub max_depth:          Option<usize>,
+    /// Which files (and folders) below the input root are walked, see `filter::PathFilter`.
+    pub filter:             PathFilter,
+    /// Leave out what `.gitignore`, `.ignore` and `.convertignore` files ignore, see `walk_dir`.
+    pub ignore_files:       bool,
+    /// Walk into symlinked folders, see `walk_dir`.
+    pub follow_symlinks:    bool,
+    /// The input list separates its paths with NULs rather than newlines, see `list_paths`.
+    pub list_nul_separated: bool,
+    /// The order files are converted in, `None` converts them as they're found, see `arrange`.
+    pub order:              Option<Order>,
+    /// Only convert part of the files found (`--limit`, `--sample`), `None` converts them all.
+    pub subset:             Option<Subset>,
+    /// How dangerous characters in file (and folder) names are replaced (see `NameRules::replacement`),
+    /// and long names shortened. Output paths below an output directory are always fixed, inputs only with `renames`.
+    pub name_rules:         NameRules,
+    /// How many conversions may run at the same time.
+    pub jobs:               usize,
+    /// Where finished conversions are recorded (see `checkpoint::Checkpoint`), `None` keeps no record.
+    pub checkpoint:         Option<PathBuf>,
+    /// Skip the files `checkpoint` already lists, instead of starting it over.
+    pub resume:             bool,
+    /// Where the sources' fingerprints are kept between runs (see `fingerprint::Fingerprints`),
+    /// `None` skips every file whose output exists, changed or not.
+    pub fingerprints:       Option<PathBuf>,
+    /// Write every output straight into the output directory instead of mirroring the input tree,
+    /// with the names shared by the whole run (batch and watch alike) so none clash.
+    pub flatten:            Option<Arc<naming::FlatNames>>,
+    /// What to do with a file whose output already exists.
+    pub on_exists:          OnExists,
+    /// Copy each input's modification time (and on unix, its permissions) onto its output.
+    pub preserve_metadata:  bool,
+    /// The outputs handed out so far, shared by the whole run (batch and watch alike)
+    /// so no two inputs are written to the same one.
+    pub claimed_outputs:    Arc<naming::ClaimedOutputs>,
+    /// Check each input's content against its extension before converting it (see `sniff::detect`),
+    /// skipping empty placeholders and converting misnamed files as what they really are.
+    pub detect_content:     bool,
+    /// Rename inputs whose names break `name_rules` before converting them,
+    /// recording every rename here (`--sanitize-names`). `None` leaves them as they are.
+    pub renames:            Option<Arc<RenameJournal>>,
+    /// Where every file's lifecycle is written as JSON lines (`--events`), `None` writes none.
+    pub events:             Option<Arc<EventLog>>,
+    /// Where the run's counters, histograms and gauges are kept (`--metrics-addr`), `None` keeps none.
+    pub metrics:            Option<Arc<Metrics>>,
+    /// The `--tui` dashboard the conversions report to, whose keys pause, skip and stop them. `None` without one.
+    pub dashboard:          Option<Arc<Dashboard>>,
+    /// Stops the run early, see `shutdown::Shutdown`.
+    pub shutdown:           Shutdown,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            timeout:            None,
+            fail_fast:          false,
+            output_pattern:     naming::DEFAULT_PATTERN.to_string(),
+            retries:            0,
+            max_depth:          None,
+            filter:             PathFilter::default(),
+            ignore_files:       true,
+            follow_symlinks:    false,
+            list_nul_separated: false,
+            order:              None,
+            subset:             None,
+            name_rules:         NameRules::default(),
+            jobs:               default_jobs(),
+            checkpoint:         None,
+            resume:             false,
+            fingerprints:       None,
+            flatten:            None,
+            on_exists:          OnExists::default(),
+            preserve_metadata:  false,
+            claimed_outputs:    Arc::default(),
+            detect_content:     false,
+            renames:            None,
+            events:             None,
+            metrics:            None,
+            dashboard:          None,
+            shutdown:           Shutdown::default(),
+        }
+    }
+}
+
+impl ConvertOptions {
+    /// How many discovered-but-not-yet-started files may queue up when streaming.
+    pub fn channel_capacity(&self) -> usize {
+        self.jobs.max(1)
+    }
+
+    /// The semaphore every conversion of a watch run takes a permit from, see `jobs`.
+    /// A batch has its `pool::WorkerPool` instead.
+    pub(crate) fn job_permits(&self) -> Arc<Semaphore> {
+        Arc::new(Semaphore::new(self.jobs.max(1)))
+    }
+}
+
+/// The default for `--jobs`: one conversion per CPU.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(4, std::num::NonZeroUsize::get)
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +411,8 @@ impl AsRef<FileEntry> for FileEntry {
     }
 }
 
+/// The files a run converts, each with its path relative to `input_root`,
+/// which is where its output ends up below an output directory. See `find_by_ext`.
 #[derive(Debug, Default)]
 pub struct ConvertableEnts<P: AsRef<Path> = PathBuf, F: AsRef<FileEntry> = FileEntry> {
     pub input_root: P,
@@ -72,6 +434,12 @@ impl AsMut<ConvertableEnts> for &mut ConvertableEnts {
 }
 
 impl ConvertableEnts {
+    /// Starts building a `ConvertableEnts`, see `ConvertableEntsBuilder`.
+    pub fn builder() -> ConvertableEntsBuilder {
+        ConvertableEntsBuilder::default()
+    }
+
+    /// An empty set of files below `root`, with room for `cap` of them before it has to grow.
     pub fn new_with_capacity<P: AsRef<Path>>(root: P, cap: usize) -> Self {
         Self {
             input_root: root.as_ref().to_path_buf(),
@@ -80,148 +448,935 @@ impl ConvertableEnts {
         }
     }
 
-    pub fn add_file<P: AsRef<Path>>(&mut self, abs_path: P) {
+    /// Adds the file at `abs_path`, with its path relative to `input_root` and how deep below it it is.
+    ///
+    /// # Errors
+    /// * `Error::Generic` - If `abs_path` isn't below `input_root`, nothing is added then
+    pub fn add_file<P: AsRef<Path>>(&mut self, abs_path: P) -> Result<()> {
         let abs_path = abs_path.as_ref();
-        let relative = abs_path.strip_prefix(&self.input_root).unwrap();
-        let root_dir_depth = self.input_root.components().count();
-
-        let depth = abs_path.components().count() - root_dir_depth;
+        let relative = relative_to(&self.input_root, abs_path)?;
+        let depth = relative.components().count();
 
         // let parent = abs_path.parent().unwrap().to_path_buf();
         // let idx = self.files.len();
 
         self.files.push(FileEntry::new(abs_path, relative, depth));
         // self.files_by_parent.entry(parent).or_default().push(idx);
+        Ok(())
     }
 
-    pub fn count(&self) -> usize {
+    #[inline]
+    pub fn len(&self) -> usize {
         self.files.len()
     }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+impl IntoIterator for ConvertableEnts {
+    type IntoIter =
+        std::iter::Map<std::vec::IntoIter<FileEntry>, fn(FileEntry) -> PathBuf>;
+    type Item = PathBuf;
+
+    /// Yields the absolute path of every file, in discovery order.
+    fn into_iter(self) -> Self::IntoIter {
+        self.files.into_iter().map(|entry| entry.abs_path)
+    }
+}
+
+/// Collects files first and works out their relative paths in `build`,
+/// so the top-level folder can be given before or after the files.
+#[derive(Debug, Default)]
+pub struct ConvertableEntsBuilder {
+    input_root: PathBuf,
+    files:      Vec<PathBuf>,
+}
+
+impl ConvertableEntsBuilder {
+    /// The top-level folder the files' relative (and so output) paths are worked out from.
+    /// Only one is kept, a later call replaces an earlier one.
+    pub fn add_top_level(&mut self, root: PathBuf) -> &mut Self {
+        self.input_root = root;
+        self
+    }
+
+    /// Adds a file, which must sit below the top-level folder.
+    /// It's only checked in `build`, the folder may not be given yet.
+    pub fn add_file(&mut self, path: PathBuf) -> &mut Self {
+        self.files.push(path);
+        self
+    }
+
+    /// The `ConvertableEnts` of every file added so far, in the order they were added,
+    /// which leaves the builder without any.
+    ///
+    /// # Errors
+    /// * `Error::Generic` - If a file isn't below the top-level folder
+    pub fn build(&mut self) -> Result<ConvertableEnts> {
+        let files = std::mem::take(&mut self.files);
+        let mut pe = ConvertableEnts::new_with_capacity(&self.input_root, files.len());
+        for file in files {
+            pe.add_file(file)?;
+        }
+        Ok(pe)
+    }
 }
 
-pub async fn convert_files<Ce, C, S, P>(
+/// Converts every file in `convertables` to each of `target_exts`,
+/// each with the converter `registry` routes it to.
+///
+/// # Errors
+/// * If no converter is registered for a file, or an output folder can't be created
+pub async fn convert_files<Ce, P>(
     convertables: Ce,
-    converter: Arc<C>,
-    target_ext: S,
+    registry: &ConverterRegistry,
+    target_exts: &[String],
     output_dir: Option<P>,
-) -> Result<()>
+    options: &ConvertOptions,
+) -> Result<ConversionReport>
 where
     Ce: AsRef<ConvertableEnts>,
-    C: Converter + Send + Sync + 'static,
-    S: AsRef<str>,
     P: AsRef<Path>,
 {
-    if !converter.check_installed().await.into() {
-        return Err(Error::ConversionProgramNotInstalled(
-            converter.name().as_ref().to_string(),
-        ));
+    let convertables = convertables.as_ref();
+    let batch = Batch::new(
+        registry,
+        target_exts,
+        output_dir.as_ref().map(AsRef::as_ref),
+        options,
+    )?;
+    let mut tasks = Vec::with_capacity(convertables.len());
+
+    for (idx, entry) in convertables.files.iter().enumerate() {
+        if batch.stopped() {
+            break;
+        }
+        let task = batch
+            .queue(entry.abs_path.clone(), &entry.rel_path, idx + 1)
+            .await?;
+        tasks.extend(task);
     }
 
-    let convertables = convertables.as_ref();
-    let mut tasks = Vec::with_capacity(convertables.count());
+    info!("Queued {} conversions, {} at a time", tasks.len(), options.jobs);
 
-    for entry in &convertables.files {
-        let input = &entry.abs_path;
+    summarize(tasks, options, &batch.cancel).await
+}
 
-        let output = if let Some(ref out_dir) = output_dir {
-            let rel_with_new_ext = entry.rel_path.with_extension(target_ext.as_ref());
-            let output = out_dir.as_ref().join(rel_with_new_ext);
+/// Walks `dir` and starts converting files as soon as they're discovered,
+/// rather than collecting the whole tree into a `ConvertableEnts` first.
+///
+/// The walk runs on a blocking thread and feeds a bounded channel,
+/// so discovery can never run too far ahead of the conversions.
+/// Files whose *name* contains a dangerous character are renamed before converting,
+/// folders are left alone (see `find_by_ext` for the collect-first behaviour).
+///
+/// # Errors
+/// * If no converter is registered for a file, or an output folder can't be created
+pub async fn find_and_convert<P>(
+    dir: P,
+    exts: &[String],
+    registry: &ConverterRegistry,
+    target_exts: &[String],
+    output_dir: Option<P>,
+    options: &ConvertOptions,
+) -> Result<ConversionReport>
+where
+    P: AsRef<Path>,
+{
+    let root = dir.as_ref().to_path_buf();
+    let exts = exts.to_vec();
+    debug!("Streaming files with extension(s) '{}' from '{root:?}'", exts.join(","));
 
-            if let Some(parent) = output.parent()
-                && !parent.exists()
-            {
-                tokio::fs::create_dir_all(parent).await?;
+    if let Ok(supported) = registry.supported_input_formats().await {
+        warn_if_unsupported(&exts, &supported);
+    }
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(options.channel_capacity());
+    let walker_dir = walk_dir(root.clone(), options);
+    let (order, subset) = (options.order, options.subset);
+    let walker = tokio::task::spawn_blocking(move || {
+        let found = walker_dir
+            .map(ignore::DirEntry::into_path)
+            .filter(|path| path.is_file() && has_ext(path, &exts));
+        // Sorting (and sampling) needs the whole walk first, so only a run in walk order streams
+        let found: Box<dyn Iterator<Item = PathBuf>> =
+            if order.is_none() && subset.is_none() {
+                Box::new(found)
+            } else {
+                Box::new(arrange(found, order, subset).into_iter())
+            };
+        for path in found {
+            // Receiver only hangs up if we've bailed out already
+            if tx.blocking_send(path).is_err() {
+                break;
             }
-            output
+        }
+    });
+
+    let batch = Batch::new(
+        registry,
+        target_exts,
+        output_dir.as_ref().map(AsRef::as_ref),
+        options,
+    )?;
+    let mut tasks = Vec::new();
+    let mut n = 0;
+    while let Some(mut input) = rx.recv().await {
+        // Hanging up stops the walker too
+        if batch.stopped() {
+            break;
+        }
+        if let Some(ref renames) = options.renames
+            && input
+                .file_name()
+                .is_some_and(|name| needs_fixing(name, &options.name_rules))
+        {
+            input = fix_file_name(&input, &options.name_rules, renames).await?;
+        }
+
+        n += 1;
+        let relative = relative_to(&root, &input)?.to_path_buf();
+        let task = batch.queue(input, &relative, n).await?;
+        tasks.extend(task);
+    }
+    walker.await?;
+
+    info!("Found files and queued {} conversions", tasks.len());
+
+    summarize(tasks, options, &batch.cancel).await
+}
+
+/// Everything a batch run shares between its conversions.
+struct Batch<'a> {
+    registry:     &'a ConverterRegistry,
+    /// Converts what's queued, `ConvertOptions::jobs` files at a time.
+    pool:         WorkerPool,
+    target_exts:  &'a [String],
+    output_dir:   Option<&'a Path>,
+    options:      &'a ConvertOptions,
+    progress:     ProgressBar,
+    checkpoint:   Option<Arc<Checkpoint>>,
+    fingerprints: Option<Arc<Fingerprints>>,
+    /// Cancelled on the first failure with `--fail-fast`, or when the run is aborted (see `ConvertOptions::shutdown`),
+    /// which stops both the queued conversions and the ones in flight.
+    cancel:       CancellationToken,
+}
+
+impl<'a> Batch<'a> {
+    fn new(
+        registry: &'a ConverterRegistry,
+        target_exts: &'a [String],
+        output_dir: Option<&'a Path>,
+        options: &'a ConvertOptions,
+    ) -> Result<Self> {
+        let checkpoint = options
+            .checkpoint
+            .as_ref()
+            .map(|path| Checkpoint::open(path, options.resume).map(Arc::new))
+            .transpose()?;
+        let fingerprints = options
+            .fingerprints
+            .as_ref()
+            .map(|path| Fingerprints::open(path).map(Arc::new))
+            .transpose()?;
+
+        let (progress, cancel) = (progress::batch_bar(), options.shutdown.abort_token());
+        let pool = WorkerPool::start(Shared {
+            options:      options.clone(),
+            progress:     progress.clone(),
+            checkpoint:   checkpoint.clone(),
+            fingerprints: fingerprints.clone(),
+            cancel:       cancel.clone(),
+        });
+
+        Ok(Self {
+            registry,
+            pool,
+            target_exts,
+            output_dir,
+            options,
+            progress,
+            checkpoint,
+            fingerprints,
+            cancel,
+        })
+    }
+
+    /// Queues the conversion of `input` to each of the target formats, see `queue_one`.
+    /// With `options.detect_content` an empty `input` is skipped, see `detect_content`.
+    ///
+    /// `n` is the file's 1-based position in the batch, for the `{n}` placeholder.
+    async fn queue(
+        &self,
+        input: PathBuf,
+        relative: &Path,
+        n: usize,
+    ) -> Result<Vec<ConversionTask>> {
+        self.emit(&Event::Discovered { input: &input });
+        let detected = if self.options.detect_content {
+            detect_content(&input)
         } else {
-            input.with_extension(target_ext.as_ref())
+            Detected::AsNamed
+        };
+        if detected == Detected::Empty {
+            self.emit(&Event::Skipped {
+                input:  &input,
+                output: None,
+                reason: SkipReason::Empty,
+            });
+            return Ok(Vec::new());
+        }
+
+        let mut tasks = Vec::with_capacity(self.target_exts.len());
+        for target_ext in self.target_exts {
+            tasks.extend(
+                self.queue_one(&input, relative, n, target_ext, detected)
+                    .await?,
+            );
+        }
+        Ok(tasks)
+    }
+
+    /// Works out the `target_ext` output for `input` and queues its conversion for the workers,
+    /// waiting for room in the queue if they're behind.
+    ///
+    /// The timeout only counts from when a worker starts on it, not the time spent in the queue.
+    /// Returns `None` (and queues nothing) if the checkpoint being resumed has the output.
+    /// When resuming, an existing output that isn't in the checkpoint is assumed to be
+    /// left over from an interrupted conversion and converted again,
+    /// otherwise an existing output is dealt with as `options.on_exists` says.
+    ///
+    /// For `OnExists::Skip` an output is only converted again if `input` has changed since
+    /// it was written, going by its fingerprint. Outputs we have no fingerprint for are left alone,
+    /// we may not have written them.
+    ///
+    /// An input `detected` as another format is routed and converted as that one,
+    /// one that `sniff::Detected::Mismatch`es its extension fails without running a converter.
+    async fn queue_one(
+        &self,
+        input: &Path,
+        relative: &Path,
+        n: usize,
+        target_ext: &str,
+        detected: Detected,
+    ) -> Result<Option<ConversionTask>> {
+        let output_dir =
+            format_output_dir(self.output_dir, target_ext, self.target_exts.len() > 1);
+        let mut output = output_path(
+            input,
+            relative,
+            target_ext,
+            output_dir.as_deref(),
+            n,
+            self.options,
+        )?;
+
+        if self
+            .checkpoint
+            .as_ref()
+            .is_some_and(|checkpoint| checkpoint.is_done(input, &output))
+        {
+            trace!("Already converted before resuming, skipping: {output:?}");
+            self.skipped(input, &output, SkipReason::Checkpointed);
+            return Ok(None);
+        }
+
+        let format = match detected {
+            Detected::Actually(format) => Some(format),
+            _ => None,
         };
+        let converter = converter_for(self.registry, input, format, target_ext)?;
+        let engine = converter.name();
 
+        // A missing input fails its own conversion, there's no need to stop the batch for it
+        let print = Fingerprint::of(input).ok();
         if output.exists() {
-            warn!("Output file already exists: {output:?}");
-            continue;
+            let last = self.fingerprints.as_ref().and_then(|f| f.get(&output));
+            if self.options.resume {
+                warn!(
+                    "Output file isn't in the checkpoint, converting again: {output:?}"
+                );
+            } else {
+                let on_exists = self.options.on_exists;
+                match existing_output(input, output, on_exists, last, print) {
+                    Ok(to) => output = to,
+                    Err((output, reason)) => {
+                        self.skipped(input, &output, reason);
+                        return Ok(None);
+                    }
+                }
+            }
         }
+        create_parent(&output).await?;
 
-        let converter = Arc::clone(&converter);
-        let input = input.clone();
+        if let Some(ref metrics) = self.options.metrics {
+            metrics.queued();
+        }
+        if let Some(ref dashboard) = self.options.dashboard {
+            dashboard.queued();
+        }
+        self.progress.inc_length(1);
+        let job = Job {
+            input: input.to_path_buf(),
+            output: output.clone(),
+            converter: Arc::clone(&converter),
+            engine: engine.clone(),
+            detected,
+            print,
+        };
+        let outcome = self.pool.submit(job).await;
+
+        Ok(Some(ConversionTask {
+            input: input.to_path_buf(),
+            output,
+            engine,
+            converter,
+            outcome,
+        }))
+    }
+}
+
+impl Batch<'_> {
+    /// Writes `event` to the `--events` stream, if there is one, and counts it if it's a skip.
+    fn emit(&self, event: &Event<'_>) {
+        if let Some(ref events) = self.options.events {
+            events.emit(event);
+        }
+        if let Event::Skipped { .. } = event {
+            if let Some(ref metrics) = self.options.metrics {
+                metrics.skipped();
+            }
+            if let Some(ref dashboard) = self.options.dashboard {
+                dashboard.skipped();
+            }
+        }
+    }
 
-        tasks.push(tokio::spawn(async move { converter.convert(input, output).await }));
+    /// Whether to stop queueing conversions, after a failure with `--fail-fast` or once the run is stopping.
+    fn stopped(&self) -> bool {
+        self.cancel.is_cancelled() || self.options.shutdown.is_stopping()
     }
 
-    info!("Running conversion for {} files", tasks.len());
+    fn skipped(&self, input: &Path, output: &Path, reason: SkipReason) {
+        self.emit(&Event::Skipped {
+            input,
+            output: Some(output),
+            reason,
+        });
+    }
+}
+
+impl Drop for Batch<'_> {
+    /// Clears the bar once the batch is done, or bailed out early (`--fail-fast`).
+    fn drop(&mut self) {
+        self.progress.finish_and_clear();
+    }
+}
+
+/// Awaits all `tasks` and logs the overall numbers.
+async fn summarize(
+    tasks: Vec<ConversionTask>,
+    options: &ConvertOptions,
+    cancel: &CancellationToken,
+) -> Result<ConversionReport> {
+    let mut report = totals(tasks, options.fail_fast.then_some(cancel)).await?;
+    report.record_sizes().await;
+    info!("Successly processed: {} files", report.succeeded.len());
+    if !report.retried.is_empty() {
+        info!(
+            "{} of them on the first attempt, {} after retrying",
+            report.succeeded.len() - report.retried.len(),
+            report.retried.len()
+        );
+    }
+    let degraded = report.degraded();
+    if degraded > 0 {
+        warn!("{degraded} of them are only a degraded extraction of their text");
+    }
+
+    if report.has_failures() {
+        warn!("Conversion completed with {} errors.", report.failed.len());
+    }
+
+    info!("Overall success rate: {:.2}%", report.success_rate());
+
+    if report.outcomes.len() > 1 {
+        info!("Slowest conversions:");
+        for o in report.slowest(report::SLOWEST) {
+            let size = |size: Option<u64>| {
+                size.map_or_else(|| "-".to_string(), filter::format_size)
+            };
+            info!(
+                "  {:>8.2}s  {} ({} -> {})",
+                Duration::from_millis(o.duration_ms.try_into().unwrap_or(u64::MAX))
+                    .as_secs_f64(),
+                o.input.display(),
+                size(o.input_size),
+                size(o.output_size)
+            );
+        }
+    }
+
+    Ok(report)
+}
+
+/// Works out where `input` should be written to, creating any missing
+/// parent folders along the way (see `output_path`).
+pub(crate) async fn prepare_output_path(
+    input: &Path,
+    relative: &Path,
+    target_ext: &str,
+    output_dir: Option<&Path>,
+    n: usize,
+    options: &ConvertOptions,
+) -> Result<PathBuf> {
+    let output = output_path(input, relative, target_ext, output_dir, n, options)?;
+    create_parent(&output).await?;
+    Ok(output)
+}
+
+pub(crate) async fn create_parent(output: &Path) -> Result<()> {
+    if let Some(parent) = output.parent()
+        && !parent.exists()
+    {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    Ok(())
+}
+
+/// Moves `from` to `to`, copying and removing it where a rename can't (across filesystems).
+pub(crate) async fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if tokio::fs::rename(from, to).await.is_err() {
+        tokio::fs::copy(from, to).await?;
+        tokio::fs::remove_file(from).await?;
+    }
+    Ok(())
+}
+
+/// The output directory for `target_ext`: its own subfolder of `output_dir` when converting
+/// to `several` formats at once, so each format's tree stays separate.
+pub(crate) fn format_output_dir(
+    output_dir: Option<&Path>,
+    target_ext: &str,
+    several: bool,
+) -> Option<PathBuf> {
+    match output_dir {
+        Some(dir) if several => Some(dir.join(remove_dot(target_ext))),
+        dir => dir.map(Path::to_path_buf),
+    }
+}
+
+/// Where `input` should be written to, without touching the filesystem.
+///
+/// The file name comes from expanding `options.output_pattern` (see `naming::expand_pattern`).
+/// With an `output_dir` the output lands at `relative` below it, or right in it with
+/// `options.flatten` (see `naming::FlatNames::claim`), without one the output sits next to the input.
+///
+/// A path template (see `naming::is_path_template`) picks the folders itself instead,
+/// below `output_dir`, or the input root without one.
+///
+/// An output another input already has (see `naming::ClaimedOutputs`) gets a `_2`, `_3`, ... suffix.
+pub(crate) fn output_path(
+    input: &Path,
+    relative: &Path,
+    target_ext: &str,
+    output_dir: Option<&Path>,
+    n: usize,
+    options: &ConvertOptions,
+) -> Result<PathBuf> {
+    let file_name =
+        naming::expand_pattern(&options.output_pattern, input, relative, target_ext, n)?;
+
+    let output = match (output_dir, &options.flatten) {
+        (Some(out_dir), Some(flat)) => {
+            let file_name = file_name.file_name().map_or(file_name.as_path(), Path::new);
+            Ok(flat.claim(out_dir, file_name, input))
+        }
+        _ if naming::is_path_template(&options.output_pattern) => {
+            let root = output_dir
+                .or_else(|| input.ancestors().nth(relative.components().count()))
+                .unwrap_or(Path::new(""));
+            join_template(root, &file_name, &options.name_rules)
+        }
+        (Some(out_dir), None) => {
+            mirror_relative(relative, out_dir, &file_name, &options.name_rules)
+        }
+        (None, _) => Ok(input.with_file_name(file_name)),
+    }?;
+    Ok(options.claimed_outputs.claim(&output, input))
+}
 
-    let (success, failed) = totals(tasks).await;
-    info!("Successly processed: {success} files");
+/// The converter `registry` routes `input` (going to `target_ext`) to,
+/// going by its extension unless its actual `format` is known (see `sniff::detect`).
+pub(crate) fn converter_for(
+    registry: &ConverterRegistry,
+    input: &Path,
+    format: Option<&str>,
+    target_ext: &str,
+) -> Result<Arc<dyn Converter>> {
+    let input_ext = format
+        .or_else(|| input.extension().and_then(|s| s.to_str()))
+        .unwrap_or_default();
+    registry.get(input_ext, target_ext).ok_or_else(|| {
+        Error::Generic(format!(
+            "No converter registered for '.{input_ext}' -> '.{}': {}",
+            remove_dot(target_ext),
+            input.display()
+        ))
+    })
+}
 
-    if failed > 0 {
-        warn!("Conversion completed with {failed} errors.");
+/// The name of the span every `convert_single` runs in, with the file's path, engine, size and duration.
+pub const CONVERSION_SPAN: &str = "conversion";
+
+/// Runs a single conversion, bounded by `timeout` seconds per attempt if one is given,
+/// and retried up to `retries` times on transient failures (see `retry::with_retry`).
+/// A known `format` has `input` read as that, rather than what its extension says.
+///
+/// Returns how many retries the conversion needed.
+pub(crate) async fn convert_single(
+    converter: Arc<dyn Converter>,
+    input: PathBuf,
+    output: PathBuf,
+    format: Option<&str>,
+    timeout: Option<u64>,
+    retries: usize,
+    preserve_metadata: bool,
+) -> Result<usize> {
+    // At TRACE so it doesn't prefix the console's lines, the `otel` feature's layer enables it by itself
+    let span = tracing::trace_span!(
+        CONVERSION_SPAN,
+        input = %input.display(),
+        output = %output.display(),
+        engine = %converter.name(),
+        size_bytes = tracing::field::Empty,
+        duration_ms = tracing::field::Empty,
+        retries = tracing::field::Empty,
+        error = tracing::field::Empty,
+        otel.status_code = tracing::field::Empty,
+    );
+    if !span.is_disabled()
+        && let Ok(meta) = tokio::fs::metadata(&input).await
+    {
+        span.record("size_bytes", meta.len());
     }
 
-    let total = success + failed;
-    let success_perc = success.div(total).mul(100);
-    info!("Overall success rate: {success_perc:.2}%");
+    let started = std::time::Instant::now();
+    let res = async {
+        let retried = retry::with_retry(retries, || {
+            convert_once(converter.as_ref(), &input, &output, format, timeout)
+        })
+        .await?;
 
+        if preserve_metadata && let Err(e) = copy_metadata(&input, &output).await {
+            warn!(
+                "Failed to copy the modification time/permissions of {input:?} onto {output:?}: {e}"
+            );
+        }
+        Ok(retried)
+    }
+    .instrument(span.clone())
+    .await;
+
+    span.record("duration_ms", started.elapsed().as_millis());
+    match res {
+        Ok(retried) => span.record("retries", retried),
+        Err(ref e) => {
+            span.record("error", tracing::field::display(e));
+            span.record("otel.status_code", "ERROR")
+        }
+    };
+    res
+}
+
+/// Gives `output` the modification time (and on unix, the permission bits) of `input`.
+async fn copy_metadata(input: &Path, output: &Path) -> Result<()> {
+    let meta = tokio::fs::metadata(input).await?;
+
+    // Before the permissions, which may make the output read-only
+    let file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(output)
+        .await?
+        .into_std()
+        .await;
+    file.set_modified(meta.modified()?)?;
+
+    #[cfg(unix)]
+    tokio::fs::set_permissions(output, meta.permissions()).await?;
     Ok(())
 }
 
-pub async fn find_by_ext<S, P>(dir: P, ext: S) -> Result<ConvertableEnts>
+async fn convert_once(
+    converter: &dyn Converter,
+    input: &Path,
+    output: &Path,
+    format: Option<&str>,
+    timeout: Option<u64>,
+) -> Result<()> {
+    let fut = match format {
+        Some(format) => converter.convert_as(input, output, format),
+        None => converter.convert(input, output),
+    };
+    let Some(seconds) = timeout else {
+        return fut.await;
+    };
+
+    // Converters spawn their process with `kill_on_drop`, so giving up on `fut` also kills it
+    match tokio::time::timeout(std::time::Duration::from_secs(seconds), fut).await {
+        Ok(res) => res,
+        Err(_) => {
+            warn!("Conversion of {input:?} timed out after {seconds}s");
+            Err(Error::ConversionTimeout {
+                path: input.to_path_buf(),
+                seconds,
+            })
+        }
+    }
+}
+
+/// `sniff::detect` for `input`, logging what it found.
+/// An `input` that can't be read is taken as named, its conversion fails on its own.
+pub(crate) fn detect_content(input: &Path) -> Detected {
+    let detected = sniff::detect(input).unwrap_or_else(|e| {
+        debug!("Failed to look at the content of {input:?}: {e}");
+        Detected::AsNamed
+    });
+    match detected {
+        // A mismatch fails its conversions with `Error::ContentMismatch`, which says as much
+        Detected::AsNamed | Detected::Mismatch => {}
+        Detected::Actually(format) => {
+            warn!("{input:?} is really a '{format}' file, converting it as one");
+        }
+        Detected::Empty => {
+            warn!(
+                "{input:?} is empty, skipping it (a placeholder that was never downloaded?)"
+            );
+        }
+    }
+    detected
+}
+
+/// Collects every file with one of the extensions `exts` below `dir` in a single walk,
+/// sanitizing dangerous names first.
+/// Both are limited to `options.max_depth` folders below `dir` (see `walk_dir`).
+///
+/// If the converter's `supported` input formats are known and an extension isn't one of them,
+/// a warning is logged, the files are still collected.
+///
+/// # Errors
+/// * If the walk's thread panics, or a file or folder with a dangerous name can't be renamed
+pub async fn find_by_ext<P>(
+    dir: P,
+    exts: &[String],
+    supported: Option<&[String]>,
+    options: &ConvertOptions,
+) -> Result<ConvertableEnts>
 where
-    S: AsRef<str>,
     P: AsRef<Path>,
 {
-    let ext = ext.as_ref();
     let dir = dir.as_ref();
     let dir_path = dir.to_path_buf();
+    let ext = exts.join(",");
 
-    debug!("Finding files with extension '{ext}' in '{dir:?}'");
+    debug!("Finding files with extension(s) '{ext}' in '{dir:?}'");
 
-    let ext = remove_dot(ext).to_string();
-    debug!("Extension after removing dot: '{ext}'");
+    if let Some(supported) = supported {
+        warn_if_unsupported(exts, supported);
+    }
 
     // let dir_len = dir.components().count(); // original
 
-    let ext_clone = ext.clone();
-    let (files_to_fix, files_to_process) =
-        tokio::task::spawn_blocking(move || discover_and_cat(dir_path, ext_clone))
-            .await?;
+    let exts = exts.to_vec();
+    let walk_options = options.clone();
+    let (files_to_fix, mut files_to_process) = tokio::task::spawn_blocking(move || {
+        let (mut to_fix, mut to_process) =
+            discover_and_cat(dir_path, &exts, &walk_options);
+        to_process = arrange(to_process, walk_options.order, walk_options.subset);
+        if walk_options.subset.is_some() {
+            // Only the selected files, and the folders they're in, are renamed
+            to_fix.retain(|path| to_process.iter().any(|file| file.starts_with(path)));
+        }
+        (to_fix, to_process)
+    })
+    .await?;
 
-    if !files_to_fix.is_empty() {
-        fix_mangled_par(files_to_fix).await?;
+    if let Some(ref renames) = options.renames
+        && !files_to_fix.is_empty()
+    {
+        let renamed = fix_mangled_par(files_to_fix, &options.name_rules, renames).await?;
+        // Files first, their folders after, so replaying the renames in order catches both
+        for path in &mut files_to_process {
+            for (from, to) in &renamed {
+                if path == from {
+                    path.clone_from(to);
+                } else if let Ok(rest) = path.strip_prefix(from) {
+                    *path = to.join(rest);
+                }
+            }
+        }
     }
 
-    let mut pe = ConvertableEnts::new_with_capacity(dir, files_to_process.len());
+    let mut builder = ConvertableEnts::builder();
+    builder.add_top_level(dir.to_path_buf());
 
     // let root_depth = dir.components().count();
     for file_path in files_to_process {
-        pe.add_file(file_path);
+        builder.add_file(file_path);
     }
+    let pe = builder.build()?;
 
-    let l = pe.count();
-    debug!("Found {l} files with extension '{ext}'");
+    let l = pe.len();
+    debug!("Found {l} files with extension(s) '{ext}'");
 
     Ok(pe)
 }
 
-fn discover_and_cat<S: AsRef<str>, P: AsRef<Path>>(
+/// Builds the set of files to convert from a newline-delimited list (or NUL-delimited, see `list_paths`),
+/// read from a file or, for `-`, stdin, instead of walking a directory.
+///
+/// Blank lines and lines starting with `#` are skipped, relative paths are resolved
+/// against the current directory, and only files with one of the extensions `exts` are kept
+/// (if any are given).
+/// Each file's relative path is `<immediate parent>/<file name>`, which is
+/// what ends up below `--output`.
+///
+/// Files are put in `options.order`, and narrowed down to `options.subset`, if given.
+/// Those with dangerous names are renamed first with `options.renames` (see `ConvertOptions::renames`).
+///
+/// # Errors
+/// * If the list can't be read, or a listed file with a dangerous name can't be renamed
+pub async fn read_input_list<P: AsRef<Path>>(
+    list: P,
+    exts: &[String],
+    options: &ConvertOptions,
+) -> Result<ConvertableEnts> {
+    let (name_rules, renames) = (&options.name_rules, options.renames.as_deref());
+    let cwd = std::env::current_dir()?;
+    let listed = list_entries(list, exts, options).await?;
+    let mut pe = ConvertableEnts::new_with_capacity(&cwd, listed.len());
+
+    for mut path in listed {
+        if let Some(renames) = renames
+            && path
+                .file_name()
+                .is_some_and(|name| needs_fixing(name, name_rules))
+        {
+            path = fix_file_name(&path, name_rules, renames).await?;
+        }
+
+        let relative = list_relative(&path);
+        pe.files
+            .push(FileEntry::new(path.as_path(), relative.as_path(), 1));
+    }
+
+    debug!("Found {} listed files", pe.len());
+
+    Ok(pe)
+}
+
+/// The existing files named in `list` (see `read_input_list`), as absolute paths
+/// and with nothing renamed yet, arranged as `options` asks (see `arrange`).
+pub async fn list_entries<P: AsRef<Path>>(
+    list: P,
+    exts: &[String],
+    options: &ConvertOptions,
+) -> Result<Vec<PathBuf>> {
+    let list = list.as_ref();
+    let contents = if list == Path::new(STDIN_LIST) {
+        debug!("Reading input list from stdin");
+        let mut contents = String::new();
+        tokio::io::stdin().read_to_string(&mut contents).await?;
+        contents
+    } else {
+        debug!("Reading input list from '{list:?}'");
+        tokio::fs::read_to_string(list).await?
+    };
+    let cwd = std::env::current_dir()?;
+
+    let mut paths = Vec::new();
+    for entry in list_paths(&contents, options.list_nul_separated) {
+        let path = cwd.join(entry);
+
+        if !exts.is_empty() && !has_ext(&path, exts) {
+            trace!(
+                "Skipping listed file without extension '{}': {path:?}",
+                exts.join(",")
+            );
+            continue;
+        }
+        if !path.is_file() {
+            warn!("Listed file does not exist, skipping: {path:?}");
+            continue;
+        }
+        paths.push(path);
+    }
+
+    Ok(arrange(paths, options.order, options.subset))
+}
+
+/// The paths in an input list's `contents`: one per line, leaving out blank lines and `#` comments,
+/// or with `nul_separated` (`-0`, as `find -print0` writes them) every non-empty entry between NULs as is.
+fn list_paths(contents: &str, nul_separated: bool) -> Vec<&str> {
+    if nul_separated {
+        return contents
+            .split('\0')
+            .filter(|entry| !entry.is_empty())
+            .collect();
+    }
+
+    contents
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !(line.trim().is_empty() || line.trim_start().starts_with('#')))
+        .collect()
+}
+
+/// A listed file's path relative to the output directory, `<immediate parent>/<file name>`.
+fn list_relative(path: &Path) -> PathBuf {
+    let mut relative = PathBuf::new();
+    if let Some(parent) = path.parent().and_then(Path::file_name) {
+        relative.push(parent);
+    }
+    relative.push(path.file_name().unwrap_or_default());
+    relative
+}
+
+/// Warns for every one of `exts` that isn't in the converter's list of `supported` input formats.
+fn warn_if_unsupported(exts: &[String], supported: &[String]) {
+    for ext in exts {
+        if !supported
+            .iter()
+            .any(|f| canonical_ext(f) == canonical_ext(ext))
+        {
+            warn!(
+                "'{ext}' is not in the converter's list of input formats, conversions may fail"
+            );
+        }
+    }
+}
+
+fn discover_and_cat<P: AsRef<Path>>(
     dir: P,
-    ext: S,
-) -> (Vec<impl AsRef<Path>>, Vec<impl AsRef<Path>>) {
+    exts: &[String],
+    options: &ConvertOptions,
+) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let name_rules = &options.name_rules;
     let mut to_fix = vec![];
     let mut to_process = Vec::with_capacity(INITIAL_CAPACITY);
 
-    for entry in WalkDir::new(dir)
-        .into_iter()
-        .filter_map(std::result::Result::ok)
-    {
+    for entry in walk_dir(dir, options) {
         let path = entry.path();
 
-        if needs_fixing(path) {
+        // The input directory's own name is left alone
+        if entry.depth() > 0 && needs_fixing(entry.file_name(), name_rules) {
             to_fix.push(path.to_path_buf());
         }
 
-        if path.is_file()
-            && path.extension().and_then(|s| s.to_str()) == Some(ext.as_ref())
-        {
+        if path.is_file() && has_ext(path, exts) {
             to_process.push(path.to_path_buf());
         }
     }
@@ -229,101 +1384,519 @@ fn discover_and_cat<S: AsRef<str>, P: AsRef<Path>>(
     (to_fix, to_process)
 }
 
+/// A walk over `dir`, bounded to `options.max_depth` folders below it,
+/// that leaves out whatever `options.filter` doesn't allow and the sources `--delete-source=move`
+/// put aside (`originals::ORIGINALS_DIR`).
+///
+/// Symlinks are only followed with `options.follow_symlinks`, a link back to one of its own folders
+/// isn't walked twice. That, and anything else the walk can't get into, is logged and skipped.
+///
+/// With `options.ignore_files` it also leaves out what `.gitignore`, `.ignore` and `.convertignore` files
+/// in (and above) `dir` ignore, git repository or not. Hidden files are walked either way.
+///
+/// Our depth counts folders *below* the root, so `Some(0)` means root files only,
+/// whereas the walker counts the root itself as depth 0 and its files as depth 1.
+fn walk_dir<P: AsRef<Path>>(
+    dir: P,
+    options: &ConvertOptions,
+) -> impl Iterator<Item = ignore::DirEntry> + Send + use<P> {
+    let root = dir.as_ref().to_path_buf();
+    let filter = options.filter.clone();
+    let mut walker = WalkBuilder::new(dir);
+    walker
+        .standard_filters(false)
+        .follow_links(options.follow_symlinks)
+        .max_depth(options.max_depth.map(|depth| depth.saturating_add(1)))
+        .filter_entry(move |entry| {
+            let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            entry.file_name() != originals::ORIGINALS_DIR
+                && (entry.depth() == 0 || filter.allows(relative, is_dir))
+                && (is_dir || filter.allows_file(entry.path()))
+        });
+    if options.order.is_some() || options.subset.is_some() {
+        // So the same files are picked (and ties kept) every time, whatever order the file system lists them in
+        walker.sort_by_file_name(Ord::cmp);
+    }
+    if options.ignore_files {
+        walker
+            .git_ignore(true)
+            .ignore(true)
+            .parents(true)
+            .require_git(false)
+            .add_custom_ignore_filename(ignores::CONVERTIGNORE);
+    }
+    walker.build().filter_map(|entry| {
+        entry
+            .inspect_err(|e| warn!("Skipping what can't be walked: {e}"))
+            .ok()
+    })
+}
+
+/// Every file with one of the extensions `exts` below `dir`, arranged as `options` asks (see `arrange`),
+/// without renaming or converting anything.
+///
+/// # Errors
+/// * If the walk's thread panics
+pub async fn discover<P: AsRef<Path>>(
+    dir: P,
+    exts: &[String],
+    options: &ConvertOptions,
+) -> Result<Vec<PathBuf>> {
+    let (root, exts, options) =
+        (dir.as_ref().to_path_buf(), exts.to_vec(), options.clone());
+    let found = tokio::task::spawn_blocking(move || {
+        let found = walk_dir(root, &options)
+            .map(ignore::DirEntry::into_path)
+            .filter(|path| path.is_file() && has_ext(path, &exts));
+        arrange(found, options.order, options.subset)
+    })
+    .await?;
+    Ok(found)
+}
+
+/// Puts the files `found` in `order` (`--order`), then narrows them down to the `subset` (`--limit`, `--sample`),
+/// so a limit takes the first files in that order.
+///
+/// Without an order a `--limit` stops pulling from `found` once it has enough.
+fn arrange<I: IntoIterator<Item = PathBuf>>(
+    found: I,
+    order: Option<Order>,
+    subset: Option<Subset>,
+) -> Vec<PathBuf> {
+    let Some(order) = order else {
+        return match subset {
+            Some(subset) => subset.select(found),
+            None => found.into_iter().collect(),
+        };
+    };
+
+    let mut found: Vec<PathBuf> = found.into_iter().collect();
+    order.sort(&mut found);
+    match subset {
+        Some(subset) => subset.select(found),
+        None => found,
+    }
+}
+
+/// Whether a file at `relative` (to the input root) is within `max_depth`, see `walk_dir`.
 #[inline]
-fn needs_fixing<P: AsRef<Path>>(path: P) -> bool {
+pub(crate) fn within_depth(relative: &Path, max_depth: Option<usize>) -> bool {
+    max_depth.is_none_or(|depth| relative.components().count() <= depth.saturating_add(1))
+}
+
+#[inline]
+pub(crate) fn needs_fixing<P: AsRef<Path>>(path: P, name_rules: &NameRules) -> bool {
     path.as_ref()
         .to_str()
-        .is_some_and(|s| DANGER_CHARS.iter().any(|&c| s.contains(c)))
-
-    // .map(|s| DANGER_CHARS.iter().any(|&c| s.contains(c)))
-    // .unwrap_or(false)
+        .is_some_and(|s| fix_mangled_name(s, name_rules) != s)
 }
 
-async fn fix_mangled_par<P: AsRef<Path>>(paths: Vec<P>) -> Result<()> {
-    let tasks: Vec<_> = paths
-        .into_iter()
-        .map(|path| {
-            let path = path.as_ref().to_path_buf();
-            tokio::spawn(async move { fix_single_file(path).await })
-        })
-        .collect();
+/// Renames every one of `paths` with `fix_file_name`, deepest first so no folder
+/// is renamed out from under a path that's still to be fixed.
+/// Paths at the same depth are renamed in parallel.
+///
+/// Returns every `(from, to)` rename in the order they were done.
+async fn fix_mangled_par(
+    paths: Vec<PathBuf>,
+    name_rules: &NameRules,
+    renames: &Arc<RenameJournal>,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut done = Vec::with_capacity(paths.len());
+    let mut by_depth: BTreeMap<usize, Vec<PathBuf>> = BTreeMap::new();
+    for path in paths {
+        by_depth
+            .entry(path.components().count())
+            .or_default()
+            .push(path);
+    }
 
-    for task in tasks {
-        task.await??;
+    let name_rules = Arc::new(name_rules.clone());
+    for (_, level) in by_depth.into_iter().rev() {
+        let tasks: Vec<_> = level
+            .into_iter()
+            .map(|path| {
+                let name_rules = Arc::clone(&name_rules);
+                let renames = Arc::clone(renames);
+                tokio::spawn(async move {
+                    let fixed = fix_file_name(&path, &name_rules, &renames).await?;
+                    Ok::<_, Error>((path, fixed))
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            done.push(task.await??);
+        }
     }
 
-    Ok(())
+    Ok(done)
 }
 
-async fn fix_single_file<P: AsRef<Path>>(path: P) -> Result<()> {
-    let path_str = path
-        .as_ref()
-        .to_str()
+/// Renames just the file name of `path` (not its folders) with `fix_mangled_name`,
+/// recording it in `renames`, and returns the new path.
+/// A name that's already taken gets a `_2`, `_3`, ... suffix (see `RenameJournal::rename`).
+pub(crate) async fn fix_file_name(
+    path: &Path,
+    name_rules: &NameRules,
+    renames: &RenameJournal,
+) -> Result<PathBuf> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
         .ok_or_else(|| Error::Generic("Invalid UTF-8 in path".to_string()))?;
+    let fixed = path.with_file_name(fix_mangled_name(name, name_rules));
+
+    warn!("Fixing file: {:?} -> {:?}", path.display(), fixed.display());
 
-    let fixed = fix_mangled_name(path_str).as_ref().to_string();
+    renames.rename(path, &fixed)
+}
 
-    warn!("Fixing file/folder: {:?} -> {fixed}", path.as_ref().display());
+/// `input` with the `root` prefix stripped off.
+pub(crate) fn relative_to<'a>(root: &Path, input: &'a Path) -> Result<&'a Path> {
+    input.strip_prefix(root).map_err(|_| {
+        Error::Generic(format!(
+            "{} is not inside the input directory {}",
+            input.display(),
+            root.display()
+        ))
+    })
+}
 
-    tokio::fs::rename(&path, &fixed).await.map_err(|e| {
-        error!("Failed to rename: {e:?}");
-        Error::FailedRenameFile(path.as_ref().to_path_buf())
-    })?;
+/// Joins the folders of `relative` onto `output_base` one (sanitized) component at a time,
+/// followed by `file_name` in place of the original name.
+///
+/// So `docs/a~b/c.docx` with `c.md` ends up as `output_base/docs/a_b/c.md`.
+fn mirror_relative(
+    relative: &Path,
+    output_base: &Path,
+    file_name: &Path,
+    name_rules: &NameRules,
+) -> Result<PathBuf> {
+    let folders = relative.parent().unwrap_or(Path::new(""));
+
+    let mut output = output_base.to_path_buf();
+    for component in folders.components().chain(file_name.components()) {
+        let component = component.as_os_str().to_str().ok_or_else(|| {
+            Error::Generic(format!("Invalid UTF-8 in path: {}", relative.display()))
+        })?;
+        output.push(fix_mangled_name(component, name_rules));
+    }
 
-    debug!("Renamed file/folder to: {fixed}");
-    Ok(())
+    Ok(output)
+}
+
+/// What `on_exists` makes of `output` already existing: the output to convert `input` to
+/// (`output` itself, or a free numbered name next to it with `OnExists::Rename`),
+/// or why to skip it. `last` is the fingerprint `input` had when we wrote `output`, if we did,
+/// `print` the one it has now.
+pub(crate) fn existing_output(
+    input: &Path,
+    output: PathBuf,
+    on_exists: OnExists,
+    last: Option<Fingerprint>,
+    print: Option<Fingerprint>,
+) -> std::result::Result<PathBuf, (PathBuf, SkipReason)> {
+    match on_exists {
+        OnExists::Overwrite => {
+            debug!("Overwriting existing output: {output:?}");
+            Ok(output)
+        }
+        OnExists::Rename => {
+            let output = numbered_free_name(&output);
+            debug!("Output exists, converting to {output:?} instead");
+            Ok(output)
+        }
+        OnExists::IfNewer if is_up_to_date(input, &output) => {
+            debug!("Output is up to date, skipping: {output:?}");
+            Err((output, SkipReason::UpToDate))
+        }
+        OnExists::IfNewer => {
+            info!("Input is newer than its output, converting again: {input:?}");
+            Ok(output)
+        }
+        OnExists::Skip if last.is_some() && last != print => {
+            info!(
+                "Input changed since it was last converted, converting again: {input:?}"
+            );
+            Ok(output)
+        }
+        OnExists::Skip if last.is_some() => {
+            debug!("Input unchanged since it was last converted, skipping: {input:?}");
+            Err((output, SkipReason::Unchanged))
+        }
+        OnExists::Skip => {
+            warn!("Output file already exists: {output:?}");
+            Err((output, SkipReason::OutputExists))
+        }
+    }
+}
+
+/// Whether `output` was written after `input` was last modified.
+pub(crate) fn is_up_to_date(input: &Path, output: &Path) -> bool {
+    let (Ok(in_meta), Ok(out_meta)) =
+        (std::fs::metadata(input), std::fs::metadata(output))
+    else {
+        return false;
+    };
+
+    match (in_meta.modified(), out_meta.modified()) {
+        (Ok(in_time), Ok(out_time)) => out_time >= in_time,
+        _ => false,
+    }
+}
+
+/// The first of `name (1).ext`, `name (2).ext`, ... next to `output` that doesn't exist yet.
+pub(crate) fn numbered_free_name(output: &Path) -> PathBuf {
+    let stem = output
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = output
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|i| output.with_file_name(format!("{stem} ({i}){ext}")))
+        .find(|candidate| !candidate.exists())
+        .expect("some numbered name is free")
+}
+
+/// Joins an expanded path template onto `root` one (sanitized) component at a time.
+///
+/// Empty folders (from an empty `{rel_dir}` or `{top}`) are dropped,
+/// anything that would climb out of `root` is refused.
+fn join_template(
+    root: &Path,
+    expanded: &Path,
+    name_rules: &NameRules,
+) -> Result<PathBuf> {
+    let mut output = root.to_path_buf();
+    for component in expanded.components() {
+        match component {
+            std::path::Component::Normal(part) => {
+                let part = part.to_str().ok_or_else(|| {
+                    Error::Generic(format!(
+                        "Invalid UTF-8 in path: {}",
+                        expanded.display()
+                    ))
+                })?;
+                output.push(fix_mangled_name(part, name_rules));
+            }
+            std::path::Component::CurDir | std::path::Component::RootDir => {}
+            _ => {
+                return Err(Error::Generic(format!(
+                    "Output template must stay below {}: {}",
+                    root.display(),
+                    expanded.display()
+                )));
+            }
+        }
+    }
+    Ok(output)
 }
 
 #[inline]
-fn remove_dot(ext: &str) -> &str {
+pub(crate) fn remove_dot(ext: &str) -> &str {
     ext.strip_prefix('.').unwrap_or(ext)
 }
 
+/// Splits a comma-separated list of extensions (`docx,.doc, rtf`) into bare extensions.
+pub fn split_extensions(list: &str) -> Vec<String> {
+    list.split(',')
+        .map(|ext| remove_dot(ext.trim()))
+        .filter(|ext| !ext.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Whether `path`'s extension is one of `exts`, ignoring case and aliases (see `canonical_ext`).
 #[inline]
-fn fix_mangled_name<S: AsRef<str>>(name: S) -> impl AsRef<str> + Into<String> {
-    let mut name = name.as_ref().to_string();
-    for c in &DANGER_CHARS {
-        name = name.replace(c, "_");
-    }
-    name
+pub(crate) fn has_ext(path: &Path, exts: &[String]) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .map(canonical_ext)
+        .is_some_and(|ext| exts.iter().any(|e| canonical_ext(e) == ext))
 }
 
-type SuccessCount = usize;
-type FailedCount = usize;
-type TotalsResult = (SuccessCount, FailedCount);
+/// The lowercase name of the format `ext` stands for, with aliases resolved (`.MD` -> `markdown`),
+/// so differently spelled extensions of one format are matched and routed alike.
+pub(crate) fn canonical_ext(ext: &str) -> String {
+    let ext = remove_dot(ext);
+    dealias(ext).map_or_else(|| ext.to_ascii_lowercase(), String::from)
+}
+
+/// The format the alias `ext` stands for (`htm` -> `html`), in any case.
+/// Also the name pandoc reads it as.
+pub(crate) fn dealias(ext: &str) -> Option<&'static str> {
+    EXT_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(remove_dot(ext)))
+        .map(|&(_, format)| format)
+}
+
+/// `name` with `name_rules` applied to each of its components, so whole paths can be fixed too.
+fn fix_mangled_name<S: AsRef<str>>(name: S, name_rules: &NameRules) -> String {
+    let name = name.as_ref();
+    let mut fixed = String::with_capacity(name.len());
+    for part in name.split_inclusive(std::path::is_separator) {
+        let component = part.trim_end_matches(std::path::is_separator);
+        fixed.push_str(&name_rules.apply(component));
+        fixed.push_str(&part[component.len()..]);
+    }
+    fixed
+}
 
-async fn totals(tasks: Vec<tokio::task::JoinHandle<Result<()>>>) -> TotalsResult {
-    // let task_len: f64 = tasks.len() as f64;
-    // let mut errors = vec![];
+/// A queued conversion.
+struct ConversionTask {
+    input:     PathBuf,
+    output:    PathBuf,
+    /// The name of the converter doing the conversion.
+    engine:    String,
+    /// The converter itself, asked for what it has to add to the report once it's done.
+    converter: Arc<dyn Converter>,
+    /// Yields how many retries the conversion needed, and how long it took once a worker had it.
+    outcome:   tokio::sync::oneshot::Receiver<Outcome>,
+}
 
-    let mut success: usize = 0;
-    let mut failed: usize = 0;
+/// Awaits every conversion task as it completes, recording a per-file outcome for each.
+///
+/// With `fail_fast` set to the batch's token, the first error cancels it (if the task didn't already)
+/// and is returned, the conversions it cancelled aren't counted.
+async fn totals(
+    tasks: Vec<ConversionTask>,
+    fail_fast: Option<&CancellationToken>,
+) -> Result<ConversionReport> {
+    let mut report = ConversionReport::default();
+
+    let mut pending: FuturesUnordered<_> = tasks
+        .into_iter()
+        .map(|task| {
+            async move {
+                let res = task.outcome.await;
+                (task.input, task.output, task.engine, task.converter, res)
+            }
+        })
+        .collect();
 
-    for task in tasks {
-        match task.await {
-            Ok(Ok(())) => success += 1,
-            Ok(Err(e)) => {
+    while let Some((input, output, engine, converter, res)) = pending.next().await {
+        let (err, took) = match res {
+            Ok((Ok(retries), took)) => {
+                let confidence = converter.ocr_confidence(&output);
+                let degraded = converter.degraded(&output);
+                // Outcomes of their own, but not conversions of their own
+                let extras = converter
+                    .extra_outputs(&output)
+                    .into_iter()
+                    .map(|extra| FileOutcome::ok(input.clone(), extra, retries))
+                    .collect::<Vec<_>>();
+                let outcome = report.record_success(input, output, retries, took);
+                outcome.ocr_confidence = confidence;
+                outcome.degraded = degraded;
+                report.outcomes.extend(extras);
+                continue;
+            }
+            Ok((Err(Error::Cancelled), _)) => continue,
+            Ok((Err(e), took)) => {
                 error!("Task failed with error: {:?}", e);
-                failed += 1;
+                (e, took)
             }
-            Err(e) => {
-                error!("Task panicked or was cancelled: {:?}", e);
-                failed += 1;
+            Err(_) => {
+                error!("The conversion of {input:?} was dropped by its worker");
+                (
+                    Error::Generic(format!("The conversion of {input:?} was dropped")),
+                    Duration::ZERO,
+                )
             }
+        };
+
+        if let Some(cancel) = fail_fast {
+            warn!("Cancelling remaining conversions (--fail-fast)");
+            cancel.cancel();
+            return Err(err);
         }
+        report.record_failure(input, output, &engine, err, took);
     }
-    (success, failed)
+
+    Ok(report)
 }
 
 #[cfg(test)]
 mod conversion_tests {
     use super::*;
 
+    #[test]
+    fn test_split_extensions() {
+        assert_eq!(split_extensions("docx, .doc,,rtf"), ["docx", "doc", "rtf"]);
+        assert!(split_extensions(" , ").is_empty());
+        assert!(has_ext(Path::new("a/b.doc"), &split_extensions("docx,doc")));
+        assert!(!has_ext(Path::new("a/b.odt"), &split_extensions("docx,doc")));
+    }
+
+    #[test]
+    fn test_ext_case_and_aliases() {
+        let exts = split_extensions("docx,md,HTML");
+        assert!(has_ext(Path::new("a/b.DOCX"), &exts));
+        assert!(has_ext(Path::new("a/b.Docx"), &exts));
+        assert!(has_ext(Path::new("a/b.markdown"), &exts));
+        assert!(has_ext(Path::new("a/b.MD"), &exts));
+        assert!(has_ext(Path::new("a/b.htm"), &exts));
+        assert!(!has_ext(Path::new("a/b.docm"), &exts));
+
+        assert_eq!(canonical_ext(".JPEG"), "jpg");
+        assert_eq!(canonical_ext("Docx"), "docx");
+        assert_eq!(dealias("MD"), Some("markdown"));
+        assert_eq!(dealias("markdown"), None);
+    }
+
+    #[test]
+    fn test_list_paths() {
+        assert_eq!(
+            list_paths("a.docx\r\n\n# notes\n  \nb/c.docx\n", false),
+            ["a.docx", "b/c.docx"]
+        );
+        assert_eq!(
+            list_paths("a.docx\0# not a comment.docx\0two\nlines.docx\0", true),
+            ["a.docx", "# not a comment.docx", "two\nlines.docx"]
+        );
+    }
+
     #[tokio::test]
     async fn test_fix_mangled_name() {
         let name = "some~file$";
-        let fixed = fix_mangled_name(name).as_ref().to_string();
+        let fixed = fix_mangled_name(name, &NameRules::default());
         assert_eq!(fixed, "some_file_");
+
+        let fixed = fix_mangled_name("50% (final)!", &chars(&['%', '(', ')', '!']));
+        assert_eq!(fixed, "50_ _final__");
+        assert_eq!(fix_mangled_name("a~b", &chars(&[])), "a~b");
+        assert_eq!(fix_mangled_name("a~/b$/c", &NameRules::default()), "a_/b_/c");
+    }
+
+    fn chars(danger_chars: &[char]) -> NameRules {
+        NameRules {
+            danger_chars: danger_chars.to_vec(),
+            ..NameRules::default()
+        }
+    }
+
+    #[test]
+    fn test_name_rules() {
+        let rules = NameRules {
+            danger_chars:  vec!['#', '~'],
+            replacement:   "-".to_string(),
+            max_len:       Some(10),
+            trim_trailing: true,
+        };
+        assert_eq!(rules.apply("a#b~c.docx"), "a-b-c.docx");
+        assert_eq!(rules.apply("a very long name.docx"), "a ver.docx");
+        assert_eq!(rules.apply("trailing. "), "trailing");
+        assert_eq!(rules.apply(".."), "..");
+        assert_eq!(rules.apply("x.extension-too-long"), "x.extensio");
+        assert_eq!(fix_mangled_name("dir. /a~.md", &rules), "dir/a-.md");
+        assert!(needs_fixing(Path::new("ok.md "), &rules));
+        assert!(!needs_fixing(Path::new("ok.md"), &rules));
     }
 
     #[test]
@@ -333,10 +1906,141 @@ mod conversion_tests {
         assert_eq!(remove_dot(".tar.gz"), "tar.gz");
     }
 
+    #[test]
+    fn test_relative_output_path_keeps_hierarchy() {
+        let relative =
+            relative_to(Path::new("/src"), Path::new("/src/docs/internal/re~port.docx"))
+                .unwrap();
+        let out = mirror_relative(
+            relative,
+            Path::new("/out"),
+            Path::new("re~port.md"),
+            &NameRules::default(),
+        )
+        .unwrap();
+        assert_eq!(out, PathBuf::from("/out/docs/internal/re_port.md"));
+
+        assert!(relative_to(Path::new("/src"), Path::new("/elsewhere/a.docx")).is_err());
+    }
+
+    #[test]
+    fn test_convertable_ents_builder() {
+        let pe = ConvertableEnts::builder()
+            .add_file(PathBuf::from("/src/a.docx"))
+            .add_top_level(PathBuf::from("/src"))
+            .add_file(PathBuf::from("/src/docs/b.docx"))
+            .build()
+            .unwrap();
+
+        assert_eq!(pe.len(), 2);
+        assert_eq!(pe.files[1].rel_path, PathBuf::from("docs/b.docx"));
+        assert_eq!(pe.files[1].depth, 2);
+        assert_eq!(
+            pe.into_iter().collect::<Vec<_>>(),
+            [
+                PathBuf::from("/src/a.docx"),
+                PathBuf::from("/src/docs/b.docx")
+            ]
+        );
+
+        let outside = ConvertableEnts::builder()
+            .add_top_level(PathBuf::from("/src"))
+            .add_file(PathBuf::from("/elsewhere/a.docx"))
+            .build();
+        assert!(matches!(outside, Err(Error::Generic(_))));
+
+        let mut pe = ConvertableEnts::new_with_capacity("/src", 1);
+        assert!(pe.add_file("/elsewhere/a.docx").is_err());
+        assert!(pe.is_empty());
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let tmp =
+            std::env::temp_dir().join(format!("dcc_max_depth_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(tmp.join("a/b")).unwrap();
+        for file in ["top.docx", "a/mid.docx", "a/b/deep.docx"] {
+            std::fs::write(tmp.join(file), "").unwrap();
+        }
+
+        let count = |max_depth| {
+            let options = ConvertOptions {
+                max_depth,
+                ..ConvertOptions::default()
+            };
+            discover_and_cat(&tmp, &["docx".to_string()], &options)
+                .1
+                .len()
+        };
+        assert_eq!(count(Some(0)), 1);
+        assert_eq!(count(Some(1)), 2);
+        assert_eq!(count(None), 3);
+
+        assert!(within_depth(Path::new("top.docx"), Some(0)));
+        assert!(!within_depth(Path::new("a/mid.docx"), Some(0)));
+        assert!(within_depth(Path::new("a/b/deep.docx"), None));
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+
     #[test]
     fn test_needs_fixing() {
-        assert!(needs_fixing(Path::new("file~.txt")));
-        assert!(needs_fixing(Path::new("$file.txt")));
-        assert!(!needs_fixing(Path::new("normal.txt")));
+        assert!(needs_fixing(Path::new("file~.txt"), &NameRules::default()));
+        assert!(needs_fixing(Path::new("$file.txt"), &NameRules::default()));
+        assert!(!needs_fixing(Path::new("normal.txt"), &NameRules::default()));
+        assert!(needs_fixing(Path::new("my file.txt"), &chars(&[' '])));
+    }
+    #[test]
+    fn test_numbered_free_name() {
+        let tmp = std::env::temp_dir()
+            .join(format!("dcc_numbered_name_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(&tmp).unwrap();
+        let output = tmp.join("report.md");
+        std::fs::write(&output, "").unwrap();
+
+        assert_eq!(numbered_free_name(&output), tmp.join("report (1).md"));
+        std::fs::write(tmp.join("report (1).md"), "").unwrap();
+        assert_eq!(numbered_free_name(&output), tmp.join("report (2).md"));
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn test_existing_output() {
+        let tmp = std::env::temp_dir()
+            .join(format!("dcc_existing_output_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&tmp);
+        std::fs::create_dir_all(&tmp).unwrap();
+        let (input, output) = (tmp.join("report.docx"), tmp.join("report.md"));
+        std::fs::write(&input, "old").unwrap();
+        std::fs::write(&output, "").unwrap();
+        let print = Fingerprint::of(&input).ok();
+        let changed = Some(Fingerprint {
+            size:     1,
+            modified: 0,
+        });
+
+        let skip =
+            |last| existing_output(&input, output.clone(), OnExists::Skip, last, print);
+        assert_eq!(skip(None), Err((output.clone(), SkipReason::OutputExists)));
+        assert_eq!(skip(print), Err((output.clone(), SkipReason::Unchanged)));
+        assert_eq!(skip(changed), Ok(output.clone()));
+
+        assert_eq!(
+            existing_output(&input, output.clone(), OnExists::IfNewer, None, print),
+            Err((output.clone(), SkipReason::UpToDate))
+        );
+        assert_eq!(
+            existing_output(&input, output.clone(), OnExists::Overwrite, print, print),
+            Ok(output.clone())
+        );
+        assert_eq!(
+            existing_output(&input, output.clone(), OnExists::Rename, print, print),
+            Ok(tmp.join("report (1).md"))
+        );
+
+        std::fs::remove_dir_all(&tmp).unwrap();
     }
 }
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::conversion::remove_dot;
+use crate::prelude::*;
+
+/// The pattern that reproduces the classic `file.with_extension(target_ext)` naming.
+pub const DEFAULT_PATTERN: &str = "{stem}.{ext}";
+
+/// Expands an output file name pattern for `input`, found at `relative` below the input root.
+///
+/// Supported placeholders:
+/// * `{stem}` - the input's file name without its extension
+/// * `{ext}` - the target extension, without a leading dot
+/// * `{parent}` - the name of the input's immediate parent folder
+/// * `{n}` - the 1-based position of the file within the batch
+/// * `{rel_dir}` - the folders between the input root and the input, e.g. `docs/2023`
+/// * `{top}` - the first of those folders, e.g. `docs`
+/// * `{date}` - today's (UTC) date, `YYYY-MM-DD`
+///
+/// See `is_path_template` for patterns that also pick the output's folders.
+///
+/// # Errors
+/// * `Error::InvalidExtension` - If the expanded name has an empty stem (e.g. `.{ext}`)
+/// * `Error::Generic` - On an unknown or unterminated placeholder
+pub fn expand_pattern(
+    pattern: &str,
+    input: &Path,
+    relative: &Path,
+    target_ext: &str,
+    n: usize,
+) -> Result<PathBuf> {
+    let target_ext = remove_dot(target_ext);
+    let rel_dir = relative.parent().unwrap_or(Path::new(""));
+    let top = rel_dir
+        .components()
+        .next()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .unwrap_or_default();
+    let rel_dir = rel_dir
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let stem = input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let parent = input
+        .parent()
+        .and_then(Path::file_name)
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+
+    let mut expanded = String::with_capacity(pattern.len() + stem.len());
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| {
+            Error::Generic(format!(
+                "Unterminated placeholder in output pattern: {pattern}"
+            ))
+        })? + start;
+
+        match &rest[start + 1..end] {
+            "stem" => expanded.push_str(stem),
+            "ext" => expanded.push_str(target_ext),
+            "parent" => expanded.push_str(parent),
+            "n" => expanded.push_str(&n.to_string()),
+            "rel_dir" => expanded.push_str(&rel_dir),
+            "top" => expanded.push_str(&top),
+            "date" => expanded.push_str(&today()),
+            other => {
+                return Err(Error::Generic(format!(
+                    "Unknown placeholder '{{{other}}}' in output pattern: {pattern}"
+                )));
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+
+    let name = Path::new(&expanded)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let name_stem = name
+        .strip_suffix(target_ext)
+        .and_then(|s| s.strip_suffix('.'))
+        .unwrap_or(name);
+    if name_stem.trim_matches(['.', ' ']).is_empty() {
+        return Err(Error::InvalidExtension(format!(
+            "output pattern '{pattern}' produces an empty file name for {}",
+            input.display()
+        )));
+    }
+
+    Ok(PathBuf::from(expanded))
+}
+
+/// Whether `pattern` is a whole path template (`{rel_dir}/{stem}.{ext}`), which decides
+/// the output's folders below the output root itself, rather than just its file name.
+/// That's the case as soon as it has a `/` in it.
+#[inline]
+pub fn is_path_template(pattern: &str) -> bool {
+    pattern.contains('/')
+}
+
+/// Today's date in UTC as `YYYY-MM-DD`.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (year, month, day) = civil_from_days((secs / 86_400).cast_signed());
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// The `(year, month, day)` `days` after 1970-01-01, in the proleptic Gregorian calendar.
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    (year, month as u32, day as u32)
+}
+
+/// The days between 1970-01-01 and `(year, month, day)`, the inverse of `civil_from_days`.
+///
+/// See <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from(if month > 2 { month - 3 } else { month + 9 });
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// `path` with `_<n>` appended to its stem, e.g. `report_2.md`.
+pub fn with_counter(path: &Path, n: usize) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    path.with_file_name(format!("{stem}_{n}{ext}"))
+}
+
+/// Every output handed out so far this run, so two inputs can't be written to the same one,
+/// e.g. `a~b.docx` and `a$b.docx` once their names are sanitized, or `a.docx` and `a.doc`.
+///
+/// Outputs are handed out first come first served, in the order files are discovered.
+#[derive(Debug, Default)]
+pub struct ClaimedOutputs {
+    /// output -> the input it belongs to
+    taken: Mutex<HashMap<PathBuf, PathBuf>>,
+}
+
+impl ClaimedOutputs {
+    /// `output` if no other input has it yet, otherwise the first free `stem_2.ext`, `stem_3.ext`, ...
+    /// The same input always gets the same output back.
+    pub fn claim(&self, output: &Path, input: &Path) -> PathBuf {
+        let mut taken = self.taken.lock().expect("claimed outputs lock poisoned");
+        let is_free =
+            |candidate: &PathBuf| taken.get(candidate).is_none_or(|owner| owner == input);
+
+        let mut candidate = output.to_path_buf();
+        let mut count = 2;
+        while !is_free(&candidate) {
+            candidate = with_counter(output, count);
+            count += 1;
+        }
+        if candidate != output {
+            warn!(
+                "{} would share the output {} with {}, writing {} instead",
+                input.display(),
+                output.display(),
+                taken[output].display(),
+                candidate.display()
+            );
+        }
+
+        taken.insert(candidate.clone(), input.to_path_buf());
+        candidate
+    }
+}
+
+/// How `--flatten` tells apart outputs that would end up with the same name.
+#[derive(Debug, clap::ValueEnum, Clone, Copy, PartialEq, Eq, Default)]
+#[clap(name = "FlattenSuffix", rename_all = "lower")]
+pub enum FlattenSuffix {
+    /// `report_project-a.md`, falling back to a counter if that's taken too.
+    #[default]
+    Parent,
+    /// `report_2.md`, `report_3.md`, ...
+    Counter,
+}
+
+/// The names handed out so far in flattened output directories, so no two inputs share one.
+///
+/// Names are handed out first come first served, in the order files are discovered.
+#[derive(Debug)]
+pub struct FlatNames {
+    suffix: FlattenSuffix,
+    /// output -> the input it belongs to
+    taken:  Mutex<HashMap<PathBuf, PathBuf>>,
+}
+
+impl FlatNames {
+    pub fn new(suffix: FlattenSuffix) -> Self {
+        Self {
+            suffix,
+            taken: Mutex::default(),
+        }
+    }
+
+    /// The output for `input` in `dir`: `dir/file_name` if no other input has it yet,
+    /// otherwise `file_name` with a suffix added to its stem.
+    /// The same input always gets the same output back.
+    pub fn claim(&self, dir: &Path, file_name: &Path, input: &Path) -> PathBuf {
+        let mut taken = self.taken.lock().expect("flat names lock poisoned");
+        let is_free =
+            |candidate: &PathBuf| taken.get(candidate).is_none_or(|owner| owner == input);
+
+        let stem = file_name
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let ext = file_name
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+        let with_suffix = |suffix: &str| dir.join(format!("{stem}_{suffix}{ext}"));
+
+        let mut candidate = dir.join(file_name);
+        if !is_free(&candidate) && self.suffix == FlattenSuffix::Parent {
+            let parent = input
+                .parent()
+                .and_then(Path::file_name)
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            candidate = with_suffix(&parent);
+        }
+        let mut count = 2;
+        while !is_free(&candidate) {
+            candidate = with_suffix(&count.to_string());
+            count += 1;
+        }
+
+        taken.insert(candidate.clone(), input.to_path_buf());
+        candidate
+    }
+}
+
+#[cfg(test)]
+mod naming_tests {
+    use super::*;
+
+    fn expand(pattern: &str, input: &str, n: usize) -> Result<PathBuf> {
+        let input = Path::new(input);
+        expand_pattern(pattern, input, input, "md", n)
+    }
+
+    #[test]
+    fn test_default_pattern() {
+        let out = expand(DEFAULT_PATTERN, "docs/report.docx", 1).unwrap();
+        assert_eq!(out, PathBuf::from("report.md"));
+    }
+
+    #[test]
+    fn test_all_placeholders() {
+        let out =
+            expand("{n}-{parent}-{stem}.{ext}", "project-a/README.docx", 7).unwrap();
+        assert_eq!(out, PathBuf::from("7-project-a-README.md"));
+
+        let input = Path::new("a/b.docx");
+        let dotted = expand_pattern("{stem}.{ext}", input, input, ".html", 1);
+        assert_eq!(dotted.unwrap(), PathBuf::from("b.html"));
+    }
+
+    #[test]
+    fn test_collision_avoidance() {
+        let a = expand("{parent}_{stem}.{ext}", "project-a/README.docx", 1).unwrap();
+        let b = expand("{parent}_{stem}.{ext}", "project-b/README.docx", 2).unwrap();
+        assert_ne!(a, b);
+
+        let a = expand("{stem}-{n}.{ext}", "project-a/README.docx", 1).unwrap();
+        let b = expand("{stem}-{n}.{ext}", "project-b/README.docx", 2).unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_path_placeholders() {
+        let out = expand("{rel_dir}/{stem}.{ext}", "docs/2023/a.docx", 1).unwrap();
+        assert_eq!(out, PathBuf::from("docs/2023/a.md"));
+        let out = expand("{top}/{stem}.{ext}", "docs/2023/a.docx", 1).unwrap();
+        assert_eq!(out, PathBuf::from("docs/a.md"));
+        assert_eq!(
+            expand("{rel_dir}/{stem}.{ext}", "a.docx", 1).unwrap(),
+            PathBuf::from("/a.md")
+        );
+
+        assert!(is_path_template("{date}/{top}/{stem}.{ext}"));
+        assert!(!is_path_template("{stem}-{date}.{ext}"));
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(20_743), (2026, 10, 17));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!(matches!(
+            expand(".{ext}", "a/b.docx", 1),
+            Err(Error::InvalidExtension(_))
+        ));
+        assert!(matches!(expand("{nope}.{ext}", "a/b.docx", 1), Err(Error::Generic(_))));
+        assert!(matches!(expand("{stem.{ext}", "a/b.docx", 1), Err(Error::Generic(_))));
+    }
+
+    #[test]
+    fn test_flat_names() {
+        let (dir, name) = (Path::new("out"), Path::new("README.md"));
+        let parent = FlatNames::new(FlattenSuffix::Parent);
+        let a = Path::new("in/project-a/README.docx");
+        assert_eq!(parent.claim(dir, name, a), PathBuf::from("out/README.md"));
+        assert_eq!(parent.claim(dir, name, a), PathBuf::from("out/README.md"));
+        assert_eq!(
+            parent.claim(dir, name, Path::new("in/project-b/README.docx")),
+            PathBuf::from("out/README_project-b.md")
+        );
+        assert_eq!(
+            parent.claim(dir, name, Path::new("other/project-b/README.docx")),
+            PathBuf::from("out/README_2.md")
+        );
+
+        let counter = FlatNames::new(FlattenSuffix::Counter);
+        counter.claim(dir, name, a);
+        assert_eq!(
+            counter.claim(dir, name, Path::new("in/project-b/README.docx")),
+            PathBuf::from("out/README_2.md")
+        );
+    }
+
+    #[test]
+    fn test_claimed_outputs() {
+        let claimed = ClaimedOutputs::default();
+        let output = Path::new("out/a_b.md");
+        assert_eq!(claimed.claim(output, Path::new("a~b.docx")), output);
+        assert_eq!(claimed.claim(output, Path::new("a~b.docx")), output);
+        assert_eq!(
+            claimed.claim(output, Path::new("a$b.docx")),
+            PathBuf::from("out/a_b_2.md")
+        );
+        assert_eq!(
+            claimed.claim(output, Path::new("a#b.docx")),
+            PathBuf::from("out/a_b_3.md")
+        );
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::report::html_escape;
+use crate::conversion::shutdown::shield_from_ctrl_c;
+use crate::conversion::{Converter, canonical_ext, find_program, image, version_of};
+use crate::prelude::*;
+
+/// What `--ocr` routes to the `OcrConverter`: scanned PDFs and images,
+/// going to anything but the formats the image converter writes.
+pub(crate) const INPUT_FORMATS: [&str; 7] =
+    ["pdf", "png", "jpg", "tiff", "tif", "webp", "bmp"];
+
+/// Tesseract's language when `--ocr-lang` isn't given.
+pub const DEFAULT_LANG: &str = "eng";
+
+/// The resolution PDF pages are rendered at for Tesseract, which reads best at 300 dpi.
+const PDF_DPI: &str = "300";
+
+/// How the `OcrConverter` reads the text.
+#[derive(Debug, Clone)]
+pub struct OcrOptions {
+    /// The Tesseract language(s) to read, `+`-separated (`eng+deu`), forwarded as `-l`.
+    pub lang: String,
+}
+
+impl Default for OcrOptions {
+    fn default() -> Self {
+        Self {
+            lang: DEFAULT_LANG.to_string(),
+        }
+    }
+}
+
+/// Reads the text of scanned PDFs and images with Tesseract (`--ocr`), and hands it to pandoc as HTML,
+/// a paragraph per paragraph Tesseract found, to write the output.
+///
+/// PDFs are rendered to a PNG per page with poppler's `pdftoppm` first, Tesseract can't read them.
+/// The mean confidence of the words read is kept for the report, see `Converter::ocr_confidence`.
+pub struct OcrConverter<P = PathBuf> {
+    program_name: P,
+    /// `pdftoppm`, `None` if it isn't installed, which only fails the PDFs.
+    rasterizer:   Option<PathBuf>,
+    options:      OcrOptions,
+    /// Writes the output from the text read.
+    pandoc:       Box<dyn Converter>,
+    /// The mean word confidence of every conversion, by output, until `ocr_confidence` takes it.
+    confidences:  Mutex<HashMap<PathBuf, f64>>,
+}
+
+impl OcrConverter {
+    /// Create a new `OcrConverter` for the `tesseract` in PATH, writing its outputs with `pandoc`,
+    /// falling back to the bare name for `check_installed` to report as missing.
+    #[inline]
+    pub fn new(pandoc: Box<dyn Converter>) -> Self {
+        let program_name = find_program("tesseract").unwrap_or_else(|| {
+            debug!("Couldn't find tesseract in PATH, falling back to 'tesseract'");
+            PathBuf::from("tesseract")
+        });
+        Self {
+            program_name,
+            rasterizer: find_program("pdftoppm"),
+            options: OcrOptions::default(),
+            pandoc,
+            confidences: Mutex::default(),
+        }
+    }
+
+    /// Applies `options`, the language Tesseract reads.
+    #[must_use]
+    pub fn with_options(mut self, options: OcrOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Renders every page of the PDF `input` to `<dir>/page-<n>.png`, returning them in page order.
+    async fn rasterize(&self, input: &Path, dir: &Path) -> Result<Vec<PathBuf>> {
+        let Some(ref rasterizer) = self.rasterizer else {
+            return Err(Error::ConversionProgramNotInstalled(
+                "pdftoppm (for reading PDFs with --ocr)".to_string(),
+            ));
+        };
+
+        let mut command = tokio::process::Command::new(rasterizer);
+        let cmd_output = shield_from_ctrl_c(&mut command)
+            .args(["-r", PDF_DPI, "-png"])
+            .arg(input)
+            .arg(dir.join("page"))
+            .kill_on_drop(true)
+            .output()
+            .await?;
+        self.check_exit("pdftoppm", input, &cmd_output)?;
+
+        let mut pages = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with("page-") && name.ends_with(".png") {
+                pages.push(entry.path());
+            }
+        }
+        // pdftoppm pads the page numbers to the same width, so they sort by name
+        pages.sort_unstable();
+        Ok(pages)
+    }
+
+    /// Reads `page` with Tesseract into `<base>.txt` and `<base>.tsv`, returning the text.
+    async fn read_page(&self, input: &Path, page: &Path, base: &Path) -> Result<Page> {
+        let mut command = tokio::process::Command::new(&self.program_name);
+        let cmd_output = shield_from_ctrl_c(&mut command)
+            .arg(page)
+            .arg(base)
+            .arg("-l")
+            .arg(&self.options.lang)
+            .args(["txt", "tsv"])
+            // A timed-out conversion drops this future, take tesseract down with it
+            .kill_on_drop(true)
+            .output()
+            .await?;
+        self.check_exit(&self.name(), input, &cmd_output)?;
+
+        Ok(Page {
+            text: tokio::fs::read_to_string(base.with_extension("txt")).await?,
+            tsv:  tokio::fs::read_to_string(base.with_extension("tsv")).await?,
+        })
+    }
+
+    fn check_exit(
+        &self,
+        program: &str,
+        input: &Path,
+        cmd_output: &std::process::Output,
+    ) -> Result<()> {
+        if cmd_output.status.success() {
+            return Ok(());
+        }
+        let mut stderr = String::new();
+        cmd_output.stderr.as_slice().read_to_string(&mut stderr)?;
+        Err(Error::ConverterFailed {
+            program: program.to_string(),
+            input: input.to_path_buf(),
+            code: cmd_output.status.code(),
+            stderr,
+        })
+    }
+}
+
+/// What Tesseract read of a single page.
+struct Page {
+    text: String,
+    tsv:  String,
+}
+
+#[async_trait::async_trait]
+impl Converter for OcrConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        trace!("Reading '{}' with OCR for '{}'", input.display(), output.display());
+
+        let ext = output
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        // The pages, what Tesseract reads of them and the HTML pandoc gets all go in the staging folder,
+        // which goes once the output is written
+        let partial =
+            PartialOutput::in_staging_dir(output, Path::new(&format!("{stem}.{ext}")))
+                .await?;
+        let staging = partial
+            .path()
+            .parent()
+            .unwrap_or(partial.path())
+            .to_path_buf();
+
+        let is_pdf = input
+            .extension()
+            .is_some_and(|ext| canonical_ext(&ext.to_string_lossy()) == "pdf");
+        let pages = if is_pdf {
+            self.rasterize(input, &staging).await?
+        } else {
+            vec![input.to_path_buf()]
+        };
+
+        let mut read = Vec::with_capacity(pages.len());
+        for (n, page) in pages.iter().enumerate() {
+            let base = staging.join(format!("ocr-{}", n + 1));
+            read.push(self.read_page(input, page, &base).await?);
+        }
+        let confidence = mean_confidence(read.iter().map(|page| page.tsv.as_str()));
+        debug!(
+            "Read {} pages of {input:?} with OCR, confidence {confidence:?}",
+            read.len()
+        );
+
+        let html = staging.join(format!("{stem}.ocr.html"));
+        tokio::fs::write(
+            &html,
+            to_html(&stem, read.iter().map(|page| page.text.as_str())),
+        )
+        .await?;
+        self.pandoc.convert(&html, partial.path()).await?;
+        partial.commit().await?;
+
+        if let Some(confidence) = confidence {
+            self.confidences
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .insert(output.to_path_buf(), confidence);
+        }
+        Ok(())
+    }
+
+    async fn check_installed(&self) -> bool {
+        let checked = tokio::process::Command::new(&self.program_name)
+            .arg("--version")
+            .output()
+            .await
+            .map(|output| output.status.success());
+
+        debug!("Checked if {:?} is installed: {checked:?}", self.program_name);
+        if self.rasterizer.is_none() {
+            warn!("pdftoppm (poppler) is not installed, PDFs can't be read with --ocr");
+        }
+        let installed = checked.unwrap_or_else(|_| {
+            warn!("{:?} is not installed", self.program_name);
+            false
+        });
+        installed && self.pandoc.check_installed().await
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        self.program_name.display().to_string()
+    }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Ok(INPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        self.pandoc.supported_output_formats().await
+    }
+
+    async fn version(&self) -> Option<String> {
+        version_of(&self.program_name).await
+    }
+
+    async fn check_version(&self) -> Result<()> {
+        self.pandoc.check_version().await
+    }
+
+    fn ocr_confidence(&self, output: &Path) -> Option<f64> {
+        self.confidences
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(output)
+    }
+}
+
+/// Every pair of one of `INPUT_FORMATS` going to one of `target_exts` that `--ocr` routes to the `OcrConverter`:
+/// all but what the image converter writes, which takes the image as it is.
+pub(crate) fn routed_pairs(target_exts: &[String]) -> Vec<(String, String)> {
+    target_exts
+        .iter()
+        .filter(|output| !image::OUTPUT_FORMATS.contains(&canonical_ext(output).as_str()))
+        .flat_map(|output| {
+            INPUT_FORMATS
+                .iter()
+                .map(move |input| (input.to_string(), output.clone()))
+        })
+        .collect()
+}
+
+/// The mean confidence (0 - 100) of the words in Tesseract's `tsv` outputs, `None` if they hold none.
+///
+/// Only rows of words (level 5) count, the others have a confidence of -1.
+fn mean_confidence<'a>(tsvs: impl Iterator<Item = &'a str>) -> Option<f64> {
+    let (mut sum, mut words) = (0.0, 0_usize);
+    for line in tsvs
+        .flat_map(str::lines)
+        .skip_while(|line| line.starts_with("level"))
+    {
+        let columns: Vec<_> = line.split('\t').collect();
+        if columns.first() != Some(&"5")
+            || columns.get(11).is_none_or(|word| word.trim().is_empty())
+        {
+            continue;
+        }
+        if let Some(conf) = columns.get(10).and_then(|conf| conf.parse::<f64>().ok())
+            && conf >= 0.0
+        {
+            sum += conf;
+            words += 1;
+        }
+    }
+    (words > 0).then(|| sum / words as f64)
+}
+
+/// An HTML document of the `pages` Tesseract read, each of their blank-line separated paragraphs a `<p>`
+/// with its lines joined back up.
+fn to_html<'a>(title: &str, pages: impl Iterator<Item = &'a str>) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n",
+        html_escape(title)
+    );
+    for page in pages {
+        // Tesseract ends every page with a form feed
+        for paragraph in page.trim_matches(['\u{c}', '\n', ' ']).split("\n\n") {
+            let lines: Vec<_> = paragraph
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect();
+            if !lines.is_empty() {
+                html.push_str(&format!("<p>{}</p>\n", html_escape(&lines.join(" "))));
+            }
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod ocr_tests {
+    use super::*;
+
+    #[test]
+    fn test_mean_confidence() {
+        let tsv = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext\n\
+                   1\t1\t0\t0\t0\t0\t0\t0\t100\t100\t-1\t\n\
+                   5\t1\t1\t1\t1\t1\t10\t10\t20\t10\t90.5\tHello\n\
+                   5\t1\t1\t1\t1\t2\t40\t10\t20\t10\t70.5\tworld\n\
+                   5\t1\t1\t1\t1\t3\t70\t10\t20\t10\t95\t \n";
+        assert_eq!(mean_confidence([tsv].into_iter()), Some(80.5));
+        assert_eq!(mean_confidence(["level\tpage_num\n"].into_iter()), None);
+    }
+
+    #[test]
+    fn test_to_html() {
+        let html =
+            to_html("scan", ["First line\nwraps here\n\nA & B\n\u{c}"].into_iter());
+        assert!(html.contains("<p>First line wraps here</p>\n<p>A &amp; B</p>\n"));
+        assert!(html.contains("<title>scan</title>"));
+    }
+
+    #[test]
+    fn test_routed_pairs() {
+        let pairs = routed_pairs(&["md".to_string(), "png".to_string()]);
+        assert!(pairs.contains(&("pdf".to_string(), "md".to_string())));
+        assert!(pairs.contains(&("tiff".to_string(), "md".to_string())));
+        assert!(!pairs.iter().any(|(_, output)| output == "png"));
+    }
+}
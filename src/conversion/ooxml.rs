@@ -0,0 +1,208 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::attributes::Attribute;
+use quick_xml::events::{BytesRef, BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+
+use crate::prelude::*;
+
+/// An Office Open XML package (docx, pptx), the zip of XML parts the in-process converters read.
+pub(crate) struct Package {
+    path:    PathBuf,
+    archive: zip::ZipArchive<File>,
+}
+
+impl Package {
+    /// Opens the package at `path`.
+    ///
+    /// # Errors
+    /// * `Error::UnreadableDocument` - If it isn't a zip.
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let archive = zip::ZipArchive::new(file).map_err(|e| unreadable(path, e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            archive,
+        })
+    }
+
+    /// The part `name` (`word/document.xml`) as text.
+    ///
+    /// # Errors
+    /// * `Error::UnreadableDocument` - If there's no such part.
+    pub(crate) fn read_string(&mut self, name: &str) -> Result<String> {
+        let mut text = String::new();
+        self.part(name)?.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    /// The part `name` (`ppt/media/image1.png`) as it is.
+    ///
+    /// # Errors
+    /// * `Error::UnreadableDocument` - If there's no such part.
+    pub(crate) fn read_bytes(&mut self, name: &str) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.part(name)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// The relationships of the part `name` (`ppt/slides/slide1.xml`), from their id (`rId2`) to the part
+    /// they point at, by its name in the package. Links to outside the package are left out.
+    /// A part without relationships has none.
+    ///
+    /// # Errors
+    /// * `Error::UnreadableDocument` - If its relationships aren't XML.
+    pub(crate) fn relationships(
+        &mut self,
+        name: &str,
+    ) -> Result<HashMap<String, String>> {
+        let (dir, file_name) = name.rsplit_once('/').unwrap_or(("", name));
+        let rels_name = if dir.is_empty() {
+            format!("_rels/{file_name}.rels")
+        } else {
+            format!("{dir}/_rels/{file_name}.rels")
+        };
+        if self.archive.index_for_name(&rels_name).is_none() {
+            return Ok(HashMap::new());
+        }
+
+        let xml = self.read_string(&rels_name)?;
+        parse_relationships(&xml, dir).map_err(|reason| {
+            Error::UnreadableDocument {
+                path: self.path.clone(),
+                reason,
+            }
+        })
+    }
+
+    fn part(&mut self, name: &str) -> Result<zip::read::ZipFile<'_, File>> {
+        let path = &self.path;
+        self.archive
+            .by_name(name)
+            .map_err(|e| unreadable(path, format!("{name}: {e}")))
+    }
+}
+
+fn unreadable(path: &Path, reason: impl ToString) -> Error {
+    Error::UnreadableDocument {
+        path:   path.to_path_buf(),
+        reason: reason.to_string(),
+    }
+}
+
+/// The `Relationship`s of a `.rels` part, with their targets resolved against `dir`, the folder of the part they belong to.
+fn parse_relationships(
+    xml: &str,
+    dir: &str,
+) -> std::result::Result<HashMap<String, String>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut relationships = HashMap::new();
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) | Event::Empty(e)
+                if e.local_name().as_ref() == "Relationship" =>
+            {
+                if attribute(&e, "TargetMode").as_deref() == Some("External") {
+                    continue;
+                }
+                if let (Some(id), Some(target)) =
+                    (attribute(&e, "Id"), attribute(&e, "Target"))
+                {
+                    relationships.insert(id, resolve_target(dir, &target));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(relationships)
+}
+
+/// The name in the package of `target`, relative to `dir` unless it starts at the root (`/`).
+fn resolve_target(dir: &str, target: &str) -> String {
+    let mut parts: Vec<&str> = match target.strip_prefix('/') {
+        Some(_) => Vec::new(),
+        None => dir.split('/').filter(|part| !part.is_empty()).collect(),
+    };
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// The value of `e`'s unprefixed attribute `name`.
+pub(crate) fn attribute(e: &BytesStart<'_>, name: &str) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attr| attr.key.as_ref() == name)
+        .and_then(|attr| value(&attr))
+}
+
+/// The value of `e`'s attribute `name` whatever its namespace prefix (`w:val`, `r:embed`).
+pub(crate) fn prefixed_attribute(e: &BytesStart<'_>, name: &str) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|attr| {
+            attr.key.prefix().is_some() && attr.key.local_name().as_ref() == name
+        })
+        .and_then(|attr| value(&attr))
+}
+
+/// `attr`'s value with its references resolved.
+fn value(attr: &Attribute<'_>) -> Option<String> {
+    attr.normalized_value(XmlVersion::Implicit1_0)
+        .ok()
+        .map(Cow::into_owned)
+}
+
+/// Adds what the entity or character reference `e` (`&amp;`, `&#8364;`) stands for to `text`,
+/// nothing for the ones XML doesn't define.
+pub(crate) fn push_ref(
+    text: &mut String,
+    e: &BytesRef<'_>,
+) -> std::result::Result<(), String> {
+    if let Some(c) = e.resolve_char_ref().map_err(|e| e.to_string())? {
+        text.push(c);
+    } else if let Some(resolved) = resolve_predefined_entity(e) {
+        text.push_str(resolved);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod ooxml_tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_relationships() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slideLayout" Target="../slideLayouts/slideLayout2.xml"/>
+<Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/image" Target="../media/image1.png"/>
+<Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="https://example.com" TargetMode="External"/>
+</Relationships>"#;
+        let rels = parse_relationships(xml, "ppt/slides").unwrap();
+        assert_eq!(rels["rId1"], "ppt/slideLayouts/slideLayout2.xml");
+        assert_eq!(rels["rId2"], "ppt/media/image1.png");
+        assert!(!rels.contains_key("rId3"));
+    }
+
+    #[test]
+    fn test_resolve_target() {
+        assert_eq!(resolve_target("ppt", "slides/slide1.xml"), "ppt/slides/slide1.xml");
+        assert_eq!(resolve_target("ppt/slides", "./../media/a.png"), "ppt/media/a.png");
+        assert_eq!(resolve_target("ppt/slides", "/ppt/media/a.png"), "ppt/media/a.png");
+        assert_eq!(resolve_target("", "ppt/presentation.xml"), "ppt/presentation.xml");
+    }
+}
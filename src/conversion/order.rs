@@ -0,0 +1,119 @@
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The order a batch converts its files in (`--order`), instead of the order they're walked in.
+#[derive(Debug, clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+#[clap(name = "Order", rename_all = "kebab-case")]
+pub enum Order {
+    /// By path, folder by folder.
+    Path,
+    /// Quick wins first.
+    SmallestFirst,
+    /// The long conversions first, so they aren't what the batch ends on.
+    LargestFirst,
+    /// Most recently modified first.
+    NewestFirst,
+    /// Least recently modified first.
+    OldestFirst,
+}
+
+impl Order {
+    /// Sorts `files`, reading each one's metadata only once.
+    /// Files whose metadata can't be read go last, ties keep the order they came in.
+    pub fn sort(self, files: &mut [PathBuf]) {
+        match self {
+            Order::Path => files.sort(),
+            Order::SmallestFirst => {
+                files.sort_by_cached_key(|path| last_if_none(size(path)));
+            }
+            Order::LargestFirst => {
+                files.sort_by_cached_key(|path| last_if_none(size(path).map(Reverse)));
+            }
+            Order::NewestFirst => {
+                files
+                    .sort_by_cached_key(|path| last_if_none(modified(path).map(Reverse)));
+            }
+            Order::OldestFirst => {
+                files.sort_by_cached_key(|path| last_if_none(modified(path)));
+            }
+        }
+    }
+}
+
+/// A sort key that puts `None` after every `Some`.
+fn last_if_none<T: Ord>(key: Option<T>) -> (bool, Option<T>) {
+    (key.is_none(), key)
+}
+
+fn size(path: &Path) -> Option<u64> {
+    path.metadata().ok().map(|meta| meta.len())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    path.metadata().ok()?.modified().ok()
+}
+
+#[cfg(test)]
+mod order_tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_sort() {
+        let tmp = std::env::temp_dir().join(format!("dcc_order_{}", std::process::id()));
+        std::fs::create_dir_all(tmp.join("b")).unwrap();
+        let file = |name: &str, len: usize, age_secs: u64| {
+            let path = tmp.join(name);
+            std::fs::write(&path, "x".repeat(len)).unwrap();
+            let modified = SystemTime::now() - Duration::from_secs(age_secs);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(modified)
+                .unwrap();
+        };
+        file("c.docx", 30, 300);
+        file("a.docx", 10, 10);
+        file("b/x.docx", 20, 100);
+
+        let sorted = |order: Order| {
+            let mut files: Vec<PathBuf> = ["c.docx", "gone.docx", "a.docx", "b/x.docx"]
+                .iter()
+                .map(|name| tmp.join(name))
+                .collect();
+            order.sort(&mut files);
+            files
+                .iter()
+                .map(|path| {
+                    path.strip_prefix(&tmp)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted(Order::Path), ["a.docx", "b/x.docx", "c.docx", "gone.docx"]);
+        assert_eq!(
+            sorted(Order::SmallestFirst),
+            ["a.docx", "b/x.docx", "c.docx", "gone.docx"]
+        );
+        assert_eq!(
+            sorted(Order::LargestFirst),
+            ["c.docx", "b/x.docx", "a.docx", "gone.docx"]
+        );
+        assert_eq!(
+            sorted(Order::NewestFirst),
+            ["a.docx", "b/x.docx", "c.docx", "gone.docx"]
+        );
+        assert_eq!(
+            sorted(Order::OldestFirst),
+            ["c.docx", "b/x.docx", "a.docx", "gone.docx"]
+        );
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::conversion::report::{FileOutcome, OutcomeStatus};
+use crate::conversion::{create_parent, move_file};
+use crate::prelude::*;
+
+/// The folder (below the input root) that `SourceDisposal::Move` collects converted sources in.
+/// Discovery and the watcher never look inside it.
+pub const ORIGINALS_DIR: &str = "converted-originals";
+
+/// What `--delete-source` does with a source once it has been converted.
+#[derive(Debug, clap::ValueEnum, Clone, Copy, PartialEq, Eq, Default)]
+#[clap(name = "SourceDisposal", rename_all = "lower")]
+pub enum SourceDisposal {
+    /// Remove it.
+    #[default]
+    Delete,
+    /// Move it into `converted-originals/`, keeping its folders below the input root.
+    Move,
+}
+
+/// Deletes (or moves into `ORIGINALS_DIR`) every source whose conversions all succeeded
+/// and wrote a non-empty output. Sources with any failed or empty output are kept.
+///
+/// Moved sources below `root` keep their folders relative to it, anything else
+/// goes into a `converted-originals` folder next to it.
+///
+/// Returns how many sources were disposed of, one that can't be is logged and kept.
+pub async fn dispose_sources(
+    outcomes: &[FileOutcome],
+    root: Option<&Path>,
+    disposal: SourceDisposal,
+) -> usize {
+    let mut by_input: BTreeMap<&Path, Vec<&FileOutcome>> = BTreeMap::new();
+    for outcome in outcomes {
+        by_input.entry(&outcome.input).or_default().push(outcome);
+    }
+
+    let mut disposed = 0;
+    for (input, outcomes) in by_input {
+        if outcomes.iter().any(|o| o.status != OutcomeStatus::Ok) {
+            continue;
+        }
+        if let Some(empty) = outcomes.iter().find(|o| !is_non_empty(&o.output)) {
+            warn!(
+                "Output {:?} is missing or empty, keeping its source {input:?}",
+                empty.output
+            );
+            continue;
+        }
+
+        match dispose_one(input, root, disposal).await {
+            Ok(()) => disposed += 1,
+            Err(e) => warn!("Failed to dispose of the source {input:?}: {e}"),
+        }
+    }
+
+    info!("Disposed of {disposed} converted sources");
+    disposed
+}
+
+async fn dispose_one(
+    input: &Path,
+    root: Option<&Path>,
+    disposal: SourceDisposal,
+) -> Result<()> {
+    match disposal {
+        SourceDisposal::Delete => tokio::fs::remove_file(input).await?,
+        SourceDisposal::Move => {
+            let target = originals_path(input, root);
+            create_parent(&target).await?;
+            move_file(input, &target).await?;
+        }
+    }
+    Ok(())
+}
+
+fn is_non_empty(output: &Path) -> bool {
+    std::fs::metadata(output).is_ok_and(|meta| meta.len() > 0)
+}
+
+/// Where `SourceDisposal::Move` puts `input`.
+fn originals_path(input: &Path, root: Option<&Path>) -> PathBuf {
+    if let Some(root) = root
+        && let Ok(relative) = input.strip_prefix(root)
+        && !relative.as_os_str().is_empty()
+    {
+        return root.join(ORIGINALS_DIR).join(relative);
+    }
+
+    let file_name = input.file_name().unwrap_or(input.as_os_str());
+    input
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(ORIGINALS_DIR)
+        .join(file_name)
+}
+
+#[cfg(test)]
+mod originals_tests {
+    use super::*;
+
+    #[test]
+    fn test_originals_path() {
+        let root = Path::new("/in");
+        assert_eq!(
+            originals_path(Path::new("/in/a/b.docx"), Some(root)),
+            PathBuf::from("/in/converted-originals/a/b.docx")
+        );
+        assert_eq!(
+            originals_path(Path::new("/elsewhere/b.docx"), Some(root)),
+            PathBuf::from("/elsewhere/converted-originals/b.docx")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_verified_sources_are_disposed() {
+        let tmp =
+            std::env::temp_dir().join(format!("dcc_originals_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp).unwrap();
+        let file = |name: &str, contents: &str| {
+            let path = tmp.join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        };
+        let (good, empty, failed) =
+            (file("good.docx", "x"), file("empty.docx", "x"), file("failed.docx", "x"));
+        let outcomes = [
+            FileOutcome::ok(&good, &file("good.md", "converted"), 0),
+            FileOutcome::ok(&good, &file("good.html", "converted"), 0),
+            FileOutcome::ok(&empty, &file("empty.md", ""), 0),
+            FileOutcome::ok(&failed, &file("failed.md", "converted"), 0),
+            FileOutcome::error(&failed, &tmp.join("failed.html"), "boom"),
+        ];
+
+        let disposed = dispose_sources(&outcomes, Some(&tmp), SourceDisposal::Move).await;
+
+        assert_eq!(disposed, 1);
+        assert!(!good.exists());
+        assert!(tmp.join("converted-originals/good.docx").exists());
+        assert!(empty.exists());
+        assert!(failed.exists());
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}
@@ -1,28 +1,289 @@
+use std::ffi::OsString;
+use std::fmt;
 use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use crate::conversion::Converter;
-use crate::pandoc_path;
+use tokio::sync::OnceCell;
+
+use crate::conversion::pandoc_server::PandocServer;
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::pdf::PdfEngine;
+use crate::conversion::shutdown::shield_from_ctrl_c;
+use crate::conversion::{Converter, dealias, version_of};
 use crate::prelude::*;
 
+/// The pandoc options that need a newer pandoc than the rest, and the version that brought each in.
+/// Anything passed with `--pandoc-arg` counts too.
+const OPTION_VERSIONS: [(&str, &[u64]); 3] = [
+    ("--defaults", &[2, 8]),
+    ("--citeproc", &[2, 11]),
+    ("--embed-resources", &[2, 19]),
+];
+
+/// A pandoc version like `3.1.11`, compared number by number: `2.9.1` is older than `2.19`.
+///
+/// Trailing zeros don't count, `2.19` and `2.19.0` are the same version.
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct PandocVersion(Vec<u64>);
+
+impl PandocVersion {
+    /// The version in the first line `pandoc --version` prints, e.g. `pandoc 3.1.11` or `pandoc.exe 2.19.2`.
+    pub fn from_version_line(line: &str) -> Option<Self> {
+        line.split_whitespace()
+            .skip(1)
+            .find_map(|word| word.parse().ok())
+    }
+
+    fn from_numbers(numbers: &[u64]) -> Self {
+        let mut numbers = numbers.to_vec();
+        while numbers.last() == Some(&0) {
+            numbers.pop();
+        }
+        Self(numbers)
+    }
+}
+
+impl FromStr for PandocVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let numbers = s
+            .trim()
+            .split('.')
+            .map(str::parse)
+            .collect::<std::result::Result<Vec<u64>, _>>()
+            .map_err(|_| format!("'{s}' isn't a version like 2.19 or 3.1.11"))?;
+        Ok(Self::from_numbers(&numbers))
+    }
+}
+
+impl TryFrom<String> for PandocVersion {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PandocVersion> for String {
+    fn from(version: PandocVersion) -> Self {
+        version.to_string()
+    }
+}
+
+impl fmt::Display for PandocVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.as_slice() {
+            [] => write!(f, "0"),
+            [first, rest @ ..] => {
+                write!(f, "{first}")?;
+                rest.iter().try_for_each(|n| write!(f, ".{n}"))
+            }
+        }
+    }
+}
+
+/// Per-run pandoc settings, applied to every file in the batch.
+#[derive(Debug, Clone, Default)]
+pub struct PandocOptions {
+    /// Forwarded as `--defaults <file>`, ahead of the other options so they can override it.
+    pub defaults:      Option<PathBuf>,
+    /// Forwarded as `--template <file>`.
+    pub template:      Option<PathBuf>,
+    /// Forwarded as `--reference-doc <file>`.
+    pub reference_doc: Option<PathBuf>,
+    /// Each forwarded as `--bibliography <file>`.
+    pub bibliography:  Vec<PathBuf>,
+    /// Forwarded as `--csl <file>`.
+    pub csl:           Option<PathBuf>,
+    /// Forwarded as `--citeproc`, which resolves the citations before any Lua filter runs.
+    pub citeproc:      bool,
+    /// Each forwarded as `--lua-filter <file>`, pandoc applies them in this order.
+    pub lua_filters:   Vec<PathBuf>,
+    /// Passed through verbatim, after everything else.
+    pub extra_args:    Vec<String>,
+    /// Convert through a single `pandoc server` rather than a pandoc per file, see `PandocServer`.
+    /// Not forwarded, and ignored along with any of the options the server can't take (see `needs_files`).
+    pub server:        bool,
+    /// The oldest pandoc to convert with (`--min-pandoc-version`), see `required_version`.
+    pub min_version:   Option<PandocVersion>,
+    /// Forwarded as `--pdf-engine <program>`, which only pdf outputs go through.
+    pub pdf_engine:    Option<PdfEngine>,
+}
+
+impl PandocOptions {
+    /// The arguments these options add to a pandoc invocation.
+    pub fn to_args(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+
+        if let Some(ref defaults) = self.defaults {
+            args.push("--defaults".into());
+            args.push(defaults.into());
+        }
+        if let Some(ref template) = self.template {
+            args.push("--template".into());
+            args.push(template.into());
+        }
+        if let Some(ref reference_doc) = self.reference_doc {
+            args.push("--reference-doc".into());
+            args.push(reference_doc.into());
+        }
+        for bibliography in &self.bibliography {
+            args.push("--bibliography".into());
+            args.push(bibliography.into());
+        }
+        if let Some(ref csl) = self.csl {
+            args.push("--csl".into());
+            args.push(csl.into());
+        }
+        if self.citeproc {
+            args.push("--citeproc".into());
+        }
+        for filter in &self.lua_filters {
+            args.push("--lua-filter".into());
+            args.push(filter.into());
+        }
+        if let Some(engine) = self.pdf_engine {
+            args.push("--pdf-engine".into());
+            args.push(engine.program().into());
+        }
+        args.extend(self.extra_args.iter().map(OsString::from));
+
+        args
+    }
+
+    /// The first of these options that names a file, which the server can't read.
+    /// It only ever sees the document itself.
+    pub fn needs_files(&self) -> Option<&'static str> {
+        [
+            (self.defaults.is_some(), "--defaults"),
+            (self.template.is_some(), "--template"),
+            (self.reference_doc.is_some(), "--reference-doc"),
+            (!self.bibliography.is_empty(), "--bibliography"),
+            (self.csl.is_some(), "--csl"),
+            (!self.lua_filters.is_empty(), "--lua-filter"),
+            (!self.extra_args.is_empty(), "--pandoc-arg"),
+        ]
+        .into_iter()
+        .find_map(|(set, option)| set.then_some(option))
+    }
+
+    /// The first of these options that changes what pandoc writes, which the built-in converters
+    /// (see `MarkdownConverter`) can't take, so pandoc keeps the files they'd convert.
+    pub fn shapes_output(&self) -> Option<&'static str> {
+        self.needs_files()
+            .or_else(|| self.citeproc.then_some("--citeproc"))
+    }
+
+    /// The oldest pandoc these options work with, and why: `min_version`, or the version
+    /// that brought in the newest of the options given (see `OPTION_VERSIONS`), whichever is newer.
+    pub fn required_version(&self) -> Option<(PandocVersion, String)> {
+        let args = self.to_args();
+        let needed = OPTION_VERSIONS
+            .iter()
+            .filter(|(option, _)| {
+                args.iter()
+                    .filter_map(|arg| arg.to_str())
+                    .any(|arg| arg == *option || arg.starts_with(&format!("{option}=")))
+            })
+            .map(|(option, version)| {
+                (PandocVersion::from_numbers(version), format!("for {option}"))
+            });
+        let configured = self
+            .min_version
+            .clone()
+            .map(|version| (version, "--min-pandoc-version".to_string()));
+
+        configured
+            .into_iter()
+            .chain(needed)
+            .max_by(|a, b| a.0.cmp(&b.0))
+    }
+
+    /// `failed`, a failed run's `ConverterFailed`, as a `ReferenceDocRejected` if its stderr is pandoc failing
+    /// to open or parse our `--reference-doc`, so a broken or mismatched reference doc isn't retried
+    /// like an ordinary failure. Anything else that mentions a reference (a citation, a cross-reference) is left as it is.
+    fn rejected_reference_doc(&self, failed: Error) -> Error {
+        let (Some(path), Some(stderr)) = (self.reference_doc.as_ref(), failed.stderr())
+        else {
+            return failed;
+        };
+
+        let lower = stderr.to_lowercase();
+        // `word/styles.xml missing in reference docx` and the like, when it doesn't hold what it should
+        let unparsable = ["docx", "pptx", "odt"]
+            .iter()
+            .any(|format| lower.contains(&format!("in reference {format}")));
+        // `styles/corp.docx: withBinaryFile: does not exist`, when it can't be opened
+        let unopenable = ["openBinaryFile", "withBinaryFile"]
+            .iter()
+            .any(|call| stderr.contains(&format!("{}: {call}", path.display())));
+
+        if unparsable || unopenable {
+            Error::ReferenceDocRejected {
+                path:   path.clone(),
+                source: Box::new(failed),
+            }
+        } else {
+            failed
+        }
+    }
+}
+
+/// Converts with pandoc, extracting a document's images into a folder named after its output.
 pub struct PandocConverter<P = PathBuf> {
     program_name: P,
+    options:      PandocOptions,
+    /// Started by the first conversion with `PandocOptions::server`, `None` if it couldn't be.
+    server:       OnceCell<Option<PandocServer>>,
 }
 
 impl PandocConverter {
-    /// Create a new `PandocConverter`
-    ///
-    /// ### Note:
-    /// This calls a macro `pandoc_path!` which is defined in `src/prelude.rs`
-    ///
-    /// The macro handles embedding the official pandoc binary into the executable
-    /// and if it doesn't exist, it generates a temporary file and dumps the binary to it from the embed.
+    /// Create a new `PandocConverter`, see `try_new`.
     ///
-    /// If hash compare - this has been compressed using upx (`upx --best pandoc -o pandoc_upx`)
+    /// Without a pandoc to run it falls back to a plain `pandoc`, which `check_installed` then reports as missing.
     #[inline]
     pub fn new() -> Self {
-        let program_name = pandoc_path!();
-        Self { program_name }
+        Self::try_new().unwrap_or_else(|e| {
+            debug!("{e}, falling back to plain `pandoc`");
+            Self {
+                program_name: PathBuf::from("pandoc"),
+                options:      PandocOptions::default(),
+                server:       OnceCell::new(),
+            }
+        })
+    }
+
+    /// Create a new `PandocConverter` for the pandoc `get_pandoc_path` finds: on unix the one in `PATH`
+    /// (or a Homebrew/MacPorts prefix on macOS), on Windows the embedded binary,
+    /// unpacked to the temp folder if it isn't already.
+    ///
+    /// The embedded binary has been compressed using upx (`upx --best pandoc -o pandoc_upx`).
+    ///
+    /// # Errors
+    /// * `Error::PandocNotFound` - If there's no pandoc to be found
+    /// * `Error::PandocBinaryIntegrityFailure` - If the unpacked binary doesn't match the embedded one
+    pub fn try_new() -> Result<Self> {
+        Ok(Self {
+            program_name: get_pandoc_path()?,
+            options:      PandocOptions::default(),
+            server:       OnceCell::new(),
+        })
+    }
+
+    /// Options forwarded to every pandoc invocation.
+    ///
+    /// These are placed after the default `--extract-media <dir> -s` flags
+    /// and before the input/output positionals, so they can add to but never
+    /// remove the defaults.
+    #[must_use]
+    pub fn with_options(mut self, options: PandocOptions) -> Self {
+        self.options = options;
+        self
     }
 
     /// Creates a folder
@@ -47,27 +308,67 @@ impl PandocConverter {
         })?;
         Ok(parent_folder.join(filename))
     }
-}
 
-impl Default for PandocConverter {
-    #[inline]
-    fn default() -> Self {
-        Self::new()
+    /// The server to convert through, started on the first call, if `PandocOptions::server` asks for one
+    /// and it can be used at all. Otherwise every file is converted by a pandoc of its own.
+    async fn server(&self) -> Option<&PandocServer> {
+        if !self.options.server {
+            return None;
+        }
+
+        let server = self.server.get_or_init(|| {
+            async {
+                if let Some(option) = self.options.needs_files() {
+                    warn!("The pandoc server can't be used with {option}, starting a pandoc per file instead");
+                    return None;
+                }
+                match PandocServer::start(&self.program_name).await {
+                    Ok(server) => {
+                        info!("Converting through the pandoc server at {}", server.addr());
+                        Some(server)
+                    }
+                    Err(e) => {
+                        warn!("{e}, starting a pandoc per file instead");
+                        None
+                    }
+                }
+            }
+        });
+        server.await.as_ref()
     }
-}
 
-#[async_trait::async_trait]
-impl Converter for PandocConverter {
-    async fn convert<P>(&self, input: P, output: P) -> Result<()>
-    where
-        P: AsRef<Path> + Send + Sync,
-    {
-        let input = input.as_ref();
-        let output = output.as_ref();
+    /// Runs pandoc with a `--list-*-formats` style `flag` and parses its output.
+    async fn list_formats(&self, flag: &str) -> Result<Vec<String>> {
+        let output = tokio::process::Command::new(&self.program_name)
+            .arg(flag)
+            .output()
+            .await?;
 
+        if !output.status.success() {
+            return Err(Error::Generic(format!(
+                "{} {flag} failed: {}",
+                self.program_name.display(),
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(parse_format_list(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Converts `input` to `output`, reading it as the format its extension says
+    /// unless `from` names another.
+    async fn run(&self, input: &Path, output: &Path, from: Option<&str>) -> Result<()> {
         trace!("Converting '{}' to '{}'", input.display(), output.display());
 
-        let media_folder = match PandocConverter::media_folder(&output) {
+        // Formats the server can't convert between, and files it couldn't be reached for, get a pandoc of their own
+        if let Some(server) = self.server().await
+            && let Some((reader, writer)) = PandocServer::formats(input, output, from)
+            && let Some(res) = server.convert(input, output, &reader, &writer).await
+        {
+            return res;
+        }
+
+        let media_folder = match PandocConverter::media_folder(output) {
             Ok(folder) => folder,
             Err(e) => {
                 warn!("Failed to create media folder: {e}");
@@ -87,31 +388,73 @@ impl Converter for PandocConverter {
         // let parent_folder = input.parent().unwrap();
         // let media_folder = parent_folder.join(filename);
 
-        let cmd = tokio::process::Command::new(&self.program_name)
+        // Dropped (and removed) on any early return, so a failed run leaves no half-written output
+        let partial = PartialOutput::new(output);
+
+        let mut command = tokio::process::Command::new(&self.program_name);
+        let cmd = shield_from_ctrl_c(&mut command)
             .arg("--extract-media")
             .arg(media_folder)
             .arg("-s")
+            .args(from.into_iter().flat_map(|format| ["--from", format]))
+            .args(self.options.to_args())
             .arg(input)
             .arg("-o")
-            .arg(output)
+            .arg(partial.path())
+            // A timed-out conversion drops this future, take pandoc down with it
+            .kill_on_drop(true)
             .output()
             .await;
 
-        let output = cmd.map_err(Error::from)?;
+        let cmd_output = cmd.map_err(Error::from)?;
 
-        if !output.status.success() {
+        if !cmd_output.status.success() {
             let mut stderr = String::new();
-            output.stderr.as_slice().read_to_string(&mut stderr)?;
-            return Err(Error::Generic(format!(
-                "Success checker: Failed to convert {} to {output:?}: {stderr}",
-                input.display(),
-            )));
+            cmd_output.stderr.as_slice().read_to_string(&mut stderr)?;
+            return Err(self.options.rejected_reference_doc(Error::ConverterFailed {
+                program: self.name(),
+                input: input.to_path_buf(),
+                code: cmd_output.status.code(),
+                stderr,
+            }));
         }
 
-        Ok(())
+        partial.commit().await
+    }
+}
+
+/// One format per line, blank lines ignored.
+fn parse_format_list(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl Default for PandocConverter {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    async fn check_installed(&self) -> impl Into<bool> {
+#[async_trait::async_trait]
+impl Converter for PandocConverter {
+    /// Pandoc goes by the extension itself, except for aliases (see `dealias`), which are spelled out.
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        let from = input
+            .extension()
+            .and_then(|ext| dealias(&ext.to_string_lossy()));
+        self.run(input, output, from).await
+    }
+
+    async fn convert_as(&self, input: &Path, output: &Path, format: &str) -> Result<()> {
+        self.run(input, output, Some(format)).await
+    }
+
+    async fn check_installed(&self) -> bool {
         let program_name = self.program_name.clone();
         let program_name_c = program_name.clone();
 
@@ -138,7 +481,198 @@ impl Converter for PandocConverter {
     }
 
     #[inline]
-    fn name(&self) -> impl AsRef<str> {
+    fn name(&self) -> String {
         self.program_name.display().to_string()
     }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        self.list_formats("--list-input-formats").await
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        self.list_formats("--list-output-formats").await
+    }
+
+    async fn version(&self) -> Option<String> {
+        version_of(&self.program_name).await
+    }
+
+    /// Fails with `Error::PandocTooOld` if this pandoc is older than `PandocOptions::required_version`.
+    /// One that doesn't say which version it is gets the benefit of the doubt.
+    async fn check_version(&self) -> Result<()> {
+        let Some((required, reason)) = self.options.required_version() else {
+            return Ok(());
+        };
+        let version = self.version().await;
+        let Some(found) = version
+            .as_deref()
+            .and_then(PandocVersion::from_version_line)
+        else {
+            warn!(
+                "Couldn't tell which version {:?} is, not checking it is at least {required}",
+                self.program_name
+            );
+            return Ok(());
+        };
+
+        debug!(
+            "{:?} is pandoc {found}, {required} is needed ({reason})",
+            self.program_name
+        );
+        if found < required {
+            error!(
+                "Pandoc {required} or newer is needed ({reason}), {:?} is {found}",
+                self.program_name
+            );
+            return Err(Error::PandocTooOld {
+                found:    found.to_string(),
+                required: required.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod pandoc_tests {
+    use super::*;
+
+    #[test]
+    fn test_pandoc_options_order() {
+        let options = PandocOptions {
+            defaults:      Some(PathBuf::from("defaults.yaml")),
+            template:      Some(PathBuf::from("t.tpl")),
+            reference_doc: Some(PathBuf::from("ref.docx")),
+            bibliography:  vec![PathBuf::from("refs.bib")],
+            csl:           Some(PathBuf::from("apa.csl")),
+            citeproc:      true,
+            lua_filters:   vec![PathBuf::from("a.lua"), PathBuf::from("b.lua")],
+            extra_args:    vec!["--wrap=none".to_string()],
+            server:        true,
+            min_version:   None,
+            pdf_engine:    Some(PdfEngine::Tectonic),
+        };
+        assert_eq!(
+            options.to_args(),
+            [
+                "--defaults",
+                "defaults.yaml",
+                "--template",
+                "t.tpl",
+                "--reference-doc",
+                "ref.docx",
+                "--bibliography",
+                "refs.bib",
+                "--csl",
+                "apa.csl",
+                "--citeproc",
+                "--lua-filter",
+                "a.lua",
+                "--lua-filter",
+                "b.lua",
+                "--pdf-engine",
+                "tectonic",
+                "--wrap=none"
+            ]
+            .map(OsString::from)
+        );
+        assert!(PandocOptions::default().to_args().is_empty());
+    }
+
+    #[test]
+    fn test_needs_files() {
+        let mut options = PandocOptions {
+            citeproc: true,
+            server: true,
+            ..Default::default()
+        };
+        assert_eq!(options.needs_files(), None);
+
+        options.lua_filters.push(PathBuf::from("a.lua"));
+        assert_eq!(options.needs_files(), Some("--lua-filter"));
+        options.csl = Some(PathBuf::from("apa.csl"));
+        assert_eq!(options.needs_files(), Some("--csl"));
+    }
+
+    #[test]
+    fn test_rejected_reference_doc() {
+        let failed = |stderr: &str| {
+            Error::ConverterFailed {
+                program: "pandoc".to_string(),
+                input:   PathBuf::from("a.md"),
+                code:    Some(1),
+                stderr:  stderr.to_string(),
+            }
+        };
+        let mut options = PandocOptions::default();
+        let stderr = "word/styles.xml missing in reference docx";
+        assert!(matches!(
+            options.rejected_reference_doc(failed(stderr)),
+            Error::ConverterFailed { .. }
+        ));
+
+        options.reference_doc = Some(PathBuf::from("styles/corp.docx"));
+        let rejected = options.rejected_reference_doc(failed(stderr));
+        assert!(matches!(rejected, Error::ReferenceDocRejected { .. }));
+        assert_eq!(rejected.exit_code(), Some(1));
+        assert_eq!(rejected.stderr(), Some(stderr));
+        assert!(matches!(
+            options.rejected_reference_doc(failed(
+                "styles/corp.docx: withBinaryFile: does not exist (No such file or directory)"
+            )),
+            Error::ReferenceDocRejected { .. }
+        ));
+
+        for stderr in [
+            "Unknown input format foo",
+            "[WARNING] Citeproc: citation smith2020 not found\nreference not found",
+            "Undefined cross-reference: fig:corp",
+            "Could not fetch resource corp.docx",
+        ] {
+            assert!(matches!(
+                options.rejected_reference_doc(failed(stderr)),
+                Error::ConverterFailed { .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn test_pandoc_version() {
+        let version = |s: &str| s.parse::<PandocVersion>().unwrap();
+        assert!(version("2.9.1") < version("2.19"));
+        assert_eq!(version("2.19"), version("2.19.0"));
+        assert_eq!(version("3.1.11").to_string(), "3.1.11");
+        assert!("2.x".parse::<PandocVersion>().is_err());
+        assert_eq!(
+            PandocVersion::from_version_line("pandoc.exe 2.19.2"),
+            Some(version("2.19.2"))
+        );
+        assert_eq!(PandocVersion::from_version_line("pandoc"), None);
+
+        let options = PandocOptions {
+            citeproc: true,
+            extra_args: vec!["--embed-resources".into()],
+            min_version: Some(version("2.10")),
+            ..Default::default()
+        };
+        let (required, reason) = options.required_version().unwrap();
+        assert_eq!(
+            (required, reason.as_str()),
+            (version("2.19"), "for --embed-resources")
+        );
+
+        let options = PandocOptions {
+            citeproc: true,
+            min_version: Some(version("3")),
+            ..Default::default()
+        };
+        assert_eq!(options.required_version().unwrap().0, version("3"));
+        assert_eq!(PandocOptions::default().required_version(), None);
+    }
+
+    #[test]
+    fn test_parse_format_list() {
+        let formats = parse_format_list("docx\r\n  markdown \n\nhtml\n");
+        assert_eq!(formats, ["docx", "markdown", "html"]);
+    }
 }
@@ -0,0 +1,316 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::shutdown::shield_from_ctrl_c;
+use crate::prelude::*;
+
+/// How long a started server gets to accept connections before we give up on it.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The server's own per-conversion limit, 2s by default. Ours (`--timeout`) is applied around each request.
+const SERVER_TIMEOUT_SECS: u64 = 24 * 60 * 60;
+
+/// Input formats the server wants base64 encoded.
+const BINARY_INPUTS: [&str; 5] = ["docx", "odt", "epub", "pptx", "xlsx"];
+
+/// The formats the server reads and writes, by extension. Anything else (like pdf, which needs a LaTeX engine
+/// the server won't run) is converted by a pandoc of its own.
+const FORMATS: [(&str, &str); 20] = [
+    ("md", "markdown"),
+    ("markdown", "markdown"),
+    ("html", "html"),
+    ("htm", "html"),
+    ("docx", "docx"),
+    ("odt", "odt"),
+    ("epub", "epub"),
+    ("rst", "rst"),
+    ("tex", "latex"),
+    ("latex", "latex"),
+    ("org", "org"),
+    ("rtf", "rtf"),
+    ("json", "json"),
+    ("ipynb", "ipynb"),
+    ("typ", "typst"),
+    ("adoc", "asciidoc"),
+    ("textile", "textile"),
+    ("txt", "plain"),
+    ("xml", "docbook"),
+    ("dbk", "docbook"),
+];
+
+/// A `pandoc server` started once for the whole run, which converts each file in a request of its own
+/// instead of a pandoc process per file (`--pandoc-server`).
+///
+/// It only listens on localhost, and is killed once dropped.
+#[derive(Debug)]
+pub struct PandocServer {
+    addr:  SocketAddr,
+    /// Set once a request couldn't reach the server, the files are converted by a pandoc per file from then on.
+    gone:  AtomicBool,
+    child: Child,
+}
+
+/// The server's answer to a conversion asking for JSON.
+#[derive(Debug, Deserialize)]
+struct Response {
+    output: Option<String>,
+    #[serde(default)]
+    base64: bool,
+    error:  Option<String>,
+}
+
+impl PandocServer {
+    /// Starts `pandoc server` with `program`, or the `pandoc-server` next to it for pandoc versions before 3.0,
+    /// and waits for it to accept connections.
+    ///
+    /// # Errors
+    /// * If neither starts, e.g. because this pandoc has no server mode
+    pub async fn start(program: &Path) -> Result<Self> {
+        let standalone = program
+            .with_file_name(format!("pandoc-server{}", std::env::consts::EXE_SUFFIX));
+        let mut failures = Vec::new();
+        for (program, args) in [(program, &["server"][..]), (&standalone, &[])] {
+            match Self::start_with(program, args).await {
+                Ok(server) => return Ok(server),
+                Err(e) => failures.push(format!("{}: {e}", program.display())),
+            }
+        }
+        Err(Error::Generic(format!(
+            "Failed to start a pandoc server ({})",
+            failures.join(", ")
+        )))
+    }
+
+    async fn start_with(program: &Path, args: &[&str]) -> std::io::Result<Self> {
+        // Whatever port is free right now, the server takes it over
+        let addr = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?.local_addr()?;
+
+        let mut command = Command::new(program);
+        let mut child = shield_from_ctrl_c(&mut command)
+            .args(args)
+            .arg("--port")
+            .arg(addr.port().to_string())
+            .arg("--timeout")
+            .arg(SERVER_TIMEOUT_SECS.to_string())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let started = tokio::time::Instant::now();
+        while TcpStream::connect(addr).await.is_err() {
+            if let Some(status) = child.try_wait()? {
+                return Err(std::io::Error::other(format!("exited with {status}")));
+            }
+            if started.elapsed() > STARTUP_TIMEOUT {
+                return Err(std::io::Error::other(format!(
+                    "not listening after {}s",
+                    STARTUP_TIMEOUT.as_secs()
+                )));
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        Ok(Self {
+            addr,
+            gone: AtomicBool::new(false),
+            child,
+        })
+    }
+
+    /// Where the server listens.
+    #[inline]
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The formats to read `input` as and write `output` as, `None` if the server can't convert between them.
+    /// `from` names the input format when its extension doesn't.
+    pub fn formats(
+        input: &Path,
+        output: &Path,
+        from: Option<&str>,
+    ) -> Option<(String, String)> {
+        let format_of = |path: &Path| {
+            let ext = path.extension()?.to_string_lossy();
+            FORMATS
+                .iter()
+                .find(|(known, _)| known.eq_ignore_ascii_case(&ext))
+                .map(|&(_, format)| format)
+        };
+
+        let reader = match from {
+            Some(format) => format,
+            // pandoc reads .txt as markdown too, `plain` is only a writer
+            None => {
+                format_of(input).map(|f| if f == "plain" { "markdown" } else { f })?
+            }
+        };
+        Some((reader.to_string(), format_of(output)?.to_string()))
+    }
+
+    /// Converts `input` from `reader` to `output` as `writer` with a single request.
+    ///
+    /// `None` if the server couldn't be reached, which is only logged (once), the file is then up to the caller.
+    pub async fn convert(
+        &self,
+        input: &Path,
+        output: &Path,
+        reader: &str,
+        writer: &str,
+    ) -> Option<Result<()>> {
+        if self.gone.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let content = match tokio::fs::read(input).await {
+            Ok(content) => content,
+            Err(e) => return Some(Err(e.into())),
+        };
+        let text = if BINARY_INPUTS.contains(&reader) {
+            STANDARD.encode(&content)
+        } else {
+            String::from_utf8_lossy(&content).into_owned()
+        };
+        let request = serde_json::json!({
+            "text": text,
+            "from": reader,
+            "to": writer,
+            "standalone": true,
+        });
+
+        let (status, body) = match self.post(&request.to_string()).await {
+            Ok(answer) => answer,
+            Err(e) => {
+                if !self.gone.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        "The pandoc server at {} can't be reached, starting a pandoc per file instead: {e}",
+                        self.addr
+                    );
+                }
+                return None;
+            }
+        };
+
+        Some(self.write(input, output, status, &body).await)
+    }
+
+    /// Writes the converted document in the server's answer to `output`, or fails with the error it reported.
+    async fn write(
+        &self,
+        input: &Path,
+        output: &Path,
+        status: u16,
+        body: &[u8],
+    ) -> Result<()> {
+        let failed = |message: &str| {
+            Error::PandocConversionError(format!("{input:?}: {}", message.trim()))
+        };
+
+        let document = match serde_json::from_slice(body).ok() {
+            Some(Response {
+                output: Some(document),
+                base64,
+                error: None,
+            }) if (200..300).contains(&status) => {
+                if base64 {
+                    STANDARD
+                        .decode(document.trim())
+                        .map_err(|e| failed(&format!("invalid base64 output: {e}")))?
+                } else {
+                    document.into_bytes()
+                }
+            }
+            Some(Response { error: Some(e), .. }) => return Err(failed(&e)),
+            // Not JSON, like the plain text errors of older servers
+            _ => return Err(failed(&String::from_utf8_lossy(body))),
+        };
+
+        // Removed again if the write fails, or the conversion is dropped half way
+        let partial = PartialOutput::new(output);
+        tokio::fs::write(partial.path(), document).await?;
+        partial.commit().await
+    }
+
+    /// POSTs the JSON `body` and returns the status and body of the response.
+    ///
+    /// HTTP/1.0, which keeps the response from being chunked: it's over when the server closes the connection.
+    async fn post(&self, body: &str) -> std::io::Result<(u16, Vec<u8>)> {
+        let mut stream = TcpStream::connect(self.addr).await?;
+        let head = format!(
+            "POST / HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\nAccept: application/json\r\nContent-Length: {}\r\n\r\n",
+            self.addr,
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).await?;
+        stream.write_all(body.as_bytes()).await?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        parse_response(&response).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "not an HTTP response")
+        })
+    }
+}
+
+impl Drop for PandocServer {
+    fn drop(&mut self) {
+        if let Err(e) = self.child.start_kill() {
+            debug!("Failed to stop the pandoc server: {e}");
+        }
+    }
+}
+
+/// The status code and body of a raw HTTP response.
+fn parse_response(response: &[u8]) -> Option<(u16, Vec<u8>)> {
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = std::str::from_utf8(&response[..split]).ok()?;
+    let status = head.split_whitespace().nth(1)?.parse().ok()?;
+    Some((status, response[split + 4..].to_vec()))
+}
+
+#[cfg(test)]
+mod pandoc_server_tests {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+        let formats = |input: &str, output: &str, from| {
+            PandocServer::formats(Path::new(input), Path::new(output), from)
+        };
+        assert_eq!(
+            formats("a.DOCX", "a.md", None),
+            Some(("docx".to_string(), "markdown".to_string()))
+        );
+        assert_eq!(
+            formats("notes.txt", "notes.txt", None),
+            Some(("markdown".to_string(), "plain".to_string()))
+        );
+        assert_eq!(
+            formats("page.docx", "page.html", Some("html")),
+            Some(("html".to_string(), "html".to_string()))
+        );
+        assert_eq!(formats("a.md", "a.pdf", None), None);
+        assert_eq!(formats("a.doc", "a.md", None), None);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let response =
+            b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 5\r\n\r\noops\n";
+        assert_eq!(parse_response(response), Some((500, b"oops\n".to_vec())));
+        assert_eq!(parse_response(b"HTTP/1.1 200 OK\r\n"), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use crate::prelude::*;
+
+/// A temporary stand-in for an output file that a converter writes to instead,
+/// moved onto the output by `commit` only once the conversion succeeded.
+/// Until then the output is either missing or its previous, complete version, never a truncated one
+/// that a later run would skip because it "already exists".
+///
+/// Dropping it uncommitted (a failed conversion, or a timeout dropping the conversion) removes
+/// whatever was written to it.
+#[derive(Debug)]
+pub struct PartialOutput {
+    path:        PathBuf,
+    output:      PathBuf,
+    /// The folder `path` was put in, for converters that only take an output folder.
+    staging_dir: Option<PathBuf>,
+    committed:   bool,
+}
+
+impl PartialOutput {
+    /// Writes go to a hidden `.<stem>.partial.<ext>` next to `output`,
+    /// which keeps the extension converters pick the target format from.
+    pub fn new(output: &Path) -> Self {
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match output.extension() {
+            Some(ext) => format!(".{stem}.partial.{}", ext.to_string_lossy()),
+            None => format!(".{stem}.partial"),
+        };
+
+        Self {
+            path:        output.with_file_name(name),
+            output:      output.to_path_buf(),
+            staging_dir: None,
+            committed:   false,
+        }
+    }
+
+    /// Writes go to `file_name` in a hidden `.<output file name>.partial` folder next to `output`,
+    /// for converters that pick the output's name themselves and only take its folder.
+    ///
+    /// # Errors
+    /// * If the folder can't be created
+    pub async fn in_staging_dir(output: &Path, file_name: &Path) -> Result<Self> {
+        let mut dir_name = output.file_name().unwrap_or_default().to_os_string();
+        dir_name.push(".partial");
+        let mut hidden = std::ffi::OsString::from(".");
+        hidden.push(dir_name);
+        let staging_dir = output.with_file_name(hidden);
+        tokio::fs::create_dir_all(&staging_dir).await?;
+
+        Ok(Self {
+            path:        staging_dir.join(file_name),
+            output:      output.to_path_buf(),
+            staging_dir: Some(staging_dir),
+            committed:   false,
+        })
+    }
+
+    /// Where the converter should write.
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Moves what was written onto the output, replacing any previous version.
+    ///
+    /// # Errors
+    /// * `Error::FailedRenameFile` - If the rename fails
+    pub async fn commit(mut self) -> Result<()> {
+        tokio::fs::rename(&self.path, &self.output)
+            .await
+            .map_err(|e| {
+                error!("Failed to rename: {e:?}");
+                Error::FailedRenameFile(self.path.clone())
+            })?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+/// Whether `name` is that of a partial output, or staging folder (see `PartialOutput::new`, `in_staging_dir`).
+/// Outside a running conversion, only a run that crashed or was killed leaves those behind.
+pub fn is_partial_name(name: &OsStr) -> bool {
+    let name = name.to_string_lossy();
+    name.starts_with('.') && (name.ends_with(".partial") || name.contains(".partial."))
+}
+
+impl Drop for PartialOutput {
+    fn drop(&mut self) {
+        // Also runs when a timed-out conversion is dropped, so it can't be async
+        if let Some(ref dir) = self.staging_dir {
+            let _ = std::fs::remove_dir_all(dir);
+        } else if !self.committed && self.path.exists() {
+            trace!("Removing partial output {:?}", self.path);
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod partial_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_commit_and_discard() {
+        let tmp =
+            std::env::temp_dir().join(format!("dcc_partial_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp).unwrap();
+        let output = tmp.join("report.md");
+
+        let partial = PartialOutput::new(&output);
+        assert_eq!(partial.path(), tmp.join(".report.partial.md"));
+        std::fs::write(partial.path(), "half").unwrap();
+        drop(partial);
+        assert!(!tmp.join(".report.partial.md").exists());
+        assert!(!output.exists());
+
+        let partial = PartialOutput::in_staging_dir(&output, Path::new("input.md"))
+            .await
+            .unwrap();
+        std::fs::write(partial.path(), "done").unwrap();
+        partial.commit().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "done");
+        assert!(!tmp.join(".report.md.partial").exists());
+
+        assert!(is_partial_name(OsStr::new(".report.partial.md")));
+        assert!(is_partial_name(OsStr::new(".report.md.partial")));
+        assert!(!is_partial_name(OsStr::new("report.partial.md")));
+        assert!(!is_partial_name(OsStr::new(".gitignore")));
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+
+use crate::conversion::{
+    Engine,
+    LIBREOFFICE_ONLY_INPUTS,
+    asciidoctor,
+    calibre,
+    canonical_ext,
+    find_program,
+    image,
+    typst,
+};
+use crate::prelude::*;
+
+/// The program pandoc turns a document into a PDF with (`--pdf-engine`).
+///
+/// Pandoc goes through LaTeX (`pdflatex` unless told otherwise), which most machines don't have,
+/// so a run converting to pdf looks for one that's installed up front, see `PdfEngine::find`.
+#[derive(Debug, clap::ValueEnum, Clone, Copy, PartialEq, Eq)]
+#[clap(name = "PdfEngine", rename_all = "lower")]
+pub enum PdfEngine {
+    #[value(name = "pdflatex")]
+    Pdflatex,
+    #[value(name = "xelatex")]
+    Xelatex,
+    #[value(name = "lualatex")]
+    Lualatex,
+    /// A self-contained LaTeX engine, fetching the packages it needs on demand.
+    #[value(name = "tectonic")]
+    Tectonic,
+    /// Goes through pandoc's typst output.
+    #[value(name = "typst")]
+    Typst,
+    /// Goes through HTML, styled with CSS rather than a LaTeX template.
+    #[value(name = "weasyprint")]
+    Weasyprint,
+    /// Goes through HTML, styled with CSS rather than a LaTeX template.
+    #[value(name = "wkhtmltopdf")]
+    Wkhtmltopdf,
+}
+
+impl PdfEngine {
+    /// Every engine, in the order `find` tries them: pandoc's own default first.
+    pub const ALL: [PdfEngine; 7] = [
+        PdfEngine::Pdflatex,
+        PdfEngine::Xelatex,
+        PdfEngine::Lualatex,
+        PdfEngine::Tectonic,
+        PdfEngine::Typst,
+        PdfEngine::Weasyprint,
+        PdfEngine::Wkhtmltopdf,
+    ];
+
+    /// The program's name, which is also what pandoc's `--pdf-engine` takes.
+    pub fn program(self) -> &'static str {
+        match self {
+            PdfEngine::Pdflatex => "pdflatex",
+            PdfEngine::Xelatex => "xelatex",
+            PdfEngine::Lualatex => "lualatex",
+            PdfEngine::Tectonic => "tectonic",
+            PdfEngine::Typst => "typst",
+            PdfEngine::Weasyprint => "weasyprint",
+            PdfEngine::Wkhtmltopdf => "wkhtmltopdf",
+        }
+    }
+
+    /// Where the engine is in `PATH`, `None` if it isn't installed.
+    pub fn installed(self) -> Option<PathBuf> {
+        find_program(self.program())
+    }
+
+    /// `preferred` if it's installed, or without one the first of `ALL` that is.
+    ///
+    /// # Errors
+    /// * `Error::NoPdfEngine` - If `preferred` isn't installed, or without one none is,
+    ///   instead of every file failing with pandoc's complaint about a missing `pdflatex`
+    pub fn find(preferred: Option<PdfEngine>) -> Result<PdfEngine> {
+        if let Some(engine) = preferred {
+            return match engine.installed() {
+                Some(path) => {
+                    debug!("Converting to pdf with {path:?}");
+                    Ok(engine)
+                }
+                None => {
+                    Err(Error::NoPdfEngine(format!(
+                        "{} (--pdf-engine) isn't installed",
+                        engine.program()
+                    )))
+                }
+            };
+        }
+
+        for engine in PdfEngine::ALL {
+            if let Some(path) = engine.installed() {
+                info!("Converting to pdf with {path:?}");
+                return Ok(engine);
+            }
+        }
+        let tried: Vec<_> = PdfEngine::ALL
+            .iter()
+            .map(|engine| engine.program())
+            .collect();
+        Err(Error::NoPdfEngine(format!("none of {} is installed", tried.join(", "))))
+    }
+}
+
+/// Whether pandoc will be writing pdf files in a run with `engine` from `input_exts` to `output_exts`:
+/// not if LibreOffice, typst, Asciidoctor, Calibre or the image converter convert everything going to pdf, which they do on their own.
+pub fn needs_engine(
+    engine: Engine,
+    input_exts: &[String],
+    output_exts: &[String],
+) -> bool {
+    if !output_exts.iter().any(|ext| canonical_ext(ext) == "pdf") {
+        return false;
+    }
+    match engine {
+        Engine::Pandoc => true,
+        Engine::LibreOffice => false,
+        Engine::Auto => {
+            input_exts.iter().any(|ext| {
+                let ext = canonical_ext(ext);
+                !LIBREOFFICE_ONLY_INPUTS.contains(&ext.as_str())
+                    && !typst::INPUT_FORMATS.contains(&ext.as_str())
+                    && !asciidoctor::INPUT_FORMATS.contains(&ext.as_str())
+                    && !calibre::INPUT_FORMATS.contains(&ext.as_str())
+                    && !image::INPUT_FORMATS.contains(&ext.as_str())
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod pdf_tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_engine() {
+        let exts =
+            |exts: &[&str]| exts.iter().map(|ext| ext.to_string()).collect::<Vec<_>>();
+        let needs = |engine, inputs: &[&str], outputs: &[&str]| {
+            needs_engine(engine, &exts(inputs), &exts(outputs))
+        };
+        assert!(needs(Engine::Pandoc, &["md"], &["html", ".PDF"]));
+        assert!(!needs(Engine::Pandoc, &["md"], &["html"]));
+        assert!(!needs(Engine::LibreOffice, &["docx"], &["pdf"]));
+        assert!(!needs(Engine::Auto, &["doc", "xlsx"], &["pdf"]));
+        assert!(needs(Engine::Auto, &["doc", "docx"], &["pdf"]));
+        assert!(!needs(Engine::Auto, &["typ", "adoc", "azw3", "tiff"], &["pdf"]));
+        assert!(needs(Engine::Pandoc, &["typ"], &["pdf"]));
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::conversion::registry::ConverterRegistry;
+use crate::conversion::{convert_single, converter_for, remove_dot};
+use crate::prelude::*;
+
+/// What the temporary folder of a `--pipe` run is named, along with its process id, see `clean`.
+pub(crate) const WORK_DIR_PREFIX: &str = "dcc_pipe_";
+
+/// Converts a single document to `output_ext` and writes the result to stdout (`--pipe`).
+///
+/// The document is read from `input`, whose extension says what it is, or from stdin as `input_ext`.
+/// The converters only work on files, so stdin and the output go through a temporary folder,
+/// which is removed again whatever happens (media pandoc extracts along with it).
+///
+/// # Errors
+/// * If stdin can't be read, the conversion fails, or the result can't be written to stdout
+pub async fn pipe(
+    registry: &ConverterRegistry,
+    input: Option<&Path>,
+    input_ext: Option<&str>,
+    output_ext: &str,
+    timeout: Option<u64>,
+    retries: usize,
+) -> Result<()> {
+    let work_dir =
+        std::env::temp_dir().join(format!("{WORK_DIR_PREFIX}{}", std::process::id()));
+    tokio::fs::create_dir_all(&work_dir).await?;
+
+    let res = convert_to_stdout(
+        registry, &work_dir, input, input_ext, output_ext, timeout, retries,
+    )
+    .await;
+
+    if let Err(e) = tokio::fs::remove_dir_all(&work_dir).await {
+        debug!("Failed to remove the temporary folder {work_dir:?}: {e}");
+    }
+    res
+}
+
+async fn convert_to_stdout(
+    registry: &ConverterRegistry,
+    work_dir: &Path,
+    input: Option<&Path>,
+    input_ext: Option<&str>,
+    output_ext: &str,
+    timeout: Option<u64>,
+    retries: usize,
+) -> Result<()> {
+    let input = match input {
+        Some(input) => input.to_path_buf(),
+        None => stdin_to_file(work_dir, input_ext.unwrap_or_default()).await?,
+    };
+    let output = work_dir
+        .join("output")
+        .with_extension(remove_dot(output_ext));
+
+    let converter = converter_for(registry, &input, None, output_ext)?;
+    debug!("Converting {input:?} to '{output_ext}' for stdout with {}", converter.name());
+    convert_single(converter, input, output.clone(), None, timeout, retries, false)
+        .await?;
+
+    let converted = tokio::fs::read(&output).await?;
+    let mut stdout = tokio::io::stdout();
+    stdout.write_all(&converted).await?;
+    stdout.flush().await?;
+    Ok(())
+}
+
+/// Saves stdin as `stdin.<ext>` in `work_dir`, so the converter can tell its format by its name.
+async fn stdin_to_file(work_dir: &Path, ext: &str) -> Result<PathBuf> {
+    let mut contents = Vec::new();
+    tokio::io::stdin().read_to_end(&mut contents).await?;
+
+    let path = work_dir.join("stdin").with_extension(remove_dot(ext));
+    tokio::fs::write(&path, contents).await?;
+    Ok(path)
+}
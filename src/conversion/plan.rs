@@ -0,0 +1,223 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::conversion::registry::ConverterRegistry;
+use crate::conversion::sniff::Detected;
+use crate::conversion::{
+    ConvertOptions,
+    OnExists,
+    converter_for,
+    detect_content,
+    discover,
+    fix_mangled_name,
+    format_output_dir,
+    is_up_to_date,
+    list_entries,
+    list_relative,
+    needs_fixing,
+    numbered_free_name,
+    output_path,
+    relative_to,
+};
+use crate::prelude::*;
+
+/// What a run would do with a single file, as worked out for `--dry-run` and `list`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedConversion {
+    pub input:     PathBuf,
+    /// Where `input` would be renamed to before converting, if it has dangerous characters
+    /// and `--sanitize-names` is set.
+    pub renamed:   Option<PathBuf>,
+    pub output:    PathBuf,
+    /// The name of the converter `input` is routed to.
+    pub converter: String,
+    /// The output already exists (and `--on-exists` keeps it), so the file would be skipped.
+    pub skipped:   bool,
+}
+
+impl fmt::Display for PlannedConversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.input.display())?;
+        if let Some(ref renamed) = self.renamed {
+            write!(f, " (renamed to {})", renamed.display())?;
+        }
+        write!(f, " -> {} [{}]", self.output.display(), self.converter)?;
+        if self.skipped {
+            write!(f, " (output exists, skipped)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything planning a file needs besides the file itself.
+pub struct Planner<'a> {
+    pub registry:    &'a ConverterRegistry,
+    pub target_exts: &'a [String],
+    pub output_dir:  Option<&'a Path>,
+    pub options:     &'a ConvertOptions,
+}
+
+impl Planner<'_> {
+    /// Plans every file with one of the extensions `exts` below `dir`, the way `find_and_convert` would,
+    /// or `find_by_ext` if `rename_folders` is set.
+    ///
+    /// Nothing is renamed, created or converted.
+    ///
+    /// # Errors
+    /// * If a file has no converter, or its output name can't be worked out
+    pub async fn plan_dir<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        exts: &[String],
+        rename_folders: bool,
+    ) -> Result<Vec<PlannedConversion>> {
+        let root = dir.as_ref().to_path_buf();
+        let found = discover(&root, exts, self.options).await?;
+
+        let name_rules = &self.options.name_rules;
+        let mut planned = Vec::with_capacity(found.len());
+        for (idx, input) in found.into_iter().enumerate() {
+            let relative = relative_to(&root, &input)?.to_path_buf();
+
+            let (renamed, relative) = if self.options.renames.is_none() {
+                (None, relative)
+            } else if rename_folders {
+                let fixed = PathBuf::from(fix_mangled_name(
+                    relative.to_string_lossy(),
+                    name_rules,
+                ));
+                ((fixed != relative).then(|| root.join(&fixed)), fixed)
+            } else {
+                self.rename_file_name(&input, relative)
+            };
+
+            planned.extend(self.plan(input, renamed, &relative, idx + 1)?);
+        }
+
+        Ok(planned)
+    }
+
+    /// Plans every file named in `list`, the way `read_input_list` and `convert_files` would.
+    ///
+    /// # Errors
+    /// * If the list can't be read, a file has no converter, or its output name can't be worked out
+    pub async fn plan_list<P: AsRef<Path>>(
+        &self,
+        list: P,
+        exts: &[String],
+    ) -> Result<Vec<PlannedConversion>> {
+        let listed = list_entries(list, exts, self.options).await?;
+
+        let mut planned = Vec::with_capacity(listed.len());
+        for (idx, input) in listed.into_iter().enumerate() {
+            let (renamed, relative) =
+                self.rename_file_name(&input, list_relative(&input));
+            planned.extend(self.plan(input, renamed, &relative, idx + 1)?);
+        }
+
+        Ok(planned)
+    }
+
+    /// Where `input` would be renamed to if its file name needs fixing,
+    /// along with its `relative` path after the rename.
+    fn rename_file_name(
+        &self,
+        input: &Path,
+        relative: PathBuf,
+    ) -> (Option<PathBuf>, PathBuf) {
+        let name_rules = &self.options.name_rules;
+        match input.file_name() {
+            Some(name)
+                if self.options.renames.is_some() && needs_fixing(name, name_rules) =>
+            {
+                let fixed = fix_mangled_name(name.to_string_lossy(), name_rules);
+                (Some(input.with_file_name(&fixed)), relative.with_file_name(fixed))
+            }
+            _ => (None, relative),
+        }
+    }
+
+    /// One planned conversion per target format, none for an input `--detect-content` would skip.
+    fn plan(
+        &self,
+        input: PathBuf,
+        renamed: Option<PathBuf>,
+        relative: &Path,
+        n: usize,
+    ) -> Result<Vec<PlannedConversion>> {
+        let converted = renamed.as_deref().unwrap_or(&input);
+        let several = self.target_exts.len() > 1;
+        let detected = if self.options.detect_content {
+            detect_content(&input)
+        } else {
+            Detected::AsNamed
+        };
+        let format = match detected {
+            Detected::Empty => return Ok(Vec::new()),
+            Detected::Actually(format) => Some(format),
+            Detected::AsNamed | Detected::Mismatch => None,
+        };
+
+        let mut planned = Vec::with_capacity(self.target_exts.len());
+        for target_ext in self.target_exts {
+            let converter = converter_for(self.registry, converted, format, target_ext)?;
+            let output_dir = format_output_dir(self.output_dir, target_ext, several);
+            let output = output_path(
+                converted,
+                relative,
+                target_ext,
+                output_dir.as_deref(),
+                n,
+                self.options,
+            )?;
+            let (output, skipped) = match self.options.on_exists {
+                _ if !output.exists() => (output, false),
+                OnExists::Skip => (output, true),
+                OnExists::Overwrite => (output, false),
+                OnExists::Rename => (numbered_free_name(&output), false),
+                OnExists::IfNewer => {
+                    let skipped = is_up_to_date(converted, &output);
+                    (output, skipped)
+                }
+            };
+
+            planned.push(PlannedConversion {
+                input: input.clone(),
+                renamed: renamed.clone(),
+                skipped,
+                converter: converter.name(),
+                output,
+            });
+        }
+        Ok(planned)
+    }
+}
+
+#[cfg(test)]
+mod plan_tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let mut planned = PlannedConversion {
+            input:     PathBuf::from("in/a~b.docx"),
+            renamed:   Some(PathBuf::from("in/a_b.docx")),
+            output:    PathBuf::from("out/a_b.md"),
+            converter: "pandoc".into(),
+            skipped:   false,
+        };
+        assert_eq!(
+            planned.to_string(),
+            "in/a~b.docx (renamed to in/a_b.docx) -> out/a_b.md [pandoc]"
+        );
+
+        planned.renamed = None;
+        planned.skipped = true;
+        assert_eq!(
+            planned.to_string(),
+            "in/a~b.docx -> out/a_b.md [pandoc] (output exists, skipped)"
+        );
+    }
+}
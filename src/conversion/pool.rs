@@ -0,0 +1,307 @@
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::FutureExt;
+use indicatif::ProgressBar;
+use tokio::sync::{Mutex, mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+
+use crate::conversion::checkpoint::Checkpoint;
+use crate::conversion::events::Event;
+use crate::conversion::fingerprint::{Fingerprint, Fingerprints};
+use crate::conversion::sniff::Detected;
+use crate::conversion::{ConvertOptions, Converter, convert_single};
+use crate::prelude::*;
+use crate::progress;
+
+/// What a conversion came to: how many retries it needed, and how long it took once a worker had it.
+pub(crate) type Outcome = (Result<usize>, Duration);
+
+/// A file's conversion to one of the target formats, queued for a `WorkerPool`.
+pub(crate) struct Job {
+    pub input:     PathBuf,
+    pub output:    PathBuf,
+    pub converter: Arc<dyn Converter>,
+    /// The name of `converter`.
+    pub engine:    String,
+    /// Converts the input as another format, or fails it outright on a `Detected::Mismatch`.
+    pub detected:  Detected,
+    /// The input's fingerprint from before it was converted, recorded once it is.
+    pub print:     Option<Fingerprint>,
+}
+
+/// What every worker of a batch shares.
+pub(crate) struct Shared {
+    pub options:      ConvertOptions,
+    pub progress:     ProgressBar,
+    pub checkpoint:   Option<Arc<Checkpoint>>,
+    pub fingerprints: Option<Arc<Fingerprints>>,
+    /// See `Batch::cancel`.
+    pub cancel:       CancellationToken,
+}
+
+type Queue = mpsc::Receiver<(Job, oneshot::Sender<Outcome>)>;
+
+/// A fixed number of long-lived workers (`--jobs`), each converting one queued `Job` at a time.
+///
+/// The queue is bounded too, so whoever submits jobs faster than they're converted is held up,
+/// rather than piling up a task per file. The workers finish once the pool is dropped and the queue is empty.
+pub(crate) struct WorkerPool {
+    jobs: mpsc::Sender<(Job, oneshot::Sender<Outcome>)>,
+}
+
+impl WorkerPool {
+    /// Starts `shared.options.jobs` workers.
+    pub fn start(shared: Shared) -> Self {
+        let size = shared.options.jobs.max(1);
+        let (jobs, queue) = mpsc::channel(shared.options.channel_capacity());
+        let (shared, queue) = (Arc::new(shared), Arc::new(Mutex::new(queue)));
+
+        for id in 1..=size {
+            let worker = Worker {
+                id,
+                shared: Arc::clone(&shared),
+                queue: Arc::clone(&queue),
+            };
+            tokio::spawn(worker.run());
+        }
+        Self { jobs }
+    }
+
+    /// Queues `job` once there's room, the receiver yields its `Outcome`.
+    pub async fn submit(&self, job: Job) -> oneshot::Receiver<Outcome> {
+        let (done, outcome) = oneshot::channel();
+        // The workers only go away with the pool, the receiver reports a job they dropped anyway
+        let _ = self.jobs.send((job, done)).await;
+        outcome
+    }
+}
+
+/// One of the workers of a `WorkerPool`.
+struct Worker {
+    /// 1-based, for the log.
+    id:     usize,
+    shared: Arc<Shared>,
+    queue:  Arc<Mutex<Queue>>,
+}
+
+impl Worker {
+    async fn run(self) {
+        loop {
+            // Only held while waiting, the next worker gets its turn as soon as this one has a job
+            let next = self.queue.lock().await.recv().await;
+            let Some((job, done)) = next else {
+                break;
+            };
+            trace!("Worker {} converting {:?}", self.id, job.input);
+
+            let input = job.input.clone();
+            // A panicking converter only fails its own file, the worker carries on with the next
+            let outcome = AssertUnwindSafe(self.convert(job))
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|_| {
+                    error!("Converting {input:?} panicked");
+                    (
+                        Err(Error::Generic(format!("Converting {input:?} panicked"))),
+                        Duration::ZERO,
+                    )
+                });
+            // Nobody waits for the outcome if the batch bailed out early
+            let _ = done.send(outcome);
+        }
+        trace!("Worker {} done", self.id);
+    }
+
+    /// Converts `job` once the run lets it start, unless the batch is cancelled first.
+    async fn convert(&self, job: Job) -> Outcome {
+        let shared = &*self.shared;
+        // Dropping `converting` mid-conversion kills the converter's process
+        let (res, took) = tokio::select! {
+            () = shared.cancel.cancelled() => return (Err(Error::Cancelled), Duration::ZERO),
+            converted = self.converting(&job) => converted,
+        };
+        shared.progress.inc(1);
+        // Conversions a stopping run didn't start aren't failures
+        if shared.options.fail_fast
+            && res.as_ref().is_err_and(|e| !matches!(e, Error::Cancelled))
+        {
+            shared.cancel.cancel();
+        }
+
+        if res.is_ok() {
+            let extras = job.converter.extra_outputs(&job.output);
+            for output in std::iter::once(&job.output).chain(&extras) {
+                if let Some(ref checkpoint) = shared.checkpoint
+                    && let Err(e) = checkpoint.record(&job.input, output)
+                {
+                    warn!("Failed to record {:?} in the checkpoint: {e}", job.input);
+                }
+                if let (Some(fingerprints), Some(print)) =
+                    (&shared.fingerprints, job.print)
+                    && let Err(e) = fingerprints.record(&job.input, output, print)
+                {
+                    warn!("Failed to record the fingerprint of {:?}: {e}", job.input);
+                }
+            }
+        }
+        (res, took)
+    }
+
+    async fn converting(&self, job: &Job) -> Outcome {
+        let options = &self.shared.options;
+        let (metrics, dashboard) = (&options.metrics, &options.dashboard);
+
+        let mut started = Instant::now();
+        let res = if job.detected == Detected::Mismatch {
+            if let Some(metrics) = metrics {
+                metrics.dequeued();
+                metrics.failed();
+            }
+            if let Some(dashboard) = dashboard {
+                dashboard.failed();
+            }
+            Err(Error::ContentMismatch(job.input.clone()))
+        } else {
+            // Held here while the run is paused, and not started at all once it's stopping
+            let admitted = match dashboard {
+                Some(dashboard) => dashboard.admit().await,
+                None => !options.shutdown.is_stopping(),
+            };
+            if !admitted {
+                if let Some(metrics) = metrics {
+                    metrics.dequeued();
+                }
+                return (Err(Error::Cancelled), Duration::ZERO);
+            }
+            progress::set_current(&self.shared.progress, &job.input);
+            // The time spent in the queue isn't the conversion's
+            started = Instant::now();
+            if let Some(ref events) = options.events {
+                events.emit(&Event::Started {
+                    input:  &job.input,
+                    output: &job.output,
+                    engine: &job.engine,
+                });
+            }
+            if let Some(metrics) = metrics {
+                metrics.dequeued();
+                metrics.started();
+            }
+            let lane = dashboard
+                .as_ref()
+                .map(|dashboard| dashboard.started(&job.input, &job.engine));
+            let format = match job.detected {
+                Detected::Actually(format) => Some(format),
+                _ => None,
+            };
+            let converting = convert_single(
+                Arc::clone(&job.converter),
+                job.input.clone(),
+                job.output.clone(),
+                format,
+                options.timeout,
+                options.retries,
+                options.preserve_metadata,
+            );
+            let res = match lane {
+                Some(ref lane) => {
+                    tokio::select! {
+                        () = lane.skipped() => Err(Error::Skipped(job.input.clone())),
+                        res = converting => res,
+                    }
+                }
+                None => converting.await,
+            };
+            if let Some(lane) = lane {
+                lane.finished(&res);
+            }
+            if let Some(metrics) = metrics {
+                metrics.finished(&job.input, &res, started.elapsed());
+            }
+            res
+        };
+        let took = started.elapsed();
+        if let Some(ref events) = options.events {
+            events.finished(&job.input, &job.output, &job.engine, &res, took);
+        }
+        (res, took)
+    }
+}
+
+#[cfg(test)]
+mod pool_tests {
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Counts the conversions running at once, and fails inputs named `fail`.
+    #[derive(Default)]
+    struct Counting {
+        running: AtomicUsize,
+        most:    AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl Converter for Counting {
+        async fn convert(&self, input: &Path, _output: &Path) -> Result<()> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            if input.ends_with("fail") {
+                return Err(Error::Generic("failed".into()));
+            }
+            Ok(())
+        }
+
+        async fn check_installed(&self) -> bool {
+            true
+        }
+
+        fn name(&self) -> String {
+            "counting".into()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool() {
+        let converter = Arc::new(Counting::default());
+        let options = ConvertOptions {
+            jobs: 2,
+            ..Default::default()
+        };
+        let pool = WorkerPool::start(Shared {
+            options,
+            progress: ProgressBar::hidden(),
+            checkpoint: None,
+            fingerprints: None,
+            cancel: CancellationToken::new(),
+        });
+
+        let mut outcomes = Vec::new();
+        for name in ["a", "fail", "b", "c", "d"] {
+            let job = Job {
+                input:     PathBuf::from(name),
+                output:    PathBuf::from(format!("{name}.out")),
+                converter: Arc::clone(&converter) as Arc<dyn Converter>,
+                engine:    "counting".into(),
+                detected:  Detected::AsNamed,
+                print:     None,
+            };
+            outcomes.push(pool.submit(job).await);
+        }
+        drop(pool);
+
+        let mut failed = 0;
+        for outcome in outcomes {
+            let (res, _) = outcome.await.unwrap();
+            failed += usize::from(res.is_err());
+        }
+        assert_eq!(failed, 1);
+        assert_eq!(converter.most.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+
+use crate::conversion::Converter;
+use crate::conversion::ooxml::{Package, attribute, prefixed_attribute, push_ref};
+use crate::conversion::pandoc::PandocConverter;
+use crate::conversion::partial::PartialOutput;
+use crate::prelude::*;
+
+/// What `Engine::Auto` routes to the `PptxConverter`: decks going to any of `OUTPUT_FORMATS`.
+/// LibreOffice still converts them to everything else (pdf, html and so on).
+pub(crate) const INPUT_FORMATS: [&str; 2] = ["pptx", "ppsx"];
+
+/// What the decks are written as, by canonical extension.
+pub(crate) const OUTPUT_FORMATS: [&str; 1] = ["markdown"];
+
+/// What the `PptxConverter` reports as its name and version, there's no program to ask.
+const NAME: &str = "pptx-markdown";
+const VERSION: &str = "built-in";
+
+/// Where a deck lists its slides, in order.
+const PRESENTATION_XML: &str = "ppt/presentation.xml";
+
+/// Converts PowerPoint decks (`.pptx`) to markdown in-process, a file per deck with a `##` heading per slide
+/// (its title, or `Slide <n>` without one), the text of its content placeholders as bullets (nested by level)
+/// and of its other text boxes as paragraphs.
+///
+/// Pictures are extracted into the output's media folder like pandoc's `--extract-media`
+/// (`<stem>/media/`, see `PandocConverter::media_folder`) and linked where they are on the slide.
+/// Speaker notes, charts and SmartArt aren't read.
+#[derive(Debug, Default)]
+pub struct PptxConverter;
+
+impl PptxConverter {
+    /// Create a new `PptxConverter`.
+    #[inline]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// What a slide holds, in the order it's on the slide.
+#[derive(Debug, PartialEq)]
+enum Block {
+    /// A bullet, nested `level` deep (0 for the outermost).
+    Bullet {
+        level: usize,
+        text:  String,
+    },
+    Paragraph(String),
+    /// A picture, by its part in the package.
+    Picture {
+        alt:  String,
+        part: String,
+    },
+}
+
+/// What `read_slide` found on a slide.
+#[derive(Debug, Default, PartialEq)]
+struct Slide {
+    title:  Option<String>,
+    blocks: Vec<Block>,
+}
+
+/// What kind of shape the text being read is in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum ShapeKind {
+    Title,
+    /// A content placeholder, whose paragraphs are bullets.
+    Body,
+    /// A date, footer or slide number placeholder, which isn't the slide's content.
+    Chrome,
+    /// A text box, table or the like.
+    #[default]
+    Other,
+}
+
+/// The markdown of the deck at `path`, its pictures written to `media_dir` and linked as `<media_link>/<name>`.
+fn deck_markdown(path: &Path, media_dir: &Path, media_link: &str) -> Result<String> {
+    let unreadable = |reason: String| {
+        Error::UnreadableDocument {
+            path: path.to_path_buf(),
+            reason,
+        }
+    };
+
+    let mut package = Package::open(path)?;
+    let presentation = package.read_string(PRESENTATION_XML)?;
+    let presentation_rels = package.relationships(PRESENTATION_XML)?;
+    let parts = slide_parts(&presentation, &presentation_rels).map_err(unreadable)?;
+
+    let mut markdown = String::new();
+    for (n, part) in parts.iter().enumerate() {
+        let rels = package.relationships(part)?;
+        let slide = read_slide(&package.read_string(part)?, &rels).map_err(unreadable)?;
+
+        let title = slide.title.unwrap_or_else(|| format!("Slide {}", n + 1));
+        markdown.push_str(&format!("## {title}\n\n"));
+        let mut bullets = false;
+        for block in slide.blocks {
+            // A list ends with a blank line, its bullets don't have one between them
+            if bullets && !matches!(block, Block::Bullet { .. }) {
+                markdown.push('\n');
+            }
+            bullets = matches!(block, Block::Bullet { .. });
+            match block {
+                Block::Bullet { level, text } => {
+                    markdown.push_str(&format!("{}- {text}\n", "  ".repeat(level)));
+                }
+                Block::Paragraph(text) => markdown.push_str(&format!("{text}\n\n")),
+                Block::Picture { alt, part } => {
+                    let name = part.rsplit('/').next().unwrap_or(&part);
+                    std::fs::create_dir_all(media_dir).map_err(|e| {
+                        Error::MediaFolderCreationFailed(format!(
+                            "{}: {e}",
+                            media_dir.display()
+                        ))
+                    })?;
+                    std::fs::write(media_dir.join(name), package.read_bytes(&part)?)?;
+                    markdown.push_str(&format!("![{alt}](<{media_link}/{name}>)\n\n"));
+                }
+            }
+        }
+        if bullets {
+            markdown.push('\n');
+        }
+    }
+    Ok(markdown.trim_end_matches('\n').to_string() + "\n")
+}
+
+/// The slides of a deck, by their part in the package, in the order its `presentation.xml` lists them,
+/// resolved through `rels`, its relationships.
+fn slide_parts(
+    xml: &str,
+    rels: &HashMap<String, String>,
+) -> std::result::Result<Vec<String>, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut parts = Vec::new();
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == "sldId" => {
+                if let Some(part) =
+                    prefixed_attribute(&e, "id").and_then(|id| rels.get(&id))
+                {
+                    parts.push(part.clone());
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(parts)
+}
+
+/// The title and content of a slide's XML, its pictures resolved through `rels`, the slide's relationships.
+fn read_slide(
+    xml: &str,
+    rels: &HashMap<String, String>,
+) -> std::result::Result<Slide, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut slide = SlideReader::default();
+
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) => slide.start(&e, rels),
+            Event::Empty(e) => {
+                slide.start(&e, rels);
+                slide.end(e.local_name().as_ref());
+            }
+            Event::End(e) => slide.end(e.local_name().as_ref()),
+            Event::Text(e) if slide.in_text => {
+                slide.paragraph.push_str(&e.xml10_content());
+            }
+            Event::GeneralRef(e) if slide.in_text => push_ref(&mut slide.paragraph, &e)?,
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(slide.slide)
+}
+
+/// Builds up a `Slide` as `read_slide` reads its XML.
+#[derive(Default)]
+struct SlideReader {
+    slide:     Slide,
+    /// What the shape being read is.
+    kind:      ShapeKind,
+    /// The text of the `a:p` being read.
+    paragraph: String,
+    /// How deep the paragraph's bullet is nested.
+    level:     usize,
+    /// The paragraph turns its bullet off (`a:buNone`).
+    no_bullet: bool,
+    /// In an `a:t`, whose text is the slide's.
+    in_text:   bool,
+    /// The description of the picture being read, its alt text.
+    alt:       String,
+}
+
+impl SlideReader {
+    /// Reads the start of `e`, resolving a picture's part through `rels`, the slide's relationships.
+    fn start(&mut self, e: &BytesStart<'_>, rels: &HashMap<String, String>) {
+        match e.local_name().as_ref() {
+            "ph" => {
+                self.kind = match attribute(e, "type").as_deref() {
+                    Some("title" | "ctrTitle") => ShapeKind::Title,
+                    Some("dt" | "ftr" | "sldNum" | "hdr") => ShapeKind::Chrome,
+                    Some("subTitle") => ShapeKind::Other,
+                    _ => ShapeKind::Body,
+                };
+            }
+            "p" => {
+                self.paragraph.clear();
+                self.level = 0;
+                self.no_bullet = false;
+            }
+            "pPr" => {
+                self.level = attribute(e, "lvl")
+                    .and_then(|level| level.parse().ok())
+                    .unwrap_or(0);
+            }
+            "buNone" => self.no_bullet = true,
+            "t" => self.in_text = true,
+            "br" => self.paragraph.push(' '),
+            "cNvPr" => self.alt = attribute(e, "descr").unwrap_or_default(),
+            "blip" => {
+                if let Some(part) =
+                    prefixed_attribute(e, "embed").and_then(|id| rels.get(&id))
+                {
+                    self.slide.blocks.push(Block::Picture {
+                        alt:  std::mem::take(&mut self.alt)
+                            .replace(['[', ']', '\n'], " "),
+                        part: part.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        match name {
+            "t" => self.in_text = false,
+            "sp" => self.kind = ShapeKind::Other,
+            "p" => self.end_paragraph(),
+            _ => {}
+        }
+    }
+
+    fn end_paragraph(&mut self) {
+        let text = std::mem::take(&mut self.paragraph).trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+        let block = match self.kind {
+            ShapeKind::Title => {
+                let title = self.slide.title.get_or_insert_with(String::new);
+                if !title.is_empty() {
+                    title.push(' ');
+                }
+                title.push_str(&text);
+                return;
+            }
+            ShapeKind::Chrome => return,
+            ShapeKind::Body if !self.no_bullet => {
+                Block::Bullet {
+                    level: self.level,
+                    text,
+                }
+            }
+            ShapeKind::Body | ShapeKind::Other => Block::Paragraph(text),
+        };
+        self.slide.blocks.push(block);
+    }
+}
+
+#[async_trait::async_trait]
+impl Converter for PptxConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        trace!("Converting '{}' to '{}'", input.display(), output.display());
+
+        // Where pandoc would extract them to, `<stem>/media` next to the output, linked relative to it
+        let media_dir = PandocConverter::media_folder(output)?
+            .as_ref()
+            .join("media");
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        let media_link = format!("{stem}/media");
+
+        let path = input.to_path_buf();
+        let markdown = tokio::task::spawn_blocking(move || {
+            deck_markdown(&path, &media_dir, &media_link)
+        })
+        .await??;
+
+        // Dropped (and removed) on any early return, so a failed write leaves no half-written output
+        let partial = PartialOutput::new(output);
+        tokio::fs::write(partial.path(), markdown).await?;
+        partial.commit().await
+    }
+
+    async fn check_installed(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        NAME.to_string()
+    }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Ok(INPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        Ok(OUTPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn version(&self) -> Option<String> {
+        Some(VERSION.to_string())
+    }
+}
+
+#[cfg(test)]
+mod pptx_tests {
+    use super::*;
+
+    const SLIDE: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<p:sld xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+<p:cSld><p:spTree>
+<p:sp><p:nvSpPr><p:cNvPr id="2" name="Title 1"/><p:cNvSpPr/><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr>
+<p:txBody><a:p><a:r><a:t>Q3 &amp; Q4</a:t></a:r></a:p></p:txBody></p:sp>
+<p:sp><p:nvSpPr><p:cNvPr id="3" name="Content 2"/><p:cNvSpPr/><p:nvPr><p:ph idx="1"/></p:nvPr></p:nvSpPr>
+<p:txBody><a:p><a:r><a:t>Revenue up</a:t></a:r></a:p><a:p><a:pPr lvl="1"/><a:r><a:t>Mostly</a:t></a:r><a:br/><a:r><a:t>EMEA</a:t></a:r></a:p><a:p><a:pPr><a:buNone/></a:pPr><a:r><a:t>No bullet</a:t></a:r></a:p></p:txBody></p:sp>
+<p:pic><p:nvPicPr><p:cNvPr id="4" name="Picture 3" descr="A chart"/><p:cNvPicPr/><p:nvPr/></p:nvPicPr><p:blipFill><a:blip r:embed="rId2"/></p:blipFill></p:pic>
+<p:sp><p:nvSpPr><p:cNvPr id="5" name="TextBox 4"/><p:cNvSpPr txBox="1"/><p:nvPr/></p:nvSpPr>
+<p:txBody><a:p><a:r><a:t>Source: finance</a:t></a:r></a:p></p:txBody></p:sp>
+<p:sp><p:nvSpPr><p:cNvPr id="6" name="Slide Number 5"/><p:cNvSpPr/><p:nvPr><p:ph type="sldNum" idx="12"/></p:nvPr></p:nvSpPr>
+<p:txBody><a:p><a:r><a:t>3</a:t></a:r></a:p></p:txBody></p:sp>
+</p:spTree></p:cSld>
+</p:sld>"#;
+
+    #[test]
+    fn test_read_slide() {
+        let rels =
+            HashMap::from([("rId2".to_string(), "ppt/media/image1.png".to_string())]);
+        let slide = read_slide(SLIDE, &rels).unwrap();
+        assert_eq!(slide.title.as_deref(), Some("Q3 & Q4"));
+        assert_eq!(
+            slide.blocks,
+            [
+                Block::Bullet {
+                    level: 0,
+                    text:  "Revenue up".to_string(),
+                },
+                Block::Bullet {
+                    level: 1,
+                    text:  "Mostly EMEA".to_string(),
+                },
+                Block::Paragraph("No bullet".to_string()),
+                Block::Picture {
+                    alt:  "A chart".to_string(),
+                    part: "ppt/media/image1.png".to_string(),
+                },
+                Block::Paragraph("Source: finance".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_slide_parts() {
+        let xml = r#"<p:presentation xmlns:p="p" xmlns:r="r"><p:sldIdLst>
+<p:sldId id="257" r:id="rId3"/><p:sldId id="256" r:id="rId2"/>
+</p:sldIdLst></p:presentation>"#;
+        let rels = HashMap::from([
+            ("rId2".to_string(), "ppt/slides/slide1.xml".to_string()),
+            ("rId3".to_string(), "ppt/slides/slide2.xml".to_string()),
+        ]);
+        assert_eq!(
+            slide_parts(xml, &rels).unwrap(),
+            ["ppt/slides/slide2.xml", "ppt/slides/slide1.xml"]
+        );
+    }
+
+    #[test]
+    fn test_deck_markdown() {
+        use std::io::Write;
+
+        let tmp = std::env::temp_dir().join(format!("dcc_pptx_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp).unwrap();
+        let path = tmp.join("deck.pptx");
+
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        for (name, contents) in [
+            (
+                PRESENTATION_XML,
+                r#"<p:presentation xmlns:p="p" xmlns:r="r"><p:sldIdLst><p:sldId id="256" r:id="rId2"/><p:sldId id="257" r:id="rId3"/></p:sldIdLst></p:presentation>"#,
+            ),
+            (
+                "ppt/_rels/presentation.xml.rels",
+                r#"<Relationships><Relationship Id="rId2" Target="slides/slide1.xml"/><Relationship Id="rId3" Target="slides/slide2.xml"/></Relationships>"#,
+            ),
+            ("ppt/slides/slide1.xml", SLIDE),
+            (
+                "ppt/slides/_rels/slide1.xml.rels",
+                r#"<Relationships><Relationship Id="rId2" Target="../media/image1.png"/></Relationships>"#,
+            ),
+            ("ppt/slides/slide2.xml", r#"<p:sld xmlns:p="p"/>"#),
+            ("ppt/media/image1.png", "png"),
+        ] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let media_dir = tmp.join("deck").join("media");
+        let markdown = deck_markdown(&path, &media_dir, "deck/media").unwrap();
+        assert_eq!(
+            markdown,
+            "## Q3 & Q4\n\n- Revenue up\n  - Mostly EMEA\n\nNo bullet\n\n![A chart](<deck/media/image1.png>)\n\n\
+             Source: finance\n\n## Slide 2\n"
+        );
+        assert_eq!(std::fs::read(media_dir.join("image1.png")).unwrap(), b"png");
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::conversion::move_file;
+use crate::conversion::report::FailureDetail;
+use crate::prelude::*;
+
+/// Appended to an input's file name for the note written next to it in quarantine.
+const NOTE_SUFFIX: &str = ".error.txt";
+
+/// Copies (or with `move_inputs`, moves) every input that failed to convert into `dir`,
+/// each next to a `<file name>.error.txt` note with the error and the converter's stderr.
+///
+/// Inputs below `root` keep their folders relative to it, so same-named files don't collide,
+/// anything else lands at the top of `dir`.
+/// An input that failed for several output formats is quarantined once, with every failure in its note.
+///
+/// Returns how many inputs were quarantined, one that can't be is logged and skipped.
+///
+/// # Errors
+/// * If `dir` can't be created
+pub async fn quarantine<P: AsRef<Path>>(
+    dir: P,
+    failures: &[FailureDetail],
+    root: Option<&Path>,
+    move_inputs: bool,
+) -> Result<usize> {
+    let dir = dir.as_ref();
+    tokio::fs::create_dir_all(dir).await?;
+
+    let mut by_input: BTreeMap<&Path, Vec<&FailureDetail>> = BTreeMap::new();
+    for failure in failures {
+        by_input.entry(&failure.input).or_default().push(failure);
+    }
+
+    let mut quarantined = 0;
+    for (input, failures) in by_input {
+        let target = dir.join(quarantine_relative(input, root));
+        match quarantine_one(input, &target, &failures, move_inputs).await {
+            Ok(()) => quarantined += 1,
+            Err(e) => warn!("Failed to quarantine {input:?}: {e}"),
+        }
+    }
+
+    info!("Quarantined {quarantined} failed inputs in {}", dir.display());
+    Ok(quarantined)
+}
+
+async fn quarantine_one(
+    input: &Path,
+    target: &Path,
+    failures: &[&FailureDetail],
+    move_inputs: bool,
+) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    if move_inputs {
+        move_file(input, target).await?;
+    } else {
+        tokio::fs::copy(input, target).await?;
+    }
+
+    let mut note_name = target.file_name().unwrap_or_default().to_os_string();
+    note_name.push(NOTE_SUFFIX);
+    tokio::fs::write(target.with_file_name(note_name), note(input, failures)).await?;
+    Ok(())
+}
+
+/// Where below the quarantine dir `input` goes.
+fn quarantine_relative(input: &Path, root: Option<&Path>) -> PathBuf {
+    root.and_then(|root| input.strip_prefix(root).ok())
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .map_or_else(
+            || PathBuf::from(input.file_name().unwrap_or(input.as_os_str())),
+            Path::to_path_buf,
+        )
+}
+
+/// The contents of the note kept next to a quarantined `input`.
+fn note(input: &Path, failures: &[&FailureDetail]) -> String {
+    let mut note = format!("input: {}\n", input.display());
+    for failure in failures {
+        note.push_str(&format!(
+            "\noutput: {}\nengine: {}\n",
+            failure.output.display(),
+            failure.engine
+        ));
+        if let Some(code) = failure.exit_code {
+            note.push_str(&format!("exit code: {code}\n"));
+        }
+        note.push_str(&format!("error: {}\n", failure.error));
+        if let Some(ref stderr) = failure.stderr {
+            note.push_str(&format!("stderr:\n{}\n", stderr.trim_end()));
+        }
+    }
+    note
+}
+
+#[cfg(test)]
+mod quarantine_tests {
+    use super::*;
+
+    #[test]
+    fn test_quarantine_relative() {
+        let root = Path::new("/in");
+        assert_eq!(
+            quarantine_relative(Path::new("/in/a/b.docx"), Some(root)),
+            PathBuf::from("a/b.docx")
+        );
+        assert_eq!(
+            quarantine_relative(Path::new("/elsewhere/b.docx"), Some(root)),
+            PathBuf::from("b.docx")
+        );
+        assert_eq!(
+            quarantine_relative(Path::new("/in/a/b.docx"), None),
+            PathBuf::from("b.docx")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_copies_input_with_note() {
+        let base =
+            std::env::temp_dir().join(format!("dcc_quarantine_{}", std::process::id()));
+        let (root, dir) = (base.join("in"), base.join("quarantine"));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        let input = root.join("sub/bad.docx");
+        std::fs::write(&input, "broken").unwrap();
+
+        let failures = ["md", "html"].map(|ext| {
+            FailureDetail {
+                input:     input.clone(),
+                output:    input.with_extension(ext),
+                engine:    "pandoc".into(),
+                exit_code: Some(64),
+                stderr:    Some("Unknown reader".into()),
+                error:     "pandoc failed".into(),
+            }
+        });
+        let quarantined = quarantine(&dir, &failures, Some(&root), false)
+            .await
+            .unwrap();
+
+        assert_eq!(quarantined, 1);
+        assert!(input.exists());
+        assert_eq!(std::fs::read_to_string(dir.join("sub/bad.docx")).unwrap(), "broken");
+        let note = std::fs::read_to_string(dir.join("sub/bad.docx.error.txt")).unwrap();
+        assert_eq!(note.matches("Unknown reader").count(), 2);
+        assert!(note.contains("exit code: 64"));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,498 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::conversion::asciidoctor::{self, AsciidoctorConverter};
+use crate::conversion::calibre::{self, CalibreConverter};
+use crate::conversion::chained::{self, ChainedConverter};
+use crate::conversion::docx::{self, DocxFallbackConverter};
+use crate::conversion::email::{self, EmailConverter};
+use crate::conversion::image::{self, ImageConverter};
+use crate::conversion::libreoffice::{self, LibreOfficeConverter, LibreOfficeOptions};
+use crate::conversion::markdown::{self, MarkdownConverter};
+use crate::conversion::ocr::{self, OcrConverter};
+use crate::conversion::pandoc::{PandocConverter, PandocOptions};
+use crate::conversion::pptx::{self, PptxConverter};
+use crate::conversion::spreadsheet::{self, SpreadsheetConverter, SpreadsheetOptions};
+use crate::conversion::typst::{self, TypstConverter};
+use crate::conversion::{Converter, Engine, LIBREOFFICE_ONLY_INPUTS, canonical_ext};
+use crate::prelude::*;
+
+/// Routes each `(input, output)` extension pair to the `Converter` that handles it,
+/// so a single run can mix engines.
+///
+/// Pairs without an explicit route go to the fallback converter, if one is registered.
+#[derive(Default)]
+pub struct ConverterRegistry {
+    converters: Vec<Arc<dyn Converter>>,
+    /// `(input_ext, output_ext)` -> index into `converters`
+    routes:     HashMap<(String, String), usize>,
+    fallback:   Option<usize>,
+}
+
+impl ConverterRegistry {
+    /// The registry for `--engine`: a single converter for everything,
+    /// or for `Engine::Auto` pandoc with LibreOffice routed in for the formats pandoc can't read,
+    /// going to any of `target_exts` it writes, and chained through a docx (or pptx) to pandoc (or the pptx reader)
+    /// for those it doesn't, typst for Typst sources going to pdf or images,
+    /// Asciidoctor for AsciiDoc, Calibre for the Kindle formats (MOBI/AZW3) in or out
+    /// ImageMagick (or libvips) for images going to other images or pdf,
+    /// `calamine` for spreadsheets going to csv or markdown (see `register_spreadsheets`)
+    /// and PowerPoint decks going to markdown are read in-process.
+    /// Markdown going to HTML is converted in-process, unless a pandoc option would shape the output
+    /// (see `PandocOptions::shapes_output`), and docx going to markdown or text falls back to
+    /// extracting its text when pandoc fails or isn't installed.
+    /// Emails (`.eml`/`.msg`) are read in-process and written by pandoc, their attachments saved next to the output.
+    pub fn for_engine(
+        engine: Engine,
+        pandoc_options: PandocOptions,
+        libreoffice_options: LibreOfficeOptions,
+        target_exts: &[String],
+    ) -> Self {
+        let native_markdown = pandoc_options.shapes_output().is_none();
+        let pandoc =
+            || Box::new(PandocConverter::new().with_options(pandoc_options.clone()));
+        let libreoffice = || {
+            Box::new(
+                LibreOfficeConverter::new().with_options(libreoffice_options.clone()),
+            )
+        };
+
+        let mut registry = Self::default();
+        match engine {
+            Engine::Pandoc => {
+                registry.register_fallback(pandoc());
+            }
+            Engine::LibreOffice => {
+                registry.register_fallback(libreoffice());
+            }
+            Engine::Auto => {
+                registry.register_fallback(pandoc());
+                // Only if there's something to convert to, a missing program isn't worth a warning otherwise
+                let office = routed_pairs(
+                    &LIBREOFFICE_ONLY_INPUTS,
+                    libreoffice::OUTPUT_FORMATS,
+                    target_exts,
+                );
+                if !office.is_empty() {
+                    registry.register(libreoffice(), office);
+                }
+                registry
+                    .register_spreadsheets(SpreadsheetOptions::default(), target_exts);
+
+                let compiled = routed_pairs(
+                    &typst::INPUT_FORMATS,
+                    &typst::OUTPUT_FORMATS,
+                    target_exts,
+                );
+                if !compiled.is_empty() {
+                    registry.register(Box::new(TypstConverter::new()), compiled);
+                }
+                let asciidoc = routed_pairs(
+                    &asciidoctor::INPUT_FORMATS,
+                    &asciidoctor::OUTPUT_FORMATS,
+                    target_exts,
+                );
+                if !asciidoc.is_empty() {
+                    registry.register(Box::new(AsciidoctorConverter::new()), asciidoc);
+                }
+                let mut ebooks = routed_pairs(
+                    &calibre::INPUT_FORMATS,
+                    &calibre::OUTPUT_FORMATS,
+                    target_exts,
+                );
+                ebooks.extend(routed_pairs(
+                    &calibre::READ_FORMATS,
+                    &calibre::EBOOK_OUTPUTS,
+                    target_exts,
+                ));
+                if !ebooks.is_empty() {
+                    registry.register(Box::new(CalibreConverter::new()), ebooks);
+                }
+                let (input, output) = markdown::ROUTE;
+                if native_markdown
+                    && target_exts.iter().any(|ext| canonical_ext(ext) == output)
+                {
+                    registry
+                        .register(Box::new(MarkdownConverter::new()), [(input, output)]);
+                }
+                let decks = routed_pairs(
+                    &pptx::INPUT_FORMATS,
+                    &pptx::OUTPUT_FORMATS,
+                    target_exts,
+                );
+                if !decks.is_empty() {
+                    registry.register(Box::new(PptxConverter::new()), decks);
+                }
+                // What LibreOffice can't write goes on from its docx (or pptx) to pandoc (or the pptx reader)
+                let documents = chained::document_pairs(target_exts);
+                if !documents.is_empty() {
+                    registry.register(
+                        Box::new(ChainedConverter::new(libreoffice(), "docx", pandoc())),
+                        documents,
+                    );
+                }
+                let presentations = routed_pairs(
+                    &chained::PRESENTATION_INPUTS,
+                    &pptx::OUTPUT_FORMATS,
+                    target_exts,
+                );
+                if !presentations.is_empty() {
+                    registry.register(
+                        Box::new(ChainedConverter::new(
+                            libreoffice(),
+                            "pptx",
+                            Box::new(PptxConverter::new()),
+                        )),
+                        presentations,
+                    );
+                }
+                let extracted = routed_pairs(
+                    &docx::INPUT_FORMATS,
+                    &docx::OUTPUT_FORMATS,
+                    target_exts,
+                );
+                if !extracted.is_empty() {
+                    registry.register(
+                        Box::new(DocxFallbackConverter::new(pandoc())),
+                        extracted,
+                    );
+                }
+                let emails = email::routed_pairs(target_exts);
+                if !emails.is_empty() {
+                    registry.register(Box::new(EmailConverter::new(pandoc())), emails);
+                }
+                let images = routed_pairs(
+                    &image::INPUT_FORMATS,
+                    &image::OUTPUT_FORMATS,
+                    target_exts,
+                );
+                if !images.is_empty() {
+                    registry.register(Box::new(ImageConverter::new()), images);
+                }
+            }
+        }
+        registry
+    }
+
+    /// Registers `converter` for every `(input_ext, output_ext)` in `pairs`,
+    /// replacing any earlier route for the same pair.
+    pub fn register<I, S>(&mut self, converter: Box<dyn Converter>, pairs: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (S, S)>,
+        S: AsRef<str>,
+    {
+        let idx = self.push(converter);
+        for (input, output) in pairs {
+            self.routes
+                .insert(route_key(input.as_ref(), output.as_ref()), idx);
+        }
+        self
+    }
+
+    /// Routes spreadsheets going to csv or markdown (among `target_exts`) to a `SpreadsheetConverter` with `options`,
+    /// over whatever had them before.
+    pub fn register_spreadsheets(
+        &mut self,
+        options: SpreadsheetOptions,
+        target_exts: &[String],
+    ) -> &mut Self {
+        let pairs = routed_pairs(
+            &spreadsheet::INPUT_FORMATS,
+            &spreadsheet::OUTPUT_FORMATS,
+            target_exts,
+        );
+        if pairs.is_empty() {
+            return self;
+        }
+        self.register(Box::new(SpreadsheetConverter::new().with_options(options)), pairs)
+    }
+
+    /// Routes scanned PDFs and images going to any of `target_exts` through `converter` (`--ocr`),
+    /// over whatever had them before, except going to the formats the image converter writes.
+    pub fn register_ocr(
+        &mut self,
+        converter: OcrConverter,
+        target_exts: &[String],
+    ) -> &mut Self {
+        let pairs = ocr::routed_pairs(target_exts);
+        if pairs.is_empty() {
+            return self;
+        }
+        self.register(Box::new(converter), pairs)
+    }
+
+    /// Drops the fallback if every pair of `input_exts` going to `output_exts` has a route of its own,
+    /// so a run that doesn't need it doesn't need it installed either (markdown to HTML without pandoc).
+    pub fn drop_unused_fallback(
+        &mut self,
+        input_exts: &[String],
+        output_exts: &[String],
+    ) -> &mut Self {
+        let routed = !input_exts.is_empty()
+            && input_exts.iter().all(|input| {
+                output_exts
+                    .iter()
+                    .all(|output| self.routes.contains_key(&route_key(input, output)))
+            });
+        if routed && let Some(fallback) = self.fallback.take() {
+            debug!(
+                "Every conversion is routed, {} isn't needed",
+                self.converters[fallback].name()
+            );
+        }
+        self
+    }
+
+    /// Registers `converter` for every pair that has no explicit route.
+    pub fn register_fallback(&mut self, converter: Box<dyn Converter>) -> &mut Self {
+        self.fallback = Some(self.push(converter));
+        self
+    }
+
+    /// The converter for files going from `input_ext` to `output_ext`.
+    pub fn get(&self, input_ext: &str, output_ext: &str) -> Option<Arc<dyn Converter>> {
+        self.routes
+            .get(&route_key(input_ext, output_ext))
+            .copied()
+            .or(self.fallback)
+            .map(|idx| Arc::clone(&self.converters[idx]))
+    }
+
+    /// Every registered converter, in registration order.
+    pub fn converters(&self) -> impl Iterator<Item = &Arc<dyn Converter>> {
+        self.converters.iter()
+    }
+
+    /// Checks every converter is installed before any conversion starts.
+    ///
+    /// Routed converters that are missing (or too old, see `Converter::check_version`) are dropped
+    /// with a warning, so their pairs go to the fallback instead of failing every file up front.
+    ///
+    /// # Errors
+    /// * `Error::ConversionProgramNotInstalled` - If the fallback is missing,
+    ///   or nothing usable is left.
+    /// * `Error::PandocTooOld` - If the fallback is a pandoc older than the run needs.
+    pub async fn ensure_installed(&mut self) -> Result<()> {
+        for (idx, converter) in self.converters.iter().enumerate() {
+            let unusable = if !converter.check_installed().await {
+                Error::ConversionProgramNotInstalled(converter.name())
+            } else if let Err(e) = converter.check_version().await {
+                e
+            } else {
+                continue;
+            };
+            if self.fallback == Some(idx) {
+                return Err(unusable);
+            }
+
+            warn!("{unusable}, its formats go to the fallback");
+            self.routes.retain(|_, routed| *routed != idx);
+        }
+
+        if self.fallback.is_none() && self.routes.is_empty() {
+            let names: Vec<_> = self.converters.iter().map(|c| c.name()).collect();
+            return Err(Error::ConversionProgramNotInstalled(names.join(", ")));
+        }
+        Ok(())
+    }
+
+    /// Every input format any registered converter reports, de-duplicated.
+    pub async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        let mut formats = Vec::new();
+        for converter in &self.converters {
+            formats.extend(converter.supported_input_formats().await?);
+        }
+        formats.sort_unstable();
+        formats.dedup();
+        Ok(formats)
+    }
+
+    fn push(&mut self, converter: Box<dyn Converter>) -> usize {
+        self.converters.push(Arc::from(converter));
+        self.converters.len() - 1
+    }
+}
+
+/// An output of each converter `Engine::Auto` routes in, so a registry for them has every one of them,
+/// for `doctor` to check.
+pub const ROUTED_OUTPUTS: [&str; 4] = ["pdf", "html", "mobi", "md"];
+
+fn route_key(input_ext: &str, output_ext: &str) -> (String, String) {
+    (canonical_ext(input_ext), canonical_ext(output_ext))
+}
+
+/// Every pair of one of `inputs` going to one of `target_exts` that's also one of `outputs`.
+fn routed_pairs(
+    inputs: &[&str],
+    outputs: &[&str],
+    target_exts: &[String],
+) -> Vec<(String, String)> {
+    target_exts
+        .iter()
+        .filter(|output| outputs.contains(&canonical_ext(output).as_str()))
+        .flat_map(|output| {
+            inputs
+                .iter()
+                .map(move |input| (input.to_string(), output.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod registry_tests {
+    use std::path::Path;
+
+    use super::*;
+
+    struct Named(&'static str, bool);
+
+    #[async_trait::async_trait]
+    impl Converter for Named {
+        async fn convert(&self, _input: &Path, _output: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        async fn check_installed(&self) -> bool {
+            self.1
+        }
+
+        fn name(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_routes_and_fallback() {
+        let mut registry = ConverterRegistry::default();
+        registry
+            .register_fallback(Box::new(Named("pandoc", true)))
+            .register(Box::new(Named("soffice", true)), [("doc", "md"), (".XLS", "md")]);
+
+        let name = |i: &str, o: &str| registry.get(i, o).map(|c| c.name());
+        assert_eq!(name("doc", ".md").as_deref(), Some("soffice"));
+        assert_eq!(name("xls", "md").as_deref(), Some("soffice"));
+        assert_eq!(name("docx", "md").as_deref(), Some("pandoc"));
+        assert_eq!(name("doc", "html").as_deref(), Some("pandoc"));
+        assert_eq!(name("DOC", "MD").as_deref(), Some("soffice"));
+        assert_eq!(name("doc", "markdown").as_deref(), Some("soffice"));
+
+        assert!(ConverterRegistry::default().get("doc", "md").is_none());
+    }
+
+    #[test]
+    fn test_auto_routes() {
+        let targets = ["md".to_string(), "png".to_string(), "htm".to_string()];
+        let registry = ConverterRegistry::for_engine(
+            Engine::Auto,
+            PandocOptions::default(),
+            LibreOfficeOptions::default(),
+            &targets,
+        );
+        let name = |i: &str, o: &str| registry.get(i, o).unwrap().name();
+        assert!(name("typ", "png").ends_with("typst"));
+        assert!(name("doc", "htm").ends_with("soffice"));
+        let chained = name("doc", "md");
+        assert!(chained.contains("soffice+") && chained.ends_with("pandoc"));
+        assert!(name("ppt", "md").ends_with("soffice+pptx-markdown"));
+        assert!(!name("typ", "md").ends_with("typst"));
+        assert!(!name("docx", "png").ends_with("typst"));
+        let image = name("tiff", "png");
+        assert!(image.ends_with("magick") || image.ends_with("vips"));
+        assert!(name("adoc", "html").ends_with("asciidoctor"));
+        assert!(!name("asciidoc", "md").ends_with("asciidoctor"));
+        assert_eq!(name("md", "htm"), "pulldown-cmark");
+        assert_ne!(name("md", "md"), "pulldown-cmark");
+        assert!(name("azw3", "md").ends_with("ebook-convert"));
+        assert!(!name("epub", "md").ends_with("ebook-convert"));
+        assert_eq!(name("docx", "md"), "docx-text");
+        assert_eq!(name("xlsx", "md"), "calamine");
+        assert!(name("xlsx", "htm").ends_with("soffice"));
+        assert_eq!(name("pptx", "md"), "pptx-markdown");
+        assert!(name("pptx", "htm").ends_with("soffice"));
+        assert_ne!(name("docx", "htm"), "docx-text");
+        assert_eq!(name("eml", "md"), "mail-parser");
+        assert_eq!(name("msg", "htm"), "mail-parser");
+        assert_ne!(name("eml", "png"), "mail-parser");
+
+        let registry = ConverterRegistry::for_engine(
+            Engine::Auto,
+            PandocOptions::default(),
+            LibreOfficeOptions::default(),
+            &["md".to_string()],
+        );
+        // pandoc, calamine, Calibre for the Kindle formats, the pptx reader, LibreOffice chained to pandoc
+        // and to the pptx reader, the docx fallback and the email reader,
+        // no LibreOffice on its own (it doesn't write markdown), typst or Asciidoctor
+        assert_eq!(registry.converters().count(), 8);
+
+        let targets = ["mobi".to_string()];
+        let registry = ConverterRegistry::for_engine(
+            Engine::Auto,
+            PandocOptions::default(),
+            LibreOfficeOptions::default(),
+            &targets,
+        );
+        let name = |i: &str, o: &str| registry.get(i, o).unwrap().name();
+        assert!(name("epub", "mobi").ends_with("ebook-convert"));
+        assert!(name("md", "mobi").ends_with("ebook-convert"));
+    }
+
+    #[test]
+    fn test_routed_outputs() {
+        let targets: Vec<_> = ROUTED_OUTPUTS.iter().map(ToString::to_string).collect();
+        let registry = ConverterRegistry::for_engine(
+            Engine::Auto,
+            PandocOptions::default(),
+            LibreOfficeOptions::default(),
+            &targets,
+        );
+        // pandoc, LibreOffice, calamine, typst, Asciidoctor, Calibre, pulldown-cmark, the pptx reader,
+        // LibreOffice chained to pandoc and to the pptx reader, the docx fallback, the email reader and ImageMagick
+        assert_eq!(registry.converters().count(), 13);
+
+        let registry = ConverterRegistry::for_engine(
+            Engine::Auto,
+            PandocOptions {
+                citeproc: true,
+                ..Default::default()
+            },
+            LibreOfficeOptions::default(),
+            &["html".to_string()],
+        );
+        assert_ne!(registry.get("md", "html").unwrap().name(), "pulldown-cmark");
+    }
+
+    #[tokio::test]
+    async fn test_drop_unused_fallback() {
+        let exts =
+            |exts: &[&str]| exts.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let mut registry = ConverterRegistry::default();
+        registry
+            .register_fallback(Box::new(Named("pandoc", false)))
+            .register(Box::new(Named("native", true)), [("markdown", "html")]);
+
+        registry.drop_unused_fallback(&exts(&["md", "docx"]), &exts(&["html"]));
+        assert!(registry.ensure_installed().await.is_err());
+
+        registry.drop_unused_fallback(&exts(&["md"]), &exts(&["html"]));
+        registry.ensure_installed().await.unwrap();
+        assert!(registry.get("docx", "html").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ensure_installed() {
+        let mut registry = ConverterRegistry::default();
+        registry
+            .register_fallback(Box::new(Named("pandoc", true)))
+            .register(Box::new(Named("soffice", false)), [("doc", "md")]);
+        registry.ensure_installed().await.unwrap();
+        assert_eq!(registry.get("doc", "md").unwrap().name(), "pandoc");
+
+        let mut registry = ConverterRegistry::default();
+        registry.register_fallback(Box::new(Named("pandoc", false)));
+        assert!(matches!(
+            registry.ensure_installed().await,
+            Err(Error::ConversionProgramNotInstalled(_))
+        ));
+    }
+}
@@ -0,0 +1,148 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::conversion::naming::with_counter;
+use crate::prelude::*;
+
+/// Kept in the output directory (or the input directory without `--output`).
+pub const FILE_NAME: &str = ".document_converter_renames.jsonl";
+
+/// One line of the rename journal.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    from: PathBuf,
+    to:   PathBuf,
+}
+
+/// Every input file (or folder) `--sanitize-names` renamed, one JSON object per line,
+/// so the renames can be reviewed and reversed (newest last).
+///
+/// Unlike the checkpoint it's appended to across runs, and only created once something is renamed.
+#[derive(Debug)]
+pub struct RenameJournal {
+    path: PathBuf,
+    file: Mutex<Option<std::fs::File>>,
+}
+
+impl RenameJournal {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            file: Mutex::default(),
+        }
+    }
+
+    /// Renames `from` to `to` and records it, returning where it ended up.
+    ///
+    /// If something already has that name (say `a~b.docx` and `a$b.docx` both sanitized to `a_b.docx`),
+    /// the first free `stem_2.ext`, `stem_3.ext`, ... is used instead and the remapping is logged.
+    /// Renames go one at a time, so two can't pick the same free name.
+    ///
+    /// # Errors
+    /// * `Error::FailedRenameFile` - If the rename fails
+    /// * If the journal can't be opened, or the line can't be written
+    pub fn rename(&self, from: &Path, to: &Path) -> Result<PathBuf> {
+        let mut file = self.file.lock().expect("rename journal lock poisoned");
+
+        let is_free = |candidate: &Path| candidate.symlink_metadata().is_err();
+        let mut target = to.to_path_buf();
+        let mut count = 2;
+        while !is_free(&target) {
+            target = with_counter(to, count);
+            count += 1;
+        }
+        if target != to {
+            warn!(
+                "{} is taken, renaming {} to {} instead",
+                to.display(),
+                from.display(),
+                target.display()
+            );
+        }
+
+        std::fs::rename(from, &target).map_err(|e| {
+            error!("Failed to rename: {e:?}");
+            Error::FailedRenameFile(from.to_path_buf())
+        })?;
+        self.append(&mut file, from, &target)?;
+        Ok(target)
+    }
+
+    /// Appends the rename of `from` to `to` to the (already locked) journal `file`.
+    fn append(
+        &self,
+        file: &mut Option<std::fs::File>,
+        from: &Path,
+        to: &Path,
+    ) -> Result<()> {
+        let entry = Entry {
+            from: from.to_path_buf(),
+            to:   to.to_path_buf(),
+        };
+        let mut line = serde_json::to_string(&entry).map_err(|e| {
+            Error::Generic(format!(
+                "Failed to write rename journal {}: {e}",
+                self.path.display()
+            ))
+        })?;
+        line.push('\n');
+
+        if file.is_none() {
+            *file = Some(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            );
+        }
+        // A single write per line, so a crash can't leave more than the last one cut short
+        file.as_mut()
+            .expect("just opened")
+            .write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod renames_tests {
+    use super::*;
+
+    #[test]
+    fn test_collisions_and_appending() {
+        let tmp =
+            std::env::temp_dir().join(format!("dcc_renames_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp).unwrap();
+        let path = tmp.join(FILE_NAME);
+        for name in ["a~b.docx", "a$b.docx", "c~.docx"] {
+            std::fs::write(tmp.join(name), name).unwrap();
+        }
+
+        let journal = RenameJournal::new(&path);
+        assert!(!path.exists());
+        let wanted = tmp.join("a_b.docx");
+        assert_eq!(journal.rename(&tmp.join("a~b.docx"), &wanted).unwrap(), wanted);
+        assert_eq!(
+            journal.rename(&tmp.join("a$b.docx"), &wanted).unwrap(),
+            tmp.join("a_b_2.docx")
+        );
+        assert_eq!(std::fs::read_to_string(&wanted).unwrap(), "a~b.docx");
+        drop(journal);
+        RenameJournal::new(&path)
+            .rename(&tmp.join("c~.docx"), &tmp.join("c_.docx"))
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let entries: Vec<Entry> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].from, tmp.join("a$b.docx"));
+        assert_eq!(entries[1].to, tmp.join("a_b_2.docx"));
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}
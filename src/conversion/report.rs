@@ -0,0 +1,672 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::conversion::filter::format_size;
+use crate::prelude::*;
+
+/// How many of the slowest conversions the summary and the HTML `--report` list.
+pub const SLOWEST: usize = 10;
+
+/// The file format used for the `--report` output.
+#[derive(Debug, clap::ValueEnum, Clone, Copy, PartialEq, Eq, Default)]
+#[clap(name = "ReportFormat", rename_all = "lower")]
+pub enum ReportFormat {
+    #[default]
+    #[value(name = "json", alias = "JSON")]
+    Json,
+    #[value(name = "csv", alias = "CSV")]
+    Csv,
+    /// A page to open in a browser, with the run's totals above the files.
+    #[value(name = "html", alias = "HTML")]
+    Html,
+}
+
+impl ReportFormat {
+    /// The format a report at `path` is written in going by its extension, JSON if it's neither CSV nor HTML.
+    pub fn for_path(path: &Path) -> Self {
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("csv") => ReportFormat::Csv,
+            Some("html" | "htm") => ReportFormat::Html,
+            _ => ReportFormat::Json,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutcomeStatus {
+    Ok,
+    Error,
+}
+
+impl OutcomeStatus {
+    #[inline]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OutcomeStatus::Ok => "ok",
+            OutcomeStatus::Error => "error",
+        }
+    }
+}
+
+/// The result of converting a single file, as written to the `--report` file.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileOutcome {
+    pub input:          PathBuf,
+    pub output:         PathBuf,
+    pub status:         OutcomeStatus,
+    pub error_message:  Option<String>,
+    /// How many retries it took, `0` if the first attempt succeeded (or for failures).
+    pub retries:        usize,
+    /// How long the conversion took once it had a job slot, retries included.
+    pub duration_ms:    u128,
+    /// In bytes, `None` if it couldn't be read.
+    pub input_size:     Option<u64>,
+    /// In bytes, `None` if there's no output (or it couldn't be read).
+    pub output_size:    Option<u64>,
+    /// The mean confidence (0 - 100) of the words read with `--ocr`, `None` if the file wasn't OCRed.
+    pub ocr_confidence: Option<f64>,
+    /// The output is a lossy text extraction, what a fallback wrote when the real conversion couldn't be done.
+    pub degraded:       bool,
+}
+
+impl FileOutcome {
+    pub fn ok<P: AsRef<Path>>(input: P, output: P, retries: usize) -> Self {
+        Self {
+            input: input.as_ref().to_path_buf(),
+            output: output.as_ref().to_path_buf(),
+            status: OutcomeStatus::Ok,
+            error_message: None,
+            retries,
+            duration_ms: 0,
+            input_size: None,
+            output_size: None,
+            ocr_confidence: None,
+            degraded: false,
+        }
+    }
+
+    pub fn error<P: AsRef<Path>>(
+        input: P,
+        output: P,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            input:          input.as_ref().to_path_buf(),
+            output:         output.as_ref().to_path_buf(),
+            status:         OutcomeStatus::Error,
+            error_message:  Some(message.into()),
+            retries:        0,
+            duration_ms:    0,
+            input_size:     None,
+            output_size:    None,
+            ocr_confidence: None,
+            degraded:       false,
+        }
+    }
+
+    /// The outcome of a conversion that `took` this long.
+    #[must_use]
+    pub fn took(mut self, took: Duration) -> Self {
+        self.duration_ms = took.as_millis();
+        self
+    }
+}
+
+/// A failed conversion and everything we know about why, as written to the `--error-report` file.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailureDetail {
+    pub input:     PathBuf,
+    pub output:    PathBuf,
+    /// The converter that was used.
+    pub engine:    String,
+    /// Only known if the converter's process ran and failed.
+    pub exit_code: Option<i32>,
+    /// Only known if the converter's process ran and failed.
+    pub stderr:    Option<String>,
+    pub error:     String,
+}
+
+/// A run's totals as a single JSON document (`--summary json`), for wrapper scripts that would rather not parse the log.
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary<'a> {
+    pub files:         usize,
+    pub converted:     usize,
+    pub failed:        usize,
+    /// How many of `converted` only got there after retrying.
+    pub retried:       usize,
+    /// How many of `converted` are only a degraded extraction of their text, see `FileOutcome::degraded`.
+    pub degraded:      usize,
+    /// Percentage (0 - 100) of `files` converted, see `ConversionReport::success_rate`.
+    pub success_rate:  f64,
+    /// How long the whole run took.
+    pub duration_ms:   u128,
+    /// The time spent converting, summed over every file, so more than `duration_ms` when they ran side by side.
+    pub conversion_ms: u128,
+    /// The longest a single file took.
+    pub slowest_ms:    u128,
+    pub failed_paths:  Vec<&'a Path>,
+}
+
+/// Everything a conversion run produced, one entry per attempted file.
+#[derive(Debug, Default)]
+pub struct ConversionReport {
+    pub succeeded: Vec<PathBuf>,
+    /// The subset of `succeeded` that only got there after retrying.
+    pub retried:   Vec<PathBuf>,
+    pub failed:    Vec<(PathBuf, Error)>,
+    /// Flattened per-file view used for the `--report` file.
+    pub outcomes:  Vec<FileOutcome>,
+    /// The details of every entry in `failed`, used for the `--error-report` file.
+    pub failures:  Vec<FailureDetail>,
+}
+
+impl ConversionReport {
+    /// Records a converted file, returning its outcome for the converter to add to.
+    pub fn record_success<P: AsRef<Path>>(
+        &mut self,
+        input: P,
+        output: P,
+        retries: usize,
+        took: Duration,
+    ) -> &mut FileOutcome {
+        self.succeeded.push(input.as_ref().to_path_buf());
+        if retries > 0 {
+            self.retried.push(input.as_ref().to_path_buf());
+        }
+        self.outcomes
+            .push(FileOutcome::ok(input, output, retries).took(took));
+        self.outcomes.last_mut().expect("just pushed")
+    }
+
+    pub fn record_failure<P: AsRef<Path>>(
+        &mut self,
+        input: P,
+        output: P,
+        engine: &str,
+        error: Error,
+        took: Duration,
+    ) {
+        self.outcomes
+            .push(FileOutcome::error(&input, &output, error.to_string()).took(took));
+        self.failures.push(FailureDetail {
+            input:     input.as_ref().to_path_buf(),
+            output:    output.as_ref().to_path_buf(),
+            engine:    engine.to_string(),
+            exit_code: error.exit_code(),
+            stderr:    error.stderr().map(str::to_string),
+            error:     error.to_string(),
+        });
+        self.failed.push((input.as_ref().to_path_buf(), error));
+    }
+
+    #[inline]
+    pub fn total(&self) -> usize {
+        self.succeeded.len() + self.failed.len()
+    }
+
+    /// Percentage (0 - 100) of attempted files that converted successfully.
+    /// An empty run counts as fully successful.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn success_rate(&self) -> f64 {
+        let total = self.total();
+        if total == 0 {
+            return 100.0;
+        }
+        (self.succeeded.len() as f64 / total as f64) * 100.0
+    }
+
+    /// How many outputs are only a degraded extraction of their text.
+    pub fn degraded(&self) -> usize {
+        self.outcomes.iter().filter(|o| o.degraded).count()
+    }
+
+    #[inline]
+    pub fn has_failures(&self) -> bool {
+        !self.failed.is_empty()
+    }
+
+    /// Fills in the input and output sizes of every outcome, as the files are now.
+    pub async fn record_sizes(&mut self) {
+        let size = async |path: &Path| {
+            tokio::fs::metadata(path).await.ok().map(|meta| meta.len())
+        };
+        for outcome in &mut self.outcomes {
+            outcome.input_size = size(&outcome.input).await;
+            outcome.output_size = match outcome.status {
+                OutcomeStatus::Ok => size(&outcome.output).await,
+                OutcomeStatus::Error => None,
+            };
+        }
+    }
+
+    /// The run's totals, for a run that `took` this long.
+    pub fn summary(&self, took: Duration) -> Summary<'_> {
+        Summary {
+            files:         self.total(),
+            converted:     self.succeeded.len(),
+            failed:        self.failed.len(),
+            retried:       self.retried.len(),
+            degraded:      self.degraded(),
+            success_rate:  self.success_rate(),
+            duration_ms:   took.as_millis(),
+            conversion_ms: self.outcomes.iter().map(|o| o.duration_ms).sum(),
+            slowest_ms:    self
+                .outcomes
+                .iter()
+                .map(|o| o.duration_ms)
+                .max()
+                .unwrap_or(0),
+            failed_paths:  self
+                .failed
+                .iter()
+                .map(|(input, _)| input.as_path())
+                .collect(),
+        }
+    }
+
+    /// The `n` conversions that took longest, slowest first.
+    pub fn slowest(&self, n: usize) -> Vec<&FileOutcome> {
+        let mut outcomes: Vec<&FileOutcome> = self.outcomes.iter().collect();
+        outcomes.sort_by_key(|o| std::cmp::Reverse(o.duration_ms));
+        outcomes.truncate(n);
+        outcomes
+    }
+}
+
+/// Writes the per-file outcomes to `path` in the requested format (see `write_atomically`).
+///
+/// # Errors
+/// * If the parent directory can't be created, or the file can't be written/renamed.
+pub async fn write_report<P: AsRef<Path>>(
+    path: P,
+    report: &ConversionReport,
+    format: ReportFormat,
+) -> Result<()> {
+    let path = path.as_ref();
+    let outcomes = report.outcomes.as_slice();
+
+    let contents = match format {
+        ReportFormat::Json => serde_json::to_string_pretty(outcomes)?,
+        ReportFormat::Csv => to_csv(outcomes),
+        ReportFormat::Html => to_html(report),
+    };
+
+    write_atomically(path, contents).await?;
+    info!("Wrote conversion report to {}", path.display());
+    Ok(())
+}
+
+/// Writes every failed conversion to `path` as a JSON array, with the engine used,
+/// its exit code and captured stderr where known. A run without failures writes `[]`.
+///
+/// # Errors
+/// * If the parent directory can't be created, or the file can't be written/renamed.
+pub async fn write_error_report<P: AsRef<Path>>(
+    path: P,
+    report: &ConversionReport,
+) -> Result<()> {
+    let path = path.as_ref();
+    let contents = serde_json::to_string_pretty(&report.failures)?;
+
+    write_atomically(path, contents).await?;
+    info!(
+        "Wrote error report ({} failures) to {}",
+        report.failures.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Writes `summary` to `path` as pretty-printed JSON (see `write_atomically`).
+///
+/// # Errors
+/// * If the parent directory can't be created, or the file can't be written/renamed.
+pub async fn write_summary<P: AsRef<Path>>(path: P, summary: &Summary<'_>) -> Result<()> {
+    let path = path.as_ref();
+    write_atomically(path, serde_json::to_string_pretty(summary)?).await?;
+    info!("Wrote the run's summary to {}", path.display());
+    Ok(())
+}
+
+/// Writes `contents` to a `.tmp` sibling of `path` first and then renames it into place,
+/// so a crash mid-write never leaves a truncated file behind.
+async fn write_atomically(path: &Path, contents: String) -> Result<()> {
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+        && !parent.exists()
+    {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    tokio::fs::write(&tmp_path, contents).await?;
+    tokio::fs::rename(&tmp_path, path).await.map_err(|e| {
+        error!("Failed to rename: {e:?}");
+        Error::FailedRenameFile(tmp_path.clone())
+    })?;
+    Ok(())
+}
+
+fn to_csv(outcomes: &[FileOutcome]) -> String {
+    let mut out = String::from(
+        "input,output,status,error_message,retries,duration_ms,input_size,output_size,ocr_confidence,degraded\n",
+    );
+    for o in outcomes {
+        out.push_str(&csv_field(&o.input.display().to_string()));
+        out.push(',');
+        out.push_str(&csv_field(&o.output.display().to_string()));
+        out.push(',');
+        out.push_str(o.status.as_str());
+        out.push(',');
+        out.push_str(&csv_field(o.error_message.as_deref().unwrap_or_default()));
+        out.push(',');
+        out.push_str(&o.retries.to_string());
+        out.push(',');
+        out.push_str(&o.duration_ms.to_string());
+        out.push(',');
+        out.push_str(
+            &o.input_size
+                .map(|size| size.to_string())
+                .unwrap_or_default(),
+        );
+        out.push(',');
+        out.push_str(
+            &o.output_size
+                .map(|size| size.to_string())
+                .unwrap_or_default(),
+        );
+        out.push(',');
+        out.push_str(
+            &o.ocr_confidence
+                .map(|confidence| format!("{confidence:.1}"))
+                .unwrap_or_default(),
+        );
+        out.push(',');
+        out.push_str(&o.degraded.to_string());
+        out.push('\n');
+    }
+    out
+}
+
+/// A self-contained page with the run's totals and a row per file, failures first.
+fn to_html(report: &ConversionReport) -> String {
+    let total_ms: u128 = report.outcomes.iter().map(|o| o.duration_ms).sum();
+    let mut outcomes: Vec<&FileOutcome> = report.outcomes.iter().collect();
+    outcomes.sort_by_key(|o| o.status == OutcomeStatus::Ok);
+
+    let mut out = String::from(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Conversion report</title>
+<style>
+body { font-family: system-ui, sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+td.number { text-align: right; }
+tr.error td { background: #fde8e8; }
+pre { margin: 0; white-space: pre-wrap; }
+</style>
+</head>
+<body>
+<h1>Conversion report</h1>
+"#,
+    );
+
+    out.push_str("<table class=\"totals\">\n");
+    for (name, value) in [
+        ("Files", report.total().to_string()),
+        ("Converted", report.succeeded.len().to_string()),
+        ("Failed", report.failed.len().to_string()),
+        ("Converted after retrying", report.retried.len().to_string()),
+        ("Degraded extractions", report.degraded().to_string()),
+        ("Success rate", format!("{:.2}%", report.success_rate())),
+        ("Total conversion time", format!("{:.1}s", total_ms as f64 / 1000.0)),
+    ] {
+        out.push_str(&format!(
+            "<tr><th>{name}</th><td class=\"number\">{value}</td></tr>\n"
+        ));
+    }
+    out.push_str("</table>\n");
+
+    out.push_str(&format!(
+        "<h2>Slowest files</h2>\n<table class=\"slowest\">\n\
+         <tr><th>Input</th><th>Status</th><th>Duration (ms)</th><th>Input size</th><th>Output size</th></tr>\n\
+         {}</table>\n",
+        report
+            .slowest(SLOWEST)
+            .into_iter()
+            .map(|o| {
+                format!(
+                    "<tr class=\"{status}\"><td>{input}</td><td>{status}</td><td class=\"number\">{duration}</td>\
+                     <td class=\"number\">{input_size}</td><td class=\"number\">{output_size}</td></tr>\n",
+                    status = o.status.as_str(),
+                    input = html_escape(&o.input.display().to_string()),
+                    duration = o.duration_ms,
+                    input_size = o.input_size.map(format_size).unwrap_or_default(),
+                    output_size = o.output_size.map(format_size).unwrap_or_default(),
+                )
+            })
+            .collect::<String>()
+    ));
+
+    out.push_str(
+        "<h2>Files</h2>\n<table class=\"files\">\n\
+         <tr><th>Input</th><th>Output</th><th>Status</th><th>Duration (ms)</th><th>Input size</th><th>Output size</th>\
+         <th>Retries</th><th>OCR confidence</th><th>Extraction</th><th>Error</th></tr>\n",
+    );
+    for o in outcomes {
+        out.push_str(&format!(
+            "<tr class=\"{status}\"><td>{input}</td><td>{output}</td><td>{status}</td>\
+             <td class=\"number\">{duration}</td><td class=\"number\">{input_size}</td><td class=\"number\">{output_size}</td>\
+             <td class=\"number\">{retries}</td><td class=\"number\">{confidence}</td><td>{extraction}</td><td><pre>{error}</pre></td></tr>\n",
+            status = o.status.as_str(),
+            input = html_escape(&o.input.display().to_string()),
+            output = html_escape(&o.output.display().to_string()),
+            duration = o.duration_ms,
+            input_size = o.input_size.map(format_size).unwrap_or_default(),
+            output_size = o.output_size.map(format_size).unwrap_or_default(),
+            retries = o.retries,
+            confidence = o
+                .ocr_confidence
+                .map(|confidence| format!("{confidence:.1}%"))
+                .unwrap_or_default(),
+            extraction = if o.degraded { "degraded" } else { "" },
+            error = html_escape(o.error_message.as_deref().unwrap_or_default()),
+        ));
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+/// `s` with the characters HTML gives a meaning escaped.
+pub(crate) fn html_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Quotes a CSV field if (and only if) it needs it.
+pub(crate) fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod report_tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_field_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_conversion_report_rates() {
+        let mut report = ConversionReport::default();
+        assert!((report.success_rate() - 100.0).abs() < f64::EPSILON);
+        assert!(!report.has_failures());
+
+        report.record_success("a.docx", "a.md", 0, Duration::ZERO);
+        report.record_success("c.docx", "c.md", 2, Duration::ZERO);
+        assert_eq!(report.retried, [PathBuf::from("c.docx")]);
+        report.record_failure(
+            "b.docx",
+            "b.md",
+            "pandoc",
+            Error::Generic("boom".into()),
+            Duration::ZERO,
+        );
+        assert!((report.success_rate() - 200.0 / 3.0).abs() < 1e-9);
+        assert!(report.has_failures());
+        assert_eq!(report.outcomes.len(), 3);
+        assert_eq!(report.failures.len(), 1);
+    }
+
+    #[test]
+    fn test_json_shape() {
+        let outcomes = vec![
+            FileOutcome::ok("a.docx", "a.md", 1).took(Duration::from_millis(1500)),
+            FileOutcome::error("b.docx", "b.md", "boom"),
+        ];
+        let json = serde_json::to_value(&outcomes).unwrap();
+        assert_eq!(json[0]["status"], "ok");
+        assert!(json[0]["error_message"].is_null());
+        assert_eq!(json[0]["retries"], 1);
+        assert_eq!(json[0]["duration_ms"], 1500);
+        assert!(json[0]["ocr_confidence"].is_null());
+        assert_eq!(json[0]["degraded"], false);
+        assert_eq!(json[1]["status"], "error");
+        assert_eq!(json[1]["error_message"], "boom");
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut report = ConversionReport::default();
+        report.record_success("a.docx", "a.md", 0, Duration::from_millis(700));
+        report.record_success("b.docx", "b.md", 2, Duration::from_millis(1200));
+        report.record_failure(
+            "c.docx",
+            "c.md",
+            "pandoc",
+            Error::Generic("boom".into()),
+            Duration::from_millis(100),
+        );
+
+        let json = serde_json::to_value(report.summary(Duration::from_secs(2))).unwrap();
+        assert_eq!(json["files"], 3);
+        assert_eq!(json["converted"], 2);
+        assert_eq!(json["failed"], 1);
+        assert_eq!(json["retried"], 1);
+        assert_eq!(json["degraded"], 0);
+        assert!((json["success_rate"].as_f64().unwrap() - 66.67).abs() < 0.01);
+        assert_eq!(json["duration_ms"], 2000);
+        assert_eq!(json["conversion_ms"], 2000);
+        assert_eq!(json["slowest_ms"], 1200);
+        assert_eq!(json["failed_paths"], serde_json::json!(["c.docx"]));
+    }
+
+    #[test]
+    fn test_failure_detail() {
+        let mut report = ConversionReport::default();
+        let error = Error::ConverterFailed {
+            program: "pandoc".into(),
+            input:   PathBuf::from("b.docx"),
+            code:    Some(64),
+            stderr:  "Unknown reader".into(),
+        };
+        report.record_failure("b.docx", "b.md", "pandoc", error, Duration::ZERO);
+        report.record_failure(
+            "c.docx",
+            "c.md",
+            "soffice",
+            Error::Generic("boom".into()),
+            Duration::ZERO,
+        );
+
+        let json = serde_json::to_value(&report.failures).unwrap();
+        assert_eq!(json[0]["engine"], "pandoc");
+        assert_eq!(json[0]["exit_code"], 64);
+        assert_eq!(json[0]["stderr"], "Unknown reader");
+        assert!(json[1]["exit_code"].is_null());
+        assert!(json[1]["stderr"].is_null());
+        assert_eq!(json[1]["error"], "Generic error handler: boom");
+    }
+
+    #[test]
+    fn test_slowest() {
+        let mut report = ConversionReport::default();
+        for (name, ms) in [("a", 20), ("b", 300), ("c", 5), ("d", 300)] {
+            report.record_success(name, name, 0, Duration::from_millis(ms));
+        }
+
+        let slowest: Vec<_> = report
+            .slowest(3)
+            .iter()
+            .map(|o| o.input.display().to_string())
+            .collect();
+        assert_eq!(slowest, ["b", "d", "a"]);
+        assert_eq!(report.slowest(SLOWEST).len(), 4);
+    }
+
+    #[test]
+    fn test_html() {
+        let mut report = ConversionReport::default();
+        report.record_success("a.docx", "a.md", 0, Duration::from_millis(1200));
+        report.record_failure(
+            "<b>.docx",
+            "<b>.md",
+            "pandoc",
+            Error::Generic("a & b".into()),
+            Duration::from_millis(300),
+        );
+
+        let html = to_html(&report);
+        assert!(html.contains("<tr><th>Failed</th><td class=\"number\">1</td></tr>"));
+        assert!(
+            html.contains(
+                "<tr><th>Success rate</th><td class=\"number\">50.00%</td></tr>"
+            )
+        );
+        assert!(html.contains("<td>&lt;b&gt;.docx</td>"));
+        assert!(html.contains("a &amp; b"));
+        // The slowest file comes first there, failures first in the list of every file
+        let files = html.find("<h2>Files</h2>").unwrap();
+        assert!(html.find("a.docx").unwrap() < html.find("&lt;b&gt;.docx").unwrap());
+        assert!(
+            html[files..].find("&lt;b&gt;.docx").unwrap()
+                < html[files..].find("a.docx").unwrap()
+        );
+
+        assert_eq!(
+            ReportFormat::for_path(Path::new("out/report.HTML")),
+            ReportFormat::Html
+        );
+        assert_eq!(ReportFormat::for_path(Path::new("report.csv")), ReportFormat::Csv);
+        assert_eq!(ReportFormat::for_path(Path::new("report")), ReportFormat::Json);
+    }
+}
@@ -0,0 +1,104 @@
+use std::future::Future;
+use std::time::Duration;
+
+use crate::prelude::*;
+
+/// Delay before the first retry, doubled for every attempt after that.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+/// Upper bound for the delay between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Runs `f`, retrying up to `n` more times with exponential backoff if it fails
+/// with an error that might be transient (see `is_retryable`).
+///
+/// Returns how many retries it took, `0` if the first attempt succeeded.
+///
+/// # Errors
+/// * The first non-retryable error, or the last error once all retries are used up.
+pub async fn with_retry<F, Fut>(n: usize, f: F) -> Result<usize>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 0;
+
+    loop {
+        match f().await {
+            Ok(()) => return Ok(attempt),
+            Err(e) if attempt < n && is_retryable(&e) => {
+                attempt += 1;
+                warn!("Attempt {attempt}/{n} failed, retrying in {backoff:?}: {e}");
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Only errors coming out of the converter itself are worth another go,
+/// a timeout or a missing media folder won't fix itself.
+fn is_retryable(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::Generic(_)
+            | Error::PandocConversionError(_)
+            | Error::ConverterFailed { .. }
+    )
+}
+
+#[cfg(test)]
+mod retry_tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let calls = AtomicUsize::new(0);
+        let res = with_retry(3, || {
+            async {
+                if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                    Err(Error::Generic("flaky".into()))
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(res.unwrap(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_propagates_immediately() {
+        let calls = AtomicUsize::new(0);
+        let res = with_retry(3, || {
+            async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(Error::MediaFolderCreationFailed("nope".into()))
+            }
+        })
+        .await;
+
+        assert!(matches!(res, Err(Error::MediaFolderCreationFailed(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_n_retries() {
+        let calls = AtomicUsize::new(0);
+        let res = with_retry(1, || {
+            async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(Error::PandocConversionError("still broken".into()))
+            }
+        })
+        .await;
+
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::prelude::*;
+
+/// The default for `--grace-period`, how long the conversions running are waited for once a run is interrupted.
+pub const DEFAULT_GRACE_SECS: u64 = 30;
+
+/// How a run is brought to an early end, by a signal (see `on_signals`) or from the `--tui` dashboard.
+///
+/// Stopping a run starts no conversion anymore, those running are waited for.
+/// Aborting it kills those too, which removes their partial outputs (see `partial::PartialOutput`).
+/// Either way the run then finishes as usual, with a report of what it got done.
+///
+/// Clones share their state.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    stop:  CancellationToken,
+    abort: CancellationToken,
+}
+
+impl Shutdown {
+    /// Starts no conversion anymore.
+    pub fn stop(&self) {
+        self.stop.cancel();
+    }
+
+    /// Kills the conversions running as well.
+    pub fn abort(&self) {
+        self.stop.cancel();
+        self.abort.cancel();
+    }
+
+    /// Whether the run is stopping (or aborting), nothing new should be started then.
+    pub fn is_stopping(&self) -> bool {
+        self.stop.is_cancelled()
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.abort.is_cancelled()
+    }
+
+    /// Resolves once the run is stopping.
+    pub async fn stopping(&self) {
+        self.stop.cancelled().await;
+    }
+
+    /// A token cancelled once the run is aborted, cancelling it doesn't abort the run.
+    pub fn abort_token(&self) -> CancellationToken {
+        self.abort.child_token()
+    }
+
+    /// Stops the run on Ctrl-C or SIGTERM (Ctrl-Break on Windows), and aborts it on the next one
+    /// or once the conversions running have had `grace` to finish.
+    ///
+    /// Signals no longer end the process outright once this is listening for them.
+    pub fn on_signals(&self, grace: Duration) {
+        let shutdown = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = signalled().await {
+                warn!(
+                    "Failed to listen for Ctrl-C, it ends the run without cleaning up: {e}"
+                );
+                return;
+            }
+            warn!(
+                "Interrupted, starting no new conversions and waiting up to {}s for the ones running. \
+                 Interrupt again to stop them now",
+                grace.as_secs()
+            );
+            shutdown.stop();
+
+            tokio::select! {
+                _ = signalled() => {}
+                () = tokio::time::sleep(grace) => {}
+            }
+            warn!("Stopping the conversions still running");
+            shutdown.abort();
+        });
+    }
+}
+
+#[cfg(unix)]
+async fn signalled() -> std::io::Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => res,
+        _ = terminate.recv() => Ok(()),
+    }
+}
+
+#[cfg(windows)]
+async fn signalled() -> std::io::Result<()> {
+    let mut ctrl_break = tokio::signal::windows::ctrl_break()?;
+    tokio::select! {
+        res = tokio::signal::ctrl_c() => res,
+        _ = ctrl_break.recv() => Ok(()),
+    }
+}
+
+/// Keeps a converter's process from getting the Ctrl-C typed at the terminal, which only we should,
+/// so the conversions running can be waited for (see `Shutdown`). They're still killed when we abort them.
+pub(crate) fn shield_from_ctrl_c(
+    cmd: &mut tokio::process::Command,
+) -> &mut tokio::process::Command {
+    // Its own process group, the terminal signals ours
+    #[cfg(unix)]
+    cmd.process_group(0);
+    // CREATE_NEW_PROCESS_GROUP, the console signals only the group it started
+    #[cfg(windows)]
+    cmd.creation_flags(0x0000_0200);
+    cmd
+}
+
+#[cfg(test)]
+mod shutdown_tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stop_then_abort() {
+        let shutdown = Shutdown::default();
+        let (shared, token) = (shutdown.clone(), shutdown.abort_token());
+        token.cancel();
+        assert!(!shutdown.is_aborted());
+
+        let token = shutdown.abort_token();
+        shared.stop();
+        shutdown.stopping().await;
+        assert!(shutdown.is_stopping());
+        assert!(!token.is_cancelled());
+
+        shared.abort();
+        assert!(shutdown.is_aborted());
+        assert!(token.is_cancelled());
+    }
+}
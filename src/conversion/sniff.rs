@@ -0,0 +1,156 @@
+use std::io::Read;
+use std::path::Path;
+
+/// How many bytes of a file `detect` looks at.
+const HEAD_LEN: usize = 512;
+
+/// Zip-based formats: OOXML, OpenDocument, EPUB and Apple's iWork.
+const ZIP_EXTS: &[&str] = &[
+    "docx", "docm", "dotx", "dotm", "xlsx", "xlsm", "pptx", "pptm", "ppsx", "odt", "ott",
+    "ods", "odp", "epub", "pages",
+];
+
+/// Formats stored in an OLE compound file: legacy (pre-2007) Office.
+const OLE_EXTS: &[&str] = &["doc", "dot", "xls", "ppt", "pps", "msg"];
+
+/// What a file's first bytes say it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Content {
+    Empty,
+    Zip,
+    Ole,
+    Pdf,
+    Rtf,
+    Html,
+    /// Nothing we recognize, which includes every plain text format (markdown, csv, ...).
+    Unknown,
+}
+
+impl Content {
+    fn of(head: &[u8]) -> Self {
+        let text = head
+            .strip_prefix(b"\xEF\xBB\xBF")
+            .unwrap_or(head)
+            .trim_ascii_start();
+        let starts_with_ci = |prefix: &[u8]| {
+            text.get(..prefix.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+        };
+
+        if head.is_empty() {
+            Content::Empty
+        } else if head.starts_with(b"PK\x03\x04") {
+            Content::Zip
+        } else if head.starts_with(b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1") {
+            Content::Ole
+        } else if head.starts_with(b"%PDF-") {
+            Content::Pdf
+        } else if starts_with_ci(b"{\\rtf") {
+            Content::Rtf
+        } else if [b"<!doctype html".as_slice(), b"<html", b"<head", b"<body"]
+            .iter()
+            .any(|prefix| starts_with_ci(prefix))
+        {
+            Content::Html
+        } else {
+            Content::Unknown
+        }
+    }
+
+    /// What a file with extension `ext` should hold, `None` for formats we can't tell from their content.
+    fn expected(ext: &str) -> Option<Self> {
+        let ext = ext.to_ascii_lowercase();
+        match ext.as_str() {
+            "pdf" => Some(Content::Pdf),
+            "rtf" => Some(Content::Rtf),
+            _ if ZIP_EXTS.contains(&ext.as_str()) => Some(Content::Zip),
+            _ if OLE_EXTS.contains(&ext.as_str()) => Some(Content::Ole),
+            _ => None,
+        }
+    }
+
+    /// The format (as an extension, and pandoc reader) to convert this content as,
+    /// if it's specific enough to say.
+    fn format(self) -> Option<&'static str> {
+        match self {
+            Content::Html => Some("html"),
+            Content::Rtf => Some("rtf"),
+            Content::Pdf => Some("pdf"),
+            Content::Ole => Some("doc"),
+            Content::Empty | Content::Zip | Content::Unknown => None,
+        }
+    }
+}
+
+/// What `--detect-content` makes of an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detected {
+    /// Its content fits its extension, or is plain text we can't tell apart.
+    AsNamed,
+    /// It's really a file of this format, say an HTML page saved as `.docx`.
+    Actually(&'static str),
+    /// Zero bytes, most likely a placeholder a cloud export left for a file it never downloaded.
+    Empty,
+    /// Its content isn't what its extension promises, nor anything we could convert it as.
+    Mismatch,
+}
+
+/// Looks at the first bytes of `input` to see whether it's what its extension says.
+///
+/// # Errors
+/// * If `input` can't be read
+pub fn detect(input: &Path) -> std::io::Result<Detected> {
+    let mut head = Vec::with_capacity(HEAD_LEN);
+    std::fs::File::open(input)?
+        .take(HEAD_LEN as u64)
+        .read_to_end(&mut head)?;
+
+    let ext = input
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    Ok(classify(Content::of(&head), ext))
+}
+
+fn classify(content: Content, ext: &str) -> Detected {
+    let expected = Content::expected(ext);
+    let is_text = matches!(content, Content::Unknown | Content::Html);
+
+    if content == Content::Empty {
+        Detected::Empty
+    } else if expected == Some(content) || (expected.is_none() && is_text) {
+        Detected::AsNamed
+    } else {
+        content
+            .format()
+            .map_or(Detected::Mismatch, Detected::Actually)
+    }
+}
+
+#[cfg(test)]
+mod sniff_tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let detected = |head: &[u8], ext| classify(Content::of(head), ext);
+
+        assert_eq!(detected(b"PK\x03\x04rest", "docx"), Detected::AsNamed);
+        assert_eq!(detected(b"PK\x03\x04rest", "DOCX"), Detected::AsNamed);
+        assert_eq!(detected(b"# Title", "md"), Detected::AsNamed);
+        assert_eq!(detected(b"<p>fragment</p>", "html"), Detected::AsNamed);
+        assert_eq!(
+            detected(b"\xEF\xBB\xBF\r\n  <!DOCTYPE HTML><html>", "docx"),
+            Detected::Actually("html")
+        );
+        assert_eq!(detected(b"{\\rtf1\\ansi", "doc"), Detected::Actually("rtf"));
+        assert_eq!(
+            detected(b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1", "docx"),
+            Detected::Actually("doc")
+        );
+        assert_eq!(detected(b"", "docx"), Detected::Empty);
+        assert_eq!(detected(b"", "md"), Detected::Empty);
+        assert_eq!(detected(b"plain text", "docx"), Detected::Mismatch);
+        assert_eq!(detected(b"PK\x03\x04rest", "md"), Detected::Mismatch);
+    }
+}
@@ -0,0 +1,377 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use calamine::{Data, Reader, open_workbook_auto};
+
+use crate::conversion::naming::ClaimedOutputs;
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::report::csv_field;
+use crate::conversion::{
+    Converter,
+    OnExists,
+    canonical_ext,
+    is_up_to_date,
+    numbered_free_name,
+};
+use crate::prelude::*;
+
+/// What `Engine::Auto` routes to the `SpreadsheetConverter`: workbooks going to any of `OUTPUT_FORMATS`.
+/// LibreOffice still converts them to everything else (pdf, html and so on).
+pub(crate) const INPUT_FORMATS: [&str; 5] = ["xlsx", "xlsm", "xlsb", "xls", "ods"];
+
+/// What the sheets are written as, by canonical extension.
+pub(crate) const OUTPUT_FORMATS: [&str; 2] = ["csv", "markdown"];
+
+/// What the `SpreadsheetConverter` reports as its name and version, there's no program to ask.
+const NAME: &str = "calamine";
+const VERSION: &str = "calamine 0.32";
+
+/// How the `SpreadsheetConverter` writes a workbook's sheets.
+#[derive(Debug, Clone, Default)]
+pub struct SpreadsheetOptions {
+    /// A file per sheet (`--split-sheets`), rather than every sheet in a single markdown file
+    /// (or only the first in a csv, which can't hold more).
+    pub split_sheets:    bool,
+    /// What to do with a sheet's file that already exists, as with any output (`--on-exists`).
+    pub on_exists:       OnExists,
+    /// The run's outputs, so a sheet's file doesn't take another input's (see `naming::ClaimedOutputs`).
+    pub claimed_outputs: Arc<ClaimedOutputs>,
+}
+
+/// Reads spreadsheets (`.xlsx`/`.xls`/`.ods`) in-process with `calamine`, writing their sheets as CSV or markdown tables,
+/// none of which pandoc can read.
+///
+/// Cells are written as they were last calculated, formulas aren't evaluated. Dates are written as ISO 8601.
+#[derive(Debug, Default)]
+pub struct SpreadsheetConverter {
+    options: SpreadsheetOptions,
+    /// The sheets' files written next to each output with `split_sheets`, for the report (see `extra_outputs`).
+    sheets:  Mutex<HashMap<PathBuf, Vec<PathBuf>>>,
+}
+
+impl SpreadsheetConverter {
+    /// Create a new `SpreadsheetConverter`.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies `options`, whether every sheet gets a file of its own.
+    #[must_use]
+    pub fn with_options(mut self, options: SpreadsheetOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Where the sheet of `input` going to `sheet_output` is written, `None` if it's kept as it is,
+    /// an existing one being dealt with as `--on-exists` says.
+    fn sheet_output(&self, input: &Path, sheet_output: PathBuf) -> Option<PathBuf> {
+        if !sheet_output.exists() {
+            return Some(sheet_output);
+        }
+        match self.options.on_exists {
+            OnExists::Overwrite => Some(sheet_output),
+            OnExists::Rename => Some(numbered_free_name(&sheet_output)),
+            OnExists::IfNewer if !is_up_to_date(input, &sheet_output) => {
+                Some(sheet_output)
+            }
+            OnExists::IfNewer => {
+                debug!("Sheet output is up to date, skipping: {sheet_output:?}");
+                None
+            }
+            OnExists::Skip => {
+                warn!("Sheet output already exists, keeping it: {sheet_output:?}");
+                None
+            }
+        }
+    }
+}
+
+/// A sheet's name and its cells, row by row.
+struct Sheet {
+    name: String,
+    rows: Vec<Vec<String>>,
+}
+
+/// Every sheet of the workbook at `path` that has any cells, in workbook order.
+fn read_sheets(path: &Path) -> Result<Vec<Sheet>> {
+    let unreadable = |e: calamine::Error| {
+        Error::UnreadableDocument {
+            path:   path.to_path_buf(),
+            reason: e.to_string(),
+        }
+    };
+
+    let mut workbook = open_workbook_auto(path).map_err(unreadable)?;
+    let mut sheets = Vec::new();
+    for name in workbook.sheet_names() {
+        let range = workbook.worksheet_range(&name).map_err(unreadable)?;
+        if range.is_empty() {
+            continue;
+        }
+        let rows = range
+            .rows()
+            .map(|row| row.iter().map(cell_text).collect())
+            .collect();
+        sheets.push(Sheet { name, rows });
+    }
+    Ok(sheets)
+}
+
+/// What a cell holds as text, dates (and times) as ISO 8601 rather than Excel's serial numbers.
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(date) if date.is_datetime() => {
+            match date.as_datetime() {
+                Some(date) => {
+                    let date = date.format("%Y-%m-%dT%H:%M:%S").to_string();
+                    // A date without a time of day
+                    date.strip_suffix("T00:00:00")
+                        .map(str::to_string)
+                        .unwrap_or(date)
+                }
+                None => cell.to_string(),
+            }
+        }
+        cell => cell.to_string(),
+    }
+}
+
+/// `rows` as CSV, a line per row.
+fn to_csv(rows: &[Vec<String>]) -> String {
+    let mut csv = String::new();
+    for row in rows {
+        let fields: Vec<_> = row.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// `rows` as a markdown table, the first of them its header.
+/// Rows are padded to the widest, a markdown table can't be ragged.
+fn to_markdown_table(rows: &[Vec<String>]) -> String {
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0).max(1);
+    let mut table = String::new();
+    for (n, row) in rows.iter().enumerate() {
+        let cells: Vec<_> = (0..width)
+            .map(|col| {
+                row.get(col)
+                    .map(|cell| cell.replace('|', "\\|").replace('\n', "<br>"))
+                    .unwrap_or_default()
+            })
+            .collect();
+        table.push_str(&format!("| {} |\n", cells.join(" | ")));
+        if n == 0 {
+            table.push_str(&format!("|{}\n", "---|".repeat(width)));
+        }
+    }
+    table
+}
+
+/// A file name stem for `sheet`, with what's not allowed (or awkward) in file names replaced by `_`,
+/// and a `_2`, `_3`, ... suffix if another sheet of the workbook already has it (in `taken`, whatever the case).
+fn sheet_stem(sheet: &str, taken: &mut HashSet<String>) -> String {
+    let stem: String = sheet
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    let mut unique = stem.clone();
+    let mut n = 1;
+    while !taken.insert(unique.to_lowercase()) {
+        n += 1;
+        unique = format!("{stem}_{n}");
+    }
+    unique
+}
+
+#[async_trait::async_trait]
+impl Converter for SpreadsheetConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        trace!("Converting '{}' to '{}'", input.display(), output.display());
+
+        let markdown = output
+            .extension()
+            .is_some_and(|ext| canonical_ext(&ext.to_string_lossy()) == "markdown");
+        let render = |sheet: &Sheet| {
+            if markdown {
+                to_markdown_table(&sheet.rows)
+            } else {
+                to_csv(&sheet.rows)
+            }
+        };
+        let path = input.to_path_buf();
+        let sheets = tokio::task::spawn_blocking(move || read_sheets(&path)).await??;
+
+        // The first sheet goes to the output, the rest next to it as `<stem>-<sheet>.<ext>`
+        if self.options.split_sheets && sheets.len() > 1 {
+            let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+            let ext = output
+                .extension()
+                .map(|ext| ext.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let mut taken = HashSet::new();
+            let mut written = Vec::with_capacity(sheets.len() - 1);
+            for sheet in &sheets[1..] {
+                let sheet_output = output.with_file_name(format!(
+                    "{stem}-{}.{ext}",
+                    sheet_stem(&sheet.name, &mut taken)
+                ));
+                let sheet_output =
+                    self.options.claimed_outputs.claim(&sheet_output, input);
+                let Some(sheet_output) = self.sheet_output(input, sheet_output) else {
+                    continue;
+                };
+                let partial = PartialOutput::new(&sheet_output);
+                tokio::fs::write(partial.path(), render(sheet)).await?;
+                partial.commit().await?;
+                written.push(sheet_output);
+            }
+            self.sheets
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .insert(output.to_path_buf(), written);
+        }
+
+        let contents = match sheets.as_slice() {
+            [] => String::new(),
+            [only] => render(only),
+            [first, ..] if self.options.split_sheets => render(first),
+            [first, ..] if !markdown => {
+                warn!(
+                    "{input:?} has {} sheets, a csv only holds the first ('{}'), see --split-sheets",
+                    sheets.len(),
+                    first.name
+                );
+                render(first)
+            }
+            sheets => {
+                sheets
+                    .iter()
+                    .map(|sheet| format!("## {}\n\n{}", sheet.name, render(sheet)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        };
+
+        // Dropped (and removed) on any early return, so a failed write leaves no half-written output
+        let partial = PartialOutput::new(output);
+        tokio::fs::write(partial.path(), contents).await?;
+        partial.commit().await
+    }
+
+    async fn check_installed(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        NAME.to_string()
+    }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Ok(INPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        Ok(OUTPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn version(&self) -> Option<String> {
+        Some(VERSION.to_string())
+    }
+
+    fn extra_outputs(&self, output: &Path) -> Vec<PathBuf> {
+        self.sheets
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(output)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod spreadsheet_tests {
+    use super::*;
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(ToString::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let csv = to_csv(&rows(&[&["name", "note"], &["a,b", "say \"hi\""]]));
+        assert_eq!(csv, "name,note\n\"a,b\",\"say \"\"hi\"\"\"\n");
+    }
+
+    #[test]
+    fn test_to_markdown_table() {
+        let table =
+            to_markdown_table(&rows(&[&["name", "total"], &["a|b"], &["c", "2"]]));
+        assert_eq!(table, "| name | total |\n|---|---|\n| a\\|b |  |\n| c | 2 |\n");
+    }
+
+    #[test]
+    fn test_sheet_stem() {
+        let mut taken = HashSet::new();
+        assert_eq!(sheet_stem("Q1 2024", &mut taken), "Q1_2024");
+        assert_eq!(sheet_stem("Q1/2024", &mut taken), "Q1_2024_2");
+        assert_eq!(sheet_stem("q1 2024", &mut taken), "q1_2024_3");
+        assert_eq!(sheet_stem("a/b:c", &mut taken), "a_b_c");
+        assert_eq!(sheet_stem("Résumé", &mut taken), "Résumé");
+    }
+
+    #[test]
+    fn test_sheet_output() {
+        let tmp = std::env::temp_dir()
+            .join(format!("dcc_spreadsheet_exists_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp).unwrap();
+        let input = tmp.join("report.xlsx");
+        let existing = tmp.join("report-Q1.csv");
+        std::fs::write(&input, "xlsx").unwrap();
+        std::fs::write(&existing, "mine").unwrap();
+        let with = |on_exists| {
+            SpreadsheetConverter::new().with_options(SpreadsheetOptions {
+                split_sheets: true,
+                on_exists,
+                ..Default::default()
+            })
+        };
+
+        assert_eq!(with(OnExists::Skip).sheet_output(&input, existing.clone()), None);
+        assert_eq!(
+            with(OnExists::Overwrite).sheet_output(&input, existing.clone()),
+            Some(existing.clone())
+        );
+        assert_eq!(
+            with(OnExists::Rename).sheet_output(&input, existing.clone()),
+            Some(tmp.join("report-Q1 (1).csv"))
+        );
+        let free = tmp.join("report-Q2.csv");
+        assert_eq!(with(OnExists::Skip).sheet_output(&input, free.clone()), Some(free));
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+
+    #[test]
+    fn test_read_sheets_not_a_workbook() {
+        let tmp =
+            std::env::temp_dir().join(format!("dcc_spreadsheet_{}", std::process::id()));
+        std::fs::create_dir_all(&tmp).unwrap();
+        let path = tmp.join("broken.xlsx");
+        std::fs::write(&path, "not a workbook").unwrap();
+        assert!(matches!(read_sheets(&path), Err(Error::UnreadableDocument { .. })));
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}
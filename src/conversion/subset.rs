@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::prelude::*;
+
+/// The part of the discovered files a trial run converts (`--limit`, `--sample`),
+/// picked in walk order, which is by name once a subset is asked for (see `walk_dir`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subset {
+    /// The first `n` files.
+    First(usize),
+    /// `n` files picked at random, the same ones for the same `seed` and tree.
+    Sample { n: usize, seed: u64 },
+}
+
+impl Subset {
+    /// `--sample` wins over `--limit`, a sample without a `seed` gets one from the clock.
+    pub fn new(
+        limit: Option<usize>,
+        sample: Option<usize>,
+        seed: Option<u64>,
+    ) -> Option<Self> {
+        match (sample, limit) {
+            (Some(n), _) => {
+                Some(Subset::Sample {
+                    n,
+                    seed: seed.unwrap_or_else(clock_seed),
+                })
+            }
+            (None, Some(n)) => Some(Subset::First(n)),
+            (None, None) => None,
+        }
+    }
+
+    /// Picks the subset out of `found`, keeping the order they came in, and logs which files it picked
+    /// and how to pick them again.
+    ///
+    /// `First` stops pulling from `found` once it has enough, so a walk behind it stops early too.
+    pub fn select<I: IntoIterator<Item = PathBuf>>(&self, found: I) -> Vec<PathBuf> {
+        let selected = match *self {
+            Subset::First(n) => {
+                let selected: Vec<PathBuf> = found.into_iter().take(n).collect();
+                info!("Selected the first {} files (--limit {n})", selected.len());
+                selected
+            }
+            Subset::Sample { n, seed } => {
+                let found: Vec<PathBuf> = found.into_iter().collect();
+                let total = found.len();
+                let selected = sample(found, n, seed);
+                info!(
+                    "Selected {} of {total} files at random, rerun with --sample {n} --seed {seed} for the same ones",
+                    selected.len()
+                );
+                selected
+            }
+        };
+
+        for path in &selected {
+            info!("Selected {path:?}");
+        }
+        selected
+    }
+}
+
+/// `n` of `found` picked with a partial Fisher-Yates shuffle, back in their original order.
+fn sample(found: Vec<PathBuf>, n: usize, seed: u64) -> Vec<PathBuf> {
+    if n >= found.len() {
+        return found;
+    }
+
+    let mut rng = SplitMix64(seed);
+    let mut indices: Vec<usize> = (0..found.len()).collect();
+    for i in 0..n {
+        #[allow(clippy::cast_possible_truncation)]
+        let j = i + (rng.next() % (indices.len() - i) as u64) as usize;
+        indices.swap(i, j);
+    }
+    indices.truncate(n);
+    indices.sort_unstable();
+
+    let mut found: Vec<Option<PathBuf>> = found.into_iter().map(Some).collect();
+    indices
+        .into_iter()
+        .filter_map(|idx| found[idx].take())
+        .collect()
+}
+
+/// A seed for when `--sample` is given none, logged along with the selection so it can be reused.
+fn clock_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|since| since.as_secs() ^ u64::from(since.subsec_nanos()))
+        .unwrap_or_default()
+}
+
+/// Small and, unlike a library generator, guaranteed to pick the same files for a seed in every release.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod subset_tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let files = || (0..100).map(|i| PathBuf::from(format!("{i:03}.docx")));
+
+        assert_eq!(
+            Subset::First(2).select(files()),
+            [PathBuf::from("000.docx"), PathBuf::from("001.docx")]
+        );
+        assert_eq!(Subset::First(200).select(files()).len(), 100);
+
+        let sampled = Subset::Sample { n: 10, seed: 7 }.select(files());
+        assert_eq!(sampled.len(), 10);
+        assert!(sampled.is_sorted());
+        assert_eq!(sampled, Subset::Sample { n: 10, seed: 7 }.select(files()));
+        assert_ne!(sampled, Subset::Sample { n: 10, seed: 8 }.select(files()));
+        assert_eq!(Subset::Sample { n: 200, seed: 7 }.select(files()).len(), 100);
+
+        assert_eq!(Subset::new(Some(5), None, None), Some(Subset::First(5)));
+        assert_eq!(
+            Subset::new(Some(5), Some(3), Some(1)),
+            Some(Subset::Sample { n: 3, seed: 1 })
+        );
+        assert_eq!(Subset::new(None, None, Some(1)), None);
+    }
+}
@@ -0,0 +1,181 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::conversion::partial::PartialOutput;
+use crate::conversion::shutdown::shield_from_ctrl_c;
+use crate::conversion::{Converter, canonical_ext, find_program, version_of};
+use crate::prelude::*;
+
+/// What `Engine::Auto` routes to typst: `.typ` sources going to any of `OUTPUT_FORMATS`.
+/// Pandoc still reads them for everything else (markdown, html and so on).
+pub(crate) const INPUT_FORMATS: [&str; 1] = ["typ"];
+
+/// What `typst compile` writes, picked by the output's extension.
+pub(crate) const OUTPUT_FORMATS: [&str; 3] = ["pdf", "png", "svg"];
+
+/// Compiles Typst sources with the `typst` CLI (`typst compile`), which pandoc can't typeset.
+///
+/// A pdf holds the whole document. Images are one per page: the output is the first page,
+/// and a document with more has the rest written next to it as `<stem>-2.png`, `<stem>-3.png` and so on.
+pub struct TypstConverter<P = PathBuf> {
+    program_name: P,
+}
+
+impl TypstConverter {
+    /// Create a new `TypstConverter` for the `typst` in PATH,
+    /// falling back to the bare name for `check_installed` to report as missing.
+    #[inline]
+    pub fn new() -> Self {
+        let program_name = find_program("typst").unwrap_or_else(|| {
+            debug!("Couldn't find typst in PATH, falling back to 'typst'");
+            PathBuf::from("typst")
+        });
+        Self { program_name }
+    }
+
+    /// Runs `typst compile input target`, failing with what typst printed.
+    async fn compile(&self, input: &Path, target: &Path) -> Result<()> {
+        let mut command = tokio::process::Command::new(&self.program_name);
+        let cmd_output = shield_from_ctrl_c(&mut command)
+            .arg("compile")
+            .arg(input)
+            .arg(target)
+            // A timed-out conversion drops this future, take typst down with it
+            .kill_on_drop(true)
+            .output()
+            .await?;
+
+        if !cmd_output.status.success() {
+            let mut stderr = String::new();
+            cmd_output.stderr.as_slice().read_to_string(&mut stderr)?;
+            return Err(Error::ConverterFailed {
+                program: self.name(),
+                input: input.to_path_buf(),
+                code: cmd_output.status.code(),
+                stderr,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Default for TypstConverter {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl Converter for TypstConverter {
+    async fn convert(&self, input: &Path, output: &Path) -> Result<()> {
+        trace!("Compiling '{}' to '{}'", input.display(), output.display());
+
+        let ext = output
+            .extension()
+            .map(|ext| canonical_ext(&ext.to_string_lossy()))
+            .ok_or_else(|| Error::InvalidExtension(output.display().to_string()))?;
+        if ext == "pdf" {
+            let partial = PartialOutput::new(output);
+            self.compile(input, partial.path()).await?;
+            return partial.commit().await;
+        }
+
+        // Images take a page number template, `{p}`, typst refuses to write several pages without one
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        let partial =
+            PartialOutput::in_staging_dir(output, Path::new(&format!("{stem}.{ext}")))
+                .await?;
+        let staging = partial
+            .path()
+            .parent()
+            .unwrap_or(partial.path())
+            .to_path_buf();
+        self.compile(input, &staging.join(format!("{stem}-{{p}}.{ext}")))
+            .await?;
+
+        let mut pages = Vec::new();
+        let mut entries = tokio::fs::read_dir(&staging).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(page) =
+                page_number(&entry.file_name().to_string_lossy(), &stem, &ext)
+            {
+                pages.push((page, entry.path()));
+            }
+        }
+        pages.sort_unstable();
+
+        let Some(((_, first), rest)) = pages.split_first() else {
+            return Err(Error::ConverterFailed {
+                program: self.name(),
+                input:   input.to_path_buf(),
+                code:    Some(0),
+                stderr:  format!("no pages written to {}", staging.display()),
+            });
+        };
+        for (page, path) in rest {
+            tokio::fs::rename(
+                path,
+                output.with_file_name(format!("{stem}-{page}.{ext}")),
+            )
+            .await?;
+        }
+        tokio::fs::rename(first, partial.path()).await?;
+        partial.commit().await
+    }
+
+    async fn check_installed(&self) -> bool {
+        let checked = tokio::process::Command::new(&self.program_name)
+            .arg("--version")
+            .output()
+            .await
+            .map(|output| output.status.success());
+
+        debug!("Checked if {:?} is installed: {checked:?}", self.program_name);
+        checked.unwrap_or_else(|_| {
+            warn!("{:?} is not installed", self.program_name);
+            false
+        })
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        self.program_name.display().to_string()
+    }
+
+    async fn supported_input_formats(&self) -> Result<Vec<String>> {
+        Ok(INPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn supported_output_formats(&self) -> Result<Vec<String>> {
+        Ok(OUTPUT_FORMATS.iter().map(ToString::to_string).collect())
+    }
+
+    async fn version(&self) -> Option<String> {
+        version_of(&self.program_name).await
+    }
+}
+
+/// The page of `<stem>-<page>.<ext>`, as typst names the images of a `{p}` template.
+fn page_number(name: &str, stem: &str, ext: &str) -> Option<usize> {
+    name.strip_prefix(stem)?
+        .strip_prefix('-')?
+        .strip_suffix(ext)?
+        .strip_suffix('.')?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod typst_tests {
+    use super::*;
+
+    #[test]
+    fn test_page_number() {
+        assert_eq!(page_number("report-1.png", "report", "png"), Some(1));
+        assert_eq!(page_number("report-12.svg", "report", "svg"), Some(12));
+        assert_eq!(page_number("report-x.png", "report", "png"), None);
+        assert_eq!(page_number("report.png", "report", "png"), None);
+        assert_eq!(page_number("report-1.svg", "report", "png"), None);
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::task::JoinSet;
+
+use crate::conversion::events::{Event, SkipReason};
+use crate::conversion::filter::PathFilter;
+use crate::conversion::fingerprint::{Fingerprint, Fingerprints};
+use crate::conversion::ignores::is_ignored;
+use crate::conversion::originals::ORIGINALS_DIR;
+use crate::conversion::registry::ConverterRegistry;
+use crate::conversion::sniff::Detected;
+use crate::conversion::{
+    ConvertOptions,
+    Converter,
+    convert_single,
+    converter_for,
+    detect_content,
+    existing_output,
+    fix_file_name,
+    format_output_dir,
+    has_ext,
+    needs_fixing,
+    prepare_output_path,
+    relative_to,
+    remove_dot,
+    within_depth,
+};
+use crate::prelude::*;
+
+/// How long a path has to stay quiet before we convert it,
+/// editors and copy tools tend to fire a burst of events per write.
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+/// Watches `root` recursively and converts every file with one of the extensions `exts`
+/// that is created or modified to each of `target_exts`, until Ctrl-C is received.
+///
+/// With `options.detect_content` each file's content is checked once it settles (see `detect_content`),
+/// though its converter is still picked by its extension.
+///
+/// An output that already exists is dealt with as `options.on_exists` says, as in a batch run,
+/// and what's converted has its fingerprint recorded (see `fingerprint::Fingerprints`).
+///
+/// In-flight conversions are drained before returning.
+///
+/// # Errors
+/// * If the filesystem watcher can't be set up.
+/// * If the fingerprints file can't be read or written.
+pub async fn watch<P>(
+    root: P,
+    exts: &[String],
+    registry: &ConverterRegistry,
+    target_exts: &[String],
+    output_dir: Option<P>,
+    options: &ConvertOptions,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let root = root.as_ref().to_path_buf();
+    let ext = exts.join("', '.");
+    let output_dir = output_dir.map(|p| p.as_ref().to_path_buf());
+    let several = target_exts.len() > 1;
+    let fingerprints = options
+        .fingerprints
+        .as_ref()
+        .map(|path| Fingerprints::open(path).map(Arc::new))
+        .transpose()?;
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            match res {
+                Ok(event)
+                    if matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_)
+                    ) =>
+                {
+                    for path in event.paths {
+                        // The receiver only goes away once we're shutting down
+                        let _ = tx.send(path);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => error!("Watch error: {e:?}"),
+            }
+        })
+        .map_err(|e| Error::Generic(format!("Failed to create file watcher: {e}")))?;
+
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .map_err(|e| {
+            Error::Generic(format!("Failed to watch {}: {e}", root.display()))
+        })?;
+
+    info!("Watching {} for new '.{ext}' files, press Ctrl-C to stop", root.display());
+
+    let pending: Arc<Mutex<HashSet<PathBuf>>> = Arc::default();
+    let permits = options.job_permits();
+    let mut tasks = JoinSet::new();
+    let mut n = 0;
+
+    loop {
+        tokio::select! {
+            () = options.shutdown.stopping() => {
+                info!("Stopping the watcher");
+                break;
+            }
+            Some(path) = rx.recv() => {
+                if !has_ext(&path, exts) {
+                    continue;
+                }
+                if !relative_to(&root, &path).is_ok_and(|rel| within_depth(rel, options.max_depth)) {
+                    trace!("Outside --max-depth, ignoring: {path:?}");
+                    continue;
+                }
+                if !relative_to(&root, &path).is_ok_and(|rel| allowed_by(&options.filter, rel)) {
+                    trace!("Left out by --include/--exclude, ignoring: {path:?}");
+                    continue;
+                }
+                if options.ignore_files && is_ignored(&root, &path, false) {
+                    trace!("Ignored by an ignore file, ignoring: {path:?}");
+                    continue;
+                }
+                if relative_to(&root, &path).is_ok_and(|rel| rel.starts_with(ORIGINALS_DIR)) {
+                    trace!("Put aside by --delete-source, ignoring: {path:?}");
+                    continue;
+                }
+                if !pending.lock().expect("watch pending set poisoned").insert(path.clone()) {
+                    trace!("Already queued: {path:?}");
+                    continue;
+                }
+
+                let targets = target_exts
+                    .iter()
+                    .map(|target_ext| {
+                        let target_ext = remove_dot(target_ext).to_string();
+                        let converter = converter_for(registry, &path, None, &target_ext)?;
+                        let dir = format_output_dir(output_dir.as_deref(), &target_ext, several);
+                        Ok((target_ext, converter, dir))
+                    })
+                    .collect::<Result<Vec<_>>>();
+                let targets = match targets {
+                    Ok(targets) => targets,
+                    Err(e) => {
+                        error!("{e}");
+                        continue;
+                    }
+                };
+                let pending = Arc::clone(&pending);
+                let permits = Arc::clone(&permits);
+                let root = root.clone();
+                let options = options.clone();
+                let fingerprints = fingerprints.clone();
+                n += 1;
+
+                tasks.spawn(async move {
+                    tokio::time::sleep(SETTLE_DELAY).await;
+                    pending.lock().expect("watch pending set poisoned").remove(&path);
+                    if let Some(ref metrics) = options.metrics {
+                        metrics.queued();
+                    }
+                    let _permit = permits
+                        .acquire_owned()
+                        .await
+                        .expect("job semaphore is never closed");
+                    if let Some(ref metrics) = options.metrics {
+                        metrics.dequeued();
+                    }
+
+                    // Only now that it settled are its size and modification time final
+                    if !options.filter.allows_file(&path) {
+                        return;
+                    }
+                    if let Some(ref events) = options.events {
+                        events.emit(&Event::Discovered { input: &path });
+                    }
+                    let detected = if options.detect_content && path.is_file() {
+                        detect_content(&path)
+                    } else {
+                        Detected::AsNamed
+                    };
+                    let format = match detected {
+                        Detected::AsNamed => None,
+                        Detected::Actually(format) => Some(format),
+                        // Most likely still being downloaded, which fires another event
+                        Detected::Empty => {
+                            if let Some(ref metrics) = options.metrics {
+                                metrics.skipped();
+                            }
+                            if let Some(ref events) = options.events {
+                                events.emit(&Event::Skipped {
+                                    input:  &path,
+                                    output: None,
+                                    reason: SkipReason::Empty,
+                                });
+                            }
+                            return;
+                        }
+                        Detected::Mismatch => {
+                            if let Some(ref metrics) = options.metrics {
+                                metrics.failed();
+                            }
+                            error!("{}", Error::ContentMismatch(path));
+                            return;
+                        }
+                    };
+
+                    for (target_ext, converter, output_dir) in targets {
+                        let res = convert_watched(
+                            &root,
+                            path.clone(),
+                            converter,
+                            format,
+                            n,
+                            &target_ext,
+                            output_dir.as_deref(),
+                            &options,
+                            fingerprints.as_deref(),
+                        )
+                        .await;
+
+                        if let Err(e) = res {
+                            error!("Failed to convert {path:?} to '.{target_ext}': {e}");
+                        }
+                    }
+                });
+            }
+            Some(res) = tasks.join_next(), if !tasks.is_empty() => {
+                if let Err(e) = res {
+                    error!("Task panicked or was cancelled: {e:?}");
+                }
+            }
+        }
+    }
+
+    drop(watcher);
+
+    if !tasks.is_empty() {
+        info!("Waiting for {} in-flight conversions to finish", tasks.len());
+    }
+    let abort = options.shutdown.abort_token();
+    loop {
+        tokio::select! {
+            res = tasks.join_next() => match res {
+                Some(Err(e)) => error!("Task panicked or was cancelled: {e:?}"),
+                Some(Ok(())) => {}
+                None => break,
+            },
+            // Dropping them kills the converters, and removes their partial outputs
+            () = abort.cancelled() => {
+                tasks.shutdown().await;
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `filter` allows the file at `relative` and every folder on the way to it,
+/// as it would have to for the walk to find it.
+fn allowed_by(filter: &PathFilter, relative: &Path) -> bool {
+    relative
+        .ancestors()
+        .filter(|ancestor| !ancestor.as_os_str().is_empty())
+        .all(|ancestor| filter.allows(ancestor, ancestor != relative))
+}
+
+/// Converts a single file picked up by the watcher, read as `format` if it's known.
+#[allow(clippy::too_many_arguments)]
+async fn convert_watched(
+    root: &Path,
+    input: PathBuf,
+    converter: Arc<dyn Converter>,
+    format: Option<&str>,
+    n: usize,
+    target_ext: &str,
+    output_dir: Option<&Path>,
+    options: &ConvertOptions,
+    fingerprints: Option<&Fingerprints>,
+) -> Result<()> {
+    if !input.is_file() {
+        // Removed or renamed again before it settled
+        return Ok(());
+    }
+
+    // Renaming fires its own create event, which picks up the fixed name
+    if let Some(ref renames) = options.renames
+        && input
+            .file_name()
+            .is_some_and(|name| needs_fixing(name, &options.name_rules))
+    {
+        return fix_file_name(&input, &options.name_rules, renames)
+            .await
+            .map(|_| ());
+    }
+
+    let relative = relative_to(root, &input)?;
+    let mut output =
+        prepare_output_path(&input, relative, target_ext, output_dir, n, options).await?;
+
+    let print = Fingerprint::of(&input).ok();
+    if output.exists() {
+        let last = fingerprints.and_then(|f| f.get(&output));
+        match existing_output(&input, output, options.on_exists, last, print) {
+            Ok(to) => output = to,
+            Err((output, reason)) => {
+                if let Some(ref metrics) = options.metrics {
+                    metrics.skipped();
+                }
+                if let Some(ref events) = options.events {
+                    events.emit(&Event::Skipped {
+                        input: &input,
+                        output: Some(&output),
+                        reason,
+                    });
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    let engine = converter.name();
+    if let Some(ref events) = options.events {
+        events.emit(&Event::Started {
+            input:  &input,
+            output: &output,
+            engine: &engine,
+        });
+    }
+    if let Some(ref metrics) = options.metrics {
+        metrics.started();
+    }
+    let started = std::time::Instant::now();
+    let res = convert_single(
+        converter,
+        input.clone(),
+        output.clone(),
+        format,
+        options.timeout,
+        options.retries,
+        options.preserve_metadata,
+    )
+    .await;
+    if let Some(ref events) = options.events {
+        events.finished(&input, &output, &engine, &res, started.elapsed());
+    }
+    if let Some(ref metrics) = options.metrics {
+        metrics.finished(&input, &res, started.elapsed());
+    }
+    let retries = res?;
+    if let (Some(fingerprints), Some(print)) = (fingerprints, print)
+        && let Err(e) = fingerprints.record(&input, &output, print)
+    {
+        warn!("Failed to record the fingerprint of {input:?}: {e}");
+    }
+    match retries {
+        0 => info!("Converted '{}' to '{}'", input.display(), output.display()),
+        n => {
+            info!(
+                "Converted '{}' to '{}' after {n} retries",
+                input.display(),
+                output.display()
+            )
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,294 @@
+use std::fs::{File, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use sha2::{Digest, Sha256};
+use tracing::{debug, error, warn};
+
+use crate::prelude::{Error, Result};
+
+/// The pandoc binary `build.rs` embeds: always on Windows, on Linux and macOS
+/// with the `embed-pandoc-linux`/`embed-pandoc-macos` features.
+#[cfg(embedded_pandoc)]
+pub const PANDOC_BINARY_EMBED: &[u8] = include_bytes!(env!("PANDOC_EMBEDDED_PATH"));
+/// What the embedded binary is unpacked as, in its folder below the per-user cache, see `unpack_path`.
+#[cfg(embedded_pandoc)]
+pub const PANDOC_UNPACKED_NAME: &str = if cfg!(windows) {
+    "pandoc.exe"
+} else {
+    "pandoc"
+};
+/// What versions before the per-user cache unpacked the embedded binary as in the temp folder, see `clean`.
+#[cfg(all(embedded_pandoc, windows))]
+pub const PANDOC_TEMP_NAME: &str = "pandoc_upx.exe";
+/// What versions before the per-user cache unpacked the embedded binary as in the temp folder, see `clean`.
+#[cfg(all(embedded_pandoc, not(windows)))]
+pub const PANDOC_TEMP_NAME: &str = "pandoc_embedded";
+#[cfg(embedded_pandoc)]
+pub const PANDOC_EXPECTED_SHA256: [u8; 32] =
+    decode_sha256_hex(env!("PANDOC_EXPECTED_SHA256"));
+
+/// The binary this run unpacked, and its `pandoc.in-use` file, locked shared until the process exits.
+static IN_USE: OnceLock<(PathBuf, File)> = OnceLock::new();
+
+/// Deletes the binary `unpack` unpacked once dropped (`--ephemeral-binary`), which happens on a panic too.
+///
+/// A binary another run is still using is left where it is, the last of them to finish takes it.
+/// Without a built-in pandoc (or if it was never unpacked) there's nothing to delete.
+#[derive(Debug, Default)]
+pub struct EphemeralBinary(());
+
+impl Drop for EphemeralBinary {
+    fn drop(&mut self) {
+        let Some((pandoc_path, in_use)) = IN_USE.get() else {
+            return;
+        };
+        match remove_unpacked(pandoc_path, in_use) {
+            Ok(true) => debug!("Removed the unpacked pandoc at {pandoc_path:?}"),
+            Ok(false) => {
+                debug!(
+                    "Another run is still using the pandoc at {pandoc_path:?}, leaving it"
+                );
+            }
+            Err(e) => {
+                warn!("Failed to remove the unpacked pandoc at {pandoc_path:?}: {e}")
+            }
+        }
+    }
+}
+
+/// Where the embedded binary is unpacked: `embedded-pandoc-<hash>/pandoc(.exe)` below `pandoc_cache_dir`
+/// (`%LOCALAPPDATA%` on Windows, the XDG cache on Linux), or below the temp folder if there's none.
+///
+/// Named after `PANDOC_EXPECTED_SHA256`, so builds embedding different pandocs each unpack their own.
+#[cfg(embedded_pandoc)]
+#[must_use]
+pub fn unpack_path() -> PathBuf {
+    let hash: String = PANDOC_EXPECTED_SHA256[..6]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    crate::prelude::pandoc_cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(format!("embedded-pandoc-{hash}"))
+        .join(PANDOC_UNPACKED_NAME)
+}
+
+/// Makes sure `pandoc_path` holds the embedded binary, executable on unix, before it's ever run.
+///
+/// Runs started at the same time take turns, see `unpack_locked`. A file already there is only kept if it hashes to `PANDOC_EXPECTED_SHA256`
+/// (computed over the embedded file by `build.rs`). Anything else, like one a different version
+/// left behind or someone swapped out, is replaced, see `unpack_verified`.
+///
+/// # Errors
+/// * `Error::PandocBinaryIntegrityFailure` - If the embedded bytes, or what was written, don't match the hash
+/// * `Error::TokioIoError` - If the binary couldn't be written or read back
+#[cfg(embedded_pandoc)]
+pub fn unpack(pandoc_path: &Path) -> Result<()> {
+    let in_use =
+        unpack_locked(pandoc_path, PANDOC_BINARY_EMBED, &PANDOC_EXPECTED_SHA256)?;
+    IN_USE.get_or_init(|| (pandoc_path.to_owned(), in_use));
+    Ok(())
+}
+
+/// `unpack_verified` while holding an exclusive lock on `pandoc.lock` next to `pandoc_path`,
+/// creating the folder first.
+///
+/// Another run checking or unpacking the same binary waits for this one, instead of both writing it at once.
+/// The locks go with the file handles, so a run that's killed half way doesn't leave them held.
+///
+/// Returns `pandoc.in-use`, locked shared before `pandoc.lock` is let go: as long as it's open,
+/// `remove_unpacked` of another run leaves the binary alone.
+#[cfg_attr(not(embedded_pandoc), allow(dead_code))]
+fn unpack_locked(pandoc_path: &Path, binary: &[u8], expected: &[u8; 32]) -> Result<File> {
+    if let Some(dir) = pandoc_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let lock = open_lock(&pandoc_path.with_extension("lock"))?;
+    lock.lock()?;
+    unpack_verified(pandoc_path, binary, expected)?;
+
+    let in_use = open_lock(&pandoc_path.with_extension("in-use"))?;
+    in_use.lock_shared()?;
+    Ok(in_use)
+}
+
+/// Deletes `pandoc_path` unless another run holds its `pandoc.in-use` too, `false` if one does.
+///
+/// Under `pandoc.lock`, so no run unpacks it (and starts using it) in the meantime. The lock files themselves
+/// stay, a run waiting on one would otherwise end up locking a file nobody else sees.
+fn remove_unpacked(pandoc_path: &Path, in_use: &File) -> std::io::Result<bool> {
+    let lock = open_lock(&pandoc_path.with_extension("lock"))?;
+    lock.lock()?;
+
+    // Ours is shared like everyone else's, only exclusive once it's the last one
+    in_use.unlock()?;
+    match in_use.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            in_use.lock_shared()?;
+            return Ok(false);
+        }
+        Err(TryLockError::Error(e)) => return Err(e),
+    }
+    std::fs::remove_file(pandoc_path)?;
+    Ok(true)
+}
+
+fn open_lock(path: &Path) -> std::io::Result<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+}
+
+/// `unpack` for any `binary` that should hash to `expected`.
+///
+/// The bytes themselves are checked too before they're written, so a build that embedded
+/// something other than what it hashed fails instead of unpacking it. They're written next to `pandoc_path`
+/// and only renamed to it once they check out, it never holds half a binary.
+#[cfg_attr(not(embedded_pandoc), allow(dead_code))]
+fn unpack_verified(pandoc_path: &Path, binary: &[u8], expected: &[u8; 32]) -> Result<()> {
+    if pandoc_path.exists() {
+        let unpacked = std::fs::read(pandoc_path)?;
+        if Sha256::digest(&unpacked).as_slice() == expected {
+            return Ok(());
+        }
+        warn!(
+            "Unpacked pandoc binary at {pandoc_path:?} does not match the embedded hash, unpacking it again"
+        );
+        std::fs::remove_file(pandoc_path).inspect_err(|e| {
+            error!("Failed to remove mismatched pandoc binary: {e}");
+        })?;
+    }
+
+    if Sha256::digest(binary).as_slice() != expected {
+        error!("The embedded pandoc binary does not match the hash it was built with");
+        return Err(Error::PandocBinaryIntegrityFailure);
+    }
+
+    let partial = partial_path(pandoc_path);
+    let mut file = std::fs::File::create(&partial)?;
+    file.write_all(binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o755))?;
+    }
+    drop(file);
+
+    // Read back, in case the disk (or something else) mangled it
+    let unpacked = std::fs::read(&partial)?;
+    if Sha256::digest(&unpacked).as_slice() != expected {
+        error!("Unpacked pandoc binary at {partial:?} does not match the embedded hash");
+        if let Err(e) = std::fs::remove_file(&partial) {
+            error!("Failed to remove mismatched pandoc binary: {e}");
+        }
+        return Err(Error::PandocBinaryIntegrityFailure);
+    }
+    std::fs::rename(&partial, pandoc_path)?;
+    Ok(())
+}
+
+/// `.pandoc.partial` next to `pandoc`.
+fn partial_path(pandoc_path: &Path) -> PathBuf {
+    let name = pandoc_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy();
+    pandoc_path.with_file_name(format!(".{name}.partial"))
+}
+
+/// Decodes the hex digest `build.rs` hands us via `PANDOC_EXPECTED_SHA256`.
+#[cfg(embedded_pandoc)]
+const fn decode_sha256_hex(hex: &str) -> [u8; 32] {
+    const fn nibble(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            b'A'..=b'F' => c - b'A' + 10,
+            _ => panic!("PANDOC_EXPECTED_SHA256 is not valid hex"),
+        }
+    }
+
+    let hex = hex.as_bytes();
+    assert!(hex.len() == 64, "PANDOC_EXPECTED_SHA256 must be 64 hex characters");
+
+    let mut out = [0u8; 32];
+    let mut i = 0;
+    while i < 32 {
+        out[i] = (nibble(hex[i * 2]) << 4) | nibble(hex[i * 2 + 1]);
+        i += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod embedded_tests {
+    use super::*;
+
+    #[test]
+    fn test_unpack_verified() {
+        let path =
+            std::env::temp_dir().join(format!("dcc_unpack_{}", std::process::id()));
+        let binary = b"#!/bin/sh\necho pandoc\n";
+        let expected: [u8; 32] = Sha256::digest(binary).into();
+
+        std::fs::write(&path, "stale").unwrap();
+        unpack_verified(&path, binary, &expected).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), binary);
+        // Already there and intact, kept as it is
+        unpack_verified(&path, binary, &expected).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            unpack_verified(&path, b"something else", &expected),
+            Err(Error::PandocBinaryIntegrityFailure)
+        ));
+        assert!(!path.exists());
+        assert!(!partial_path(&path).exists());
+    }
+
+    #[test]
+    fn test_unpack_locked() {
+        let dir = std::env::temp_dir()
+            .join(format!("dcc_unpack_locked_{}", std::process::id()));
+        let path = dir.join("pandoc");
+        let binary = vec![7u8; 1 << 20];
+        let expected: [u8; 32] = Sha256::digest(&binary).into();
+
+        // Runs starting at once all end up with the same intact binary
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| unpack_locked(&path, &binary, &expected).unwrap());
+            }
+        });
+        assert_eq!(std::fs::read(&path).unwrap(), binary);
+        assert!(!partial_path(&path).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remove_unpacked() {
+        let dir = std::env::temp_dir()
+            .join(format!("dcc_remove_unpacked_{}", std::process::id()));
+        let path = dir.join("pandoc");
+        let binary = b"#!/bin/sh\necho pandoc\n";
+        let expected: [u8; 32] = Sha256::digest(binary).into();
+
+        let ours = unpack_locked(&path, binary, &expected).unwrap();
+        let theirs = unpack_locked(&path, binary, &expected).unwrap();
+        // Still in use by the other run
+        assert!(!remove_unpacked(&path, &ours).unwrap());
+        assert!(path.exists());
+
+        drop(theirs);
+        assert!(remove_unpacked(&path, &ours).unwrap());
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -12,11 +12,24 @@ pub enum Error {
     TokioIoError(#[from] tokio::io::Error),
 
     #[error("Directory traversal error: {0}")]
-    DirectoryTraversalError(#[from] walkdir::Error),
+    DirectoryTraversalError(#[from] ignore::Error),
 
     #[error("Pandoc conversion error, failed for: {0}")]
     PandocConversionError(String),
 
+    #[error(
+        "{program} failed to convert {input:?} ({}): {}",
+        code.map_or_else(|| "killed by a signal".to_string(), |c| format!("exit code {c}")),
+        stderr.trim()
+    )]
+    ConverterFailed {
+        program: String,
+        input:   PathBuf,
+        /// `None` if the process was killed by a signal.
+        code:    Option<i32>,
+        stderr:  String,
+    },
+
     #[error("Invalid extension: {0}")]
     InvalidExtension(String),
 
@@ -32,6 +45,84 @@ pub enum Error {
     #[error("Failed to rename file: {0}")]
     FailedRenameFile(PathBuf),
 
+    #[error("Pandoc {found} is too old, {required} or newer is needed")]
+    PandocTooOld { found: String, required: String },
+
+    #[error("No PDF engine to convert to pdf with: {0}")]
+    NoPdfEngine(String),
+
     #[error("Pandoc binary not found: {0}")]
     PandocNotFound(String),
+
+    #[error("Embedded pandoc binary failed its SHA256 integrity check")]
+    PandocBinaryIntegrityFailure,
+
+    #[error("Failed to install pandoc (--auto-install): {0}")]
+    PandocInstallFailed(String),
+
+    /// Wraps the `ConverterFailed` of the run, which keeps its exit code and stderr.
+    #[error("Pandoc rejected the reference doc {path:?}: {source}")]
+    ReferenceDocRejected {
+        path:   PathBuf,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[error("Conversion cancelled after an earlier failure (--fail-fast)")]
+    Cancelled,
+
+    #[error("Skipped from the --tui dashboard: {0:?}")]
+    Skipped(PathBuf),
+
+    #[error(
+        "{0:?} doesn't hold what its extension says, nor anything it could be converted as"
+    )]
+    ContentMismatch(PathBuf),
+
+    #[error("Couldn't read {path:?}: {reason}")]
+    UnreadableDocument { path: PathBuf, reason: String },
+
+    #[error("Conversion timed out after {seconds}s: {path:?}")]
+    ConversionTimeout { path: PathBuf, seconds: u64 },
+
+    #[error("Invalid glob pattern: {0}")]
+    InvalidGlob(#[from] globset::Error),
+
+    #[error("Report serialization error: {0}")]
+    ReportSerializationError(#[from] serde_json::Error),
+
+    #[error("Config file error: {0}")]
+    ConfigParseError(#[from] toml::de::Error),
+
+    #[error("Config serialization error: {0}")]
+    ConfigSerializationError(#[from] toml::ser::Error),
+
+    #[error("Log file error: {0}")]
+    LogFileError(#[from] tracing_appender::rolling::InitError),
+
+    #[cfg(feature = "otel")]
+    #[error("OpenTelemetry exporter error: {0}")]
+    OtelExporterError(#[from] opentelemetry_otlp::ExporterBuildError),
+}
+
+impl Error {
+    /// The converter's exit code, for a conversion whose process failed.
+    #[inline]
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            Error::ConverterFailed { code, .. } => *code,
+            Error::ReferenceDocRejected { source, .. } => source.exit_code(),
+            _ => None,
+        }
+    }
+
+    /// What the converter printed to stderr, for a conversion whose process failed.
+    #[inline]
+    pub fn stderr(&self) -> Option<&str> {
+        match self {
+            Error::ConverterFailed { stderr, .. } => Some(stderr),
+            Error::ReferenceDocRejected { source, .. } => source.stderr(),
+            _ => None,
+        }
+    }
 }
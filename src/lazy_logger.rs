@@ -1,15 +1,50 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use tokio::io::AsyncWriteExt;
 
+use crate::conversion::report::{FileOutcome, OutcomeStatus};
+
+/// Where `LazyLogger::flush_async` writes its buffer to.
+#[derive(Debug, Default)]
+pub enum LazyLoggerSink {
+    #[default]
+    Stdout,
+    File(tokio::fs::File),
+    Both(tokio::fs::File),
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Default)]
 pub struct LazyLogger {
-    buf: Vec<u8>,
+    buf:  Vec<u8>,
+    sink: LazyLoggerSink,
 }
 
 #[allow(dead_code)]
 impl LazyLogger {
+    /// Sends the log to `path` (appending) instead of stdout.
+    ///
+    /// # Errors
+    /// * If the file can't be opened or created
+    pub fn with_file(mut self, path: PathBuf) -> crate::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        self.sink = LazyLoggerSink::File(tokio::fs::File::from_std(file));
+        Ok(self)
+    }
+
+    /// Keeps writing to stdout as well, if a file sink has been set.
+    #[must_use]
+    pub fn tee_stdout(mut self) -> Self {
+        self.sink = match self.sink {
+            LazyLoggerSink::File(f) | LazyLoggerSink::Both(f) => LazyLoggerSink::Both(f),
+            LazyLoggerSink::Stdout => LazyLoggerSink::Stdout,
+        };
+        self
+    }
+
     pub fn log_input_output<P: AsRef<Path>>(&mut self, input: P, output: P) {
         self.new_line();
         self.insert(format!(
@@ -19,6 +54,23 @@ impl LazyLogger {
         ));
     }
 
+    pub fn log_outcome(&mut self, outcome: &FileOutcome) {
+        self.log_input_output(&outcome.input, &outcome.output);
+        match outcome.status {
+            OutcomeStatus::Ok => self.insert(": ok"),
+            OutcomeStatus::Error => {
+                self.insert(format!(
+                    ": error: {}",
+                    outcome
+                        .error_message
+                        .as_deref()
+                        .unwrap_or_default()
+                        .trim_end()
+                ))
+            }
+        }
+    }
+
     pub fn insert(&mut self, s: impl AsRef<str>) {
         self.buf.extend_from_slice(s.as_ref().as_bytes());
     }
@@ -35,8 +87,28 @@ impl LazyLogger {
 
     pub async fn flush_async(&mut self) -> crate::Result<()> {
         self.new_line();
-        tokio::io::stdout().write_all(&self.buf).await?;
+        match self.sink {
+            LazyLoggerSink::Stdout => {
+                write_stdout(&self.buf).await?;
+            }
+            LazyLoggerSink::File(ref mut file) => {
+                file.write_all(&self.buf).await?;
+                file.flush().await?;
+            }
+            LazyLoggerSink::Both(ref mut file) => {
+                file.write_all(&self.buf).await?;
+                file.flush().await?;
+                write_stdout(&self.buf).await?;
+            }
+        }
         self.buf.clear();
         Ok(())
     }
 }
+
+async fn write_stdout(buf: &[u8]) -> crate::Result<()> {
+    let mut stdout = tokio::io::stdout();
+    stdout.write_all(buf).await?;
+    stdout.flush().await?;
+    Ok(())
+}
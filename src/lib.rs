@@ -0,0 +1,86 @@
+//! Crawls a directory for documents and images and converts them with pandoc, LibreOffice, typst,
+//! Asciidoctor, Calibre or ImageMagick, the library behind the `document_conversion_crawler_rs` binary.
+//!
+//! A run is three steps: set up the converters (`ConverterRegistry`), collect the files (`find_by_ext`,
+//! or `read_input_list` for a list of them) and convert them (`convert_files`).
+//! Failed files don't fail the run, they end up in its `ConversionReport`.
+//!
+//! ```no_run
+//! use document_conversion_crawler_rs::{
+//!     ConvertOptions,
+//!     ConverterRegistry,
+//!     Engine,
+//!     convert_files,
+//!     find_by_ext,
+//! };
+//!
+//! # async fn run() -> document_conversion_crawler_rs::Result<()> {
+//! let (inputs, outputs) = (["docx".to_string()], ["md".to_string()]);
+//! let options = ConvertOptions::default();
+//!
+//! let mut registry = ConverterRegistry::for_engine(
+//!     Engine::Auto,
+//!     Default::default(),
+//!     Default::default(),
+//!     &outputs,
+//! );
+//! registry.ensure_installed().await?;
+//!
+//! let found = find_by_ext("docs", &inputs, None, &options).await?;
+//! let report = convert_files(found, &registry, &outputs, Some("converted"), &options).await?;
+//! for (input, error) in &report.failed {
+//!     eprintln!("{}: {error}", input.display());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Everything is logged through `tracing`, nothing is printed unless a subscriber is set up.
+//! Converters of your own implement `Converter` and are added with `ConverterRegistry::register`.
+
+pub mod conversion;
+// the pandoc binary built in: always on Windows, on unix with an `embed-pandoc-*` feature
+mod embedded;
+pub mod error;
+pub mod lazy_logger;
+pub mod log_file;
+#[cfg(feature = "otel")]
+pub mod otel;
+pub mod prelude;
+pub mod progress;
+pub mod tui;
+
+// platform-specific prelude setup
+#[cfg(unix)]
+mod pre_unix;
+
+// platform-specific prelude setup
+#[cfg(windows)]
+mod pre_windows;
+
+pub use crate::conversion::asciidoctor::AsciidoctorConverter;
+pub use crate::conversion::calibre::CalibreConverter;
+pub use crate::conversion::docx::DocxFallbackConverter;
+pub use crate::conversion::email::EmailConverter;
+pub use crate::conversion::image::ImageConverter;
+pub use crate::conversion::libreoffice::{LibreOfficeConverter, LibreOfficeOptions};
+pub use crate::conversion::markdown::MarkdownConverter;
+pub use crate::conversion::ocr::{OcrConverter, OcrOptions};
+pub use crate::conversion::pandoc::{PandocConverter, PandocOptions};
+pub use crate::conversion::pptx::PptxConverter;
+pub use crate::conversion::registry::ConverterRegistry;
+pub use crate::conversion::report::{ConversionReport, FileOutcome};
+pub use crate::conversion::spreadsheet::{SpreadsheetConverter, SpreadsheetOptions};
+pub use crate::conversion::typst::TypstConverter;
+pub use crate::conversion::{
+    ConvertOptions,
+    ConvertableEnts,
+    Converter,
+    Engine,
+    OnExists,
+    convert_files,
+    find_by_ext,
+    read_input_list,
+};
+pub use crate::error::Error;
+pub use crate::prelude::Result;
@@ -0,0 +1,177 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+use crate::prelude::*;
+
+/// When `--log-file` moves on to a new file (`--log-rotation`).
+#[derive(Debug, clap::ValueEnum, Clone, Copy, PartialEq, Eq, Default)]
+#[clap(name = "LogRotation", rename_all = "lower")]
+pub enum LogRotation {
+    /// Everything goes to the one file.
+    #[default]
+    Never,
+    /// A file per hour, named `<file>.YYYY-MM-DD-HH`.
+    Hourly,
+    /// A file per day, named `<file>.YYYY-MM-DD`.
+    Daily,
+    /// Once the file reaches `--log-max-size`, it's renamed to `<file>.1` (`.1` to `.2`, ...) and started over.
+    Size,
+}
+
+/// Where `--log-file` writes the log, rotated as `rotation` says and keeping the last `keep` rotated files.
+/// `max_size` is only used by `LogRotation::Size`.
+///
+/// # Errors
+/// * If the file or the folder it's in can't be created
+pub fn log_file_writer(
+    path: &Path,
+    rotation: LogRotation,
+    max_size: u64,
+    keep: usize,
+) -> Result<BoxMakeWriter> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir)?;
+
+    let rotation = match rotation {
+        LogRotation::Never => {
+            return Ok(BoxMakeWriter::new(Mutex::new(append(path)?)));
+        }
+        LogRotation::Size => {
+            return Ok(BoxMakeWriter::new(SizeRotating::open(path, max_size, keep)?));
+        }
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| Error::Generic(format!("{path:?} isn't a file name")))?;
+    let appender = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(name.to_string_lossy())
+        .max_log_files(keep.max(1))
+        .build(dir)?;
+    Ok(BoxMakeWriter::new(appender))
+}
+
+fn append(path: &Path) -> std::io::Result<File> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+}
+
+/// A log file that's moved aside once it would grow past `max_size`,
+/// keeping the last `keep` of them as `<file>.1` (the newest) to `<file>.<keep>`.
+#[derive(Debug)]
+struct SizeRotating {
+    path:     PathBuf,
+    max_size: u64,
+    keep:     usize,
+    /// The open file, `None` only while it's being rotated, and how much is in it.
+    file:     Mutex<(Option<File>, u64)>,
+}
+
+impl SizeRotating {
+    fn open(path: &Path, max_size: u64, keep: usize) -> std::io::Result<Self> {
+        let file = append(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_size,
+            keep,
+            file: Mutex::new((Some(file), len)),
+        })
+    }
+
+    /// `<file>.<n>`.
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+
+    /// Shifts the rotated files up by one, dropping the oldest, and moves the current one to `<file>.1`.
+    fn rotate(&self) -> std::io::Result<()> {
+        if self.keep == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        let _ = std::fs::remove_file(self.rotated(self.keep));
+        for n in (1..self.keep).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                std::fs::rename(from, self.rotated(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated(1))
+    }
+}
+
+impl<'a> MakeWriter<'a> for SizeRotating {
+    type Writer = &'a Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
+
+impl Write for &SizeRotating {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut file = self.file.lock().expect("log file lock poisoned");
+        let (ref mut current, ref mut len) = *file;
+        // A line bigger than the limit still gets a file of its own, rather than one per write
+        if *len > 0 && *len + buf.len() as u64 > self.max_size {
+            // Closed first, Windows can't rename a file that's open
+            drop(current.take());
+            self.rotate()?;
+            *len = 0;
+        }
+        let current = match current {
+            Some(current) => current,
+            None => current.insert(append(&self.path)?),
+        };
+        current.write_all(buf)?;
+        *len += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.file.lock().expect("log file lock poisoned").0 {
+            Some(ref mut current) => current.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod log_file_tests {
+    use super::*;
+
+    #[test]
+    fn test_size_rotation() {
+        let tmp =
+            std::env::temp_dir().join(format!("dcc_log_file_{}", std::process::id()));
+        let path = tmp.join("logs/dcc.log");
+        let writer = log_file_writer(&path, LogRotation::Size, 10, 2).unwrap();
+
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            writer.make_writer().write_all(line.as_bytes()).unwrap();
+        }
+
+        let read = |path: &Path| std::fs::read_to_string(path).unwrap();
+        assert_eq!(read(&path), "fourth\n");
+        assert_eq!(read(&tmp.join("logs/dcc.log.1")), "third\n");
+        assert_eq!(read(&tmp.join("logs/dcc.log.2")), "second\n");
+        assert!(!tmp.join("logs/dcc.log.3").exists());
+
+        std::fs::remove_dir_all(&tmp).unwrap();
+    }
+}
@@ -1,14 +1,3 @@
-#[allow(unused_imports)]
-use crate::prelude::get_pandoc_path;
-
-#[macro_export]
-macro_rules! pandoc_path {
-    () => {
-        $crate::prelude::get_pandoc_path()
-            .expect("Could not get internal/embedded pandoc path")
-    };
-}
-
 #[macro_export]
 macro_rules! crate_name {
     () => {
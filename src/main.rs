@@ -1,58 +1,695 @@
 mod cli;
-mod conversion;
-mod error;
-mod lazy_logger;
 mod macros;
-mod prelude;
-
-// platform-specific prelude setup
-#[cfg(unix)]
-mod pre_unix;
-
-// platform-specific prelude setup
-#[cfg(windows)]
-mod pre_windows;
 
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::ExitCode;
 use std::sync::Arc;
 
-pub use crate::prelude::*;
+#[cfg(feature = "otel")]
+use document_conversion_crawler_rs::otel;
+use document_conversion_crawler_rs::{
+    conversion,
+    lazy_logger,
+    log_file,
+    prelude,
+    progress,
+    tui,
+};
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
 
-// perhaps we use channels to send/recv. The Command output into a bytes channel buffer
-// -- AIM: smooth out the programm calls to pandoc binary (beofre using a native rs lib)
+use crate::prelude::*;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args = cli::Cli::new();
-    let level = args.verbosity_level().into();
-    init_logger(level).init();
+/// The exit code of a run stopped early, as shells give a command ended by Ctrl-C (128 + SIGINT).
+const INTERRUPTED: u8 = 130;
 
-    let converter = Arc::new(conversion::pandoc::PandocConverter::new());
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    let command = cli::Cli::parse_command();
+    let level = command.verbosity_level().into();
+    // With --pipe stdout is the converted document, for list the files found
+    let writer = match command {
+        cli::Command::Convert(ref args) if args.pipe => progress::LogWriter::stderr(),
+        cli::Command::Convert(ref args) | cli::Command::Watch(ref args)
+            if args.events.as_deref()
+                == Some(Path::new(conversion::events::STDOUT_EVENTS)) =>
+        {
+            progress::LogWriter::stderr()
+        }
+        cli::Command::Convert(ref args) | cli::Command::Watch(ref args)
+            if args.summary == cli::SummaryFormat::Json
+                && args.summary_file.is_none() =>
+        {
+            progress::LogWriter::stderr()
+        }
+        cli::Command::List(_) => progress::LogWriter::stderr(),
+        _ => progress::LogWriter::default(),
+    };
+    let file_layer = match command {
+        cli::Command::Convert(ref args) | cli::Command::Watch(ref args) => {
+            file_logger(args)?
+        }
+        _ => None,
+    };
+    // Only conversions are exported, so only for the commands that run them
+    #[cfg(feature = "otel")]
+    let (otel_layer, _otel_guard) = match command {
+        cli::Command::Convert(_) | cli::Command::Watch(_) => {
+            let (layer, guard) = otel::otel_layer()?;
+            (Some(layer), Some(guard))
+        }
+        _ => (None, None),
+    };
+    #[cfg(not(feature = "otel"))]
+    let otel_layer = None::<tracing_subscriber::layer::Identity>;
+    let shutdown = conversion::shutdown::Shutdown::default();
+    // The dashboard is drawn on stderr, so it's only shown if that's a terminal
+    let dashboard = match command {
+        cli::Command::Convert(ref args)
+            if args.tui && std::io::stderr().is_terminal() =>
+        {
+            let jobs = args.jobs.unwrap_or_else(conversion::default_jobs);
+            Some(Arc::new(tui::Dashboard::new(jobs, shutdown.clone())))
+        }
+        _ => None,
+    };
+    init_logger(level, writer, command.log_format())
+        .with(file_layer)
+        .with(otel_layer)
+        .with(dashboard.as_ref().map(tui::Dashboard::log_layer))
+        .with(WarningCounter.with_filter(EnvFilter::from(command.verbosity_level())))
+        .init();
 
-    let convertables = conversion::find_by_ext(
-        //
-        &args.input_directory,
-        &args.input_extension,
-    )
-    .await?;
+    match command {
+        cli::Command::Convert(args) | cli::Command::Watch(args) => {
+            convert(&args, dashboard, shutdown).await
+        }
+        cli::Command::List(args) => list(&args).await,
+        cli::Command::Doctor(args) => doctor(&args).await,
+        cli::Command::Clean(args) => clean(&args).await,
+        cli::Command::Completions(args) => {
+            cli::Cli::print_completions(args.shell);
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
 
-    info!("Found {} files to convert", convertables.as_ref().count());
-    // trace!("Processable Entities: {:#?}", processable);
+/// `convert` and `watch`: sets up the converters for `--engine`, then runs the batch (see `run`),
+/// unless only the formats (`--list-formats`) or a single document (`--pipe`) are asked for.
+async fn convert(
+    args: &cli::ConvertArgs,
+    dashboard: Option<Arc<tui::Dashboard>>,
+    shutdown: conversion::shutdown::Shutdown,
+) -> Result<ExitCode> {
+    for warning in &args.config_warnings {
+        warn!("{warning}");
+    }
+    // Dropped on the way out of here, however the run ends
+    let _ephemeral = args.ephemeral_binary.then(EphemeralBinary::default);
+    if let Some(ref pandoc_path) = args.pandoc_path {
+        set_pandoc_path(pandoc_path.clone());
+    }
+    // A pinned pandoc that isn't there fails here, not as a plain `pandoc` that isn't installed
+    if args.engine != conversion::Engine::LibreOffice {
+        pandoc_path_override()?;
+    }
 
     if let Some(ref output_dir) = args.output_directory
+        && !args.dry_run
         && !output_dir.exists()
     {
         tokio::fs::create_dir_all(output_dir).await?;
     }
 
-    conversion::convert_files(
-        convertables,
-        converter,
-        &args.output_extension.as_str(),
-        args.output_directory.as_ref(),
-    )
-    .await?;
+    #[cfg(unix)]
+    if args.auto_install
+        && args.engine != conversion::Engine::LibreOffice
+        && get_pandoc_path().is_err()
+    {
+        install_pandoc().await?;
+    }
+
+    let output_exts = args.output_extensions();
+    // Picked now, rather than every pdf failing with pandoc's complaint about a missing pdflatex
+    let pdf_engine = if !args.list_formats
+        && conversion::pdf::needs_engine(
+            args.engine,
+            &args.input_extensions(),
+            &output_exts,
+        ) {
+        Some(conversion::pdf::PdfEngine::find(args.pdf_engine)?)
+    } else {
+        args.pdf_engine
+    };
+    // Shared with the spreadsheet converter, which claims a file per sheet with --split-sheets
+    let claimed_outputs = Arc::default();
+    let mut registry = converter_registry(args, pdf_engine, &claimed_outputs);
+
+    if args.list_formats {
+        return list_formats(&registry).await;
+    }
+    // A run routed entirely to other converters (markdown to HTML with --engine auto) doesn't need pandoc
+    registry.drop_unused_fallback(&args.input_extensions(), &output_exts);
+
+    if let Err(e) = registry.ensure_installed().await {
+        #[cfg(unix)]
+        if !args.auto_install
+            && args.engine != conversion::Engine::LibreOffice
+            && get_pandoc_path().is_err()
+        {
+            info!("Run with --auto-install to download pandoc");
+        }
+        return Err(e);
+    }
+
+    if args.pipe {
+        let input_exts = args.input_extensions();
+        conversion::pipe::pipe(
+            &registry,
+            args.pipe_input.as_deref(),
+            input_exts.first().map(String::as_str),
+            &output_exts[0],
+            args.timeout,
+            args.retries,
+        )
+        .await?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    run(args, &registry, claimed_outputs, dashboard, shutdown).await
+}
+
+/// The layer writing the log to `--log-file`, if there is one.
+///
+/// # Errors
+/// * If the file can't be created
+fn file_logger<S>(
+    args: &cli::ConvertArgs,
+) -> Result<Option<Box<dyn Layer<S> + Send + Sync>>>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    let Some(ref path) = args.log_file else {
+        return Ok(None);
+    };
+    let writer = log_file::log_file_writer(
+        path,
+        args.log_rotation,
+        args.log_max_size,
+        args.log_keep,
+    )?;
+    Ok(Some(
+        log_layer(writer, args.log_format, false)
+            .with_filter(EnvFilter::from(args.log_file_level))
+            .boxed(),
+    ))
+}
+
+/// Runs the batch conversion (and optional watch mode) with the selected engine(s),
+/// on the `--tui` `dashboard` if there is one. A signal, or the dashboard, stops it early through `shutdown`.
+async fn run(
+    args: &cli::ConvertArgs,
+    registry: &conversion::registry::ConverterRegistry,
+    claimed_outputs: Arc<conversion::naming::ClaimedOutputs>,
+    dashboard: Option<Arc<tui::Dashboard>>,
+    shutdown: conversion::shutdown::Shutdown,
+) -> Result<ExitCode> {
+    let started = std::time::Instant::now();
+    let mut options = convert_options(args)?;
+    options.claimed_outputs = claimed_outputs;
+
+    if args.dry_run {
+        return dry_run(args, registry, &options).await;
+    }
+    // Opened only now, a dry run has nothing to report
+    options.events = args
+        .events
+        .as_ref()
+        .map(|target| conversion::events::EventLog::open(target).map(std::sync::Arc::new))
+        .transpose()?;
+    if let Some(addr) = args.metrics_addr {
+        let metrics = std::sync::Arc::new(conversion::metrics::Metrics::default());
+        std::sync::Arc::clone(&metrics).serve(addr).await?;
+        options.metrics = Some(metrics);
+    }
+    if args.tui && dashboard.is_none() {
+        warn!("--tui needs a terminal to draw on, carrying on without the dashboard");
+    }
+    options.dashboard.clone_from(&dashboard);
+    options.shutdown = shutdown;
+    options
+        .shutdown
+        .on_signals(std::time::Duration::from_secs(args.grace_period));
+    // Closed once the conversions are done, putting the terminal back and printing the log held back
+    let screen = dashboard.map(tui::Screen::show).transpose()?;
+
+    let input_exts = args.input_extensions();
+    let output_exts = args.output_extensions();
+
+    let report = if let Some(ref input_list) = args.input_list {
+        let convertables =
+            conversion::read_input_list(input_list, &input_exts, &options).await?;
+
+        info!("Found {} files to convert", convertables.len());
+
+        conversion::convert_files(
+            convertables,
+            registry,
+            &output_exts,
+            args.output_directory.as_ref(),
+            &options,
+        )
+        .await?
+    } else if args.no_stream {
+        let supported = registry.supported_input_formats().await.ok();
+        let convertables = conversion::find_by_ext(
+            //
+            args.input_directory(),
+            &input_exts,
+            supported.as_deref(),
+            &options,
+        )
+        .await?;
+
+        info!("Found {} files to convert", convertables.len());
+        if convertables.is_empty() {
+            warn!(
+                "No '.{}' files found in {:?}",
+                input_exts.join("', '."),
+                args.input_directory()
+            );
+        }
+        // trace!("Processable Entities: {:#?}", processable);
+
+        conversion::convert_files(
+            convertables,
+            registry,
+            &output_exts,
+            args.output_directory.as_ref(),
+            &options,
+        )
+        .await?
+    } else {
+        conversion::find_and_convert(
+            args.input_directory(),
+            &input_exts,
+            registry,
+            &output_exts,
+            args.output_directory.as_deref(),
+            &options,
+        )
+        .await?
+    };
+    drop(screen);
+    // Stopping the watcher afterwards is how watch mode ends, not stopping early
+    let stopped_early = options.shutdown.is_stopping();
+    if stopped_early {
+        warn!("Stopped early, not every file was converted");
+    }
+
+    if let Some(ref report_path) = args.report {
+        let format = args
+            .report_format
+            .unwrap_or_else(|| conversion::report::ReportFormat::for_path(report_path));
+        conversion::report::write_report(report_path, &report, format).await?;
+    }
+    if let Some(ref error_report) = args.error_report {
+        conversion::report::write_error_report(error_report, &report).await?;
+    }
+    if let Some(ref quarantine_dir) = args.quarantine_dir
+        && report.has_failures()
+    {
+        // Listed inputs can live anywhere, they're quarantined by file name
+        let root = args.input_list.is_none().then(|| args.input_directory());
+        conversion::quarantine::quarantine(
+            quarantine_dir,
+            &report.failures,
+            root,
+            args.quarantine_move,
+        )
+        .await?;
+    }
+
+    if let Some(disposal) = args.delete_source {
+        let root = args.input_list.is_none().then(|| args.input_directory());
+        conversion::originals::dispose_sources(&report.outcomes, root, disposal).await;
+    }
+
+    if let Some(ref conversion_log) = args.conversion_log {
+        let mut logger =
+            lazy_logger::LazyLogger::default().with_file(conversion_log.clone())?;
+        if args.log_tee {
+            logger = logger.tee_stdout();
+        }
+        for outcome in &report.outcomes {
+            logger.log_outcome(outcome);
+        }
+        logger.flush_async().await?;
+    }
+
+    if report.has_failures() {
+        error!("{} of {} files failed to convert", report.failed.len(), report.total());
+    } else {
+        info!("Successfully converted all files");
+    }
+    let summary = report.summary(started.elapsed());
+    if let Some(ref summary_file) = args.summary_file {
+        conversion::report::write_summary(summary_file, &summary).await?;
+    } else if args.summary == cli::SummaryFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    }
+
+    if args.watch && !stopped_early {
+        conversion::watch::watch(
+            args.input_directory(),
+            &input_exts,
+            registry,
+            &output_exts,
+            args.output_directory.as_deref(),
+            &options,
+        )
+        .await?;
+    }
+
+    if stopped_early {
+        return Ok(ExitCode::from(INTERRUPTED));
+    }
+    if report.has_failures() {
+        return Ok(ExitCode::FAILURE);
+    }
+    if args.strict && warning_count() > 0 {
+        error!("{} warnings were logged, failing the run (--strict)", warning_count());
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// The `ConvertOptions` for `args`, with the run's state (checkpoint, fingerprints, renames)
+/// kept in the output directory, or the input directory without one.
+///
+/// # Errors
+/// * If an `--include`/`--exclude` glob or `--modified-*` time from the config file is invalid
+fn convert_options(args: &cli::ConvertArgs) -> Result<conversion::ConvertOptions> {
+    let state_dir = args
+        .output_directory
+        .as_deref()
+        .unwrap_or_else(|| args.input_directory());
+    Ok(conversion::ConvertOptions {
+        timeout:            args.timeout,
+        fail_fast:          args.fail_fast,
+        output_pattern:     args.output_pattern.clone(),
+        retries:            args.retries,
+        max_depth:          args.max_depth,
+        filter:             conversion::filter::PathFilter::new(
+            &args.include,
+            &args.exclude,
+        )?
+        .with_sizes(args.min_size, args.max_size)
+        .with_modified(
+            modified_at(args.modified_since.as_deref())?,
+            modified_at(args.modified_before.as_deref())?,
+        ),
+        ignore_files:       !args.no_ignore,
+        follow_symlinks:    args.follow_symlinks,
+        list_nul_separated: args.null_separated,
+        order:              args.order,
+        subset:             conversion::subset::Subset::new(
+            args.limit,
+            args.sample,
+            args.seed,
+        ),
+        name_rules:         conversion::NameRules {
+            danger_chars:  args.danger_chars.chars().collect(),
+            replacement:   args.replacement.clone(),
+            max_len:       args.max_name_len,
+            trim_trailing: args.trim_trailing,
+        },
+        jobs:               args.jobs.unwrap_or_else(conversion::default_jobs),
+        checkpoint:         Some(state_dir.join(conversion::checkpoint::FILE_NAME)),
+        resume:             args.resume,
+        fingerprints:       Some(state_dir.join(conversion::fingerprint::FILE_NAME)),
+        flatten:            args.flatten.map(|suffix| {
+            std::sync::Arc::new(conversion::naming::FlatNames::new(suffix))
+        }),
+        on_exists:          args.on_exists,
+        preserve_metadata:  args.preserve_metadata,
+        claimed_outputs:    std::sync::Arc::default(),
+        detect_content:     args.detect_content,
+        renames:            args.sanitize_names.then(|| {
+            std::sync::Arc::new(conversion::renames::RenameJournal::new(
+                state_dir.join(conversion::renames::FILE_NAME),
+            ))
+        }),
+        events:             None,
+        metrics:            None,
+        dashboard:          None,
+        shutdown:           conversion::shutdown::Shutdown::default(),
+    })
+}
+
+/// Prints what a run would rename and convert, and to where, without changing anything (`--dry-run`).
+async fn dry_run(
+    args: &cli::ConvertArgs,
+    registry: &conversion::registry::ConverterRegistry,
+    options: &conversion::ConvertOptions,
+) -> Result<ExitCode> {
+    let planned = plan(args, registry, options).await?;
+    for conversion in &planned {
+        println!("{conversion}");
+    }
+
+    let skipped = planned.iter().filter(|p| p.skipped).count();
+    info!(
+        "Dry run: {} conversions would run, {skipped} skipped, nothing was changed",
+        planned.len() - skipped
+    );
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Plans every file the run would pick up, see `Planner`.
+async fn plan(
+    args: &cli::ConvertArgs,
+    registry: &conversion::registry::ConverterRegistry,
+    options: &conversion::ConvertOptions,
+) -> Result<Vec<conversion::plan::PlannedConversion>> {
+    let output_exts = args.output_extensions();
+    let planner = conversion::plan::Planner {
+        registry,
+        target_exts: &output_exts,
+        output_dir: args.output_directory.as_deref(),
+        options,
+    };
+
+    let input_exts = args.input_extensions();
+    if let Some(ref input_list) = args.input_list {
+        planner.plan_list(input_list, &input_exts).await
+    } else {
+        planner
+            .plan_dir(args.input_directory(), &input_exts, args.no_stream)
+            .await
+    }
+}
+
+/// Prints the formats every registered converter reports it supports (`--list-formats`).
+async fn list_formats(
+    registry: &conversion::registry::ConverterRegistry,
+) -> Result<ExitCode> {
+    let mut code = ExitCode::SUCCESS;
 
-    info!("Successfully converted all files");
+    for converter in registry.converters() {
+        let name = converter.name();
+        let formats = tokio::try_join!(
+            converter.supported_input_formats(),
+            converter.supported_output_formats()
+        );
+
+        match formats {
+            Ok((input, output)) => {
+                println!("Input formats ({name}):\n  {}", input.join("\n  "));
+                println!("Output formats ({name}):\n  {}", output.join("\n  "));
+            }
+            Err(e) => {
+                error!("{name} can't list its supported formats: {e}");
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    Ok(code)
+}
+
+/// Resolves `--modified-since`/`--modified-before`, which a config file may have set to anything.
+fn modified_at(when: Option<&str>) -> Result<Option<std::time::SystemTime>> {
+    when.map(conversion::filter::parse_time)
+        .transpose()
+        .map_err(Error::Generic)
+}
 
-    Ok(())
+/// The converters for `--engine`, set up with the pandoc and LibreOffice options, not yet checked.
+/// Pdf is written with `pdf_engine`, see `PdfEngine::find`.
+/// With `--ocr` scanned PDFs and images are read with Tesseract first, see `OcrConverter`,
+/// and with `--split-sheets` spreadsheets get a file per sheet.
+fn converter_registry(
+    args: &cli::ConvertArgs,
+    pdf_engine: Option<conversion::pdf::PdfEngine>,
+    claimed_outputs: &Arc<conversion::naming::ClaimedOutputs>,
+) -> conversion::registry::ConverterRegistry {
+    let pandoc_options = conversion::pandoc::PandocOptions {
+        defaults: args.defaults.clone(),
+        template: args.template.clone(),
+        reference_doc: args.reference_doc.clone(),
+        bibliography: args.bibliography.clone(),
+        csl: args.csl.clone(),
+        citeproc: args.citeproc,
+        lua_filters: args.lua_filters.clone(),
+        extra_args: args.pandoc_args.clone(),
+        server: args.pandoc_server,
+        min_version: args.min_pandoc_version.clone(),
+        pdf_engine,
+    };
+    let libreoffice_options = conversion::libreoffice::LibreOfficeOptions {
+        program: args.libreoffice_program.clone(),
+    };
+    let output_exts = args.output_extensions();
+    let mut registry = conversion::registry::ConverterRegistry::for_engine(
+        args.engine,
+        pandoc_options.clone(),
+        libreoffice_options,
+        &output_exts,
+    );
+    if args.ocr {
+        let pandoc =
+            conversion::pandoc::PandocConverter::new().with_options(pandoc_options);
+        let ocr = conversion::ocr::OcrConverter::new(Box::new(pandoc)).with_options(
+            conversion::ocr::OcrOptions {
+                lang: args.ocr_lang.clone(),
+            },
+        );
+        registry.register_ocr(ocr, &output_exts);
+    }
+    if args.split_sheets && args.engine == conversion::Engine::Auto {
+        registry.register_spreadsheets(
+            conversion::spreadsheet::SpreadsheetOptions {
+                split_sheets:    true,
+                on_exists:       args.on_exists,
+                claimed_outputs: Arc::clone(claimed_outputs),
+            },
+            &output_exts,
+        );
+    }
+    registry
+}
+
+/// Prints the files `convert` would pick up with the same arguments (`list`),
+/// and once an output format is given where each would be converted to and by what, as `--dry-run` plans it.
+/// Nothing is renamed, created or converted.
+async fn list(args: &cli::ListArgs) -> Result<ExitCode> {
+    let format = args.format;
+    let args = &args.convert;
+    for warning in &args.config_warnings {
+        warn!("{warning}");
+    }
+
+    let options = convert_options(args)?;
+    let input_exts = args.input_extensions();
+    let output_exts = args.output_extensions();
+
+    if output_exts.is_empty() {
+        let found = if let Some(ref input_list) = args.input_list {
+            conversion::list_entries(input_list, &input_exts, &options).await?
+        } else {
+            conversion::discover(args.input_directory(), &input_exts, &options).await?
+        };
+
+        match format {
+            cli::ListFormat::Text => {
+                for path in &found {
+                    println!("{}", path.display());
+                }
+            }
+            cli::ListFormat::Json => {
+                let found: Vec<_> = found
+                    .iter()
+                    .map(|input| serde_json::json!({ "input": input }))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&found)?);
+            }
+        }
+        info!("{} files would be converted", found.len());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // A missing converter only changes which one a file is routed to, it doesn't stop the listing
+    let mut registry =
+        converter_registry(args, args.pdf_engine, &options.claimed_outputs);
+    if let Err(e) = registry.ensure_installed().await {
+        warn!("{e}");
+    }
+    let planned = plan(args, &registry, &options).await?;
+
+    match format {
+        cli::ListFormat::Text => {
+            for conversion in &planned {
+                println!("{conversion}");
+            }
+        }
+        cli::ListFormat::Json => println!("{}", serde_json::to_string_pretty(&planned)?),
+    }
+    let skipped = planned.iter().filter(|p| p.skipped).count();
+    info!("{} conversions would run, {skipped} skipped", planned.len() - skipped);
+
+    Ok(ExitCode::SUCCESS)
+}
+/// Prints whether each converter of `--engine` is installed, and its version (`doctor`).
+/// Fails if the engine couldn't run a conversion.
+async fn doctor(args: &cli::DoctorArgs) -> Result<ExitCode> {
+    let routed: Vec<_> = conversion::registry::ROUTED_OUTPUTS
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    let mut registry = conversion::registry::ConverterRegistry::for_engine(
+        args.engine,
+        conversion::pandoc::PandocOptions::default(),
+        conversion::libreoffice::LibreOfficeOptions::default(),
+        &routed,
+    );
+
+    for converter in registry.converters() {
+        match converter.version().await {
+            Some(version) => println!("ok       {}: {version}", converter.name()),
+            None => println!("missing  {}", converter.name()),
+        }
+    }
+    match cli::config::Config::find() {
+        Some(path) => println!("config   {}", path.display()),
+        None => println!("config   none found"),
+    }
+
+    if let Err(e) = registry.ensure_installed().await {
+        error!("{e}");
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Removes what crashed runs left below a directory and in the temp folder (`clean`).
+async fn clean(args: &cli::CleanArgs) -> Result<ExitCode> {
+    let output_exts = args
+        .output_extension
+        .as_deref()
+        .map(conversion::split_extensions)
+        .unwrap_or_default();
+    let leftovers =
+        conversion::clean::clean(&args.directory, &output_exts, args.dry_run).await?;
+    if args.dry_run {
+        info!("Dry run: {} leftovers would be removed", leftovers.len());
+    } else {
+        info!("Cleaned up {} leftovers", leftovers.len());
+    }
+    Ok(ExitCode::SUCCESS)
 }
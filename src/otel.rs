@@ -0,0 +1,64 @@
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::{Level, Metadata};
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::conversion::CONVERSION_SPAN;
+use crate::prelude::*;
+
+/// Keeps the spans being exported until it's dropped, which sends off the ones still queued.
+#[derive(Debug)]
+pub struct OtelGuard {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for OtelGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to export the last OpenTelemetry spans: {e}");
+        }
+    }
+}
+
+/// A layer exporting every conversion as a span over OTLP (HTTP/protobuf), with its file path, engine,
+/// size and duration as attributes, and the warnings and errors logged during it as span events.
+///
+/// Where to is read from the standard `OTEL_EXPORTER_OTLP_*` variables, `http://localhost:4318` without them.
+/// The service is named after the crate unless `OTEL_SERVICE_NAME` says otherwise.
+///
+/// # Errors
+/// * If the exporter can't be set up from those variables
+pub fn otel_layer<S>() -> Result<(impl Layer<S>, OtelGuard)>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()?;
+
+    let mut resource = Resource::builder();
+    if std::env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name(env!("CARGO_PKG_NAME"));
+    }
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build();
+
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+        .with_filter(filter_fn(exported));
+    Ok((layer, OtelGuard { provider }))
+}
+
+/// Only the conversion spans, whatever the verbosity, and the warnings and errors logged in them.
+fn exported(meta: &Metadata<'_>) -> bool {
+    if meta.is_span() {
+        meta.name() == CONVERSION_SPAN
+    } else {
+        *meta.level() <= Level::WARN
+    }
+}
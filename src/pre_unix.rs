@@ -1,38 +1,111 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
 
-use tracing::{error, trace};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{info, trace};
 
 use crate::prelude::{Error, Result};
 
 pub static PANDOC_PATH_UNPACK: OnceLock<PathBuf> = OnceLock::new();
 
+/// Binary names pandoc is commonly installed under.
+const CANDIDATES: [&str; 3] = ["pandoc", "pandoc-bin", "pandoc-cli"];
+
+/// Well-known install prefixes on macOS, in priority order:
+/// Homebrew (Apple Silicon), Homebrew (Intel), MacPorts.
+#[cfg(target_os = "macos")]
+const MACOS_PREFIXES: [&str; 3] =
+    ["/opt/homebrew/bin", "/usr/local/bin", "/opt/local/bin"];
+
+/// The latest pandoc release, as the GitHub API describes it, see `install_pandoc`.
+const LATEST_RELEASE: &str = "https://api.github.com/repos/jgm/pandoc/releases/latest";
+
+/// The folder below the per-user cache that `install_pandoc` installs to.
+const CACHE_DIR_NAME: &str = "document_conversion_crawler";
+
+/// How the release archive for this platform ends, `None` where pandoc doesn't build one.
+const RELEASE_SUFFIX: Option<&str> =
+    if cfg!(all(target_os = "linux", target_arch = "x86_64")) {
+        Some("-linux-amd64.tar.gz")
+    } else if cfg!(all(target_os = "linux", target_arch = "aarch64")) {
+        Some("-linux-arm64.tar.gz")
+    } else if cfg!(all(target_os = "macos", target_arch = "x86_64")) {
+        Some("-x86_64-macOS.zip")
+    } else if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+        Some("-arm64-macOS.zip")
+    } else {
+        None
+    };
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets:   Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name:                 String,
+    browser_download_url: String,
+    /// `sha256:<hex>`, GitHub's checksum of the file.
+    digest:               Option<String>,
+}
+
 /// Gets the path to the pandoc binary
 /// This is platform dependent - the one you're calling
 /// here is for unix-like systems.
 /// If you're on windows, see `pre_windows.rs`
 ///
+/// Built with `embed-pandoc-linux`/`embed-pandoc-macos`, the built-in one is unpacked
+/// and used, see `embedded::unpack`. A pandoc pinned with `--pandoc-path` or `DCC_PANDOC_PATH`
+/// comes before all of them, see `pandoc_path_override`.
+///
 /// # Errors
 /// * `Result::Err` - If the pandoc binary could not be found in PATH
+/// * `Error::PandocBinaryIntegrityFailure` - If the built-in binary doesn't unpack to what was embedded
 ///
 pub fn get_pandoc_path() -> Result<PathBuf> {
+    if let Some(pinned) = crate::prelude::pandoc_path_override()? {
+        return Ok(pinned);
+    }
+
+    // A build with pandoc built in uses that over any other, unpacked to the per-user cache
+    #[cfg(embedded_pandoc)]
+    if PANDOC_PATH_UNPACK.get().is_none() {
+        let unpacked = crate::embedded::unpack_path();
+        crate::embedded::unpack(&unpacked)?;
+        trace!("Using the built-in pandoc, unpacked at {unpacked:?}");
+        PANDOC_PATH_UNPACK.get_or_init(|| unpacked);
+    }
+
     update_pandoc_unpacked(&PathBuf::new());
     let pandoc = PANDOC_PATH_UNPACK.get();
     match pandoc {
         Some(p) => Ok(p.to_owned()),
-        None => {
-            Err(Error::PandocNotFound("Could not find pandoc binary in PATH".to_string()))
-        }
+        None => Err(Error::PandocNotFound(
+            "Could not find pandoc binary in PATH, install it or run with --auto-install"
+                .to_string(),
+        )),
     }
 }
 
 /// Takes a best-effort scan of the PATH environment variable
 /// If this cannot be done via PATH,
+/// on macOS we probe the Homebrew and MacPorts prefixes (GUI-launched apps
+/// often get a minimal PATH without them), and after that
 /// we fallback to an attempt to use 'command -v pandoc'.
-/// If both fail, we exit the process with error code 1.
+/// Last of all we look for one `install_pandoc` put in the per-user cache.
+/// If all of them fail `PANDOC_PATH_UNPACK` is left unset, and `get_pandoc_path` errors.
 pub fn update_pandoc_unpacked(_pandoc_path: &PathBuf) {
     let maybe_res = scan_path_env_for_pandoc();
 
+    #[cfg(target_os = "macos")]
+    let maybe_res = maybe_res.or_else(|| {
+        trace!("Couldn't find pandoc in PATH, probing Homebrew/MacPorts prefixes");
+        scan_macos_prefixes_for_pandoc()
+    });
+
     if let Some(pandoc_path) = maybe_res.clone() {
         PANDOC_PATH_UNPACK.get_or_init(|| pandoc_path);
     }
@@ -56,18 +129,22 @@ pub fn update_pandoc_unpacked(_pandoc_path: &PathBuf) {
 
                 PANDOC_PATH_UNPACK.get_or_init(|| pandoc_path);
             } else {
-                trace!("'command -v pandoc' did not complete successfully");
-                error!(
+                trace!(
                     "'command -v pandoc' failed with status: {:?}",
                     cmd_output.status.code()
                 );
-                std::process::exit(1);
             }
         } else {
-            error!("Failed to execute 'command -v pandoc' to find pandoc binary");
-            std::process::exit(1);
+            trace!("Failed to execute 'command -v pandoc' to find pandoc binary");
         }
     }
+
+    if PANDOC_PATH_UNPACK.get().is_none()
+        && let Some(pandoc_path) = cached_pandoc()
+    {
+        trace!("Using the pandoc installed by --auto-install at {pandoc_path:?}");
+        PANDOC_PATH_UNPACK.get_or_init(|| pandoc_path);
+    }
 }
 
 /// Takes a best-effort scan of the PATH environment variable
@@ -76,8 +153,6 @@ pub fn update_pandoc_unpacked(_pandoc_path: &PathBuf) {
 ///
 #[must_use]
 pub fn scan_path_env_for_pandoc() -> Option<PathBuf> {
-    const CANDIDATES: [&str; 3] = ["pandoc", "pandoc-bin", "pandoc-cli"];
-
     let path_env = std::env::var("PATH").unwrap_or_default();
 
     for p in std::env::split_paths(&path_env) {
@@ -91,3 +166,248 @@ pub fn scan_path_env_for_pandoc() -> Option<PathBuf> {
 
     None
 }
+
+/// Checks each of the `CANDIDATES` names against the
+/// Homebrew and MacPorts install prefixes, in priority order.
+///
+#[cfg(target_os = "macos")]
+#[must_use]
+pub fn scan_macos_prefixes_for_pandoc() -> Option<PathBuf> {
+    MACOS_PREFIXES
+        .iter()
+        .flat_map(|prefix| {
+            CANDIDATES
+                .iter()
+                .map(move |cand| Path::new(prefix).join(cand))
+        })
+        .find(|full_path| full_path.is_file())
+}
+
+/// Where `install_pandoc` puts pandoc, and the built-in one is unpacked: `$XDG_CACHE_HOME` (or `~/.cache`)
+/// on Linux, `~/Library/Caches` on macOS, in a folder of ours.
+#[must_use]
+pub fn pandoc_cache_dir() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+    #[cfg(target_os = "macos")]
+    let base = home().map(|home| home.join("Library").join("Caches"));
+    #[cfg(not(target_os = "macos"))]
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home().map(|home| home.join(".cache")));
+
+    base.map(|base| base.join(CACHE_DIR_NAME))
+}
+
+/// The newest pandoc `install_pandoc` installed, if any.
+fn cached_pandoc() -> Option<PathBuf> {
+    let mut installed: Vec<PathBuf> = std::fs::read_dir(pandoc_cache_dir()?)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|dir| {
+            dir.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("pandoc-"))
+        })
+        .map(|dir| dir.join("pandoc"))
+        .filter(|pandoc| pandoc.is_file())
+        .collect();
+    installed.sort_by_key(|pandoc| version_key(pandoc));
+    installed.pop()
+}
+
+/// `.../pandoc-3.10.1/pandoc` as `[3, 10, 1]`, so versions sort by number.
+fn version_key(pandoc: &Path) -> Vec<u64> {
+    pandoc
+        .parent()
+        .and_then(Path::file_name)
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .trim_start_matches("pandoc-")
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// Downloads the latest official pandoc release for this platform from GitHub (`--auto-install`),
+/// checks it against the SHA-256 GitHub lists for it, and unpacks it below `pandoc_cache_dir`.
+/// The pandoc it installed is what `get_pandoc_path` returns from then on, in later runs too.
+///
+/// Nothing is downloaded if that release is installed already.
+/// `curl` fetches the files and `tar` unpacks them, both come with Linux and macOS.
+///
+/// # Errors
+/// * `Error::PandocInstallFailed` - If there's no release for this platform, the download fails
+///   or doesn't match its checksum, or it can't be unpacked
+pub async fn install_pandoc() -> Result<PathBuf> {
+    let failed = |reason: String| Error::PandocInstallFailed(reason);
+    let cache_dir =
+        pandoc_cache_dir().ok_or_else(|| failed("HOME isn't set".to_string()))?;
+    let suffix = RELEASE_SUFFIX.ok_or_else(|| {
+        failed(format!(
+            "pandoc has no release for {}/{}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ))
+    })?;
+
+    let release: Release = serde_json::from_slice(&curl(&[LATEST_RELEASE]).await?)?;
+    let (asset, sha256) = pick_asset(&release, suffix)?;
+    let install_dir = cache_dir.join(format!("pandoc-{}", release.tag_name));
+    let pandoc = install_dir.join("pandoc");
+    if pandoc.is_file() {
+        info!("pandoc {} is installed already", release.tag_name);
+        return Ok(PANDOC_PATH_UNPACK.get_or_init(|| pandoc).clone());
+    }
+
+    info!("Downloading {} from {}", asset.name, asset.browser_download_url);
+    tokio::fs::create_dir_all(&cache_dir).await?;
+    let archive = cache_dir.join(format!(".{}", asset.name));
+    let staging = cache_dir.join(format!(".pandoc-{}.partial", release.tag_name));
+    let res = async {
+        let downloaded = curl(&[&asset.browser_download_url]).await?;
+        let digest = hex(&Sha256::digest(&downloaded));
+        if !digest.eq_ignore_ascii_case(sha256) {
+            return Err(failed(format!(
+                "{} has SHA-256 {digest}, GitHub lists {sha256}",
+                asset.name
+            )));
+        }
+        tokio::fs::write(&archive, &downloaded).await?;
+
+        tokio::fs::create_dir_all(&staging).await?;
+        run(
+            "tar",
+            &[
+                "-xf".as_ref(),
+                archive.as_os_str(),
+                "-C".as_ref(),
+                staging.as_os_str(),
+            ],
+        )
+        .await?;
+        let unpacked = find_binary(&staging)
+            .ok_or_else(|| failed(format!("{} has no bin/pandoc", asset.name)))?;
+
+        tokio::fs::create_dir_all(&install_dir).await?;
+        tokio::fs::rename(&unpacked, &pandoc).await?;
+        Ok(())
+    }
+    .await;
+
+    for leftover in [&archive, &staging] {
+        let _ = tokio::fs::remove_file(leftover).await;
+        let _ = tokio::fs::remove_dir_all(leftover).await;
+    }
+    res?;
+
+    info!("Installed pandoc {} at {}", release.tag_name, pandoc.display());
+    Ok(PANDOC_PATH_UNPACK.get_or_init(|| pandoc).clone())
+}
+
+/// The archive of `release` for the platform's `suffix`, and the SHA-256 (hex) GitHub lists for it.
+fn pick_asset<'a>(release: &'a Release, suffix: &str) -> Result<(&'a Asset, &'a str)> {
+    let asset = release
+        .assets
+        .iter()
+        .find(|asset| asset.name.starts_with("pandoc-") && asset.name.ends_with(suffix))
+        .ok_or_else(|| {
+            Error::PandocInstallFailed(format!(
+                "pandoc {} has no '*{suffix}' download",
+                release.tag_name
+            ))
+        })?;
+    let sha256 = asset
+        .digest
+        .as_deref()
+        .and_then(|digest| digest.strip_prefix("sha256:"))
+        .ok_or_else(|| {
+            Error::PandocInstallFailed(format!(
+                "GitHub lists no SHA-256 for {}, not installing it unchecked",
+                asset.name
+            ))
+        })?;
+    Ok((asset, sha256))
+}
+
+/// The `bin/pandoc` of an unpacked release, which sits in a `pandoc-<version>` folder of its own.
+fn find_binary(unpacked: &Path) -> Option<PathBuf> {
+    std::fs::read_dir(unpacked)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .map(|dir| dir.join("bin").join("pandoc"))
+        .find(|pandoc| pandoc.is_file())
+}
+
+/// What `curl` downloads from `args`, failing on HTTP errors.
+async fn curl(args: &[&str]) -> Result<Vec<u8>> {
+    let mut curl_args = vec!["-fsSL".as_ref()];
+    curl_args.extend(args.iter().map(std::ffi::OsStr::new));
+    run("curl", &curl_args).await
+}
+
+/// Runs `program` with `args`, and returns its stdout if it succeeds.
+async fn run(program: &str, args: &[&std::ffi::OsStr]) -> Result<Vec<u8>> {
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| {
+            Error::PandocInstallFailed(format!("Failed to run {program}: {e}"))
+        })?;
+    if !output.status.success() {
+        return Err(Error::PandocInstallFailed(format!(
+            "{program} failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod pre_unix_tests {
+    use super::*;
+
+    fn asset(name: &str, digest: Option<&str>) -> Asset {
+        Asset {
+            name:                 name.to_string(),
+            browser_download_url: format!("https://example.com/{name}"),
+            digest:               digest.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_pick_asset() {
+        let release = Release {
+            tag_name: "3.7".to_string(),
+            assets:   vec![
+                asset("pandoc-3.7-linux-amd64.tar.gz", Some("sha256:abc123")),
+                asset("pandoc-3.7-arm64-macOS.zip", None),
+                asset("pandoc-3.7-arm64-macOS.pkg", Some("sha256:def")),
+            ],
+        };
+
+        let (picked, sha256) = pick_asset(&release, "-linux-amd64.tar.gz").unwrap();
+        assert_eq!(picked.name, "pandoc-3.7-linux-amd64.tar.gz");
+        assert_eq!(sha256, "abc123");
+        // Never installed unchecked
+        assert!(pick_asset(&release, "-arm64-macOS.zip").is_err());
+        assert!(pick_asset(&release, "-linux-arm64.tar.gz").is_err());
+    }
+
+    #[test]
+    fn test_version_key() {
+        let mut installed = [
+            "pandoc-3.9/pandoc",
+            "pandoc-3.10.1/pandoc",
+            "pandoc-3.2/pandoc",
+        ]
+        .map(PathBuf::from);
+        installed.sort_by_key(|pandoc| version_key(pandoc));
+        assert_eq!(installed[2], PathBuf::from("pandoc-3.10.1/pandoc"));
+    }
+}
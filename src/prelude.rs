@@ -3,9 +3,17 @@
 // use std::sync::OnceLock;
 
 // in-crate Error type
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 pub use tracing::{debug, error, info, trace, warn};
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{EnvFilter, Layer};
 
+pub use crate::embedded::EphemeralBinary;
 pub use crate::error::Error;
 
 // in-crate result type
@@ -24,23 +32,124 @@ pub fn time<T>(t: &str, f: impl FnOnce() -> T) -> T {
     r
 }
 
-pub type TracingSubscriber = tracing_subscriber::fmt::SubscriberBuilder<
-    tracing_subscriber::fmt::format::DefaultFields,
-    tracing_subscriber::fmt::format::Format<tracing_subscriber::fmt::format::Full>,
-    tracing_subscriber::EnvFilter,
->;
+/// How log lines are written (`--log-format`).
+#[derive(Debug, clap::ValueEnum, Clone, Copy, PartialEq, Eq, Default)]
+#[clap(name = "LogFormat", rename_all = "lower")]
+pub enum LogFormat {
+    /// Colored lines for a terminal, with thread ids and line numbers.
+    #[default]
+    #[value(name = "pretty", alias = "PRETTY")]
+    Pretty,
+    /// A JSON object per line, for log aggregation (Loki, ELK and the like).
+    #[value(name = "json", alias = "JSON")]
+    Json,
+}
 
-pub fn init_logger(level: EnvFilter) -> TracingSubscriber {
-    tracing_subscriber::fmt()
-        .with_level(true)
-        .with_ansi(true)
-        .with_line_number(true)
-        .with_thread_ids(true)
-        .with_env_filter(level)
+/// A layer writing every event it gets to `writer` as `format`, `ansi` colored if it's `LogFormat::Pretty`.
+pub fn log_layer<S, W>(
+    writer: W,
+    format: LogFormat,
+    ansi: bool,
+) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    match format {
+        LogFormat::Pretty => {
+            tracing_subscriber::fmt::layer()
+                .with_level(true)
+                .with_ansi(ansi)
+                .with_line_number(true)
+                .with_thread_ids(true)
+                .with_writer(writer)
+                .boxed()
+        }
+        LogFormat::Json => {
+            tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_line_number(true)
+                .with_writer(writer)
+                .boxed()
+        }
+    }
     // .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
     // .with_timer(tracing_subscriber::fmt::time::SystemTime)
 }
 
+/// The subscriber that writes every event `level` lets through to `writer`, as `format`.
+/// More layers, each with a filter of its own, can be added on top (see `log_layer`).
+pub fn init_logger(
+    level: EnvFilter,
+    writer: crate::progress::LogWriter,
+    format: LogFormat,
+) -> impl tracing::Subscriber + for<'a> LookupSpan<'a> + Send + Sync {
+    tracing_subscriber::registry()
+        .with(log_layer(writer, format, true).with_filter(level))
+}
+
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts every warning that gets logged, for `--strict`.
+pub struct WarningCounter;
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for WarningCounter {
+    fn on_event(
+        &self,
+        event: &tracing::Event<'_>,
+        _ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        if *event.metadata().level() == tracing::Level::WARN {
+            WARNINGS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// How many warnings have been logged so far (see `WarningCounter`).
+#[inline]
+pub fn warning_count() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
+
+/// Names the pandoc binary to use, ahead of the embedded one and any in `PATH`, see `pandoc_path_override`.
+pub const PANDOC_PATH_ENV: &str = "DCC_PANDOC_PATH";
+
+/// Set by `--pandoc-path`, see `set_pandoc_path`.
+static PANDOC_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Pins the pandoc binary `get_pandoc_path` returns on every platform, over `DCC_PANDOC_PATH` too.
+/// Only the first call counts.
+pub fn set_pandoc_path(path: PathBuf) {
+    if PANDOC_PATH_OVERRIDE.set(path).is_err() {
+        debug!("The pandoc path was already set, keeping the first one");
+    }
+}
+
+/// The pandoc binary pinned by `set_pandoc_path` or the `DCC_PANDOC_PATH` environment variable,
+/// which `get_pandoc_path` returns without looking anywhere else (or unpacking the embedded one).
+///
+/// # Errors
+/// * `Error::PandocNotFound` - If it's pinned to something that isn't a file, rather than quietly using another pandoc
+pub fn pandoc_path_override() -> Result<Option<PathBuf>> {
+    let (path, source) = match PANDOC_PATH_OVERRIDE.get() {
+        Some(path) => (path.clone(), "--pandoc-path"),
+        None => {
+            match std::env::var_os(PANDOC_PATH_ENV).filter(|path| !path.is_empty()) {
+                Some(path) => (PathBuf::from(path), PANDOC_PATH_ENV),
+                None => return Ok(None),
+            }
+        }
+    };
+    if !path.is_file() {
+        return Err(Error::PandocNotFound(format!(
+            "{source} points at {path:?}, which isn't a file"
+        )));
+    }
+    trace!("Using the pandoc {source} points at: {path:?}");
+    Ok(Some(path))
+}
+
 /// We don't include a binary for padnoc on unix-like systems
 /// due to the ease of aquiring it via package managers etc.
 /// we make a best-effort attempt to find pandoc in PATH or
@@ -0,0 +1,100 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Every progress bar is drawn through this, so log lines can be printed
+/// around them instead of tearing through (see `LogWriter`).
+///
+/// Draws to stderr and stays hidden if that isn't a terminal.
+static BARS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+/// Log output held back while something else draws on the terminal (see `hold`),
+/// `None` while it's written straight away.
+static HELD: Mutex<Option<Vec<Held>>> = Mutex::new(None);
+
+/// A write held back, and whether it's bound for stderr.
+type Held = (bool, Vec<u8>);
+
+const TEMPLATE: &str =
+    "{spinner} [{elapsed_precise}] {bar:30} {pos}/{len} (ETA {eta}) {wide_msg}";
+
+/// A new progress bar for a batch of conversions, starting at 0 of 0 files.
+///
+/// The length is grown as files are queued, so streaming runs that don't know
+/// their total up front work the same as collect-first ones.
+pub fn batch_bar() -> ProgressBar {
+    let style = ProgressStyle::with_template(TEMPLATE)
+        .expect("progress bar template is valid")
+        .progress_chars("=> ");
+    BARS.add(ProgressBar::new(0).with_style(style))
+}
+
+/// Shows `path`'s file name as the file currently being converted.
+pub fn set_current(bar: &ProgressBar, path: &Path) {
+    if let Some(name) = path.file_name() {
+        bar.set_message(name.to_string_lossy().into_owned());
+    }
+}
+
+/// Holds back the log output, and hides the progress bars, until `release`,
+/// so they don't tear through the `--tui` dashboard.
+pub fn hold() {
+    *HELD.lock().expect("held log lock poisoned") = Some(Vec::new());
+    BARS.set_draw_target(ProgressDrawTarget::hidden());
+}
+
+/// Writes out the log output held back since `hold`, in order, and shows the progress bars again.
+pub fn release() {
+    let held = HELD.lock().expect("held log lock poisoned").take();
+    for (to_stderr, buf) in held.into_iter().flatten() {
+        let _ = LogWriter { to_stderr }.write_all(&buf);
+    }
+    BARS.set_draw_target(ProgressDrawTarget::stderr());
+}
+
+/// Writes log output to stdout, or stderr while stdout carries a `--pipe` result,
+/// hiding any progress bars for the duration of the write.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogWriter {
+    to_stderr: bool,
+}
+
+impl LogWriter {
+    pub fn stderr() -> Self {
+        Self { to_stderr: true }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogWriter {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        *self
+    }
+}
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(ref mut held) = *HELD.lock().expect("held log lock poisoned") {
+            held.push((self.to_stderr, buf.to_vec()));
+            return Ok(buf.len());
+        }
+        if self.to_stderr {
+            BARS.suspend(|| std::io::stderr().lock().write_all(buf))?;
+        } else {
+            BARS.suspend(|| std::io::stdout().lock().write_all(buf))?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.to_stderr {
+            std::io::stderr().flush()
+        } else {
+            std::io::stdout().flush()
+        }
+    }
+}
@@ -0,0 +1,584 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{
+    self,
+    Event as Input,
+    KeyCode,
+    KeyEvent,
+    KeyEventKind,
+    KeyModifiers,
+};
+use ratatui::crossterm::terminal::{
+    EnterAlternateScreen,
+    LeaveAlternateScreen,
+    disable_raw_mode,
+    enable_raw_mode,
+};
+use ratatui::crossterm::{cursor, execute};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, Paragraph};
+use ratatui::{Frame, Terminal};
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use tracing_subscriber::Layer;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::conversion::shutdown::Shutdown;
+use crate::prelude::*;
+use crate::progress;
+
+/// How many lines the error pane keeps, older ones are dropped.
+const MAX_ERRORS: usize = 500;
+/// How often the screen is redrawn, and the keys read.
+const TICK: Duration = Duration::from_millis(200);
+/// The throughput shown is that of the conversions that finished within this long.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(60);
+/// How many lines PgUp/PgDn scroll the error pane by.
+const PAGE: usize = 5;
+
+const HELP: &str = "p pause/resume · ↑/↓ pick a lane · s skip its file · PgUp/PgDn scroll errors · q stop (again to abort)";
+
+/// What the `--tui` dashboard shows of a run: the file each job slot is converting, the totals so far
+/// and the warnings and errors logged. Its keys pause the run, skip a file or stop it (through the run's `Shutdown`).
+///
+/// The conversions report to it as they start (`started`) and finish, `Screen` draws it.
+#[derive(Debug)]
+pub struct Dashboard {
+    state:    Mutex<State>,
+    /// `true` while paused, no conversion starts until it's `false` again.
+    paused:   watch::Sender<bool>,
+    shutdown: Shutdown,
+    started:  Instant,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// A lane per job slot, `None` while it's idle.
+    lanes:     Vec<Option<Running>>,
+    /// The lane `s` skips the file of.
+    selected:  usize,
+    queued:    usize,
+    converted: usize,
+    failed:    usize,
+    skipped:   usize,
+    /// Queued conversions that didn't start, the run being stopped.
+    cancelled: usize,
+    /// When each conversion of the last `THROUGHPUT_WINDOW` finished.
+    recent:    VecDeque<Instant>,
+    /// The warnings and errors logged, newest last.
+    errors:    VecDeque<String>,
+    /// How many lines the error pane is scrolled up from the newest.
+    scroll:    usize,
+}
+
+/// The conversion a lane is on.
+#[derive(Debug)]
+struct Running {
+    input:  PathBuf,
+    engine: String,
+    since:  Instant,
+    skip:   CancellationToken,
+}
+
+impl Dashboard {
+    /// A dashboard with a lane for each of `jobs`, stopping the run through `shutdown`.
+    pub fn new(jobs: usize, shutdown: Shutdown) -> Self {
+        Self {
+            state: Mutex::new(State {
+                lanes: std::iter::repeat_with(|| None).take(jobs.max(1)).collect(),
+                ..State::default()
+            }),
+            paused: watch::Sender::new(false),
+            shutdown,
+            started: Instant::now(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("dashboard lock poisoned")
+    }
+
+    /// A conversion was queued.
+    pub fn queued(&self) {
+        self.state().queued += 1;
+    }
+
+    /// A file was skipped, without a conversion being queued for it.
+    pub fn skipped(&self) {
+        self.state().skipped += 1;
+    }
+
+    /// A queued conversion failed without being started, like one of a file that doesn't hold what its extension says.
+    pub fn failed(&self) {
+        self.state().failed += 1;
+    }
+
+    /// Waits for as long as the run is paused, then says whether a queued conversion may start,
+    /// which it may not once the run is stopping (it's counted as cancelled then).
+    pub async fn admit(&self) -> bool {
+        let mut paused = self.paused.subscribe();
+        tokio::select! {
+            _ = paused.wait_for(|paused| !paused) => {}
+            () = self.shutdown.stopping() => {}
+        }
+        if self.shutdown.is_stopping() {
+            self.state().cancelled += 1;
+            return false;
+        }
+        true
+    }
+
+    /// The conversion of `input` by `engine` started, and takes the first idle lane until the `Lane` is dropped.
+    pub fn started(self: &Arc<Self>, input: &Path, engine: &str) -> Lane {
+        let skip = CancellationToken::new();
+        let running = Running {
+            input:  input.to_path_buf(),
+            engine: engine.to_string(),
+            since:  Instant::now(),
+            skip:   skip.clone(),
+        };
+
+        let mut state = self.state();
+        let index = match state.lanes.iter().position(Option::is_none) {
+            Some(index) => index,
+            // More conversions than jobs, only if a `Lane` outlived its conversion
+            None => {
+                state.lanes.push(None);
+                state.lanes.len() - 1
+            }
+        };
+        state.lanes[index] = Some(running);
+        Lane {
+            dashboard: Arc::clone(self),
+            index,
+            skip,
+        }
+    }
+
+    /// A layer feeding the warnings and errors logged to the error pane.
+    pub fn log_layer<S>(self: &Arc<Self>) -> impl Layer<S>
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        tracing_subscriber::fmt::layer()
+            .with_writer(ErrorPane(Arc::clone(self)))
+            .with_ansi(false)
+            .without_time()
+            .with_target(false)
+            .with_filter(LevelFilter::WARN)
+    }
+
+    fn push_error(&self, line: &str) {
+        let mut state = self.state();
+        state.errors.push_back(line.to_string());
+        if state.errors.len() > MAX_ERRORS {
+            state.errors.pop_front();
+        }
+        // A scrolled pane keeps showing the same lines
+        if state.scroll > 0 {
+            state.scroll = (state.scroll + 1).min(state.errors.len() - 1);
+        }
+    }
+
+    fn on_key(&self, key: KeyEvent) {
+        let ctrl_c = key.modifiers.contains(KeyModifiers::CONTROL)
+            && key.code == KeyCode::Char('c');
+        let mut state = self.state();
+        match key.code {
+            _ if ctrl_c => self.request_stop(),
+            KeyCode::Char('q') | KeyCode::Esc => self.request_stop(),
+            KeyCode::Char('p' | ' ') => {
+                self.paused.send_modify(|paused| *paused = !*paused)
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                state.selected = state.selected.saturating_sub(1)
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                state.selected = (state.selected + 1).min(state.lanes.len() - 1);
+            }
+            KeyCode::Char('s') => {
+                if let Some(Some(running)) = state.lanes.get(state.selected) {
+                    running.skip.cancel();
+                }
+            }
+            KeyCode::PageUp => {
+                state.scroll =
+                    (state.scroll + PAGE).min(state.errors.len().saturating_sub(1));
+            }
+            KeyCode::PageDown => state.scroll = state.scroll.saturating_sub(PAGE),
+            KeyCode::End => state.scroll = 0,
+            _ => {}
+        }
+    }
+
+    /// Stops the run the first time, aborts it the second.
+    fn request_stop(&self) {
+        if self.shutdown.is_stopping() {
+            self.shutdown.abort();
+        } else {
+            self.shutdown.stop();
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        if self.shutdown.is_aborted() {
+            "Aborting"
+        } else if self.shutdown.is_stopping() {
+            "Stopping, finishing the conversions running"
+        } else if *self.paused.borrow() {
+            "Paused"
+        } else {
+            "Converting"
+        }
+    }
+
+    /// Draws the whole dashboard onto `frame`.
+    fn draw(&self, frame: &mut Frame<'_>) {
+        let status = self.status();
+        let elapsed = self.started.elapsed();
+        let mut state = self.state();
+        let now = Instant::now();
+        while state
+            .recent
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > THROUGHPUT_WINDOW)
+        {
+            state.recent.pop_front();
+        }
+
+        let [top, stats, lanes, errors, help] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(1),
+            Constraint::Length(
+                u16::try_from(state.lanes.len())
+                    .unwrap_or(u16::MAX)
+                    .saturating_add(2),
+            ),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let done = state.converted + state.failed + state.cancelled;
+        #[allow(clippy::cast_precision_loss)]
+        let ratio = if state.queued == 0 {
+            0.0
+        } else {
+            (done as f64 / state.queued as f64).min(1.0)
+        };
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::bordered().title(format!(" {status} ")))
+                .gauge_style(Style::new().fg(Color::Green))
+                .ratio(ratio)
+                .label(format!("{done}/{}", state.queued)),
+            top,
+        );
+
+        #[allow(clippy::cast_precision_loss)]
+        let per_minute = state.recent.len() as f64 * 60.0
+            / elapsed.min(THROUGHPUT_WINDOW).as_secs_f64().max(1.0);
+        frame.render_widget(
+            Paragraph::new(format!(
+                " Converted {}  Failed {}  Skipped {}  Waiting {}  ·  {per_minute:.1} files/min  ·  {}",
+                state.converted,
+                state.failed,
+                state.skipped,
+                state.queued.saturating_sub(done + busy(&state)),
+                clock(elapsed)
+            )),
+            stats,
+        );
+
+        let lines: Vec<Line<'_>> = state
+            .lanes
+            .iter()
+            .enumerate()
+            .map(|(i, lane)| {
+                let text = match lane {
+                    Some(running) => {
+                        format!(
+                            "{:>2} {:>7}  {:<12} {}",
+                            i + 1,
+                            lap(running.since.elapsed()),
+                            running.engine,
+                            running.input.display()
+                        )
+                    }
+                    None => format!("{:>2} {:>7}  idle", i + 1, ""),
+                };
+                let style = if i == state.selected {
+                    Style::new().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::new()
+                };
+                Line::styled(text, style)
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" Lanes ")),
+            lanes,
+        );
+
+        let height = usize::from(errors.height.saturating_sub(2));
+        let end = state.errors.len().saturating_sub(state.scroll);
+        let lines: Vec<Line<'_>> = state
+            .errors
+            .range(end.saturating_sub(height)..end)
+            .map(|line| {
+                let color = if line.trim_start().starts_with("ERROR") {
+                    Color::Red
+                } else {
+                    Color::Yellow
+                };
+                Line::styled(line.as_str(), Style::new().fg(color))
+            })
+            .collect();
+        let title = match state.scroll {
+            0 => format!(" Errors ({}) ", state.errors.len()),
+            scroll => format!(" Errors ({}, {scroll} newer below) ", state.errors.len()),
+        };
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(title)),
+            errors,
+        );
+
+        frame.render_widget(
+            Paragraph::new(HELP).style(Style::new().fg(Color::DarkGray)),
+            help,
+        );
+    }
+}
+
+/// How many lanes are converting.
+fn busy(state: &State) -> usize {
+    state.lanes.iter().flatten().count()
+}
+
+/// `took` as `1:02:03`.
+fn clock(took: Duration) -> String {
+    let secs = took.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+/// `took` as `4.2s`, or `3m05s` from a minute on.
+fn lap(took: Duration) -> String {
+    match took.as_secs() {
+        secs @ 60.. => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{:.1}s", took.as_secs_f64()),
+    }
+}
+
+/// A conversion shown in a lane of the `Dashboard`, which it's taken off again once this is dropped.
+#[derive(Debug)]
+pub struct Lane {
+    dashboard: Arc<Dashboard>,
+    index:     usize,
+    skip:      CancellationToken,
+}
+
+impl Lane {
+    /// Resolves once the file is skipped from the dashboard.
+    pub async fn skipped(&self) {
+        self.skip.cancelled().await;
+    }
+
+    /// Counts how the conversion ended, `res` being what `convert_single` returned.
+    pub fn finished(self, res: &Result<usize>) {
+        let mut state = self.dashboard.state();
+        match res {
+            Ok(_) => state.converted += 1,
+            Err(Error::Skipped(_)) => state.skipped += 1,
+            Err(_) => state.failed += 1,
+        }
+        state.recent.push_back(Instant::now());
+    }
+}
+
+impl Drop for Lane {
+    fn drop(&mut self) {
+        self.dashboard.state().lanes[self.index] = None;
+    }
+}
+
+/// Where `Dashboard::log_layer` writes, a line per log event.
+#[derive(Debug, Clone)]
+struct ErrorPane(Arc<Dashboard>);
+
+impl<'a> MakeWriter<'a> for ErrorPane {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+impl Write for ErrorPane {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for line in String::from_utf8_lossy(buf).lines() {
+            if !line.trim().is_empty() {
+                self.0.push_error(line.trim_end());
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The `--tui` dashboard on the terminal (drawn to stderr, in its alternate screen), redrawn
+/// and its keys read on a thread of its own. The log is held back while it's shown, see `progress::hold`.
+///
+/// Dropping it puts the terminal back as it was and writes out the log.
+#[derive(Debug)]
+pub struct Screen {
+    done:   Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Screen {
+    /// Takes over the terminal to show `dashboard` on it.
+    ///
+    /// # Errors
+    /// * If the terminal can't be switched to its alternate screen, or raw mode
+    pub fn show(dashboard: Arc<Dashboard>) -> Result<Self> {
+        progress::hold();
+        enable_raw_mode()?;
+        execute!(std::io::stderr(), EnterAlternateScreen, cursor::Hide)?;
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore();
+            previous(info);
+        }));
+        let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stderr()))?;
+
+        let done = Arc::new(AtomicBool::new(false));
+        let thread_done = Arc::clone(&done);
+        let thread = std::thread::spawn(move || {
+            while !thread_done.load(Ordering::Relaxed) {
+                if terminal.draw(|frame| dashboard.draw(frame)).is_err() {
+                    break;
+                }
+                match event::poll(TICK) {
+                    Ok(true) => {
+                        if let Ok(Input::Key(key)) = event::read()
+                            && key.kind == KeyEventKind::Press
+                        {
+                            dashboard.on_key(key);
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(Self {
+            done,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        restore();
+        progress::release();
+    }
+}
+
+/// Leaves the alternate screen and raw mode.
+fn restore() {
+    let _ = execute!(std::io::stderr(), LeaveAlternateScreen, cursor::Show);
+    let _ = disable_raw_mode();
+}
+
+#[cfg(test)]
+mod tui_tests {
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+
+    fn press(dashboard: &Dashboard, code: KeyCode) {
+        dashboard.on_key(KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    #[tokio::test]
+    async fn test_keys() {
+        let shutdown = Shutdown::default();
+        let dashboard = Arc::new(Dashboard::new(2, shutdown.clone()));
+        let first = dashboard.started(Path::new("a.docx"), "pandoc");
+        let second = dashboard.started(Path::new("b.docx"), "pandoc");
+
+        press(&dashboard, KeyCode::Down);
+        press(&dashboard, KeyCode::Down);
+        press(&dashboard, KeyCode::Char('s'));
+        assert!(!first.skip.is_cancelled());
+        assert!(second.skip.is_cancelled());
+        second.finished(&Err(Error::Skipped(PathBuf::from("b.docx"))));
+        first.finished(&Ok(0));
+        assert_eq!(dashboard.state().skipped, 1);
+        assert_eq!(dashboard.state().converted, 1);
+        assert!(dashboard.state().lanes.iter().all(Option::is_none));
+
+        press(&dashboard, KeyCode::Char('p'));
+        assert_eq!(dashboard.status(), "Paused");
+        press(&dashboard, KeyCode::Char('p'));
+        assert!(dashboard.admit().await);
+
+        press(&dashboard, KeyCode::Char('q'));
+        assert!(shutdown.is_stopping());
+        assert!(!dashboard.admit().await);
+        assert!(!shutdown.is_aborted());
+        press(&dashboard, KeyCode::Char('q'));
+        assert!(shutdown.is_aborted());
+    }
+
+    #[test]
+    fn test_draw() {
+        let dashboard = Arc::new(Dashboard::new(2, Shutdown::default()));
+        for _ in 0..3 {
+            dashboard.queued();
+        }
+        let _lane = dashboard.started(Path::new("in/a.docx"), "pandoc");
+        dashboard
+            .started(Path::new("in/b.docx"), "pandoc")
+            .finished(&Ok(0));
+        ErrorPane(Arc::clone(&dashboard))
+            .write_all(b"ERROR Task failed with error: bad\n")
+            .unwrap();
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 14)).unwrap();
+        terminal.draw(|frame| dashboard.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(ratatui::buffer::Cell::symbol)
+            .collect();
+
+        assert!(screen.contains("Converting"));
+        assert!(screen.contains("1/3"));
+        assert!(screen.contains("Converted 1  Failed 0  Skipped 0  Waiting 1"));
+        assert!(screen.contains("pandoc       in/a.docx"));
+        assert!(screen.contains("idle"));
+        assert!(screen.contains("Errors (1)"));
+        assert!(screen.contains("ERROR Task failed with error: bad"));
+    }
+}